@@ -0,0 +1,68 @@
+use ahitool::{
+    analysis::{processing, KpiSubject},
+    jobs::{self, TimeDelta},
+    synthetic,
+};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+/// The numbers of jobs to run each benchmark with.
+const SIZES: [usize; 3] = [1_000, 10_000, 50_000];
+
+fn analyze_job(c: &mut Criterion) {
+    let jobs = synthetic::generate_jobs(1_000, 0);
+    c.bench_function("analyze_job", |b| {
+        b.iter_batched(
+            || jobs.clone(),
+            |jobs| jobs.into_iter().map(jobs::analyze_job).collect::<Vec<_>>(),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn process_jobs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("process_jobs");
+    group.sample_size(10);
+    for size in SIZES {
+        let jobs = synthetic::generate_jobs(size, 0);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &jobs, |b, jobs| {
+            b.iter_batched(
+                || jobs.clone(),
+                |jobs| {
+                    processing::process_jobs(
+                        jobs.into_iter(),
+                        (None, None),
+                        None,
+                        None,
+                        TimeDelta::days(365),
+                        None,
+                    )
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn calc_stats(c: &mut Criterion) {
+    let mut group = c.benchmark_group("calc_stats");
+    for size in SIZES {
+        let jobs = synthetic::generate_jobs(size, 0);
+        let data = processing::process_jobs(
+            jobs.into_iter(),
+            (None, None),
+            None,
+            None,
+            TimeDelta::days(365),
+            None,
+        );
+        let global = &data.trackers[&KpiSubject::Global];
+        group.bench_with_input(BenchmarkId::from_parameter(size), global, |b, tracker| {
+            b.iter(|| processing::calculate_job_tracker_stats(tracker, 5).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, analyze_job, process_jobs, calc_stats);
+criterion_main!(benches);
@@ -0,0 +1,15 @@
+#![no_main]
+
+use ahitool::jobs::{self, Job};
+use libfuzzer_sys::fuzz_target;
+
+// Parses arbitrary JSON as a job from JobNimbus, then analyzes it, neither of
+// which may panic however malformed the job is.
+fuzz_target!(|data: &[u8]| {
+    let Ok(value) = serde_json::from_slice::<serde_json::Value>(data) else {
+        return;
+    };
+    if let Ok(job) = Job::try_from(value) {
+        let _ = jobs::analyze_job(job);
+    }
+});
@@ -1,3 +1,43 @@
+pub mod google_maps;
 pub mod google_sheets;
+pub mod http;
 pub mod job_nimbus;
-pub mod google_maps;
+
+use std::fs;
+
+use tracing::warn;
+
+use crate::storage;
+
+#[derive(Debug, thiserror::Error)]
+pub enum GetApiKeyError {
+    #[error("{0} API key was not specified and the cache file does not exist.")]
+    MissingApiKey(&'static str),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+}
+
+/// Returns the API key of a service (e.g. "JobNimbus"). A newly specified key
+/// is cached in the credentials file `cache_file` (see
+/// [`storage::credentials_path`]) for future invocations; otherwise, the cached
+/// key is used. Failing to cache a new key is only logged, since the key can
+/// still be used for this invocation.
+pub fn get_api_key(
+    service: &'static str,
+    cache_file: &str,
+    new_api_key: Option<String>,
+) -> Result<String, GetApiKeyError> {
+    if let Some(new_api_key) = new_api_key {
+        if let Err(e) = storage::write_credentials(cache_file, &new_api_key) {
+            warn!("Failed to cache the {} API key: {:#}", service, e);
+        }
+        return Ok(new_api_key);
+    }
+    match fs::read_to_string(storage::credentials_path(cache_file)) {
+        Ok(api_key) => Ok(api_key),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Err(GetApiKeyError::MissingApiKey(service))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
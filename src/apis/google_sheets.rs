@@ -1,24 +1,41 @@
+mod lock;
 mod oauth;
+mod payloads;
+mod quota;
 pub mod spreadsheet;
+mod user_columns;
 
 use std::borrow::Cow;
 use std::collections::HashSet;
 use std::fs::File;
 
-use std::io::BufWriter;
-use std::path::Path;
-
+use crate::apis::http;
+use crate::run_summary;
+use crate::storage;
 use anyhow::anyhow;
 use hyper::StatusCode;
+use lock::ExportLock;
 pub use oauth::run_with_credentials;
 pub use oauth::Token;
 use oauth::TryWithCredentialsError;
 use oauth2::TokenResponse as _;
+pub use quota::request_count;
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json::json;
+use spreadsheet::update::DataFilter;
+use spreadsheet::update::DeveloperMetadataLookup;
 use spreadsheet::update::Request;
+use spreadsheet::DeveloperMetadata;
+use spreadsheet::DeveloperMetadataLocation;
+use spreadsheet::ExtendedValue;
 use spreadsheet::GridCoordinate;
+use spreadsheet::GridData;
+use spreadsheet::GridProperties;
+use spreadsheet::GridRange;
+use spreadsheet::ProtectedRange;
+use spreadsheet::RowData;
+use spreadsheet::Sheet;
 use spreadsheet::SheetProperties;
 use spreadsheet::Spreadsheet;
 use std::collections::HashMap;
@@ -27,10 +44,346 @@ use tracing::debug;
 use tracing::info;
 use tracing::trace;
 use tracing::warn;
+use user_columns::UserCells;
 
 const ENDPOINT_SPREADSHEETS: &str = "https://sheets.googleapis.com/v4/spreadsheets";
+const ENDPOINT_DRIVE_FILES: &str = "https://www.googleapis.com/drive/v3/files";
 const KNOWN_SHEETS_FILE: &str = "google_sheets.json";
 
+/// The key of the developer metadata that tags the sheets generated by ahitool.
+const METADATA_KEY: &str = "ahitool";
+
+/// The value of the developer metadata of a generated sheet, as JSON.
+#[derive(Serialize, Deserialize, Debug)]
+struct SheetMetadata {
+    /// The spreadsheet that the sheet was generated for.
+    report: SheetNickname,
+    /// The title that the sheet was generated with, which identifies the sheet
+    /// even if the user renames it.
+    tab: String,
+    /// The version of ahitool that generated the sheet.
+    version: String,
+    /// The number of generated columns (counting the columns before the data)
+    /// of a sheet with a key column. Users can add their own columns to the
+    /// right of them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    columns: Option<u64>,
+}
+
+/// The description of the protected ranges that cover generated sheets, which
+/// is also used to recognize them.
+const PROTECTED_RANGE_DESCRIPTION: &str =
+    "Generated by ahitool. Changes will be overwritten by the next export.";
+
+/// Uploads the specified spreadsheet to the user's Google Drive, blocking until
+/// the upload finishes. Prompts the user for OAuth credentials if necessary. If
+/// `update` is true, updates the existing spreadsheet saved under the
+/// specified nickname (or creates one if it doesn't exist); otherwise always
+/// creates a new spreadsheet. Returns the URL of the Google Sheet.
+pub fn upload_spreadsheet_blocking(
+    nickname: SheetNickname,
+    spreadsheet: Spreadsheet,
+    update: bool,
+) -> anyhow::Result<String> {
+    let rows = spreadsheet
+        .sheets
+        .iter()
+        .flatten()
+        .chain(&spreadsheet.appended_sheets)
+        .filter_map(|sheet| sheet.data.as_ref())
+        .map(|grid_data| grid_data.row_data.len())
+        .sum();
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+    // the deadline doesn't include the time spent authorizing in the browser
+    let deadline = http::Deadline::start("upload the spreadsheet");
+    let url = run_summary::time_phase("upload", || {
+        runtime.block_on(run_with_credentials(|token| {
+            // FIXME cloning the token is a workaround because I can't get
+            // lifetimes to work correctly in run_with_credentials
+            let token = token.clone();
+            let spreadsheet = spreadsheet.clone();
+            async move {
+                deadline
+                    .run(async {
+                        if update {
+                            create_or_write_spreadsheet(&token, nickname, spreadsheet).await
+                        } else {
+                            create_spreadsheet(&token, nickname, spreadsheet).await
+                        }
+                    })
+                    .await
+            }
+        }))
+    })?;
+    info!("Made {} Google Sheets API requests so far", request_count());
+    run_summary::add_count("sheet_rows_written", rows);
+    run_summary::add_spreadsheet_url(&url);
+    Ok(url)
+}
+
+/// Where to upload a generated spreadsheet.
+#[derive(Debug, Clone, Copy)]
+pub enum Destination<'a> {
+    /// A new spreadsheet.
+    New,
+    /// The existing spreadsheet saved under the report's nickname, or a new
+    /// spreadsheet if it doesn't exist.
+    Existing,
+    /// The sheet with the specified title, written into the target range of
+    /// another spreadsheet.
+    Range(&'a TargetRange, &'a str),
+}
+
+/// Uploads the specified spreadsheet to the specified destination, blocking
+/// until the upload finishes. Returns the URL of the Google Sheet.
+pub fn upload_to_destination_blocking(
+    nickname: SheetNickname,
+    spreadsheet: Spreadsheet,
+    destination: Destination<'_>,
+) -> anyhow::Result<String> {
+    match destination {
+        Destination::New => upload_spreadsheet_blocking(nickname, spreadsheet, false),
+        Destination::Existing => upload_spreadsheet_blocking(nickname, spreadsheet, true),
+        Destination::Range(target, sheet_title) => {
+            write_sheet_to_range_blocking(target, spreadsheet, sheet_title)
+        }
+    }
+}
+
+/// A cell in a tab of an existing spreadsheet that a single sheet of a report
+/// can be written to, instead of uploading the whole report as its own
+/// spreadsheet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetRange {
+    pub spreadsheet_id: String,
+    pub tab: String,
+    pub row_index: u64,
+    pub column_index: u64,
+}
+
+/// Parses a target range of the form `SPREADSHEET_ID:TAB!CELL`, where `CELL`
+/// is in A1 notation (e.g. `KPI!B2`). If the cell is left out, then the sheet
+/// is written to the top left of the tab.
+pub fn parse_target_range(target: &str) -> Result<TargetRange, String> {
+    let malformed = || format!("expected `SPREADSHEET_ID:TAB!CELL`, but found \"{}\"", target);
+    let (spreadsheet_id, range) = target.split_once(':').ok_or_else(malformed)?;
+    let (tab, cell) = match range.rsplit_once('!') {
+        Some((tab, cell)) => (tab, Some(cell)),
+        None => (range, None),
+    };
+    // A1 notation quotes tab names that contain spaces
+    let tab = tab.strip_prefix('\'').and_then(|tab| tab.strip_suffix('\'')).unwrap_or(tab);
+    if spreadsheet_id.is_empty() || tab.is_empty() {
+        return Err(malformed());
+    }
+    let (row_index, column_index) = match cell {
+        Some(cell) => parse_a1_cell(cell).ok_or_else(|| format!("invalid cell \"{}\"", cell))?,
+        None => (0, 0),
+    };
+    Ok(TargetRange {
+        spreadsheet_id: spreadsheet_id.to_owned(),
+        tab: tab.to_owned(),
+        row_index,
+        column_index,
+    })
+}
+
+/// Parses a cell in A1 notation into its zero-based row and column indices.
+fn parse_a1_cell(cell: &str) -> Option<(u64, u64)> {
+    let digits_start = cell.find(|c: char| c.is_ascii_digit())?;
+    let (letters, digits) = cell.split_at(digits_start);
+    if letters.is_empty() || !letters.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let column = letters
+        .chars()
+        .fold(0, |column, c| column * 26 + (c.to_ascii_uppercase() as u64 - 'A' as u64 + 1));
+    let row: u64 = digits.parse().ok()?;
+    if row == 0 {
+        return None;
+    }
+    Some((row - 1, column - 1))
+}
+
+/// Writes the sheet with the specified title from the spreadsheet into the
+/// target range, blocking until the write finishes. Only the cells covered by
+/// the sheet are changed; the rest of the target spreadsheet is left untouched.
+/// Prompts the user for OAuth credentials if necessary. Returns the URL of the
+/// tab that was written to.
+pub fn write_sheet_to_range_blocking(
+    target: &TargetRange,
+    spreadsheet: Spreadsheet,
+    sheet_title: &str,
+) -> anyhow::Result<String> {
+    let Some(sheet) = spreadsheet
+        .sheets
+        .into_iter()
+        .flatten()
+        .chain(spreadsheet.appended_sheets)
+        .find(|sheet| sheet.properties.title.as_deref() == Some(sheet_title))
+    else {
+        anyhow::bail!("The report has no sheet named \"{}\"", sheet_title);
+    };
+    let rows = sheet.data.map(|grid_data| grid_data.row_data).unwrap_or_default();
+
+    let num_rows = rows.len();
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+    let deadline = http::Deadline::start("write to the target range");
+    let url = run_summary::time_phase("upload", || {
+        runtime.block_on(run_with_credentials(|token| {
+            let token = token.clone();
+            let rows = rows.clone();
+            async move { deadline.run(write_to_range(&token, target, rows)).await }
+        }))
+    })?;
+    info!("Made {} Google Sheets API requests so far", request_count());
+    run_summary::add_count("sheet_rows_written", num_rows);
+    run_summary::add_spreadsheet_url(&url);
+    Ok(url)
+}
+
+async fn write_to_range(
+    creds: &Token,
+    target: &TargetRange,
+    rows: Vec<spreadsheet::RowData>,
+) -> Result<String, TryWithCredentialsError> {
+    let client = http::client()?;
+    let spreadsheet_id = &target.spreadsheet_id;
+    let _lock = ExportLock::acquire(spreadsheet_id)?;
+
+    // find the ID of the target tab
+    let existing_spreadsheet: serde_json::Value = {
+        let url = reqwest::Url::parse_with_params(
+            &format!("{ENDPOINT_SPREADSHEETS}/{spreadsheet_id}"),
+            &[("fields", "sheets.properties")],
+        )
+        .map_err(anyhow::Error::from)?;
+        let request = client.get(url).bearer_auth(creds.access_token().secret());
+        let response =
+            quota::send(request, "get the target sheet").await.map_err(anyhow::Error::from)?;
+        if !response.status().is_success() {
+            if response.status() == StatusCode::UNAUTHORIZED {
+                return Err(TryWithCredentialsError::Unauthorized(anyhow!(
+                    "Request to get target sheet was unauthorized with status code: {}",
+                    response.status()
+                )));
+            } else {
+                return Err(TryWithCredentialsError::Other(anyhow!(
+                    "Request to get target sheet failed with status code: {}",
+                    response.status()
+                )));
+            }
+        }
+        response.json().await.map_err(anyhow::Error::from)?
+    };
+    let Some(existing) = existing_sheets(&existing_spreadsheet)
+        .into_iter()
+        .find(|sheet| sheet.title.as_deref() == Some(target.tab.as_str()))
+    else {
+        return Err(TryWithCredentialsError::Other(anyhow!(
+            "The target spreadsheet has no tab named \"{}\"",
+            target.tab
+        )));
+    };
+
+    // expand the target tab if the rows don't fit, without shrinking it
+    let mut requests = Vec::new();
+    let needed = GridData {
+        start_row: target.row_index,
+        start_column: target.column_index,
+        row_data: rows.clone(),
+    }
+    .grid_properties();
+    let grid = existing.grid.union(needed);
+    if grid != existing.grid {
+        requests.push(update_sheet_properties(
+            existing.sheet_id,
+            SheetProperties { grid_properties: Some(grid), ..Default::default() },
+        ));
+    }
+    requests.push(Request::UpdateCells {
+        rows,
+        fields: spreadsheet::CELL_FIELDS,
+        start: GridCoordinate {
+            sheet_id: existing.sheet_id,
+            row_index: target.row_index,
+            column_index: target.column_index,
+        },
+    });
+    batch_update(&client, creds, spreadsheet_id, &requests).await?;
+
+    let url = format!(
+        "https://docs.google.com/spreadsheets/d/{spreadsheet_id}/edit#gid={}",
+        existing.sheet_id
+    );
+    info!("Wrote to {} in Google Sheet at {}", target.tab, url);
+    Ok(url)
+}
+
+/// Reads the rows of the sheet that was generated with the specified title in
+/// the spreadsheet saved under the nickname, blocking until the read finishes.
+/// The rows start at the top left corner of the sheet. Returns `None` if there
+/// is no such spreadsheet or sheet. Prompts the user for OAuth credentials if
+/// necessary.
+pub fn read_sheet_blocking(
+    nickname: SheetNickname,
+    tab: &str,
+) -> anyhow::Result<Option<Vec<RowData>>> {
+    let Some(spreadsheet_id) = read_known_sheets_file(nickname)? else {
+        return Ok(None);
+    };
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+    let deadline = http::Deadline::start("read the sheet");
+    let rows = runtime.block_on(run_with_credentials(|token| {
+        let token = token.clone();
+        let spreadsheet_id = spreadsheet_id.clone();
+        async move { deadline.run(read_sheet(&token, &spreadsheet_id, tab)).await }
+    }))?;
+    info!("Made {} Google Sheets API requests so far", request_count());
+    Ok(rows)
+}
+
+async fn read_sheet(
+    creds: &Token,
+    spreadsheet_id: &str,
+    tab: &str,
+) -> Result<Option<Vec<RowData>>, TryWithCredentialsError> {
+    let client = http::client()?;
+    let existing_spreadsheet: serde_json::Value = {
+        let url = reqwest::Url::parse_with_params(
+            &format!("{ENDPOINT_SPREADSHEETS}/{spreadsheet_id}"),
+            &[("fields", "sheets(properties,developerMetadata)")],
+        )
+        .map_err(anyhow::Error::from)?;
+        let request = client.get(url).bearer_auth(creds.access_token().secret());
+        let response =
+            quota::send(request, "get the sheets to read").await.map_err(anyhow::Error::from)?;
+        if !response.status().is_success() {
+            if response.status() == StatusCode::UNAUTHORIZED {
+                return Err(TryWithCredentialsError::Unauthorized(anyhow!(
+                    "Request to get the sheets was unauthorized with status code: {}",
+                    response.status()
+                )));
+            } else {
+                return Err(TryWithCredentialsError::Other(anyhow!(
+                    "Request to get the sheets failed with status code: {}",
+                    response.status()
+                )));
+            }
+        }
+        response.json().await.map_err(anyhow::Error::from)?
+    };
+    let existing_sheets = existing_sheets(&existing_spreadsheet);
+    let Some(existing) = find_existing_sheet(&existing_sheets, tab) else {
+        return Ok(None);
+    };
+    let Some(title) = existing.title.as_deref() else {
+        return Ok(None);
+    };
+    let mut rows = get_sheet_rows(&client, creds, spreadsheet_id, &[title]).await?;
+    Ok(Some(rows.remove(&existing.sheet_id).unwrap_or_default()))
+}
+
 /// Searches the known sheets file for an existing spreadsheet with the
 /// specified key. Updates that spreadsheet with the specified data, or creates
 /// a new spreadsheet in the user's Google Drive if it doesn't exist. Returns
@@ -48,15 +401,70 @@ pub async fn create_or_write_spreadsheet(
         Ok(None) => None,
         Ok(Some(spreadsheet_id)) => Some(spreadsheet_id),
     };
+    let known_sheet = match known_sheet {
+        Some(spreadsheet_id) if !spreadsheet_exists(creds, &spreadsheet_id).await? => {
+            warn!("The spreadsheet with ID {} was deleted or moved to the trash", spreadsheet_id);
+            run_summary::add_notice(&format!(
+                "The saved spreadsheet with ID {} was deleted or moved to the trash, so a new \
+                 spreadsheet was created in its place",
+                spreadsheet_id
+            ));
+            None
+        }
+        known_sheet => known_sheet,
+    };
     if let Some(spreadsheet_id) = known_sheet {
         info!("Found existing sheet with ID {}", spreadsheet_id);
-        Ok(update_spreadsheet(creds, &spreadsheet_id, spreadsheet).await?)
+        Ok(update_spreadsheet(creds, nickname, &spreadsheet_id, spreadsheet).await?)
     } else {
         info!("No existing spreadsheet found, creating a new one");
         Ok(create_spreadsheet(creds, nickname, spreadsheet).await?)
     }
 }
 
+/// Checks with Google Drive whether the spreadsheet still exists and is not in
+/// the trash, since updating a trashed spreadsheet succeeds without the user
+/// ever seeing the result.
+async fn spreadsheet_exists(
+    creds: &Token,
+    spreadsheet_id: &str,
+) -> Result<bool, TryWithCredentialsError> {
+    let url = reqwest::Url::parse_with_params(
+        &format!("{ENDPOINT_DRIVE_FILES}/{spreadsheet_id}"),
+        &[("fields", "trashed")],
+    )
+    .map_err(anyhow::Error::from)?;
+    let request = http::client()?.get(url).bearer_auth(creds.access_token().secret());
+    let response = quota::send(request, "check that the spreadsheet exists")
+        .await
+        .map_err(anyhow::Error::from)?;
+
+    match response.status() {
+        StatusCode::NOT_FOUND => return Ok(false),
+        StatusCode::UNAUTHORIZED => {
+            return Err(TryWithCredentialsError::Unauthorized(anyhow!(
+                "Request to check the spreadsheet was unauthorized with status code: {}",
+                response.status()
+            )))
+        }
+        status if !status.is_success() => {
+            return Err(TryWithCredentialsError::Other(anyhow!(
+                "Request to check the spreadsheet failed with status code: {}",
+                status
+            )))
+        }
+        _ => {}
+    }
+
+    #[derive(Deserialize)]
+    struct ApiResponse {
+        #[serde(default)]
+        trashed: bool,
+    }
+    let ApiResponse { trashed } = response.json().await.map_err(anyhow::Error::from)?;
+    Ok(!trashed)
+}
+
 /// Creates the specified spreadsheet in the user's Google Drive. Saves the
 /// created spreadsheet ID under the specified nickname in the known sheets file
 /// and return the URL of the created sheet.
@@ -65,16 +473,54 @@ pub async fn create_spreadsheet(
     nickname: SheetNickname,
     spreadsheet: Spreadsheet,
 ) -> Result<String, TryWithCredentialsError> {
+    // a new spreadsheet has no existing rows to append to
+    let mut spreadsheet = spreadsheet;
+    if !spreadsheet.appended_sheets.is_empty() {
+        let appended_sheets = std::mem::take(&mut spreadsheet.appended_sheets);
+        spreadsheet.sheets.get_or_insert_with(Vec::new).extend(appended_sheets);
+    }
+    for sheet in spreadsheet.sheets.iter_mut().flatten() {
+        fit_grid_to_data(sheet);
+    }
+    // the configured user columns of the sheets with a key column are added
+    // to the right of the generated columns, with only their headers
+    let configured_columns = user_columns::configured();
+    let mut generated_columns = HashMap::new();
+    let mut checkboxes = HashMap::new();
+    for sheet in spreadsheet.sheets.iter_mut().flatten() {
+        let Some(title) = sheet.properties.title.clone() else {
+            continue;
+        };
+        if let Some(data) = &sheet.data {
+            checkboxes.insert(title.clone(), checkbox_columns(data));
+        }
+        let (Some(&key_column), Some(data)) =
+            (spreadsheet.key_columns.get(&title), sheet.data.as_mut())
+        else {
+            continue;
+        };
+        let columns = data.grid_properties().column_count;
+        generated_columns.insert(title.clone(), columns);
+        let (Some(names), Some(header)) = (configured_columns.get(&title), data.row_data.first())
+        else {
+            continue;
+        };
+        let user_cells = UserCells::default().with_columns(header, key_column, names);
+        let user_rows = user_cells.arrange(&data.row_data, key_column);
+        let width = (columns - data.start_column) as usize;
+        for (row, user_row) in data.row_data.iter_mut().zip(user_rows) {
+            row.values.resize_with(width, Default::default);
+            row.values.extend(user_row.values);
+        }
+        sheet.properties.grid_properties = Some(data.grid_properties());
+    }
+
     let url = reqwest::Url::parse(ENDPOINT_SPREADSHEETS).expect("hardcoded URL should be valid");
-    let client = reqwest::Client::new();
+    let client = http::client()?;
     trace!("Sending request to create sheet");
-    let response = client
-        .post(url)
-        .bearer_auth(creds.access_token().secret())
-        .json(&spreadsheet)
-        .send()
-        .await
-        .map_err(anyhow::Error::from)?;
+    let request = client.post(url).bearer_auth(creds.access_token().secret()).json(&spreadsheet);
+    let response =
+        quota::send(request, "create the spreadsheet").await.map_err(anyhow::Error::from)?;
 
     if !response.status().is_success() {
         if response.status() == StatusCode::UNAUTHORIZED {
@@ -96,10 +542,30 @@ pub async fn create_spreadsheet(
         spreadsheet_id: String,
         #[serde(rename = "spreadsheetUrl")]
         spreadsheet_url: String,
+        #[serde(default)]
+        sheets: Vec<CreatedSheet>,
     }
-    let ApiResponse { spreadsheet_id, spreadsheet_url } =
+    #[derive(Deserialize)]
+    struct CreatedSheet {
+        properties: SheetProperties,
+    }
+    let ApiResponse { spreadsheet_id, spreadsheet_url, sheets } =
         response.json().await.map_err(anyhow::Error::from)?;
 
+    let mut requests = Vec::new();
+    for CreatedSheet { properties } in sheets {
+        // the API leaves out sheet IDs of 0
+        let sheet_id = properties.sheet_id.unwrap_or(0);
+        let title = properties.title.unwrap_or_default();
+        let columns = generated_columns.get(&title).copied();
+        let checkboxes = checkboxes.remove(&title).unwrap_or_default();
+        requests.push(tag_sheet(sheet_id, nickname, &title, columns));
+        requests.push(protect_sheet(sheet_id, columns, checkboxes));
+    }
+    if let Err(e) = batch_update(&client, creds, &spreadsheet_id, &requests).await {
+        warn!("Failed to tag and protect the generated sheets: {}", e);
+    }
+
     debug!(
         "Saving the spreadsheet under the nickname {}",
         serde_json::to_string(&nickname).expect("should work")
@@ -114,21 +580,21 @@ pub async fn create_spreadsheet(
 
 async fn update_spreadsheet(
     creds: &Token,
+    nickname: SheetNickname,
     spreadsheet_id: &str,
     spreadsheet: Spreadsheet,
 ) -> Result<String, TryWithCredentialsError> {
-    let client = reqwest::Client::new();
+    let _lock = ExportLock::acquire(spreadsheet_id)?;
+    let client = http::client()?;
 
     // get the current spreadsheet data so we can merge the new data with it
-    let existing_spreadsheet: Spreadsheet = {
+    let existing_spreadsheet: serde_json::Value = {
         let url = reqwest::Url::parse(&format!("{ENDPOINT_SPREADSHEETS}/{spreadsheet_id}"))
             .map_err(anyhow::Error::from)?;
-        let request = client
-            .get(url)
-            .bearer_auth(creds.access_token().secret())
-            .build()
+        let request = client.get(url).bearer_auth(creds.access_token().secret());
+        let response = quota::send(request, "get the current spreadsheet")
+            .await
             .map_err(anyhow::Error::from)?;
-        let response = client.execute(request).await.map_err(anyhow::Error::from)?;
 
         if !response.status().is_success() {
             if response.status() == StatusCode::UNAUTHORIZED {
@@ -147,21 +613,51 @@ async fn update_spreadsheet(
         response.json().await.map_err(anyhow::Error::from)?
     };
 
-    // keep track of existing sheet IDs so we can update existing sheets, as
+    // keep track of existing sheets so we can update existing sheets, as
     // as well as delete sheets that we don't care about, as well as assign
     // sheet ids to new sheets without conflicts
-    let mut title_to_sheet_id = HashMap::new();
-    let mut existing_sheet_ids = HashSet::new();
-    if let Some(sheets) = existing_spreadsheet.sheets {
-        for sheet in sheets {
-            let SheetProperties { sheet_id, title } = sheet.properties;
-            if let (Some(sheet_id), Some(title)) = (sheet_id, title) {
-                title_to_sheet_id.insert(title, sheet_id);
-            }
-            if let Some(sheet_id) = sheet_id {
-                existing_sheet_ids.insert(sheet_id);
-            }
+    let mut existing_sheets = existing_sheets(&existing_spreadsheet);
+    let mut existing_sheet_ids: HashSet<_> =
+        existing_sheets.iter().map(|sheet| sheet.sheet_id).collect();
+
+    // read back the cells that users entered next to the rows of the sheets
+    // with a key column, before the rows are rewritten
+    let keyed_sheets: Vec<_> = spreadsheet
+        .sheets
+        .iter()
+        .flatten()
+        .filter_map(|sheet| {
+            let title = sheet.properties.title.as_deref()?;
+            let key_column = *spreadsheet.key_columns.get(title)?;
+            let data = sheet.data.as_ref()?;
+            let existing = find_existing_sheet(&existing_sheets, title);
+            Some((title.to_owned(), existing, data, key_column))
+        })
+        .collect();
+    let existing_titles: Vec<_> = keyed_sheets
+        .iter()
+        .filter_map(|(_, existing, _, _)| (*existing)?.title.as_deref())
+        .collect();
+    let mut existing_rows = if existing_titles.is_empty() {
+        HashMap::new()
+    } else {
+        get_sheet_rows(&client, creds, spreadsheet_id, &existing_titles).await?
+    };
+    let configured_columns = user_columns::configured();
+    let mut user_cells = HashMap::new();
+    for (title, existing, data, key_column) in keyed_sheets {
+        let mut cells = UserCells::default();
+        if let Some(existing) = existing {
+            let rows = existing_rows.remove(&existing.sheet_id).unwrap_or_default();
+            let columns = existing.columns.unwrap_or(data.grid_properties().column_count);
+            let key_column = data.start_column as usize + key_column;
+            cells = UserCells::read(rows, key_column, columns as usize);
+        }
+        if let (Some(names), Some(header)) = (configured_columns.get(&title), data.row_data.first())
+        {
+            cells = cells.with_columns(header, key_column, names);
         }
+        user_cells.insert(title, cells);
     }
 
     // prepare the correct JSON to send with the `batchUpdate` request. see
@@ -176,58 +672,178 @@ async fn update_spreadsheet(
         });
     }
 
-    // update the content of the sheets
-    if let Some(sheets) = spreadsheet.sheets {
-        for sheet in sheets {
-            if sheet.properties.sheet_id.is_some() {
-                warn!("sheet ID is ignored when updating a spreadsheet; use the title instead");
+    // finds the existing counterpart of the sheet, or pushes requests to
+    // create a new sheet if it doesn't have one. returns the sheet ID and
+    // whether the sheet already existed. sheets without an index are moved
+    // to the position in which they are listed, so that the order of the tabs
+    // doesn't depend on which sheets already existed
+    let mut next_index = 0;
+    let mut find_or_add_sheet = |properties: SheetProperties,
+                                 columns: Option<u64>,
+                                 checkboxes: Vec<u64>,
+                                 requests: &mut Vec<Request>| {
+        if properties.sheet_id.is_some() {
+            warn!("sheet ID is ignored when updating a spreadsheet; use the title instead");
+        }
+        let properties =
+            SheetProperties { index: Some(properties.index.unwrap_or(next_index)), ..properties };
+        next_index += 1;
+        let title = properties.title.clone().unwrap_or_default();
+        if let Some(existing) = take_existing_sheet(&mut existing_sheets, &title) {
+            // a sheet that the user renamed keeps its new title
+            requests.push(update_sheet_properties(
+                existing.sheet_id,
+                SheetProperties { title: None, ..properties },
+            ));
+            match existing.metadata_id {
+                None => requests.push(tag_sheet(existing.sheet_id, nickname, &title, columns)),
+                Some(metadata_id) if existing.columns != columns => requests.push(retag_sheet(
+                    metadata_id,
+                    existing.sheet_id,
+                    nickname,
+                    &title,
+                    columns,
+                )),
+                Some(_) => {}
             }
-            let sheet_id = 'sheet_id: {
-                if let Some(title) = &sheet.properties.title {
-                    if let Some(sheet_id) = title_to_sheet_id.remove(title) {
-                        // we would push a request to update the sheet
-                        // properties here, but there are none to update, since
-                        // sheet_id and title are the only fields we currently
-                        // support and they are already known to match at this
-                        // point
-
-                        break 'sheet_id sheet_id;
-                    }
+            match existing.protection {
+                None => requests.push(protect_sheet(existing.sheet_id, columns, checkboxes)),
+                Some(protection)
+                    if protection.columns != columns || protection.checkboxes != checkboxes =>
+                {
+                    requests.push(reprotect_sheet(
+                        protection.id,
+                        existing.sheet_id,
+                        columns,
+                        checkboxes,
+                    ))
                 }
-                // getting here means that the sheet does not have an existing
-                // counterpart. create a new sheet
+                Some(_) => {}
+            }
+            return (existing.sheet_id, true);
+        }
 
-                // find a sheet ID that is not already in use
-                let mut sheet_id = 0;
-                while existing_sheet_ids.contains(&sheet_id) {
-                    sheet_id += 1;
-                }
-                existing_sheet_ids.insert(sheet_id);
+        // getting here means that the sheet does not have an existing
+        // counterpart. create a new sheet with a sheet ID that is not
+        // already in use
+        let sheet_id = unused_sheet_id(&mut existing_sheet_ids);
+        requests.push(Request::AddSheet {
+            properties: SheetProperties { sheet_id: Some(sheet_id), ..properties },
+        });
+        requests.push(tag_sheet(sheet_id, nickname, &title, columns));
+        requests.push(protect_sheet(sheet_id, columns, checkboxes));
+        (sheet_id, false)
+    };
 
-                // push a request to add a new sheet with the new id
-                requests.push(Request::AddSheet {
-                    properties: SheetProperties { sheet_id: Some(sheet_id), ..sheet.properties },
-                });
-                sheet_id
-            };
+    // the rows written by the previous update can be skipped if they haven't
+    // changed, as long as nothing else changed the spreadsheet since
+    let version = drive_version(creds, spreadsheet_id).await;
+    let previous_payloads = payloads::load(spreadsheet_id)
+        .filter(|previous| previous.version.is_some() && previous.version == version)
+        .map(|previous| previous.sheets)
+        .unwrap_or_default();
+    let mut written_payloads = HashMap::new();
+    let mut rows_skipped = 0;
 
-            if let Some(grid_data) = sheet.data {
-                // push a request to update the content of the sheet
+    // update the content of the sheets
+    if let Some(sheets) = spreadsheet.sheets {
+        for mut sheet in sheets {
+            fit_grid_to_data(&mut sheet);
+            let title = sheet.properties.title.clone().unwrap_or_default();
+            let key_column = spreadsheet.key_columns.get(&title).copied();
+            let columns = key_column
+                .and(sheet.data.as_ref())
+                .map(|grid_data| grid_data.grid_properties().column_count);
+            // the cells that users entered are moved to the right of the
+            // generated columns, next to the row with the same key
+            let user_rows = match (key_column, user_cells.remove(&title), &sheet.data) {
+                (Some(key_column), Some(user_cells), Some(grid_data)) if user_cells.width() > 0 => {
+                    let grid =
+                        sheet.properties.grid_properties.get_or_insert_with(Default::default);
+                    grid.column_count += user_cells.width() as u64;
+                    Some(user_cells.arrange(&grid_data.row_data, key_column))
+                }
+                _ => None,
+            };
+            let checkboxes = sheet.data.as_ref().map(checkbox_columns).unwrap_or_default();
+            let (sheet_id, existed) =
+                find_or_add_sheet(sheet.properties, columns, checkboxes, &mut requests);
+            let Some(grid_data) = sheet.data else {
+                continue;
+            };
+            if let (Some(user_rows), Some(columns)) = (user_rows, columns) {
                 requests.push(Request::UpdateCells {
-                    rows: grid_data.row_data,
-                    fields: "userEnteredValue",
+                    rows: user_rows,
+                    fields: spreadsheet::USER_CELL_FIELDS,
                     start: GridCoordinate {
                         sheet_id,
                         row_index: grid_data.start_row,
+                        column_index: columns,
+                    },
+                });
+            }
+            let payload = payloads::SheetPayload::of(&grid_data);
+            let changed_rows = previous_payloads
+                .get(&title)
+                .filter(|_| existed)
+                .and_then(|previous| payload.changed_rows(previous))
+                .unwrap_or_else(|| std::iter::once(0..grid_data.row_data.len()).collect());
+            rows_skipped += grid_data.row_data.len()
+                - changed_rows.iter().map(ExactSizeIterator::len).sum::<usize>();
+            // push requests to update the content of the sheet
+            for range in changed_rows {
+                requests.push(Request::UpdateCells {
+                    rows: grid_data.row_data[range.clone()].to_vec(),
+                    fields: spreadsheet::CELL_FIELDS,
+                    start: GridCoordinate {
+                        sheet_id,
+                        row_index: grid_data.start_row + range.start as u64,
                         column_index: grid_data.start_column,
                     },
                 })
             }
+            written_payloads.insert(title, payload);
+        }
+    }
+
+    // append the rows of the appended sheets, or create them with their
+    // headers if they don't exist yet
+    for sheet in spreadsheet.appended_sheets {
+        let (sheet_id, existed) =
+            find_or_add_sheet(sheet.properties, None, Vec::new(), &mut requests);
+        let Some(grid_data) = sheet.data else {
+            continue;
+        };
+        if existed {
+            requests.push(Request::AppendCells {
+                sheet_id,
+                rows: grid_data.row_data.into_iter().skip(1).collect(),
+                fields: spreadsheet::CELL_FIELDS,
+            });
+        } else {
+            // the grid of an appended sheet is only sized when it is created,
+            // since the appended rows expand it afterwards
+            requests.push(update_sheet_properties(
+                sheet_id,
+                SheetProperties {
+                    grid_properties: Some(grid_data.grid_properties()),
+                    ..Default::default()
+                },
+            ));
+            requests.push(Request::UpdateCells {
+                rows: grid_data.row_data,
+                fields: spreadsheet::CELL_FIELDS,
+                start: GridCoordinate {
+                    sheet_id,
+                    row_index: grid_data.start_row,
+                    column_index: grid_data.start_column,
+                },
+            });
         }
     }
 
     // remove the sheets that don't exist anymore
-    for (_title, sheet_id) in title_to_sheet_id {
+    for ExistingSheet { sheet_id, .. } in existing_sheets {
         requests.push(Request::DeleteSheet { sheet_id });
     }
 
@@ -240,13 +856,9 @@ async fn update_spreadsheet(
 
     let url = reqwest::Url::parse(&format!("{ENDPOINT_SPREADSHEETS}/{spreadsheet_id}:batchUpdate"))
         .map_err(anyhow::Error::from)?;
-    let request = client
-        .post(url)
-        .bearer_auth(creds.access_token().secret())
-        .json(&request_body)
-        .build()
-        .map_err(anyhow::Error::from)?;
-    let response = client.execute(request).await.map_err(anyhow::Error::from)?;
+    let request = client.post(url).bearer_auth(creds.access_token().secret()).json(&request_body);
+    let response =
+        quota::send(request, "update the spreadsheet").await.map_err(anyhow::Error::from)?;
     if !response.status().is_success() {
         if response.status() == StatusCode::UNAUTHORIZED {
             return Err(TryWithCredentialsError::Unauthorized(anyhow!(
@@ -283,44 +895,417 @@ async fn update_spreadsheet(
         )
     };
     info!("Updated Google Sheet at {}", url);
+    if rows_skipped > 0 {
+        info!("Skipped {} rows that were unchanged since the previous update", rows_skipped);
+    }
+    run_summary::add_count("sheet_rows_unchanged", rows_skipped);
+
+    let payloads = payloads::Payloads {
+        version: drive_version(creds, spreadsheet_id).await,
+        sheets: written_payloads,
+    };
+    if let Err(e) = payloads::save(spreadsheet_id, &payloads) {
+        warn!("Failed to save the rows written to the spreadsheet: {}", e);
+    }
     Ok(url)
 }
 
+/// The version of the spreadsheet in Google Drive, which changes whenever the
+/// spreadsheet does, or `None` if it can't be found out.
+async fn drive_version(creds: &Token, spreadsheet_id: &str) -> Option<String> {
+    #[derive(Deserialize)]
+    struct ApiResponse {
+        version: String,
+    }
+    let result: anyhow::Result<ApiResponse> = async {
+        let url = reqwest::Url::parse_with_params(
+            &format!("{ENDPOINT_DRIVE_FILES}/{spreadsheet_id}"),
+            &[("fields", "version")],
+        )?;
+        let request = http::client()?.get(url).bearer_auth(creds.access_token().secret());
+        let response = quota::send(request, "get the version of the spreadsheet").await?;
+        if !response.status().is_success() {
+            anyhow::bail!("Request failed with status code: {}", response.status());
+        }
+        Ok(response.json().await?)
+    }
+    .await;
+    match result {
+        Ok(ApiResponse { version }) => Some(version),
+        Err(e) => {
+            warn!("Failed to get the version of the spreadsheet: {}", e);
+            None
+        }
+    }
+}
+
+/// A request to protect the sheet in warning-only mode, so that viewers don't
+/// accidentally edit data that will be overwritten by the next export. If the
+/// sheet has a key column, then only its generated `columns` are protected, so
+/// that users can fill in their own columns to the right of them. The columns
+/// with `checkboxes` are left unprotected, so that users can tick them.
+fn protect_sheet(sheet_id: u64, columns: Option<u64>, checkboxes: Vec<u64>) -> Request {
+    Request::AddProtectedRange {
+        protected_range: generated_range(None, sheet_id, columns, checkboxes),
+    }
+}
+
+/// A request to change which columns of the sheet are protected, e.g. because
+/// the number of generated columns changed.
+fn reprotect_sheet(
+    protected_range_id: i64,
+    sheet_id: u64,
+    columns: Option<u64>,
+    checkboxes: Vec<u64>,
+) -> Request {
+    Request::UpdateProtectedRange {
+        protected_range: generated_range(Some(protected_range_id), sheet_id, columns, checkboxes),
+        fields: "range,unprotectedRanges",
+    }
+}
+
+fn generated_range(
+    protected_range_id: Option<i64>,
+    sheet_id: u64,
+    columns: Option<u64>,
+    checkboxes: Vec<u64>,
+) -> ProtectedRange {
+    let column_range = |start_column_index, end_column_index| GridRange {
+        sheet_id,
+        start_column_index,
+        end_column_index,
+    };
+    ProtectedRange {
+        protected_range_id,
+        range: column_range(None, columns),
+        description: PROTECTED_RANGE_DESCRIPTION.to_owned(),
+        unprotected_ranges: checkboxes
+            .into_iter()
+            .map(|column| column_range(Some(column), Some(column + 1)))
+            .collect(),
+        warning_only: true,
+    }
+}
+
+/// The columns of the sheet (counting from its left edge) that contain
+/// checkboxes, in order.
+fn checkbox_columns(data: &GridData) -> Vec<u64> {
+    let mut columns: Vec<_> = data
+        .row_data
+        .iter()
+        .flat_map(|row| row.values.iter().enumerate())
+        .filter(|(_, cell)| matches!(cell.user_entered_value, Some(ExtendedValue::Checkbox(_))))
+        .map(|(column, _)| data.start_column + column as u64)
+        .collect();
+    columns.sort_unstable();
+    columns.dedup();
+    columns
+}
+
+/// A request to tag the sheet as generated by ahitool for the specified
+/// spreadsheet with the specified title.
+fn tag_sheet(sheet_id: u64, nickname: SheetNickname, tab: &str, columns: Option<u64>) -> Request {
+    Request::CreateDeveloperMetadata {
+        developer_metadata: sheet_metadata(sheet_id, nickname, tab, columns),
+    }
+}
+
+/// A request to replace the tag of a sheet that is already tagged, e.g. because
+/// the number of generated columns changed.
+fn retag_sheet(
+    metadata_id: i64,
+    sheet_id: u64,
+    nickname: SheetNickname,
+    tab: &str,
+    columns: Option<u64>,
+) -> Request {
+    Request::UpdateDeveloperMetadata {
+        data_filters: vec![DataFilter {
+            developer_metadata_lookup: DeveloperMetadataLookup { metadata_id },
+        }],
+        developer_metadata: sheet_metadata(sheet_id, nickname, tab, columns),
+        fields: "metadataValue",
+    }
+}
+
+fn sheet_metadata(
+    sheet_id: u64,
+    nickname: SheetNickname,
+    tab: &str,
+    columns: Option<u64>,
+) -> DeveloperMetadata {
+    let metadata = SheetMetadata {
+        report: nickname,
+        tab: tab.to_owned(),
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        columns,
+    };
+    DeveloperMetadata {
+        metadata_key: METADATA_KEY.to_owned(),
+        metadata_value: serde_json::to_string(&metadata).expect("metadata should serialize"),
+        location: DeveloperMetadataLocation { sheet_id },
+        visibility: "DOCUMENT".to_owned(),
+    }
+}
+
+/// Sizes the grid of the sheet to fit its data, unless its size is already
+/// specified.
+fn fit_grid_to_data(sheet: &mut Sheet) {
+    if let Some(data) = &sheet.data {
+        sheet.properties.grid_properties.get_or_insert_with(|| data.grid_properties());
+    }
+}
+
+/// A request to update the properties of the sheet that are specified. The
+/// title, if specified, is also updated.
+fn update_sheet_properties(sheet_id: u64, properties: SheetProperties) -> Request {
+    let mut fields = Vec::new();
+    if properties.title.is_some() {
+        fields.push("title");
+    }
+    if properties.index.is_some() {
+        fields.push("index");
+    }
+    if properties.tab_color.is_some() {
+        fields.push("tabColor");
+    }
+    if properties.grid_properties.is_some() {
+        fields.extend(["gridProperties.rowCount", "gridProperties.columnCount"]);
+    }
+    Request::UpdateSheetProperties {
+        properties: SheetProperties { sheet_id: Some(sheet_id), ..properties },
+        fields: fields.join(","),
+    }
+}
+
+/// The protected range that covers the generated columns of an existing sheet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Protection {
+    id: i64,
+    /// The column after the last protected column, or `None` if the whole
+    /// sheet is protected.
+    columns: Option<u64>,
+    /// The columns with checkboxes, which are left unprotected.
+    checkboxes: Vec<u64>,
+}
+
+/// A sheet in an existing spreadsheet.
+#[derive(Debug, PartialEq, Eq)]
+struct ExistingSheet {
+    sheet_id: u64,
+    title: Option<String>,
+    /// The title that ahitool generated the sheet with, if it is tagged by
+    /// `tag_sheet`.
+    tab: Option<String>,
+    /// The ID of the tag, if the sheet is tagged.
+    metadata_id: Option<i64>,
+    /// The number of generated columns, if the sheet is tagged and has a key
+    /// column.
+    columns: Option<u64>,
+    /// The protected range added by `protect_sheet`, if any.
+    protection: Option<Protection>,
+    grid: GridProperties,
+}
+
+/// The sheets of the spreadsheet, as returned by the API.
+fn existing_sheets(spreadsheet: &serde_json::Value) -> Vec<ExistingSheet> {
+    fn as_slice(value: &serde_json::Value) -> &[serde_json::Value] {
+        value.as_array().map(Vec::as_slice).unwrap_or_default()
+    }
+    as_slice(&spreadsheet["sheets"])
+        .iter()
+        .map(|sheet| {
+            let tag = as_slice(&sheet["developerMetadata"])
+                .iter()
+                .filter(|metadata| metadata["metadataKey"] == METADATA_KEY)
+                .find_map(|metadata| {
+                    let value = metadata["metadataValue"].as_str()?;
+                    let value = serde_json::from_str::<SheetMetadata>(value).ok()?;
+                    Some((metadata["metadataId"].as_i64(), value))
+                });
+            let (metadata_id, tab, columns) = match tag {
+                Some((metadata_id, metadata)) => {
+                    (metadata_id, Some(metadata.tab), metadata.columns)
+                }
+                None => (None, None, None),
+            };
+            let protection = as_slice(&sheet["protectedRanges"])
+                .iter()
+                .find(|range| range["description"] == PROTECTED_RANGE_DESCRIPTION)
+                .map(|range| Protection {
+                    id: range["protectedRangeId"].as_i64().unwrap_or(0),
+                    columns: range["range"]["endColumnIndex"].as_u64(),
+                    // the API leaves out start indexes of 0
+                    checkboxes: as_slice(&range["unprotectedRanges"])
+                        .iter()
+                        .map(|range| range["startColumnIndex"].as_u64().unwrap_or(0))
+                        .collect(),
+                });
+            ExistingSheet {
+                // the API leaves out sheet IDs of 0
+                sheet_id: sheet["properties"]["sheetId"].as_u64().unwrap_or(0),
+                title: sheet["properties"]["title"].as_str().map(str::to_owned),
+                tab,
+                metadata_id,
+                columns,
+                protection,
+                grid: serde_json::from_value(sheet["properties"]["gridProperties"].clone())
+                    .unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+/// Removes the existing sheet that the generated sheet with the specified
+/// title should replace: the sheet that was generated with that title, even
+/// if it was renamed since, or otherwise an untagged sheet with that title.
+fn take_existing_sheet(existing: &mut Vec<ExistingSheet>, title: &str) -> Option<ExistingSheet> {
+    let index = existing_sheet_position(existing, title)?;
+    Some(existing.remove(index))
+}
+
+/// Like `take_existing_sheet`, but leaves the sheet in the list.
+fn find_existing_sheet<'a>(
+    existing: &'a [ExistingSheet],
+    title: &str,
+) -> Option<&'a ExistingSheet> {
+    existing_sheet_position(existing, title).map(|index| &existing[index])
+}
+
+fn existing_sheet_position(existing: &[ExistingSheet], title: &str) -> Option<usize> {
+    existing.iter().position(|sheet| sheet.tab.as_deref() == Some(title)).or_else(|| {
+        existing
+            .iter()
+            .position(|sheet| sheet.tab.is_none() && sheet.title.as_deref() == Some(title))
+    })
+}
+
+/// Gets the rows of the sheets with the titles, starting at the top left corner
+/// of each sheet, by sheet ID. Only the values and notes of the cells are read.
+async fn get_sheet_rows(
+    client: &reqwest::Client,
+    creds: &Token,
+    spreadsheet_id: &str,
+    titles: &[&str],
+) -> Result<HashMap<u64, Vec<RowData>>, TryWithCredentialsError> {
+    let mut params = vec![(
+        "fields",
+        "sheets(properties.sheetId,data.rowData.values(userEnteredValue,note))".to_owned(),
+    )];
+    // a range that is only the title of a sheet covers the whole sheet
+    params
+        .extend(titles.iter().map(|title| ("ranges", format!("'{}'", title.replace('\'', "''")))));
+    let url = reqwest::Url::parse_with_params(
+        &format!("{ENDPOINT_SPREADSHEETS}/{spreadsheet_id}"),
+        &params,
+    )
+    .map_err(anyhow::Error::from)?;
+    let request = client.get(url).bearer_auth(creds.access_token().secret());
+    let response = quota::send(request, "read the cells entered by users")
+        .await
+        .map_err(anyhow::Error::from)?;
+    if !response.status().is_success() {
+        if response.status() == StatusCode::UNAUTHORIZED {
+            return Err(TryWithCredentialsError::Unauthorized(anyhow!(
+                "Request to read the sheets was unauthorized with status code: {}",
+                response.status()
+            )));
+        } else {
+            return Err(TryWithCredentialsError::Other(anyhow!(
+                "Request to read the sheets failed with status code: {}",
+                response.status()
+            )));
+        }
+    }
+    let response: serde_json::Value = response.json().await.map_err(anyhow::Error::from)?;
+    Ok(sheet_rows(&response))
+}
+
+/// The rows of each sheet of the spreadsheet returned by the API with grid data,
+/// by sheet ID.
+fn sheet_rows(spreadsheet: &serde_json::Value) -> HashMap<u64, Vec<RowData>> {
+    fn as_slice(value: &serde_json::Value) -> &[serde_json::Value] {
+        value.as_array().map(Vec::as_slice).unwrap_or_default()
+    }
+    as_slice(&spreadsheet["sheets"])
+        .iter()
+        .map(|sheet| {
+            // the API leaves out sheet IDs of 0, and empty rows and cells
+            let sheet_id = sheet["properties"]["sheetId"].as_u64().unwrap_or(0);
+            let rows = as_slice(&sheet["data"][0]["rowData"])
+                .iter()
+                .map(|row| RowData {
+                    values: as_slice(&row["values"])
+                        .iter()
+                        .map(|cell| serde_json::from_value(cell.clone()).unwrap_or_default())
+                        .collect(),
+                })
+                .collect();
+            (sheet_id, rows)
+        })
+        .collect()
+}
+
+/// Sends the requests to the `batchUpdate` endpoint of the spreadsheet.
+async fn batch_update(
+    client: &reqwest::Client,
+    creds: &Token,
+    spreadsheet_id: &str,
+    requests: &[Request],
+) -> anyhow::Result<()> {
+    let url =
+        reqwest::Url::parse(&format!("{ENDPOINT_SPREADSHEETS}/{spreadsheet_id}:batchUpdate"))?;
+    let request = client
+        .post(url)
+        .bearer_auth(creds.access_token().secret())
+        .json(&json!({ "requests": requests }));
+    let response = quota::send(request, "tag and protect sheets").await?;
+    if !response.status().is_success() {
+        anyhow::bail!("Request failed with status code: {}", response.status());
+    }
+    Ok(())
+}
+
+/// Finds a sheet ID that is not already in use, and marks it as used.
+fn unused_sheet_id(existing_sheet_ids: &mut HashSet<u64>) -> u64 {
+    let mut sheet_id = 0;
+    while existing_sheet_ids.contains(&sheet_id) {
+        sheet_id += 1;
+    }
+    existing_sheet_ids.insert(sheet_id);
+    sheet_id
+}
+
 /// A HashMap of known sheets, where the key is some string, and the value is
 /// the spreadsheet ID.
 type KnownSheets<'a> = HashMap<SheetNickname, Cow<'a, str>>;
 
-fn update_known_sheets_file(nickname: SheetNickname, spreadsheet_id: &str) -> std::io::Result<()> {
-    let path = Path::new(KNOWN_SHEETS_FILE);
-
-    // deserialize the existing known sheets
-    let mut known_sheets: KnownSheets = if let Ok(file) = File::open(path) {
-        let reader = BufReader::new(file);
-        match serde_json::from_reader(reader) {
-            Ok(sheets) => sheets,
-            Err(e) => {
+/// Saves the spreadsheet ID under the nickname in the known sheets file. The
+/// file may be shared with other computers, so it is updated under a lock to
+/// keep the spreadsheets that they save at the same time.
+fn update_known_sheets_file(nickname: SheetNickname, spreadsheet_id: &str) -> anyhow::Result<()> {
+    storage::update_config(KNOWN_SHEETS_FILE, |contents| {
+        // deserialize the existing known sheets
+        let mut known_sheets: KnownSheets = match contents.map(serde_json::from_str) {
+            Some(Ok(sheets)) => sheets,
+            Some(Err(e)) => {
                 warn!("failed to deserialize known sheets file: {}", e);
                 HashMap::new()
             }
-        }
-    } else {
-        HashMap::new()
-    };
-
-    // insert the new key-value pair
-    known_sheets.insert(nickname, spreadsheet_id.into());
+            None => HashMap::new(),
+        };
 
-    // Serialize the updated known sheets back to the file
-    let writer = BufWriter::new(File::create(path)?);
-    serde_json::to_writer(writer, &known_sheets)?;
+        // insert the new key-value pair
+        known_sheets.insert(nickname, spreadsheet_id.into());
 
-    Ok(())
+        Ok(serde_json::to_string(&known_sheets)?)
+    })
 }
 
 /// Reads the known sheets file and returns the value associated with the
 /// specified nickname.
 fn read_known_sheets_file(nickname: SheetNickname) -> std::io::Result<Option<String>> {
-    let file = match File::open(KNOWN_SHEETS_FILE) {
+    let file = match File::open(storage::config_path(KNOWN_SHEETS_FILE)) {
         Ok(file) => file,
         Err(e) => {
             if e.kind() != std::io::ErrorKind::NotFound {
@@ -338,4 +1323,233 @@ fn read_known_sheets_file(nickname: SheetNickname) -> std::io::Result<Option<Str
 pub enum SheetNickname {
     AccReceivable,
     Kpi,
+    InstallCalendar,
+    Backlog,
+    Carriers,
+    Supplements,
+    Losses,
+    Duplicates,
+    Audit,
+    Cohorts,
+    Areas,
+    Crews,
+    Reschedules,
+    Reassignments,
+    Geo,
+    BranchDistances,
+    RedFlags,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_target_ranges() {
+        assert_eq!(
+            parse_target_range("abc123:'Master KPIs'!AB12"),
+            Ok(TargetRange {
+                spreadsheet_id: "abc123".to_owned(),
+                tab: "Master KPIs".to_owned(),
+                row_index: 11,
+                column_index: 27,
+            })
+        );
+        assert_eq!(parse_target_range("abc123:KPI").unwrap().row_index, 0);
+        assert_eq!(parse_a1_cell("a1"), Some((0, 0)));
+        assert_eq!(parse_a1_cell("Z3"), Some((2, 25)));
+        assert_eq!(parse_a1_cell("A0"), None);
+        assert_eq!(parse_a1_cell("12"), None);
+        assert!(parse_target_range("KPI!A1").is_err());
+        assert!(parse_target_range("abc123:KPI!1A").is_err());
+    }
+
+    #[test]
+    fn sheet_properties_serialize_tab_color() {
+        let properties = SheetProperties {
+            sheet_id: Some(0),
+            index: Some(2),
+            tab_color: Some(spreadsheet::Color::rgb(0xff, 0, 0x33)),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(&properties).unwrap(),
+            json!({
+                "sheetId": 0,
+                "index": 2,
+                "tabColor": { "red": 1.0, "green": 0.0, "blue": 0.2 },
+            })
+        );
+        let color: spreadsheet::Color = serde_json::from_value(json!({ "green": 1.0 })).unwrap();
+        assert_eq!(color, spreadsheet::Color::rgb(0, 0xff, 0));
+    }
+
+    #[test]
+    fn date_cells_are_formatted_as_dates() {
+        let cell = |value| spreadsheet::CellData { user_entered_value: Some(value), note: None };
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        assert_eq!(
+            serde_json::to_value(cell(spreadsheet::ExtendedValue::DateValue(date))).unwrap(),
+            json!({
+                "userEnteredValue": { "numberValue": 45352.0 },
+                "userEnteredFormat": { "numberFormat": { "type": "DATE", "pattern": "yyyy-mm-dd" } },
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(cell(spreadsheet::ExtendedValue::BoolValue(true))).unwrap(),
+            json!({ "userEnteredValue": { "boolValue": true } })
+        );
+        assert_eq!(
+            serde_json::to_value(cell(spreadsheet::ExtendedValue::Checkbox(false))).unwrap(),
+            json!({
+                "userEnteredValue": { "boolValue": false },
+                "dataValidation": { "condition": { "type": "BOOLEAN" } },
+            })
+        );
+        let formula = spreadsheet::ExtendedValue::FormulaValue("=A1+1".to_owned());
+        assert_eq!(serde_json::to_value(formula).unwrap(), json!({ "formulaValue": "=A1+1" }));
+    }
+
+    #[test]
+    fn sheets_wider_than_default_grid_are_resized() {
+        let row =
+            |width| spreadsheet::RowData { values: vec![spreadsheet::CellData::default(); width] };
+        let data = GridData { start_row: 1, start_column: 1, row_data: vec![row(30), row(3)] };
+        assert_eq!(data.grid_properties(), GridProperties { row_count: 3, column_count: 31 });
+        let empty = GridData::default();
+        assert_eq!(empty.grid_properties(), GridProperties { row_count: 1, column_count: 1 });
+
+        let mut sheet = Sheet { properties: SheetProperties::default(), data: Some(data) };
+        fit_grid_to_data(&mut sheet);
+        let request = update_sheet_properties(4, sheet.properties);
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({ "updateSheetProperties": {
+                "properties": {
+                    "sheetId": 4,
+                    "gridProperties": { "rowCount": 3, "columnCount": 31 },
+                },
+                "fields": "gridProperties.rowCount,gridProperties.columnCount",
+            } })
+        );
+
+        // a target tab is only ever expanded
+        let existing = GridProperties { row_count: 1000, column_count: 26 };
+        assert_eq!(
+            existing.union(GridProperties { row_count: 3, column_count: 31 }),
+            GridProperties { row_count: 1000, column_count: 31 }
+        );
+    }
+
+    #[test]
+    fn checkbox_columns_are_left_unprotected() {
+        let row = |values: Vec<Option<spreadsheet::ExtendedValue>>| spreadsheet::RowData {
+            values: values
+                .into_iter()
+                .map(|value| spreadsheet::CellData { user_entered_value: value, note: None })
+                .collect(),
+        };
+        let string = |s: &str| Some(spreadsheet::ExtendedValue::StringValue(s.to_owned()));
+        let checkbox = |ticked| Some(spreadsheet::ExtendedValue::Checkbox(ticked));
+        let data = GridData {
+            start_row: 0,
+            start_column: 1,
+            row_data: vec![
+                row(vec![string("Error"), string("Acknowledged")]),
+                row(vec![string("Late"), checkbox(true)]),
+                row(vec![string("Early"), checkbox(false), None, checkbox(false)]),
+            ],
+        };
+        let checkboxes = checkbox_columns(&data);
+        assert_eq!(checkboxes, [2, 4]);
+        assert_eq!(
+            serde_json::to_value(protect_sheet(3, None, checkboxes)).unwrap(),
+            json!({ "addProtectedRange": { "protectedRange": {
+                "range": { "sheetId": 3 },
+                "description": PROTECTED_RANGE_DESCRIPTION,
+                "unprotectedRanges": [
+                    { "sheetId": 3, "startColumnIndex": 2, "endColumnIndex": 3 },
+                    { "sheetId": 3, "startColumnIndex": 4, "endColumnIndex": 5 },
+                ],
+                "warningOnly": true,
+            } } })
+        );
+    }
+
+    #[test]
+    fn find_existing_sheets() {
+        let tag = |tab: &str| {
+            let metadata = SheetMetadata {
+                report: SheetNickname::Kpi,
+                tab: tab.to_owned(),
+                version: "1".to_owned(),
+                columns: Some(4),
+            };
+            json!([{
+                "metadataId": 12,
+                "metadataKey": METADATA_KEY,
+                "metadataValue": serde_json::to_string(&metadata).unwrap(),
+            }])
+        };
+        let spreadsheet = json!({ "sheets": [
+            // sheet IDs of 0 are left out
+            {
+                "properties": { "title": "Renamed" },
+                "developerMetadata": tag("Leaderboard"),
+                "protectedRanges": [{
+                    "protectedRangeId": 5,
+                    "range": { "endColumnIndex": 4 },
+                    "unprotectedRanges": [{ "startColumnIndex": 2, "endColumnIndex": 3 }],
+                    "description": PROTECTED_RANGE_DESCRIPTION,
+                }],
+            },
+            {
+                "properties": { "sheetId": 7, "title": "Leaderboard" },
+                "protectedRanges": [{ "range": { "sheetId": 7 }, "description": "Someone else's" }],
+            },
+            { "properties": { "sheetId": 9, "title": "Funnel" } },
+        ] });
+        let mut existing = existing_sheets(&spreadsheet);
+        assert_eq!(
+            existing[0],
+            ExistingSheet {
+                sheet_id: 0,
+                title: Some("Renamed".to_owned()),
+                tab: Some("Leaderboard".to_owned()),
+                metadata_id: Some(12),
+                columns: Some(4),
+                protection: Some(Protection { id: 5, columns: Some(4), checkboxes: vec![2] }),
+                grid: GridProperties::default(),
+            }
+        );
+        assert_eq!(existing[1].protection, None);
+
+        // tagged sheets are preferred over sheets with the same title
+        assert_eq!(take_existing_sheet(&mut existing, "Leaderboard").unwrap().sheet_id, 0);
+        assert_eq!(take_existing_sheet(&mut existing, "Funnel").unwrap().sheet_id, 9);
+        assert_eq!(take_existing_sheet(&mut existing, "Red Flags"), None);
+        assert_eq!(existing.len(), 1);
+    }
+
+    #[test]
+    fn read_sheet_rows() {
+        let spreadsheet = json!({ "sheets": [
+            { "properties": {}, "data": [{ "rowData": [
+                {},
+                { "values": [{}, { "userEnteredValue": { "stringValue": "a" }, "note": "n" }] },
+            ] }] },
+            { "properties": { "sheetId": 7 }, "data": [{}] },
+        ] });
+        let rows = sheet_rows(&spreadsheet);
+        assert_eq!(rows[&7].len(), 0);
+        let rows = &rows[&0];
+        assert_eq!(rows.len(), 2);
+        assert!(rows[0].values.is_empty());
+        assert!(rows[1].values[0].user_entered_value.is_none());
+        assert!(matches!(
+            &rows[1].values[1].user_entered_value,
+            Some(spreadsheet::ExtendedValue::StringValue(value)) if value == "a"
+        ));
+        assert_eq!(rows[1].values[1].note.as_deref(), Some("n"));
+    }
 }
@@ -0,0 +1,105 @@
+use std::fs::OpenOptions;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Context as _;
+use tracing::warn;
+
+use crate::storage;
+
+/// How long a lock can be held before it is assumed to have been left behind
+/// by a run that was killed, and is taken over.
+const STALE_LOCK_AGE: Duration = Duration::from_secs(30 * 60);
+
+/// A lock that keeps two runs on this computer (e.g. a scheduled export and a
+/// manual one) from writing to the same spreadsheet at the same time, which
+/// leaves its tabs with a mix of both exports. The lock is a file in the
+/// settings directory, and is released when dropped.
+#[derive(Debug)]
+pub struct ExportLock {
+    path: PathBuf,
+}
+
+impl ExportLock {
+    /// Acquires the lock of the spreadsheet, or fails if another export to it
+    /// is in progress.
+    pub fn acquire(spreadsheet_id: &str) -> anyhow::Result<Self> {
+        Self::acquire_in(&storage::config_dir(), spreadsheet_id)
+    }
+
+    fn acquire_in(dir: &Path, spreadsheet_id: &str) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        let path = dir.join(format!("google_sheets_{}.lock", spreadsheet_id));
+        let mut took_over = false;
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    // the contents are only there to help whoever finds the file
+                    let _ = writeln!(file, "{}", std::process::id());
+                    return Ok(ExportLock { path });
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let age = std::fs::metadata(&path)
+                        .and_then(|metadata| metadata.modified())
+                        .ok()
+                        .and_then(|modified| modified.elapsed().ok());
+                    if !took_over && age.is_some_and(|age| age > STALE_LOCK_AGE) {
+                        warn!("Taking over the stale lock file {}", path.display());
+                        let _ = std::fs::remove_file(&path);
+                        took_over = true;
+                        continue;
+                    }
+                    anyhow::bail!(
+                        "Another export to the spreadsheet with ID {} is in progress. Wait for \
+                         it to finish, or delete {} if it was interrupted.",
+                        spreadsheet_id,
+                        path.display()
+                    );
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("Failed to create the lock file {}", path.display())
+                    })
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ExportLock {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            warn!("Failed to remove the lock file {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::SystemTime;
+
+    use super::*;
+
+    #[test]
+    fn exports_to_the_same_spreadsheet_are_serialized() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock = ExportLock::acquire_in(dir.path(), "abc").unwrap();
+        let err = ExportLock::acquire_in(dir.path(), "abc").unwrap_err();
+        assert!(err.to_string().starts_with("Another export"));
+        let other = ExportLock::acquire_in(dir.path(), "xyz").unwrap();
+        drop(lock);
+        drop(other);
+        let lock = ExportLock::acquire_in(dir.path(), "abc").unwrap();
+
+        // a lock left behind by a killed run is taken over
+        std::mem::forget(lock);
+        let file = std::fs::File::options()
+            .write(true)
+            .open(dir.path().join("google_sheets_abc.lock"))
+            .unwrap();
+        file.set_modified(SystemTime::now() - 2 * STALE_LOCK_AGE).unwrap();
+        ExportLock::acquire_in(dir.path(), "abc").unwrap();
+    }
+}
@@ -12,19 +12,22 @@ use hyper::StatusCode;
 use hyper::{body::Incoming as IncomingBody, server::conn::http1, Request, Response};
 use hyper_util::rt::TokioIo;
 use oauth2::basic::BasicTokenResponse;
-use oauth2::reqwest::async_http_client;
 use oauth2::{
     basic::BasicClient, AuthUrl, ClientId, ClientSecret, CsrfToken, PkceCodeChallenge, Scope,
     TokenUrl,
 };
-use oauth2::{AuthorizationCode, RedirectUrl, RefreshToken, TokenResponse};
+use oauth2::{
+    AuthorizationCode, HttpRequest, HttpResponse, RedirectUrl, RefreshToken, TokenResponse,
+};
 use serde::{Deserialize, Serialize};
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::io::BufReader;
 use thiserror::Error;
 use tokio::{net::TcpListener, sync::oneshot};
 use tracing::{debug, trace, warn};
 
+use crate::apis::http;
+use crate::storage;
 use crate::utils;
 
 pub type Token = BasicTokenResponse;
@@ -59,10 +62,10 @@ where
     O: FnMut(&Token) -> F, // TODO find a way to make this work with &Token without lifetimes screaming at you
     F: Future<Output = Result<U, TryWithCredentialsError>>,
 {
-    let cache_file = Path::new(DEFAULT_CACHE_FILE);
+    let cache_file = storage::credentials_path(DEFAULT_CACHE_FILE);
 
     // attempt to run the function with a cached token
-    let expired_token = match get_cached_token(cache_file) {
+    let expired_token = match get_cached_token(&cache_file) {
         Some((cached_token, false)) => {
             // attempt to run the function with the cached token
             trace!("using cached token to perform operation");
@@ -122,8 +125,7 @@ where
                 // the function worked with a refreshed token. cache this
                 // refreshed token
                 debug!("caching refreshed token to {}", cache_file.display());
-                let writer = BufWriter::new(File::create(cache_file)?);
-                serde_json::to_writer(writer, &refreshed_token)?;
+                cache_token(&refreshed_token)?;
                 return Ok(result);
             }
             Err(TryWithCredentialsError::Unauthorized(e)) => {
@@ -151,8 +153,7 @@ where
         Ok(result) => {
             // the function worked with a fresh token
             debug!("caching fresh token to {}", cache_file.display());
-            let writer = BufWriter::new(File::create(cache_file)?);
-            serde_json::to_writer(writer, &fresh_token)?;
+            cache_token(&fresh_token)?;
             return Ok(result);
         }
         Err(TryWithCredentialsError::Unauthorized(e)) => {
@@ -168,6 +169,10 @@ where
     Err(err)
 }
 
+fn cache_token(token: &TokenWithExpiration) -> anyhow::Result<()> {
+    storage::write_credentials(DEFAULT_CACHE_FILE, &serde_json::to_string(token)?)
+}
+
 // Returns the token from the cache file, as well as if the token is known to
 // be expired.
 fn get_cached_token(cache_file: &Path) -> Option<(TokenWithExpiration, bool)> {
@@ -340,6 +345,41 @@ async fn listen_for_code(
     Ok(())
 }
 
+/// An error of `async_http_client`.
+#[derive(Error, Debug)]
+#[error("{0:#}")]
+struct HttpClientError(anyhow::Error);
+
+/// Sends the OAuth2 requests with a client from the shared HTTP configuration,
+/// so that they go through the same proxy as the other requests. Like
+/// `oauth2::reqwest::async_http_client`, redirects aren't followed.
+async fn async_http_client(request: HttpRequest) -> Result<HttpResponse, HttpClientError> {
+    send_oauth2_request(request).await.map_err(HttpClientError)
+}
+
+async fn send_oauth2_request(request: HttpRequest) -> anyhow::Result<HttpResponse> {
+    // oauth2 uses an older version of the `http` crate than reqwest, so the
+    // request and response are converted through their raw parts
+    let client = http::client_builder()?.redirect(reqwest::redirect::Policy::none()).build()?;
+    let method = reqwest::Method::from_bytes(request.method.as_str().as_bytes())?;
+    let mut builder = client.request(method, request.url.as_str()).body(request.body);
+    for (name, value) in &request.headers {
+        builder = builder.header(name.as_str(), value.as_bytes());
+    }
+    let response = builder.send().await?;
+
+    let status_code = oauth2::http::StatusCode::from_u16(response.status().as_u16())?;
+    let mut headers = oauth2::http::HeaderMap::new();
+    for (name, value) in response.headers() {
+        headers.append(
+            oauth2::http::HeaderName::from_bytes(name.as_str().as_bytes())?,
+            oauth2::http::HeaderValue::from_bytes(value.as_bytes())?,
+        );
+    }
+    let body = response.bytes().await?.to_vec();
+    Ok(HttpResponse { status_code, headers, body })
+}
+
 fn oauth2_client() -> BasicClient {
     BasicClient::new(
         ClientId::new(CLIENT_ID.to_owned()),
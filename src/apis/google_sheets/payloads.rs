@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::spreadsheet::GridData;
+use crate::storage;
+
+const DEFAULT_PAYLOADS_DIR: &str = "sheet_payloads";
+
+/// The rows that were last written to each sheet of a spreadsheet, so that the
+/// next update only has to write the rows that changed. The payloads are
+/// stored in the cache directory, one file per spreadsheet.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Payloads {
+    /// The version of the spreadsheet in Google Drive right after the rows
+    /// were written. If the spreadsheet has a different version, then it was
+    /// changed since (e.g. by a user, or an export from another computer), so
+    /// its sheets may not contain these rows anymore.
+    pub version: Option<String>,
+    /// The payload of each sheet, by the title that it was generated with.
+    pub sheets: HashMap<String, SheetPayload>,
+}
+
+/// The rows written to a sheet, each serialized as it was sent to the API.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SheetPayload {
+    start_row: u64,
+    start_column: u64,
+    rows: Vec<String>,
+}
+
+impl SheetPayload {
+    pub fn of(grid_data: &GridData) -> Self {
+        SheetPayload {
+            start_row: grid_data.start_row,
+            start_column: grid_data.start_column,
+            rows: grid_data
+                .row_data
+                .iter()
+                .map(|row| serde_json::to_string(row).expect("rows should serialize"))
+                .collect(),
+        }
+    }
+
+    /// The ranges of consecutive rows of this payload that differ from the
+    /// previous payload, or `None` if the data starts elsewhere, in which
+    /// case every row has to be written. Rows that were removed are not
+    /// included, since the grid is resized to fit the data anyway.
+    pub fn changed_rows(&self, previous: &SheetPayload) -> Option<Vec<Range<usize>>> {
+        if (self.start_row, self.start_column) != (previous.start_row, previous.start_column) {
+            return None;
+        }
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for (i, row) in self.rows.iter().enumerate() {
+            if previous.rows.get(i) == Some(row) {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(range) if range.end == i => range.end = i + 1,
+                _ => ranges.push(i..i + 1),
+            }
+        }
+        Some(ranges)
+    }
+}
+
+fn path(dir: &Path, spreadsheet_id: &str) -> PathBuf {
+    dir.join(format!("{}.json", spreadsheet_id))
+}
+
+/// Loads the payloads last written to the spreadsheet, if any. Payloads that
+/// can't be read are treated as missing, which only means that every row is
+/// written.
+pub fn load(spreadsheet_id: &str) -> Option<Payloads> {
+    load_from(&storage::cache_path(DEFAULT_PAYLOADS_DIR), spreadsheet_id)
+}
+
+fn load_from(dir: &Path, spreadsheet_id: &str) -> Option<Payloads> {
+    let file = match File::open(path(dir, spreadsheet_id)) {
+        Ok(file) => file,
+        Err(e) => {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!("Failed to open the previous sheet payloads: {}", e);
+            }
+            return None;
+        }
+    };
+    match serde_json::from_reader(BufReader::new(file)) {
+        Ok(payloads) => Some(payloads),
+        Err(e) => {
+            warn!("Failed to deserialize the previous sheet payloads: {}", e);
+            None
+        }
+    }
+}
+
+/// Saves the payloads written to the spreadsheet, replacing the previous ones.
+pub fn save(spreadsheet_id: &str, payloads: &Payloads) -> anyhow::Result<()> {
+    save_to(&storage::cache_path(DEFAULT_PAYLOADS_DIR), spreadsheet_id, payloads)
+}
+
+fn save_to(dir: &Path, spreadsheet_id: &str, payloads: &Payloads) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let contents = serde_json::to_string(payloads)?;
+    std::fs::write(path(dir, spreadsheet_id), contents)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn payload(start_row: u64, rows: &[&str]) -> SheetPayload {
+        SheetPayload {
+            start_row,
+            start_column: 0,
+            rows: rows.iter().map(|row| row.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn only_changed_rows_are_written() {
+        let previous = payload(0, &["a", "b", "c", "d", "e"]);
+        let current = payload(0, &["a", "B", "C", "d", "E", "f", "g"]);
+        assert_eq!(current.changed_rows(&previous), Some(vec![1..3, 4..7]));
+
+        let shorter = payload(0, &["a", "b"]);
+        assert_eq!(shorter.changed_rows(&previous), Some(vec![]));
+
+        let moved = payload(1, &["a", "b", "c", "d", "e"]);
+        assert_eq!(moved.changed_rows(&previous), None);
+    }
+
+    #[test]
+    fn payloads_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(load_from(dir.path(), "abc").is_none());
+
+        let payloads = Payloads {
+            version: Some("12".to_owned()),
+            sheets: HashMap::from([("Leaderboard".to_owned(), payload(0, &["a", "b"]))]),
+        };
+        save_to(dir.path(), "abc", &payloads).unwrap();
+        let loaded = load_from(dir.path(), "abc").unwrap();
+        assert_eq!(loaded.version, payloads.version);
+        assert_eq!(loaded.sheets, payloads.sheets);
+    }
+}
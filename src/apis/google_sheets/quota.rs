@@ -0,0 +1,137 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use hyper::StatusCode;
+use tracing::{debug, warn};
+
+/// The number of requests that ahitool makes to the Google Sheets API per
+/// minute before waiting. Google's default quota is 60 requests per minute per
+/// user, so this leaves room for other processes (e.g. concurrent scheduled
+/// exports) using the same account.
+const REQUESTS_PER_MINUTE: usize = 45;
+
+/// The number of requests per minute at which to warn that the quota is being
+/// approached.
+const WARN_REQUESTS_PER_MINUTE: usize = 30;
+
+/// The delay before retrying a request that exceeded the quota, which doubles
+/// with each retry.
+const RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// The maximum number of times to retry a request that exceeded the quota.
+const MAX_RETRIES: u32 = 3;
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// The number of requests made to the Google Sheets API by this process.
+static REQUEST_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// The times of the requests made within the last minute.
+static RECENT_REQUESTS: Mutex<VecDeque<Instant>> = Mutex::new(VecDeque::new());
+
+/// The number of requests made to the Google Sheets API by this process.
+pub fn request_count() -> usize {
+    REQUEST_COUNT.load(Ordering::Relaxed)
+}
+
+/// Sends a request to the Google Sheets API, waiting first if too many
+/// requests have been made within the last minute. Retries the request with a
+/// backoff if it exceeds the quota anyway.
+pub async fn send(
+    request: reqwest::RequestBuilder,
+    description: &str,
+) -> reqwest::Result<reqwest::Response> {
+    let mut request = request;
+    let mut retries = 0;
+    loop {
+        wait_for_quota(description).await;
+        let count = REQUEST_COUNT.fetch_add(1, Ordering::Relaxed) + 1;
+        debug!("Google Sheets API request #{}: {}", count, description);
+
+        let retry = request.try_clone();
+        let response = request.send().await?;
+        if response.status() != StatusCode::TOO_MANY_REQUESTS || retries == MAX_RETRIES {
+            return Ok(response);
+        }
+        let Some(retry) = retry else {
+            return Ok(response);
+        };
+        let delay = RETRY_DELAY * 2u32.pow(retries);
+        warn!(
+            "Exceeded the Google Sheets API quota while trying to {}; retrying in {} seconds",
+            description,
+            delay.as_secs()
+        );
+        tokio::time::sleep(delay).await;
+        request = retry;
+        retries += 1;
+    }
+}
+
+async fn wait_for_quota(description: &str) {
+    loop {
+        let reserved = {
+            let mut recent = RECENT_REQUESTS.lock().unwrap();
+            reserve(&mut recent, Instant::now())
+        };
+        match reserved {
+            Ok(made) => {
+                if made == WARN_REQUESTS_PER_MINUTE {
+                    warn!(
+                        "Made {} Google Sheets API requests within a minute, approaching the quota",
+                        made
+                    );
+                }
+                return;
+            }
+            Err(wait) => {
+                warn!(
+                    "Waiting {} seconds to {} to stay under the Google Sheets API quota",
+                    wait.as_secs() + 1,
+                    description
+                );
+                tokio::time::sleep(wait).await;
+            }
+        }
+    }
+}
+
+/// Reserves a request at `now` if fewer than `REQUESTS_PER_MINUTE` requests
+/// have been made within the last minute, returning the number of requests
+/// made within the last minute including it. Otherwise returns how long to wait
+/// before trying again.
+fn reserve(recent: &mut VecDeque<Instant>, now: Instant) -> Result<usize, Duration> {
+    while recent.front().is_some_and(|&made_at| now.duration_since(made_at) >= WINDOW) {
+        recent.pop_front();
+    }
+    match recent.front() {
+        Some(&oldest) if recent.len() >= REQUESTS_PER_MINUTE => {
+            Err(WINDOW - now.duration_since(oldest))
+        }
+        _ => {
+            recent.push_back(now);
+            Ok(recent.len())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reserve_within_window() {
+        let start = Instant::now();
+        let mut recent = VecDeque::new();
+        for i in 0..REQUESTS_PER_MINUTE {
+            assert_eq!(reserve(&mut recent, start + Duration::from_secs(i as u64 / 10)), Ok(i + 1));
+        }
+
+        // the oldest request leaves the window a minute after it was made
+        let now = start + Duration::from_secs(20);
+        assert_eq!(reserve(&mut recent, now), Err(Duration::from_secs(40)));
+        assert_eq!(reserve(&mut recent, start + WINDOW), Ok(REQUESTS_PER_MINUTE - 9));
+    }
+}
@@ -1,4 +1,9 @@
-use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use serde::{
+    ser::SerializeMap as _, ser::SerializeStruct as _, Deserialize, Serialize, Serializer,
+};
 
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
 pub struct Spreadsheet {
@@ -9,6 +14,19 @@ pub struct Spreadsheet {
     pub sheets: Option<Vec<Sheet>>,
     #[serde(rename = "spreadsheetUrl", skip_serializing_if = "Option::is_none")]
     pub spreadsheet_url: Option<String>,
+    /// Sheets whose rows are appended to the existing rows when updating the
+    /// spreadsheet, instead of replacing them. The first row of each sheet is
+    /// its header, which is only written when the sheet is created.
+    #[serde(skip)]
+    pub appended_sheets: Vec<Sheet>,
+    /// The column of the data (counting from the start of the data) that
+    /// identifies each row of the sheet with the title, e.g. the job ID. When
+    /// updating such a sheet, the cells that users entered in the columns to
+    /// the right of the data are moved along with the row that has the same
+    /// key, instead of staying where they are while the rows around them
+    /// change.
+    #[serde(skip)]
+    pub key_columns: HashMap<String, usize>,
 }
 
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
@@ -31,6 +49,53 @@ pub struct SheetProperties {
     pub sheet_id: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
+    /// The position of the sheet among the tabs of the spreadsheet. If not
+    /// specified, sheets are positioned in the order in which they are listed
+    /// in the spreadsheet.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<u64>,
+    #[serde(rename = "tabColor", skip_serializing_if = "Option::is_none")]
+    pub tab_color: Option<Color>,
+    /// The size of the grid. If not specified, new sheets get the default size
+    /// of 1000 rows and 26 columns, and writing data beyond the grid fails.
+    #[serde(rename = "gridProperties", skip_serializing_if = "Option::is_none")]
+    pub grid_properties: Option<GridProperties>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct GridProperties {
+    #[serde(rename = "rowCount")]
+    pub row_count: u64,
+    #[serde(rename = "columnCount")]
+    pub column_count: u64,
+}
+
+impl GridProperties {
+    /// The smallest grid that contains both this grid and `other`.
+    pub fn union(self, other: GridProperties) -> GridProperties {
+        GridProperties {
+            row_count: self.row_count.max(other.row_count),
+            column_count: self.column_count.max(other.column_count),
+        }
+    }
+}
+
+/// A color with components from 0 to 1. Components that are zero are left out
+/// by the API.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct Color {
+    pub red: f64,
+    pub green: f64,
+    pub blue: f64,
+}
+
+impl Color {
+    /// A color from its hex components, e.g. `Color::rgb(0x34, 0xa8, 0x53)`.
+    pub const fn rgb(red: u8, green: u8, blue: u8) -> Self {
+        Color { red: red as f64 / 255.0, green: green as f64 / 255.0, blue: blue as f64 / 255.0 }
+    }
 }
 
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
@@ -43,18 +108,81 @@ pub struct GridData {
     pub row_data: Vec<RowData>,
 }
 
+impl GridData {
+    /// The smallest grid that fits the data, counting the rows and columns
+    /// before the start of the data. A grid has at least one row and column.
+    pub fn grid_properties(&self) -> GridProperties {
+        let width = self.row_data.iter().map(|row| row.values.len()).max().unwrap_or(0);
+        GridProperties {
+            row_count: (self.start_row + self.row_data.len() as u64).max(1),
+            column_count: (self.start_column + width as u64).max(1),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
 pub struct RowData {
     pub values: Vec<CellData>,
 }
 
-#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+/// A row of cells with the values and no notes.
+pub fn mk_row(cells: impl IntoIterator<Item = ExtendedValue>) -> RowData {
+    RowData {
+        values: cells
+            .into_iter()
+            .map(|cell| CellData { user_entered_value: Some(cell), note: None })
+            .collect(),
+    }
+}
+
+/// The fields of `CellData` that are written when updating cells.
+pub const CELL_FIELDS: &str = "userEnteredValue,userEnteredFormat.numberFormat,note,dataValidation";
+
+/// The fields of `CellData` that are written when moving the cells entered by
+/// users, which keep the formatting of the column that they are moved within.
+pub const USER_CELL_FIELDS: &str = "userEnteredValue,note";
+
+/// The pattern with which date cells are displayed.
+const DATE_PATTERN: &str = "yyyy-mm-dd";
+
+/// A cell of a sheet. Cells containing a date are given a date format, so that
+/// the date is displayed as such, and cells containing a checkbox are given a
+/// data validation rule that displays them as a checkbox.
+#[derive(Deserialize, Default, Debug, Clone)]
 pub struct CellData {
     #[serde(rename = "userEnteredValue")]
     pub user_entered_value: Option<ExtendedValue>,
+    pub note: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl Serialize for CellData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut cell = serializer.serialize_struct("CellData", 4)?;
+        cell.serialize_field("userEnteredValue", &self.user_entered_value)?;
+        if let Some(ExtendedValue::DateValue(_)) = self.user_entered_value {
+            let format = serde_json::json!({
+                "numberFormat": { "type": "DATE", "pattern": DATE_PATTERN },
+            });
+            cell.serialize_field("userEnteredFormat", &format)?;
+        } else {
+            cell.skip_field("userEnteredFormat")?;
+        }
+        match &self.note {
+            Some(note) => cell.serialize_field("note", note)?,
+            None => cell.skip_field("note")?,
+        }
+        if let Some(ExtendedValue::Checkbox(_)) = self.user_entered_value {
+            let rule = serde_json::json!({ "condition": { "type": "BOOLEAN" } });
+            cell.serialize_field("dataValidation", &rule)?;
+        } else {
+            cell.skip_field("dataValidation")?;
+        }
+        cell.end()
+    }
+}
+
+#[allow(clippy::enum_variant_names)] // the variant names mirror the Sheets API
+#[derive(Deserialize, Debug, Clone)]
 pub enum ExtendedValue {
     #[serde(rename = "stringValue")]
     StringValue(String),
@@ -64,6 +192,35 @@ pub enum ExtendedValue {
     BoolValue(bool),
     #[serde(rename = "formulaValue")]
     FormulaValue(String),
+    /// A date, which the API represents as the number of days since December
+    /// 30, 1899. Dates are read back as `NumberValue`s.
+    #[serde(skip_deserializing)]
+    DateValue(NaiveDate),
+    /// A checkbox that users can tick, which is ticked if the value is true.
+    /// Checkboxes are read back as `BoolValue`s.
+    #[serde(skip_deserializing)]
+    Checkbox(bool),
+}
+
+impl Serialize for ExtendedValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut value = serializer.serialize_map(Some(1))?;
+        match self {
+            ExtendedValue::StringValue(string) => value.serialize_entry("stringValue", string)?,
+            ExtendedValue::NumberValue(number) => value.serialize_entry("numberValue", number)?,
+            ExtendedValue::BoolValue(boolean) | ExtendedValue::Checkbox(boolean) => {
+                value.serialize_entry("boolValue", boolean)?
+            }
+            ExtendedValue::FormulaValue(formula) => {
+                value.serialize_entry("formulaValue", formula)?
+            }
+            ExtendedValue::DateValue(date) => {
+                let epoch = NaiveDate::from_ymd_opt(1899, 12, 30).expect("the epoch is valid");
+                value.serialize_entry("numberValue", &((*date - epoch).num_days() as f64))?
+            }
+        }
+        value.end()
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -76,6 +233,55 @@ pub struct GridCoordinate {
     pub column_index: u64,
 }
 
+/// A range of cells in a sheet. Unspecified bounds are unbounded, so a range
+/// with only a sheet ID covers the whole sheet.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GridRange {
+    #[serde(rename = "sheetId")]
+    pub sheet_id: u64,
+    #[serde(rename = "startColumnIndex", skip_serializing_if = "Option::is_none")]
+    pub start_column_index: Option<u64>,
+    /// The column after the last column of the range.
+    #[serde(rename = "endColumnIndex", skip_serializing_if = "Option::is_none")]
+    pub end_column_index: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProtectedRange {
+    /// The ID of an existing protected range. This value is ignored when
+    /// adding a protected range.
+    #[serde(rename = "protectedRangeId", skip_serializing_if = "Option::is_none")]
+    pub protected_range_id: Option<i64>,
+    pub range: GridRange,
+    pub description: String,
+    /// The parts of the range that are not protected after all.
+    #[serde(rename = "unprotectedRanges", default, skip_serializing_if = "Vec::is_empty")]
+    pub unprotected_ranges: Vec<GridRange>,
+    /// Whether editing the range only shows a warning instead of being
+    /// prevented.
+    #[serde(rename = "warningOnly")]
+    pub warning_only: bool,
+}
+
+/// Metadata attached to a part of a spreadsheet that is only visible to
+/// applications.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeveloperMetadata {
+    #[serde(rename = "metadataKey")]
+    pub metadata_key: String,
+    #[serde(rename = "metadataValue")]
+    pub metadata_value: String,
+    pub location: DeveloperMetadataLocation,
+    /// Either "DOCUMENT" or "PROJECT".
+    pub visibility: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeveloperMetadataLocation {
+    #[serde(rename = "sheetId")]
+    pub sheet_id: u64,
+}
+
 pub mod update {
     use serde::Serialize;
 
@@ -88,6 +294,8 @@ pub mod update {
         },
         #[serde(rename = "addSheet")]
         AddSheet { properties: super::SheetProperties },
+        #[serde(rename = "updateSheetProperties")]
+        UpdateSheetProperties { properties: super::SheetProperties, fields: String },
         #[serde(rename = "updateCells")]
         UpdateCells {
             rows: Vec<super::RowData>,
@@ -96,5 +304,49 @@ pub mod update {
         },
         #[serde(rename = "deleteSheet")]
         DeleteSheet { sheet_id: u64 },
+        #[serde(rename = "addProtectedRange")]
+        AddProtectedRange {
+            #[serde(rename = "protectedRange")]
+            protected_range: super::ProtectedRange,
+        },
+        #[serde(rename = "updateProtectedRange")]
+        UpdateProtectedRange {
+            #[serde(rename = "protectedRange")]
+            protected_range: super::ProtectedRange,
+            fields: &'static str,
+        },
+        #[serde(rename = "createDeveloperMetadata")]
+        CreateDeveloperMetadata {
+            #[serde(rename = "developerMetadata")]
+            developer_metadata: super::DeveloperMetadata,
+        },
+        #[serde(rename = "updateDeveloperMetadata")]
+        UpdateDeveloperMetadata {
+            #[serde(rename = "dataFilters")]
+            data_filters: Vec<DataFilter>,
+            #[serde(rename = "developerMetadata")]
+            developer_metadata: super::DeveloperMetadata,
+            fields: &'static str,
+        },
+        #[serde(rename = "appendCells")]
+        AppendCells {
+            #[serde(rename = "sheetId")]
+            sheet_id: u64,
+            rows: Vec<super::RowData>,
+            fields: &'static str,
+        },
+    }
+
+    /// Selects the developer metadata with the ID.
+    #[derive(Serialize, Debug, Clone)]
+    pub struct DataFilter {
+        #[serde(rename = "developerMetadataLookup")]
+        pub developer_metadata_lookup: DeveloperMetadataLookup,
+    }
+
+    #[derive(Serialize, Debug, Clone)]
+    pub struct DeveloperMetadataLookup {
+        #[serde(rename = "metadataId")]
+        pub metadata_id: i64,
     }
 }
@@ -0,0 +1,251 @@
+use std::{collections::HashMap, fs::File, io::BufReader};
+
+use tracing::warn;
+
+use super::spreadsheet::{CellData, ExtendedValue, RowData};
+use crate::storage;
+
+const DEFAULT_COLUMNS_FILE: &str = "user_columns.json";
+
+/// Loads the columns that users fill in themselves next to the generated
+/// columns, by the title of the tab, e.g.
+///
+/// ```json
+/// { "Accounts Receivable": ["Follow-up notes", "Called?"] }
+/// ```
+///
+/// Only tabs with a key column (see `Spreadsheet::key_columns`) can have user
+/// columns. Returns no columns if the file doesn't exist or can't be read.
+pub fn configured() -> HashMap<String, Vec<String>> {
+    let file = match File::open(storage::config_path(DEFAULT_COLUMNS_FILE)) {
+        Ok(file) => file,
+        Err(e) => {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!("Failed to open user columns file: {}", e);
+            }
+            return HashMap::new();
+        }
+    };
+    match serde_json::from_reader(BufReader::new(file)) {
+        Ok(columns) => columns,
+        Err(e) => {
+            warn!("Failed to deserialize user columns file: {}", e);
+            HashMap::new()
+        }
+    }
+}
+
+/// The cells that users entered to the right of the generated columns of a
+/// sheet with a key column (see `Spreadsheet::key_columns`), by the key of
+/// their row.
+#[derive(Debug, Default)]
+pub struct UserCells {
+    by_key: HashMap<String, Vec<CellData>>,
+    /// The number of columns that users entered cells in.
+    width: usize,
+}
+
+impl UserCells {
+    /// Reads the cells that users entered from the rows of an existing sheet,
+    /// which start at the top left corner of the sheet. `key_column` is the
+    /// column of the keys and `columns` is the number of generated columns,
+    /// both counting from the left edge of the sheet. Rows without a key are
+    /// skipped, as are all but the first row with the same key.
+    pub fn read(rows: Vec<RowData>, key_column: usize, columns: usize) -> Self {
+        let mut by_key = HashMap::new();
+        let mut width = 0;
+        for row in rows {
+            let Some(key) = row.values.get(key_column).and_then(cell_key) else {
+                continue;
+            };
+            let mut cells: Vec<_> = row.values.into_iter().skip(columns).collect();
+            while cells.last().is_some_and(is_empty) {
+                cells.pop();
+            }
+            if cells.is_empty() {
+                continue;
+            }
+            width = width.max(cells.len());
+            by_key.entry(key).or_insert(cells);
+        }
+        UserCells { by_key, width }
+    }
+
+    /// The number of columns that users entered cells in.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Puts the columns with the names first, in that order, followed by the
+    /// other columns that users added. The name of a column is its cell in
+    /// `header`, the first row of the generated data, and columns that don't
+    /// exist yet are added with their name in that row.
+    pub fn with_columns(mut self, header: &RowData, key_column: usize, names: &[String]) -> Self {
+        let Some(header_key) = header.values.get(key_column).and_then(cell_key) else {
+            return self;
+        };
+        let mut header = self.by_key.remove(&header_key).unwrap_or_default();
+        let header_names: Vec<_> = header.iter().map(cell_key).collect();
+        let mut order: Vec<_> = names
+            .iter()
+            .map(|name| {
+                let name = name.trim();
+                header_names.iter().position(|other| {
+                    other.as_deref().is_some_and(|other| other.eq_ignore_ascii_case(name))
+                })
+            })
+            .collect();
+        for i in 0..self.width {
+            if !order.contains(&Some(i)) {
+                order.push(Some(i));
+            }
+        }
+
+        let reorder = |cells: &[CellData]| -> Vec<CellData> {
+            order
+                .iter()
+                .map(|&i| i.and_then(|i| cells.get(i).cloned()).unwrap_or_default())
+                .collect()
+        };
+        for cells in self.by_key.values_mut() {
+            *cells = reorder(cells);
+        }
+        header.resize_with(self.width, CellData::default);
+        header = reorder(&header);
+        for (cell, name) in header.iter_mut().zip(names) {
+            if cell.user_entered_value.is_none() {
+                cell.user_entered_value = Some(ExtendedValue::StringValue(name.trim().to_owned()));
+            }
+        }
+        self.by_key.insert(header_key, header);
+        self.width = order.len();
+        self
+    }
+
+    /// The cells that users entered for each of the rows, found by the key in
+    /// `key_column` (counting from the start of the rows). Every row is padded
+    /// with empty cells to the same width, so that the cells of rows that moved
+    /// or were removed are cleared.
+    pub fn arrange(&self, rows: &[RowData], key_column: usize) -> Vec<RowData> {
+        rows.iter()
+            .map(|row| {
+                let mut values = row
+                    .values
+                    .get(key_column)
+                    .and_then(cell_key)
+                    .and_then(|key| self.by_key.get(&key))
+                    .cloned()
+                    .unwrap_or_default();
+                values.resize_with(self.width, CellData::default);
+                RowData { values }
+            })
+            .collect()
+    }
+}
+
+/// The key of a row from the cell in its key column, if it isn't empty.
+fn cell_key(cell: &CellData) -> Option<String> {
+    let key = match cell.user_entered_value.as_ref()? {
+        ExtendedValue::StringValue(string) => string.trim().to_owned(),
+        ExtendedValue::NumberValue(number) => number.to_string(),
+        _ => return None,
+    };
+    (!key.is_empty()).then_some(key)
+}
+
+fn is_empty(cell: &CellData) -> bool {
+    cell.user_entered_value.is_none() && cell.note.is_none()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn row(cells: &[&str]) -> RowData {
+        RowData {
+            values: cells
+                .iter()
+                .map(|cell| CellData {
+                    user_entered_value: (!cell.is_empty())
+                        .then(|| ExtendedValue::StringValue(cell.to_string())),
+                    note: None,
+                })
+                .collect(),
+        }
+    }
+
+    fn strings(rows: &[RowData]) -> Vec<Vec<String>> {
+        rows.iter()
+            .map(|row| {
+                row.values
+                    .iter()
+                    .map(|cell| match &cell.user_entered_value {
+                        Some(ExtendedValue::StringValue(string)) => string.clone(),
+                        _ => String::new(),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn user_cells_follow_their_row() {
+        // the data starts at B2 with the key in its second column
+        let existing = vec![
+            row(&[]),
+            row(&["", "Job Name", "Job ID", "Follow-up"]),
+            row(&["", "Smith", "a", "Called", "Left a message"]),
+            row(&["", "Jones", "b"]),
+            row(&["", "Brown", "c", "Paid?"]),
+        ];
+        let user_cells = UserCells::read(existing, 2, 3);
+        assert_eq!(user_cells.width(), 2);
+
+        // the rows are reordered, one is removed, one is added, and a new
+        // generated column pushes the user columns to the right
+        let rows = [
+            row(&["Job Name", "Job ID", "Amount"]),
+            row(&["Brown", "c", "10"]),
+            row(&["Green", "d", "20"]),
+            row(&["Smith", "a", "30"]),
+        ];
+        assert_eq!(
+            strings(&user_cells.arrange(&rows, 1)),
+            [
+                vec!["Follow-up", ""],
+                vec!["Paid?", ""],
+                vec!["", ""],
+                vec!["Called", "Left a message"],
+            ]
+        );
+    }
+
+    #[test]
+    fn configured_columns_come_first() {
+        let existing = vec![
+            row(&["Job ID", "Follow-up", "", "Notes"]),
+            row(&["a", "Called", "Extra", "Nice"]),
+            row(&["b", "", "", "Rude"]),
+        ];
+        let names = ["notes".to_owned(), "Paid?".to_owned()];
+        let header = row(&["Job ID"]);
+        let user_cells = UserCells::read(existing, 0, 1).with_columns(&header, 0, &names);
+        assert_eq!(user_cells.width(), 4);
+        let rows = [header, row(&["b"]), row(&["a"])];
+        assert_eq!(
+            strings(&user_cells.arrange(&rows, 0)),
+            [
+                vec!["Notes", "Paid?", "Follow-up", ""],
+                vec!["Rude", "", "", ""],
+                vec!["Nice", "", "Called", "Extra"],
+            ]
+        );
+
+        // a new sheet only gets the headers of the configured columns
+        let user_cells = UserCells::default().with_columns(&rows[0], 0, &names);
+        assert_eq!(
+            strings(&user_cells.arrange(&rows, 0)),
+            [vec!["notes", "Paid?"], vec!["", ""], vec!["", ""]]
+        );
+    }
+}
@@ -1,49 +1,99 @@
-use std::{fs, path::Path};
+pub mod import;
 
-use anyhow::bail;
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+};
+
+use anyhow::{bail, Context as _};
+use chrono::{DateTime, Utc};
 use reqwest::{self, blocking::Response, header::CONTENT_TYPE};
 use serde::Deserialize;
-use tracing::info;
+use tracing::{info, warn};
 
-use crate::jobs::Job;
+use crate::{
+    apis::{self, http, GetApiKeyError},
+    date_filter::DateFilter,
+    jobs::{Job, Timestamp},
+    run_summary, snapshots, synthetic,
+};
 
 const DEFAULT_CACHE_FILE: &str = "job_nimbus_api_key.txt";
 
-#[derive(Debug, thiserror::Error)]
-pub enum GetApiKeyError {
-    #[error("JobNimbus API key was not specified and the cache file does not exist.")]
-    MissingApiKey,
-    #[error(transparent)]
-    IoError(#[from] std::io::Error),
+/// The number of jobs in the demo dataset.
+const DEMO_JOB_COUNT: usize = 500;
+
+/// Whether the jobs come from the demo dataset instead of JobNimbus.
+static DEMO_MODE: AtomicBool = AtomicBool::new(false);
+
+/// Makes every fetch return a synthetic demo dataset instead of contacting
+/// JobNimbus, so that every report can be tried out without an API key or real
+/// customer data. The dataset is always the same, so reports generated from it
+/// can be compared across versions.
+pub fn enable_demo_mode() {
+    DEMO_MODE.store(true, Ordering::Relaxed);
 }
 
-pub fn get_api_key(new_api_key: Option<String>) -> Result<String, GetApiKeyError> {
-    let cache_file = Path::new(DEFAULT_CACHE_FILE);
+fn demo_mode() -> bool {
+    DEMO_MODE.load(Ordering::Relaxed)
+}
 
-    if let Some(new_api_key) = new_api_key {
-        let _ = fs::write(cache_file, &new_api_key);
-        Ok(new_api_key)
-    } else if cache_file.exists() {
-        Ok(fs::read_to_string(cache_file)?)
-    } else {
-        Err(GetApiKeyError::MissingApiKey)
+/// The file exported from JobNimbus that the jobs are imported from instead of
+/// being fetched from JobNimbus, if any.
+static IMPORT_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Makes every fetch read the jobs from a file exported from JobNimbus (see
+/// [`import::read_jobs`]) instead of contacting JobNimbus, so that the reports
+/// can be generated without an API key.
+pub fn import_jobs_from(path: PathBuf) {
+    let _ = IMPORT_FILE.set(path);
+}
+
+pub fn get_api_key(new_api_key: Option<String>) -> Result<String, GetApiKeyError> {
+    if demo_mode() || IMPORT_FILE.get().is_some() {
+        // no key is needed, and a real one shouldn't be overwritten
+        return Ok(new_api_key.unwrap_or_default());
     }
+    apis::get_api_key("JobNimbus", DEFAULT_CACHE_FILE, new_api_key)
+}
+
+/// Builds a filter (in ElasticSearch syntax) that matches jobs whose fields
+/// have all of the specified values, e.g. `[("status_name", "Collections")]`.
+pub fn build_filter<'a>(terms: impl IntoIterator<Item = (&'a str, &'a str)>) -> String {
+    let must: Vec<_> = terms
+        .into_iter()
+        .map(|(field, value)| serde_json::json!({ "term": { field: value } }))
+        .collect();
+    serde_json::json!({ "must": must }).to_string()
 }
 
 const ENDPOINT_JOBS: &str = "https://app.jobnimbus.com/api1/jobs";
+const ENDPOINT_ACTIVITIES: &str = "https://app.jobnimbus.com/api1/activities";
+
+/// The URL of the page of the specified job in the JobNimbus web app.
+pub fn job_url(jnid: &str) -> String {
+    format!("https://app.jobnimbus.com/job/{}", jnid)
+}
 
 fn request_from_job_nimbus(
+    endpoint: &str,
     api_key: &str,
-    num_jobs: usize,
+    num_records: usize,
     filter: Option<&str>,
+    deadline: &http::Deadline,
 ) -> anyhow::Result<Response> {
-    let url = reqwest::Url::parse(ENDPOINT_JOBS)?;
-    let client = reqwest::blocking::Client::new();
-    let mut request = client
-        .get(url.clone())
-        .bearer_auth(&api_key)
-        .header(CONTENT_TYPE, "application/json")
-        .query(&[("size", num_jobs.to_string().as_str())]);
+    let url = reqwest::Url::parse(endpoint)?;
+    let client = http::blocking_client()?;
+    let mut request = deadline.limit_request(
+        client
+            .get(url.clone())
+            .bearer_auth(api_key)
+            .header(CONTENT_TYPE, "application/json")
+            .query(&[("size", num_records.to_string().as_str())]),
+    )?;
     if let Some(filter) = filter {
         request = request.query(&[("filter", filter)]);
     }
@@ -59,6 +109,63 @@ pub fn get_all_jobs_from_job_nimbus(
     api_key: &str,
     filter: Option<&str>,
 ) -> anyhow::Result<Vec<Job>> {
+    let jobs = run_summary::time_phase("fetch", || fetch_all_jobs(api_key, filter))?;
+    run_summary::add_count("jobs_fetched", jobs.len());
+    Ok(jobs)
+}
+
+/// Reads the ElasticSearch filter from the specified file, if any, for the
+/// subcommands that take a `--filter` file.
+pub fn read_filter_file(filter_filename: Option<&str>) -> anyhow::Result<Option<String>> {
+    filter_filename
+        .map(|filename| {
+            std::fs::read_to_string(filename)
+                .with_context(|| format!("Failed to read the filter file {}", filename))
+        })
+        .transpose()
+}
+
+/// Fetches all the jobs, using the contents of the specified file (if any) as
+/// the ElasticSearch filter.
+pub fn get_all_jobs_with_filter_file(
+    api_key: &str,
+    filter_filename: Option<&str>,
+) -> anyhow::Result<Vec<Job>> {
+    let filter = read_filter_file(filter_filename)?;
+    get_all_jobs_from_job_nimbus(api_key, filter.as_deref())
+}
+
+/// Fetches only the jobs that match the filter (if any) and were created or
+/// updated at or after `since`, e.g. to refresh a previous fetch without
+/// downloading every job again. The demo dataset and imported jobs can't be
+/// fetched partially, so all of them are returned instead.
+pub fn get_jobs_updated_since(
+    api_key: &str,
+    filter: Option<&str>,
+    since: Timestamp,
+) -> anyhow::Result<Vec<Job>> {
+    if demo_mode() || IMPORT_FILE.get().is_some() {
+        return get_all_jobs_from_job_nimbus(api_key, filter);
+    }
+    let filter = updated_since_filter(filter, since)?;
+    get_all_jobs_from_job_nimbus(api_key, Some(&filter))
+}
+
+/// Combines the filter (in ElasticSearch syntax) with a condition that the job
+/// was updated at or after `since`.
+fn updated_since_filter(filter: Option<&str>, since: Timestamp) -> anyhow::Result<String> {
+    let mut must = vec![serde_json::json!({
+        "range": { "date_updated": { "gte": since.timestamp() } }
+    })];
+    if let Some(filter) = filter {
+        let filter: serde_json::Value =
+            serde_json::from_str(filter).context("The filter is not valid JSON")?;
+        must.push(serde_json::json!({ "bool": filter }));
+    }
+    Ok(serde_json::json!({ "must": must }).to_string())
+}
+
+fn fetch_all_jobs(api_key: &str, filter: Option<&str>) -> anyhow::Result<Vec<Job>> {
     use serde_json::Value;
     #[derive(Deserialize)]
     struct ApiResponse {
@@ -66,21 +173,196 @@ pub fn get_all_jobs_from_job_nimbus(
         results: Vec<Value>,
     }
 
+    if demo_mode() {
+        if filter.is_some() {
+            warn!("Ignoring the filter, which doesn't apply to the demo dataset");
+        }
+        info!("using the demo dataset of {} jobs", DEMO_JOB_COUNT);
+        return Ok(synthetic::generate_jobs(DEMO_JOB_COUNT, 0));
+    }
+    if let Some(path) = IMPORT_FILE.get() {
+        if filter.is_some() {
+            warn!("Ignoring the filter, which doesn't apply to imported jobs");
+        }
+        info!("importing the jobs from {}", path.display());
+        return import::read_jobs(path);
+    }
+
     info!("getting all jobs from JobNimbus");
+    let deadline = http::Deadline::start("fetch the jobs from JobNimbus");
 
     // make a request to find out the number of jobs
-    let response = request_from_job_nimbus(api_key, 1, filter)?;
+    let response = request_from_job_nimbus(ENDPOINT_JOBS, api_key, 1, filter, &deadline)?;
     let response: ApiResponse = response.json()?;
     let count = response.count as usize;
 
     info!("detected {} jobs in JobNimbus", count);
 
     // make a request to actually get those jobs
-    let response = request_from_job_nimbus(api_key, count, filter)?;
+    let response = request_from_job_nimbus(ENDPOINT_JOBS, api_key, count, filter, &deadline)?;
     let response: ApiResponse = response.json()?;
     info!("recieved {} jobs from JobNimbus", response.count);
     assert_eq!(response.count as usize, count);
 
-    let results: Result<Vec<_>, _> = response.results.into_iter().map(Job::try_from).collect();
-    Ok(results?)
+    // only unfiltered fetches contain all of the jobs
+    if filter.is_none() && snapshots::enabled() {
+        if let Err(e) = snapshots::save(&response.results, Utc::now()) {
+            warn!("Failed to save snapshot: {}", e);
+        }
+    }
+
+    Ok(parse_jobs(response.results, &DateFilter::load()))
+}
+
+/// Parses the jobs returned by the JobNimbus API, filtering their dates with
+/// `date_filter`. Jobs that can't be parsed are skipped with a warning, so that
+/// one malformed record doesn't fail the whole fetch.
+pub fn parse_jobs(values: Vec<serde_json::Value>, date_filter: &DateFilter) -> Vec<Job> {
+    let num_values = values.len();
+    let jobs: Vec<_> = values
+        .into_iter()
+        .filter_map(|value| match Job::from_json(value, date_filter) {
+            Ok(job) => Some(job),
+            Err(e) => {
+                warn!("Skipping job: {}", e);
+                None
+            }
+        })
+        .collect();
+    if jobs.len() < num_values {
+        run_summary::add_count("jobs_skipped", num_values - jobs.len());
+    }
+    jobs
+}
+
+/// An entry in the activity feed of a record in JobNimbus, e.g. a note, or a
+/// change to a field logged by JobNimbus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Activity {
+    /// The JobNimbus ID of the record (e.g. job) that the activity belongs to.
+    pub primary_jnid: String,
+    pub date_created: Timestamp,
+    pub note: String,
+}
+
+impl TryFrom<serde_json::Value> for Activity {
+    type Error = anyhow::Error;
+
+    fn try_from(value: serde_json::Value) -> anyhow::Result<Self> {
+        let primary_jnid = value
+            .pointer("/primary/id")
+            .and_then(|id| id.as_str())
+            .context("Expected a 'primary.id' field in the activity")?
+            .to_owned();
+        let date_created = value
+            .get("date_created")
+            .and_then(|date| date.as_i64())
+            .and_then(|secs| DateTime::<Utc>::from_timestamp(secs, 0))
+            .context("Expected a 'date_created' field in the activity")?;
+        let note = value.get("note").and_then(|note| note.as_str()).unwrap_or_default().to_owned();
+        Ok(Activity { primary_jnid, date_created, note })
+    }
+}
+
+/// Fetches all the activities that match the filter (in ElasticSearch syntax).
+/// Activities that can't be parsed are skipped with a warning.
+pub fn get_all_activities(api_key: &str, filter: Option<&str>) -> anyhow::Result<Vec<Activity>> {
+    let activities =
+        run_summary::time_phase("fetch_activities", || fetch_all_activities(api_key, filter))?;
+    run_summary::add_count("activities_fetched", activities.len());
+    Ok(activities)
+}
+
+fn fetch_all_activities(api_key: &str, filter: Option<&str>) -> anyhow::Result<Vec<Activity>> {
+    #[derive(Deserialize)]
+    struct ApiResponse {
+        count: u64,
+        #[serde(default)]
+        activity: Vec<serde_json::Value>,
+    }
+
+    if demo_mode() {
+        info!("the demo dataset has no activities");
+        return Ok(Vec::new());
+    }
+    if IMPORT_FILE.get().is_some() {
+        info!("imported jobs have no activities");
+        return Ok(Vec::new());
+    }
+
+    info!("getting activities from JobNimbus");
+    let deadline = http::Deadline::start("fetch the activities from JobNimbus");
+    let response = request_from_job_nimbus(ENDPOINT_ACTIVITIES, api_key, 1, filter, &deadline)?;
+    let count = response.json::<ApiResponse>()?.count as usize;
+    let response = request_from_job_nimbus(ENDPOINT_ACTIVITIES, api_key, count, filter, &deadline)?;
+    let response: ApiResponse = response.json()?;
+    info!("recieved {} activities from JobNimbus", response.activity.len());
+
+    Ok(response
+        .activity
+        .into_iter()
+        .filter_map(|value| match Activity::try_from(value) {
+            Ok(activity) => Some(activity),
+            Err(e) => {
+                warn!("Skipping activity: {}", e);
+                None
+            }
+        })
+        .collect())
+}
+
+/// Updates the specified fields of the job with the specified JobNimbus ID.
+/// `fields` must be a JSON object mapping field names to their new values.
+pub fn update_job(api_key: &str, jnid: &str, fields: &serde_json::Value) -> anyhow::Result<()> {
+    if demo_mode() {
+        bail!("Jobs from the demo dataset can't be updated in JobNimbus");
+    }
+    if IMPORT_FILE.get().is_some() {
+        bail!("Imported jobs can't be updated in JobNimbus without an API key");
+    }
+    let url = reqwest::Url::parse(&format!("{}/{}", ENDPOINT_JOBS, jnid))?;
+    let client = http::blocking_client()?;
+    let response = client
+        .put(url)
+        .bearer_auth(api_key)
+        .header(CONTENT_TYPE, "application/json")
+        .json(fields)
+        .send()?;
+    if !response.status().is_success() {
+        bail!("Request failed with status code: {}", response.status());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn build_filter_terms() {
+        let filter = build_filter([("status_name", "Collections"), ("sales_rep_name", "Jo")]);
+        let filter: serde_json::Value = serde_json::from_str(&filter).unwrap();
+        assert_eq!(
+            filter,
+            serde_json::json!({ "must": [
+                { "term": { "status_name": "Collections" } },
+                { "term": { "sales_rep_name": "Jo" } },
+            ] })
+        );
+    }
+
+    #[test]
+    fn updated_since_filter_keeps_filter() {
+        let since = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let filter = build_filter([("status_name", "Collections")]);
+        let filter = updated_since_filter(Some(&filter), since).unwrap();
+        let filter: serde_json::Value = serde_json::from_str(&filter).unwrap();
+        assert_eq!(
+            filter,
+            serde_json::json!({ "must": [
+                { "range": { "date_updated": { "gte": 1_700_000_000 } } },
+                { "bool": { "must": [{ "term": { "status_name": "Collections" } }] } },
+            ] })
+        );
+    }
 }
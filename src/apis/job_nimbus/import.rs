@@ -0,0 +1,297 @@
+use std::path::Path;
+
+use anyhow::{bail, Context as _};
+use calamine::{Data, Reader as _};
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone as _};
+use serde_json::{Map, Value};
+use tracing::warn;
+
+use crate::{date_filter::DateFilter, jobs::Job, run_summary};
+
+/// The fields of the JobNimbus API that are exported under other column names.
+/// Columns are matched case-insensitively. Every other column (including custom
+/// fields, e.g. "Install Date") has the same name as the field in the API.
+const COLUMNS: &[(&str, &str)] = &[
+    ("job id", "jnid"),
+    ("id", "jnid"),
+    ("name", "name"),
+    ("job name", "name"),
+    ("number", "number"),
+    ("job number", "number"),
+    ("job #", "number"),
+    ("status", "status_name"),
+    ("date status change", "date_status_change"),
+    ("status changed", "date_status_change"),
+    ("date updated", "date_updated"),
+    ("date created", "date_created"),
+    ("sales rep", "sales_rep_name"),
+    ("source", "source_name"),
+    ("lead source", "source_name"),
+    ("address", "address_line1"),
+    ("address line 1", "address_line1"),
+    ("city", "city"),
+    ("state", "state_text"),
+    ("zip", "zip"),
+    ("zip code", "zip"),
+    ("postal code", "zip"),
+    ("estimate total", "approved_estimate_total"),
+    ("approved estimate total", "approved_estimate_total"),
+    ("invoice due", "approved_invoice_due"),
+    ("approved invoice due", "approved_invoice_due"),
+    ("latitude", LATITUDE),
+    ("longitude", LONGITUDE),
+];
+
+const LATITUDE: &str = "geo.lat";
+const LONGITUDE: &str = "geo.lon";
+
+/// The formats of dates written as text, e.g. in a CSV file.
+const DATE_TIME_FORMATS: &[&str] =
+    &["%m/%d/%Y %I:%M %p", "%m/%d/%Y %I:%M:%S %p", "%m/%d/%Y %H:%M", "%Y-%m-%d %H:%M:%S"];
+const DATE_FORMATS: &[&str] = &["%m/%d/%Y", "%m/%d/%y", "%Y-%m-%d"];
+
+/// A cell of an exported file.
+#[derive(Debug, Clone, PartialEq)]
+enum Cell {
+    Empty,
+    Text(String),
+    Number(f64),
+    Bool(bool),
+    DateTime(NaiveDateTime),
+}
+
+/// Reads the jobs from a file that was exported from the list of jobs in
+/// JobNimbus, either as a CSV file or as an Excel workbook (in which case the
+/// first sheet is read). The first row must contain the column names.
+///
+/// Dates in the file are in the local time zone of this computer. If there is
+/// no job ID column, then jobs are identified by their job number (or by their
+/// row if they have none). If there is no column for the time the status last
+/// changed, then the time the job was last updated (or created) is used
+/// instead. Jobs that can't be read (e.g. because they have no status) are
+/// skipped with a warning.
+pub fn read_jobs(path: &Path) -> anyhow::Result<Vec<Job>> {
+    let is_csv = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+    let rows = if is_csv {
+        let reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        read_csv(reader)?
+    } else {
+        read_workbook(path)?
+    };
+    Ok(super::parse_jobs(to_json(rows)?, &DateFilter::load()))
+}
+
+fn read_csv<R: std::io::Read>(mut reader: csv::Reader<R>) -> anyhow::Result<Vec<Vec<Cell>>> {
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        rows.push(record?.iter().map(|text| Cell::Text(text.to_owned())).collect());
+    }
+    Ok(rows)
+}
+
+fn read_workbook(path: &Path) -> anyhow::Result<Vec<Vec<Cell>>> {
+    let mut workbook = calamine::open_workbook_auto(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let Some(range) = workbook.worksheet_range_at(0) else {
+        bail!("{} has no sheets", path.display());
+    };
+    let rows = range?
+        .rows()
+        .map(|row| {
+            row.iter()
+                .map(|data| match data {
+                    Data::String(text) | Data::DateTimeIso(text) => Cell::Text(text.clone()),
+                    Data::Float(number) => Cell::Number(*number),
+                    Data::Int(number) => Cell::Number(*number as f64),
+                    Data::Bool(b) => Cell::Bool(*b),
+                    Data::DateTime(date) => date.as_datetime().map_or(Cell::Empty, Cell::DateTime),
+                    Data::DurationIso(_) | Data::Error(_) | Data::Empty => Cell::Empty,
+                })
+                .collect()
+        })
+        .collect();
+    Ok(rows)
+}
+
+/// Converts the rows of an exported file into jobs as returned by the
+/// JobNimbus API.
+fn to_json(rows: Vec<Vec<Cell>>) -> anyhow::Result<Vec<Value>> {
+    let mut rows = rows.into_iter();
+    let Some(header) = rows.next() else {
+        bail!("The file is empty");
+    };
+    let keys: Vec<_> = header
+        .into_iter()
+        .map(|cell| match cell {
+            Cell::Text(name) => field_name(&name),
+            _ => String::new(),
+        })
+        .collect();
+
+    let mut unreadable_dates = 0;
+    let mut jobs = Vec::new();
+    for (index, row) in rows.enumerate() {
+        let mut job = Map::new();
+        for (key, cell) in keys.iter().zip(row) {
+            let is_date = key.to_lowercase().contains("date");
+            let value = match cell {
+                Cell::Empty => continue,
+                Cell::Text(text) if text.trim().is_empty() => continue,
+                Cell::Text(text) if is_date => match parse_date(text.trim()) {
+                    Some(date) => Value::from(to_timestamp(date)),
+                    None => {
+                        unreadable_dates += 1;
+                        continue;
+                    }
+                },
+                Cell::Text(text) => parse_amount(&text).map_or(Value::String(text), Value::from),
+                // dates that are only formatted as numbers are days since 1900
+                Cell::Number(days) if is_date => {
+                    let date = calamine::ExcelDateTime::new(
+                        days,
+                        calamine::ExcelDateTimeType::DateTime,
+                        false,
+                    );
+                    match date.as_datetime() {
+                        Some(date) => Value::from(to_timestamp(date)),
+                        None => continue,
+                    }
+                }
+                Cell::Number(number) => Value::from(number),
+                Cell::Bool(b) => Value::Bool(b),
+                Cell::DateTime(date) => Value::from(to_timestamp(date)),
+            };
+            match key.as_str() {
+                "" => {}
+                LATITUDE | LONGITUDE => {
+                    let geo = job.entry("geo").or_insert_with(|| Value::Object(Map::new()));
+                    geo[&key[4..]] = value;
+                }
+                _ => {
+                    job.insert(key.clone(), value);
+                }
+            }
+        }
+        if job.is_empty() {
+            continue;
+        }
+
+        if !job.contains_key("jnid") {
+            let jnid =
+                job.get("number").cloned().unwrap_or_else(|| format!("row-{}", index + 2).into());
+            job.insert("jnid".to_owned(), jnid);
+        }
+        if !job.contains_key("date_status_change") {
+            if let Some(date) = job.get("date_updated").or_else(|| job.get("date_created")) {
+                job.insert("date_status_change".to_owned(), date.clone());
+            }
+        }
+        jobs.push(Value::Object(job));
+    }
+
+    if unreadable_dates > 0 {
+        warn!("Ignoring {} dates in an unknown format", unreadable_dates);
+        run_summary::add_count("imported_dates_unreadable", unreadable_dates);
+    }
+    Ok(jobs)
+}
+
+/// The name of the field of the JobNimbus API in the column with the specified
+/// name.
+fn field_name(column: &str) -> String {
+    let column = column.trim();
+    COLUMNS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(column))
+        .map_or_else(|| column.to_owned(), |(_, key)| key.to_string())
+}
+
+fn parse_date(text: &str) -> Option<NaiveDateTime> {
+    let date_time = DATE_TIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok());
+    date_time.or_else(|| {
+        DATE_FORMATS.iter().find_map(|format| {
+            NaiveDate::parse_from_str(text, format)
+                .ok()
+                .map(|date| date.and_time(Default::default()))
+        })
+    })
+}
+
+/// The amount of dollars in text such as `$1,234.50` or `-$20`.
+fn parse_amount(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(text) => (true, text),
+        None => (false, text),
+    };
+    let amount: f64 = text.strip_prefix('$')?.replace(',', "").parse().ok()?;
+    Some(if negative { -amount } else { amount })
+}
+
+/// The number of seconds since the Unix epoch of a date in the local time zone.
+fn to_timestamp(date: NaiveDateTime) -> i64 {
+    match Local.from_local_datetime(&date).earliest() {
+        Some(date) => date.timestamp(),
+        // the date is skipped by a change to daylight saving time
+        None => date.and_utc().timestamp(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jobs::Status;
+
+    #[test]
+    fn jobs_are_read_from_an_exported_csv_file() {
+        let csv = "\
+Job ID,Job Name,Status,Date Status Change,Sales Rep,Insurance Job?,Sales Appt Date,Install Date,Estimate Total,Latitude,Longitude
+abc,Smith Roof,Job Completed,06/03/2024 2:30 PM,Jo Rep,true,05/01/2024,2024-06-01,\"$12,345.67\",39.78,-89.65
+,Jones Gutters,Lead,6/2/2024,,false,someday,,,,
+";
+        let rows =
+            read_csv(csv::ReaderBuilder::new().has_headers(false).from_reader(csv.as_bytes()))
+                .unwrap();
+        let jobs =
+            crate::apis::job_nimbus::parse_jobs(to_json(rows).unwrap(), &DateFilter::default());
+        let date = |date: &str| {
+            let date = NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M").unwrap();
+            Local.from_local_datetime(&date).unwrap().to_utc()
+        };
+
+        assert_eq!(jobs.len(), 2);
+        let job = &jobs[0];
+        assert_eq!(job.jnid, "abc");
+        assert_eq!(job.job_name.as_deref(), Some("Smith Roof"));
+        assert_eq!(job.status, Status::from("Job Completed"));
+        assert_eq!(job.status_mod_date, date("2024-06-03 14:30"));
+        assert_eq!(job.sales_rep.as_deref(), Some("Jo Rep"));
+        assert!(job.insurance_checkbox);
+        assert_eq!(job.milestone_dates.appointment_date, Some(date("2024-05-01 00:00")));
+        assert_eq!(job.milestone_dates.install_date, Some(date("2024-06-01 00:00")));
+        assert_eq!(job.amt_estimate, 1234567);
+        assert!(job.geo.is_some());
+
+        let job = &jobs[1];
+        assert_eq!(job.jnid, "row-3");
+        assert_eq!(job.status_mod_date, date("2024-06-02 00:00"));
+        assert_eq!(job.milestone_dates.appointment_date, None);
+    }
+
+    #[test]
+    fn amounts_and_dates_are_parsed() {
+        assert_eq!(parse_amount("$1,234.50"), Some(1234.5));
+        assert_eq!(parse_amount("-$20"), Some(-20.0));
+        assert_eq!(parse_amount("20"), None);
+        assert_eq!(parse_amount("$abc"), None);
+        assert!(parse_date("12/31/24").is_some());
+        assert!(parse_date("12/31/2024 23:59").is_some());
+        assert_eq!(parse_date("31/12/2024"), None);
+    }
+}
@@ -0,0 +1,66 @@
+use std::{collections::BTreeMap, fs::File, io::BufReader};
+
+use tracing::warn;
+
+use crate::jobs::Geo;
+use crate::storage;
+
+const DEFAULT_BRANCHES_FILE: &str = "branches.json";
+
+/// The locations of the branch offices, keyed by the branch names used in
+/// JobNimbus.
+///
+/// The branches are stored as a JSON object whose keys are branch names and
+/// whose values are coordinates, e.g.
+///
+/// ```json
+/// { "Springfield": { "lat": 39.78, "lon": -89.65 } }
+/// ```
+///
+/// Branch names are matched case-insensitively and ignoring surrounding
+/// whitespace.
+#[derive(Debug, Default)]
+pub struct Branches {
+    offices: BTreeMap<String, Geo>,
+}
+
+impl Branches {
+    /// Loads the branch locations from the default branches file. Returns no
+    /// branches if the file doesn't exist or can't be read.
+    pub fn load() -> Self {
+        let path = storage::config_path(DEFAULT_BRANCHES_FILE);
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to open branches file: {}", e);
+                }
+                return Self::default();
+            }
+        };
+        match serde_json::from_reader(BufReader::new(file)) {
+            Ok(offices) => Self::from_offices(offices),
+            Err(e) => {
+                warn!("Failed to deserialize branches file: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn from_offices(offices: BTreeMap<String, Geo>) -> Self {
+        Self { offices: offices.into_iter().map(|(name, geo)| (lookup_key(&name), geo)).collect() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offices.is_empty()
+    }
+
+    /// The location of the specified branch office, if it is known.
+    pub fn location(&self, branch: &str) -> Option<Geo> {
+        self.offices.get(&lookup_key(branch)).copied()
+    }
+}
+
+fn lookup_key(name: &str) -> String {
+    name.trim().to_lowercase()
+}
@@ -0,0 +1,121 @@
+use std::{collections::BTreeMap, fs::File, io::BufReader};
+
+use regex::Regex;
+use tracing::warn;
+
+use crate::jobs::{Job, JobAnalysisError};
+use crate::storage;
+
+const DEFAULT_FORMATS_FILE: &str = "claim_number_formats.json";
+
+/// The format of the claim numbers issued by each insurance carrier, keyed by
+/// the carrier names used in JobNimbus. Claim numbers that don't match their
+/// carrier's format are usually typos, which get supplements rejected later.
+///
+/// The formats are stored as a JSON object whose keys are carrier names and
+/// whose values are regular expressions, e.g.
+///
+/// ```json
+/// { "State Farm": "\\d{2}-[A-Z0-9]{4}-\\d{3}" }
+/// ```
+///
+/// The whole claim number (ignoring surrounding whitespace) must match the
+/// regular expression. Carrier names are matched case-insensitively and
+/// ignoring surrounding whitespace. Claim numbers of carriers without a format
+/// are not checked.
+#[derive(Debug, Default)]
+pub struct ClaimNumberFormats {
+    formats: BTreeMap<String, Regex>,
+}
+
+impl ClaimNumberFormats {
+    /// Loads the claim number formats from the default formats file. Returns
+    /// no formats if the file doesn't exist or can't be read. Formats that
+    /// aren't valid regular expressions are skipped.
+    pub fn load() -> Self {
+        let path = storage::config_path(DEFAULT_FORMATS_FILE);
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to open claim number formats file: {}", e);
+                }
+                return Self::default();
+            }
+        };
+        match serde_json::from_reader(BufReader::new(file)) {
+            Ok(formats) => Self::from_formats(formats),
+            Err(e) => {
+                warn!("Failed to deserialize claim number formats file: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn from_formats(formats: BTreeMap<String, String>) -> Self {
+        let formats = formats
+            .into_iter()
+            .filter_map(|(carrier, format)| match Regex::new(&format!("^(?:{})$", format)) {
+                Ok(regex) => Some((lookup_key(&carrier), regex)),
+                Err(e) => {
+                    warn!("Invalid claim number format for {}: {}", carrier, e);
+                    None
+                }
+            })
+            .collect();
+        Self { formats }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.formats.is_empty()
+    }
+
+    /// Returns a red flag if the job's claim number doesn't match the format
+    /// of its carrier.
+    pub fn check(&self, job: &Job) -> Option<JobAnalysisError> {
+        let carrier = job.insurance_company_name.as_deref()?;
+        let claim_number = job.insurance_claim_number.as_deref()?.trim();
+        let format = self.formats.get(&lookup_key(carrier))?;
+        if format.is_match(claim_number) {
+            None
+        } else {
+            Some(JobAnalysisError::MalformedClaimNumber(
+                claim_number.to_owned(),
+                carrier.trim().to_owned(),
+            ))
+        }
+    }
+}
+
+fn lookup_key(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jobs::test::make_job;
+
+    #[test]
+    fn claim_numbers_are_checked_against_their_carrier() {
+        let formats = ClaimNumberFormats::from_formats(BTreeMap::from([
+            (" gekko ".to_owned(), r"\d{2}-[A-Z]{3}".to_owned()),
+            ("Broken".to_owned(), "(".to_owned()),
+        ]));
+        let mut job = make_job(true, None, None, None, None, None);
+
+        job.insurance_claim_number = Some(" 12-ABC ".to_owned());
+        assert_eq!(formats.check(&job), None);
+
+        job.insurance_claim_number = Some("12-ABCD".to_owned());
+        assert_eq!(
+            formats.check(&job),
+            Some(JobAnalysisError::MalformedClaimNumber("12-ABCD".to_owned(), "Gekko".to_owned()))
+        );
+
+        job.insurance_company_name = Some("Broken".to_owned());
+        assert_eq!(formats.check(&job), None);
+        job.insurance_company_name = None;
+        assert_eq!(formats.check(&job), None);
+    }
+}
@@ -0,0 +1,316 @@
+use std::{fs::File, io::BufReader};
+
+use serde::Deserialize;
+use thiserror::Error;
+use tracing::warn;
+
+use crate::jobs::{Job, Timestamp};
+use crate::storage;
+
+const DEFAULT_COLUMNS_FILE: &str = "computed_columns.json";
+
+/// A user-defined column that is calculated from other fields of each job and
+/// appended to exports. Columns are stored as a JSON array in the columns file,
+/// e.g.
+///
+/// ```json
+/// [
+///     { "name": "Days Since Contract", "expr": "today - contract_date" },
+///     { "name": "Net", "expr": "amount_estimate - [Material Costs]" }
+/// ]
+/// ```
+///
+/// An expression combines numbers, fields, and parentheses with `+`, `-`, `*`,
+/// and `/`. Dates are measured in days, so subtracting two dates gives the
+/// number of days between them, and amounts are measured in dollars. The
+/// following fields are available:
+///
+/// - `today`
+/// - `appointment_date`, `contingency_date`, `contract_date`, `install_date`,
+///   `loss_date`, `status_date`
+/// - `amount_receivable`, `amount_estimate`, `supplement_requested`,
+///   `supplement_approved`
+///
+/// Any other field is looked up by name among the job's JobNimbus fields (such
+/// as custom fields), and must be a number. Field names containing spaces or
+/// symbols must be surrounded by square brackets.
+#[derive(Debug)]
+pub struct ComputedColumn {
+    pub name: String,
+    expr: Expr,
+}
+
+#[derive(Deserialize)]
+struct ColumnDef {
+    name: String,
+    expr: String,
+}
+
+impl ComputedColumn {
+    pub fn new(name: String, expr: &str) -> Result<Self, ParseError> {
+        Ok(Self { name, expr: Parser::new(expr).parse()? })
+    }
+
+    /// Loads the computed columns from the default columns file. Returns no
+    /// columns if the file doesn't exist or can't be read, and skips columns
+    /// whose expressions are invalid.
+    pub fn load_all() -> Vec<Self> {
+        let path = storage::config_path(DEFAULT_COLUMNS_FILE);
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to open computed columns file: {}", e);
+                }
+                return Vec::new();
+            }
+        };
+        let defs: Vec<ColumnDef> = match serde_json::from_reader(BufReader::new(file)) {
+            Ok(defs) => defs,
+            Err(e) => {
+                warn!("Failed to deserialize computed columns file: {}", e);
+                return Vec::new();
+            }
+        };
+        defs.into_iter()
+            .filter_map(|ColumnDef { name, expr }| match Self::new(name, &expr) {
+                Ok(column) => Some(column),
+                Err(e) => {
+                    warn!("Skipping computed column with invalid expression \"{}\": {}", expr, e);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Evaluates this column for the specified job. `None` if a field that the
+    /// expression depends on is missing or if the result isn't a number (e.g.
+    /// division by zero).
+    pub fn evaluate(&self, job: &Job, today: Timestamp) -> Option<f64> {
+        Some(self.expr.evaluate(job, today)?).filter(|value| value.is_finite())
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("Unexpected character '{0}'")]
+    UnexpectedChar(char),
+    #[error("Unexpected end of expression")]
+    UnexpectedEnd,
+    #[error("Unclosed '{0}'")]
+    Unclosed(char),
+    #[error("Unexpected trailing input \"{0}\"")]
+    TrailingInput(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, PartialEq)]
+enum Expr {
+    Number(f64),
+    Field(String),
+    Neg(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn evaluate(&self, job: &Job, today: Timestamp) -> Option<f64> {
+        match self {
+            Expr::Number(n) => Some(*n),
+            Expr::Field(name) => field_value(job, name, today),
+            Expr::Neg(expr) => Some(-expr.evaluate(job, today)?),
+            Expr::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.evaluate(job, today)?, rhs.evaluate(job, today)?);
+                Some(match op {
+                    Op::Add => lhs + rhs,
+                    Op::Sub => lhs - rhs,
+                    Op::Mul => lhs * rhs,
+                    Op::Div => lhs / rhs,
+                })
+            }
+        }
+    }
+}
+
+fn field_value(job: &Job, name: &str, today: Timestamp) -> Option<f64> {
+    fn days(timestamp: Timestamp) -> f64 {
+        timestamp.timestamp() as f64 / 86400.0
+    }
+    fn dollars(cents: i32) -> f64 {
+        cents as f64 / 100.0
+    }
+
+    let dates = &job.milestone_dates;
+    match name {
+        "today" => Some(days(today)),
+        "appointment_date" => dates.appointment_date.map(days),
+        "contingency_date" => dates.contingency_date.map(days),
+        "contract_date" => dates.contract_date.map(days),
+        "install_date" => dates.install_date.map(days),
+        "loss_date" => dates.loss_date.map(days),
+        "status_date" => Some(days(job.status_mod_date)),
+        "amount_receivable" => Some(dollars(job.amt_receivable)),
+        "amount_estimate" => Some(dollars(job.amt_estimate)),
+        "supplement_requested" => Some(dollars(job.supplement.amt_requested)),
+        "supplement_approved" => Some(dollars(job.supplement.amt_approved)),
+        other => match job.field(other)? {
+            serde_json::Value::Number(n) => n.as_f64(),
+            serde_json::Value::String(s) => s.trim().parse().ok(),
+            _ => None,
+        },
+    }
+}
+
+/// A recursive descent parser for expressions.
+struct Parser<'a> {
+    input: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self { input }
+    }
+
+    fn parse(mut self) -> Result<Expr, ParseError> {
+        let expr = self.parse_sum()?;
+        self.skip_whitespace();
+        if self.input.is_empty() {
+            Ok(expr)
+        } else {
+            Err(ParseError::TrailingInput(self.input.to_owned()))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.input = self.input.trim_start();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.input.chars().next()
+    }
+
+    fn advance(&mut self, num_bytes: usize) -> &'a str {
+        let (taken, rest) = self.input.split_at(num_bytes);
+        self.input = rest;
+        taken
+    }
+
+    fn parse_sum(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.parse_product()?;
+        loop {
+            let op = match self.peek() {
+                Some('+') => Op::Add,
+                Some('-') => Op::Sub,
+                _ => return Ok(expr),
+            };
+            self.advance(1);
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.parse_product()?));
+        }
+    }
+
+    fn parse_product(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.parse_atom()?;
+        loop {
+            let op = match self.peek() {
+                Some('*') => Op::Mul,
+                Some('/') => Op::Div,
+                _ => return Ok(expr),
+            };
+            self.advance(1);
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.parse_atom()?));
+        }
+    }
+
+    fn parse_atom(&mut self) -> Result<Expr, ParseError> {
+        match self.peek().ok_or(ParseError::UnexpectedEnd)? {
+            '-' => {
+                self.advance(1);
+                Ok(Expr::Neg(Box::new(self.parse_atom()?)))
+            }
+            '(' => {
+                self.advance(1);
+                let expr = self.parse_sum()?;
+                if self.peek() != Some(')') {
+                    return Err(ParseError::Unclosed('('));
+                }
+                self.advance(1);
+                Ok(expr)
+            }
+            '[' => {
+                self.advance(1);
+                let len = self.input.find(']').ok_or(ParseError::Unclosed('['))?;
+                let name = self.advance(len).trim().to_owned();
+                self.advance(1);
+                Ok(Expr::Field(name))
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let len = self
+                    .input
+                    .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                    .unwrap_or(self.input.len());
+                let number = self.advance(len);
+                number.parse().map(Expr::Number).map_err(|_| ParseError::UnexpectedChar('.'))
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let len = self
+                    .input
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(self.input.len());
+                Ok(Expr::Field(self.advance(len).to_owned()))
+            }
+            c => Err(ParseError::UnexpectedChar(c)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jobs::{test::make_job, TimeDelta};
+
+    #[test]
+    fn parse_precedence() {
+        use Expr::*;
+        let field = |name: &str| Box::new(Field(name.to_owned()));
+        assert_eq!(
+            Parser::new("a - b * 2").parse().unwrap(),
+            Binary(
+                Op::Sub,
+                field("a"),
+                Box::new(Binary(Op::Mul, field("b"), Box::new(Number(2.0))))
+            )
+        );
+        assert_eq!(
+            Parser::new("-([Roof Squares] + 1.5)").parse().unwrap(),
+            Neg(Box::new(Binary(Op::Add, field("Roof Squares"), Box::new(Number(1.5)))))
+        );
+        assert_eq!(Parser::new("(a + b").parse(), Err(ParseError::Unclosed('(')));
+        assert_eq!(Parser::new("a +").parse(), Err(ParseError::UnexpectedEnd));
+        assert_eq!(Parser::new("a b").parse(), Err(ParseError::TrailingInput("b".to_owned())));
+    }
+
+    #[test]
+    fn evaluate() {
+        let contract_date = Timestamp::UNIX_EPOCH + TimeDelta::days(10);
+        let mut job = make_job(false, None, None, Some(contract_date), None, None);
+        job.amt_estimate = 1_234_500;
+        job.raw.insert("Material Costs".to_owned(), serde_json::json!(4000));
+        let today = Timestamp::UNIX_EPOCH + TimeDelta::days(40);
+
+        let column = |expr| ComputedColumn::new("test".to_owned(), expr).unwrap();
+        assert_eq!(column("today - contract_date").evaluate(&job, today), Some(30.0));
+        assert_eq!(
+            column("amount_estimate - [Material Costs]").evaluate(&job, today),
+            Some(8345.0)
+        );
+        assert_eq!(column("install_date - contract_date").evaluate(&job, today), None);
+        assert_eq!(column("1 / (contract_date - contract_date)").evaluate(&job, today), None);
+    }
+}
@@ -0,0 +1,123 @@
+use std::{fs::File, io::BufReader};
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::jobs::Timestamp;
+use crate::storage;
+
+const DEFAULT_FILTER_FILE: &str = "date_filter.json";
+
+/// Which dates of jobs to accept from JobNimbus. Dates that have no value are
+/// sometimes filled in with a placeholder such as `0` or `-1` (e.g. by an
+/// integration that writes to JobNimbus), which would otherwise show up as a
+/// date in 1970, and dates entered by hand can have a typo in the year.
+///
+/// The filter is stored as a JSON object, e.g.
+///
+/// ```json
+/// { "sentinels": [0, -1, 86400], "earliest": "2000-01-01" }
+/// ```
+///
+/// Timestamps (in seconds since the Unix epoch) listed in `sentinels` are
+/// treated as missing dates. Dates before `earliest` are implausible, so they
+/// are also treated as missing, but are listed among the red flags of the job.
+/// Whether a date is too far in the future depends on when a report is
+/// generated as of, so that is checked by the report instead (see the KPI
+/// report's future horizon), and the filter doesn't depend on the time. Fields
+/// that are left out keep the defaults, which are the values above. The date
+/// that a job's status last changed is required and set by JobNimbus itself,
+/// so it isn't filtered.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DateFilter {
+    pub sentinels: Vec<i64>,
+    pub earliest: NaiveDate,
+}
+
+/// How a date was filtered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilteredDate {
+    Accepted(Timestamp),
+    /// The date is a placeholder for a missing date.
+    Missing,
+    /// The date is implausible.
+    Rejected,
+}
+
+impl Default for DateFilter {
+    fn default() -> Self {
+        DateFilter {
+            sentinels: vec![0, -1, 86400],
+            earliest: NaiveDate::from_ymd_opt(2000, 1, 1).expect("the date is valid"),
+        }
+    }
+}
+
+impl DateFilter {
+    /// Loads the date filter from the default filter file. Returns the default
+    /// filter if the file doesn't exist or can't be read.
+    pub fn load() -> Self {
+        let path = storage::config_path(DEFAULT_FILTER_FILE);
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to open date filter file: {}", e);
+                }
+                return Self::default();
+            }
+        };
+        match serde_json::from_reader(BufReader::new(file)) {
+            Ok(filter) => filter,
+            Err(e) => {
+                warn!("Failed to deserialize date filter file: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Filters a timestamp (in seconds since the Unix epoch).
+    pub fn filter(&self, seconds: i64) -> FilteredDate {
+        if self.sentinels.contains(&seconds) {
+            return FilteredDate::Missing;
+        }
+        let Some(date) = Timestamp::from_timestamp(seconds, 0) else {
+            return FilteredDate::Rejected;
+        };
+        if date.date_naive() < self.earliest {
+            return FilteredDate::Rejected;
+        }
+        FilteredDate::Accepted(date)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sentinels_and_implausible_dates_are_filtered() {
+        let filter = DateFilter::default();
+        let date = |s: &str| s.parse::<Timestamp>().unwrap();
+
+        assert_eq!(filter.filter(0), FilteredDate::Missing);
+        assert_eq!(filter.filter(-1), FilteredDate::Missing);
+        assert_eq!(filter.filter(86400), FilteredDate::Missing);
+        assert_eq!(filter.filter(2 * 86400), FilteredDate::Rejected);
+        assert_eq!(filter.filter(i64::MAX), FilteredDate::Rejected);
+
+        let accepted = date("2000-01-01T00:00:00Z");
+        assert_eq!(filter.filter(accepted.timestamp()), FilteredDate::Accepted(accepted));
+        // far future dates are left to the report to check
+        let far_future = date("2099-06-01T00:00:00Z");
+        assert_eq!(filter.filter(far_future.timestamp()), FilteredDate::Accepted(far_future));
+
+        let filter: DateFilter = serde_json::from_str(r#"{ "sentinels": [] }"#).unwrap();
+        assert_eq!(filter.earliest, DateFilter::default().earliest);
+        let filter: DateFilter =
+            serde_json::from_str(r#"{ "sentinels": [], "earliest": "1970-01-01" }"#).unwrap();
+        assert_eq!(filter.filter(0), FilteredDate::Accepted(Timestamp::UNIX_EPOCH));
+    }
+}
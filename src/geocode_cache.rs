@@ -0,0 +1,95 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::PathBuf,
+};
+
+use tracing::warn;
+
+use crate::jobs::Geo;
+use crate::storage;
+
+const DEFAULT_CACHE_FILE: &str = "geocode_cache.json";
+
+/// The coordinates of addresses that have already been geocoded, so that each
+/// address only needs to be looked up (and paid for) once.
+///
+/// The cache is stored as a JSON object whose keys are addresses and whose
+/// values are coordinates, e.g.
+///
+/// ```json
+/// { "123 main st, springfield, il 62701": { "lat": 39.78, "lon": -89.65 } }
+/// ```
+///
+/// Addresses are matched case-insensitively and ignoring extra whitespace. The
+/// cache file is kept in the cache directory.
+#[derive(Debug, Default)]
+pub struct GeocodeCache {
+    entries: BTreeMap<String, Geo>,
+}
+
+impl GeocodeCache {
+    /// The path of the default cache file.
+    pub fn path() -> PathBuf {
+        storage::cache_path(DEFAULT_CACHE_FILE)
+    }
+
+    /// Loads the geocode cache from the default cache file. Returns an empty
+    /// cache if the file doesn't exist or can't be read.
+    pub fn load() -> Self {
+        let file = match File::open(Self::path()) {
+            Ok(file) => file,
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to open geocode cache file: {}", e);
+                }
+                return Self::default();
+            }
+        };
+        match serde_json::from_reader(BufReader::new(file)) {
+            Ok(entries) => Self { entries },
+            Err(e) => {
+                warn!("Failed to deserialize geocode cache file: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Saves the geocode cache to the default cache file.
+    pub fn save(&self) -> std::io::Result<()> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, &self.entries)?;
+        Ok(())
+    }
+
+    pub fn get(&self, address: &str) -> Option<Geo> {
+        self.entries.get(&cache_key(address)).copied()
+    }
+
+    pub fn insert(&mut self, address: &str, geo: Geo) {
+        self.entries.insert(cache_key(address), geo);
+    }
+}
+
+fn cache_key(address: &str) -> String {
+    address.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn get_ignores_case_and_whitespace() {
+        let mut cache = GeocodeCache::default();
+        cache.insert("123 Main St,  Springfield", Geo { lat: 1.0, lon: 2.0 });
+
+        assert_eq!(cache.get(" 123 main st, springfield "), Some(Geo { lat: 1.0, lon: 2.0 }));
+        assert_eq!(cache.get("124 Main St, Springfield"), None);
+    }
+}
@@ -1,4 +1,6 @@
-use std::{fmt::Display, usize};
+use std::fmt::Display;
+
+use thiserror::Error;
 
 use crate::jobs::{TimeDelta, Timestamp};
 
@@ -7,13 +9,30 @@ pub struct Bucket<J> {
     /// The jobs that have achieved this milestone.
     pub achieved: Vec<J>,
     /// The cumulative time in it took for all jobs to reach this milestone. The
-    /// average time per job is this field divided by `achieved`.
+    /// average time per job is this field divided by the difference between
+    /// `achieved` and `num_outliers`.
     pub cum_achieve_time: TimeDelta,
     /// The cumulative time it took for jobs that were trying to reach this
     /// milestone but were lost to be lost. The average time per job is this
     /// field divided by the difference between the number of jobs trying to
     /// reach this field and `achieved`.
     pub cum_loss_time: TimeDelta,
+    /// The number of jobs in `achieved` whose time to reach this milestone was
+    /// an outlier, and so was not included in `cum_achieve_time`.
+    pub num_outliers: usize,
+}
+
+impl<J> Bucket<J> {
+    /// The average time it took for jobs to reach this milestone, excluding
+    /// outliers. Zero if no jobs reached this milestone.
+    pub fn average_achieve_time(&self) -> TimeDelta {
+        let num_timed = self.achieved.len() - self.num_outliers;
+        if num_timed == 0 {
+            TimeDelta::zero()
+        } else {
+            self.cum_achieve_time / num_timed.try_into().unwrap()
+        }
+    }
 }
 
 impl<J> Default for Bucket<J> {
@@ -22,22 +41,48 @@ impl<J> Default for Bucket<J> {
             achieved: Vec::new(),
             cum_achieve_time: TimeDelta::zero(),
             cum_loss_time: TimeDelta::zero(),
+            num_outliers: 0,
         }
     }
 }
 
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum JobTrackerError {
+    #[error("The job has no milestones.")]
+    NoMilestones,
+    #[error("The job reached {0} milestones, but there are only {1}.")]
+    TooManyMilestones(usize, usize),
+    #[error("The job has a date for milestone {1}, which does not apply to jobs of kind {0}.")]
+    InapplicableMilestone(usize, usize),
+    #[error("The job was not lost, but it has not reached the final milestone.")]
+    NotLostOrFinished,
+    #[error("Jobs of kind {0} are not able to reach milestone {1}.")]
+    UnreachableMilestone(usize, usize),
+}
+
 /// Each row corresponds to one possible kind of job, and tracks data for that
 /// kind of job.
 #[derive(Debug)]
 pub struct JobTracker<const M: usize, const N: usize, J> {
     buckets: [[Option<Bucket<J>>; N]; M],
+    /// The maximum time that it can take to reach a milestone from the
+    /// previous one. Longer times are considered outliers (e.g. caused by a
+    /// typo in a date), and are excluded from the cumulative times.
+    max_achieve_time: Option<TimeDelta>,
 }
 
 impl<const M: usize, const N: usize, J: Clone + PartialEq> JobTracker<M, N, J> {
     pub fn new(mask: [[bool; N]; M]) -> Self {
         let buckets =
             mask.map(|row| row.map(|enabled| if enabled { Some(Bucket::default()) } else { None }));
-        JobTracker { buckets }
+        JobTracker { buckets, max_achieve_time: None }
+    }
+
+    /// Sets the maximum time that it can take to reach a milestone from the
+    /// previous one before the time is considered an outlier.
+    pub fn with_max_achieve_time(mut self, max_achieve_time: TimeDelta) -> Self {
+        self.max_achieve_time = Some(max_achieve_time);
+        self
     }
 
     /// Adds a job of the specified kind to the tracker. The timestamps
@@ -50,22 +95,28 @@ impl<const M: usize, const N: usize, J: Clone + PartialEq> JobTracker<M, N, J> {
     /// job; it must not exceed the total number of job milestones (i.e. N), and
     /// must be greater than 0. The loss_timestamp is the time at which the job
     /// was lost, if it was lost. If the job was not lost, which is equivalent
-    /// to if the job reached the final milestone, this should be None.
+    /// to if the job reached the final milestone, this should be None. A job
+    /// that reached the final milestone is never counted as lost, so its
+    /// loss_timestamp is ignored.
+    ///
+    /// Returns the milestones whose time to reach was an outlier, along with
+    /// the time. If the job does not meet the requirements above, returns an
+    /// error without adding the job.
     pub fn add_job(
         &mut self,
         job: &J,
         kind: usize,
         timestamps: &[Option<Timestamp>],
         loss_timestamp: Option<Timestamp>,
-    ) {
-        assert!(timestamps.len() > 0 && timestamps.len() <= N);
+    ) -> Result<Vec<(usize, TimeDelta)>, JobTrackerError> {
+        self.check_job(kind, timestamps, loss_timestamp)?;
 
+        let mut outliers = Vec::new();
         let mut latest_timestamp = None;
         for (milestone, &timestamp) in timestamps.iter().enumerate() {
             // only iterate over those timestamps that correspond to actual
             // milestones for this kind of job.
             let Some(bucket) = &mut self.buckets[kind][milestone] else {
-                assert!(timestamp.is_none(), "Timestamp must be None for a milestone that does not apply to the specific job kind");
                 continue;
             };
 
@@ -77,7 +128,12 @@ impl<const M: usize, const N: usize, J: Clone + PartialEq> JobTracker<M, N, J> {
                 } else {
                     TimeDelta::zero()
                 };
-                bucket.cum_achieve_time = bucket.cum_achieve_time + time_till_this_milestone;
+                if self.max_achieve_time.is_some_and(|max| time_till_this_milestone > max) {
+                    bucket.num_outliers += 1;
+                    outliers.push((milestone, time_till_this_milestone));
+                } else {
+                    bucket.cum_achieve_time += time_till_this_milestone;
+                }
                 latest_timestamp = Some(timestamp);
             }
         }
@@ -89,22 +145,60 @@ impl<const M: usize, const N: usize, J: Clone + PartialEq> JobTracker<M, N, J> {
                 TimeDelta::zero()
             };
 
-            // add the time it took for the job to be lost to the next milestone
-            self.bucket_after(kind, timestamps.len() - 1)
-                .expect("If a job was lost, it must not have reached all milestones")
-                .cum_loss_time += loss_time;
-        } else {
-            assert!(
-                timestamps.len() == N,
-                "If a job was not lost, it must have reached all milestones"
-            );
+            // add the time it took for the job to be lost to the next milestone.
+            // there is no next milestone if the job reached the final one
+            // anyway (e.g. a loss date was set on an installed job), in which
+            // case it was not really lost
+            if let Some(bucket) = self.bucket_after(kind, timestamps.len() - 1) {
+                bucket.cum_loss_time += loss_time;
+            }
         }
+
+        Ok(outliers)
+    }
+
+    /// Checks that a job meets the requirements of `add_job`, so that invalid
+    /// jobs are rejected before any of the buckets are modified.
+    fn check_job(
+        &self,
+        kind: usize,
+        timestamps: &[Option<Timestamp>],
+        loss_timestamp: Option<Timestamp>,
+    ) -> Result<(), JobTrackerError> {
+        if timestamps.is_empty() {
+            return Err(JobTrackerError::NoMilestones);
+        }
+        if timestamps.len() > N {
+            return Err(JobTrackerError::TooManyMilestones(timestamps.len(), N));
+        }
+        for (milestone, timestamp) in timestamps.iter().enumerate() {
+            if timestamp.is_some() && self.buckets[kind][milestone].is_none() {
+                return Err(JobTrackerError::InapplicableMilestone(kind, milestone));
+            }
+        }
+        if loss_timestamp.is_none() && timestamps.len() != N {
+            return Err(JobTrackerError::NotLostOrFinished);
+        }
+        Ok(())
     }
 
     pub fn get_bucket(&self, kind: usize, milestone: usize) -> Option<&Bucket<J>> {
         self.buckets[kind][milestone].as_ref()
     }
 
+    /// Returns the number of jobs of the given kind that have achieved each
+    /// milestone, in milestone order. Milestones that do not apply to the kind
+    /// of job are skipped.
+    pub fn funnel(&self, kind: usize) -> Vec<(usize, usize)> {
+        self.buckets[kind]
+            .iter()
+            .enumerate()
+            .filter_map(|(milestone, bucket)| {
+                bucket.as_ref().map(|bucket| (milestone, bucket.achieved.len()))
+            })
+            .collect()
+    }
+
     fn bucket_before(&self, kind: usize, milestone: usize) -> Option<&Bucket<J>> {
         (0..milestone).rev().find_map(|ms| self.buckets[kind][ms].as_ref())
     }
@@ -117,23 +211,23 @@ impl<const M: usize, const N: usize, J: Clone + PartialEq> JobTracker<M, N, J> {
 
     /// Considering the set of all the jobs of the given numerator kinds that
     /// have achieved the given milestone, calculates stats for this set with
-    /// respect to all the jobs of the given denominator kinds.
-    ///
-    /// # Panics
-    ///
-    /// Panics if one of the specified kinds of jobs is not able to reach the
+    /// respect to all the jobs of the given denominator kinds. Returns an
+    /// error if one of the specified kinds of jobs is not able to reach the
     /// specified milestone.
-    pub fn calc_stats(&self, milestone: usize, kinds: &[usize]) -> CalcStatsResult<J> {
+    pub fn calc_stats(
+        &self,
+        milestone: usize,
+        kinds: &[usize],
+    ) -> Result<CalcStatsResult<J>, JobTrackerError> {
         let buckets: Vec<&Bucket<J>> = kinds
             .iter()
             .map(|&kind| {
                 self.buckets[kind][milestone]
                     .as_ref()
-                    .expect(&format!("kind {} is not able to reach milestone {}", kind, milestone))
+                    .ok_or(JobTrackerError::UnreachableMilestone(kind, milestone))
             })
-            .collect();
-        let total: Vec<J> =
-            buckets.iter().map(|bucket| &bucket.achieved).flatten().cloned().collect();
+            .collect::<Result<_, _>>()?;
+        let total: Vec<J> = buckets.iter().flat_map(|bucket| &bucket.achieved).cloned().collect();
         let num_total = total.len();
         let num_potential = kinds
             .iter()
@@ -148,22 +242,30 @@ impl<const M: usize, const N: usize, J: Clone + PartialEq> JobTracker<M, N, J> {
             if num_potential == 0 { None } else { Some(num_total as f64 / num_potential as f64) };
         let total_time_to_achieve =
             buckets.iter().map(|bucket| bucket.cum_achieve_time).sum::<TimeDelta>();
-        let average_time_to_achieve = if num_total == 0 {
+        let num_timed = num_total - buckets.iter().map(|bucket| bucket.num_outliers).sum::<usize>();
+        let average_time_to_achieve = if num_timed == 0 {
             TimeDelta::zero()
         } else {
-            total_time_to_achieve / num_total.try_into().unwrap()
+            total_time_to_achieve / num_timed.try_into().unwrap()
         };
 
-        CalcStatsResult { achieved: total, conversion_rate, average_time_to_achieve }
+        Ok(CalcStatsResult {
+            achieved: total,
+            conversion_rate,
+            average_time_to_achieve,
+            num_candidates: num_potential,
+        })
     }
 
     /// Considering all jobs, collects all losses and the average time it took
     /// to lose the job (counting from the last achieved milestone until the
     /// time of loss). The average time is zero if there were no losses.
-    pub fn calc_stats_of_loss(&self) -> (Vec<J>, TimeDelta) {
+    /// Returns an error if some kind of job is not able to reach the second or
+    /// the final milestone.
+    pub fn calc_stats_of_loss(&self) -> Result<(Vec<J>, TimeDelta), JobTrackerError> {
         let mut total_lost = Vec::new();
         let mut total_loss_time = TimeDelta::zero();
-        for row in self.buckets.iter() {
+        for (kind, row) in self.buckets.iter().enumerate() {
             // calculate the total time spent on losing jobs (lol). skip the
             // first two milestones because we don't want to count leads that
             // don't turn into appointments as lost jobs
@@ -173,24 +275,24 @@ impl<const M: usize, const N: usize, J: Clone + PartialEq> JobTracker<M, N, J> {
 
             let installed = &row[N - 1]
                 .as_ref()
-                .expect("every job kind should have a final milestone")
+                .ok_or(JobTrackerError::UnreachableMilestone(kind, N - 1))?
                 .achieved;
             total_lost.extend(
                 row[1]
                     .as_ref()
-                    .expect("every job kind should have a second milestone")
+                    .ok_or(JobTrackerError::UnreachableMilestone(kind, 1))?
                     .achieved
                     .iter()
                     .filter(|j| !installed.contains(j))
                     .cloned(),
             );
         }
-        let average_loss_time = if total_lost.len() == 0 {
+        let average_loss_time = if total_lost.is_empty() {
             TimeDelta::zero()
         } else {
             total_loss_time / total_lost.len().try_into().unwrap()
         };
-        (total_lost, average_loss_time)
+        Ok((total_lost, average_loss_time))
     }
 }
 
@@ -207,6 +309,9 @@ pub struct CalcStatsResult<J> {
     /// The average duration it took for a job in the set to reach the specified
     /// milestone. This is zero if the set is empty.
     pub average_time_to_achieve: TimeDelta,
+    /// The number of candidates for conversion into the given set, i.e. the
+    /// denominator of the conversion rate.
+    pub num_candidates: usize,
 }
 
 impl<const M: usize, const N: usize, J> Display for JobTracker<M, N, J> {
@@ -242,72 +347,79 @@ mod test {
         let tracker = JobTracker {
             buckets: [
                 [
-                    Some(Bucket { achieved: vec![(); 80], cum_achieve_time: tu, cum_loss_time: tu }),
-                    Some(Bucket { achieved: vec![(); 70], cum_achieve_time: tu, cum_loss_time: tu }),
-                    Some(Bucket { achieved: vec![(); 60], cum_achieve_time: tu, cum_loss_time: tu }),
-                    Some(Bucket { achieved: vec![(); 50], cum_achieve_time: tu, cum_loss_time: tu }),
-                    Some(Bucket { achieved: vec![(); 40], cum_achieve_time: tu, cum_loss_time: tu }),
+                    Some(Bucket { achieved: vec![(); 80], cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
+                    Some(Bucket { achieved: vec![(); 70], cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
+                    Some(Bucket { achieved: vec![(); 60], cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
+                    Some(Bucket { achieved: vec![(); 50], cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
+                    Some(Bucket { achieved: vec![(); 40], cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
                 ],
                 [
-                    Some(Bucket { achieved: vec![(); 40], cum_achieve_time: tu, cum_loss_time: tu }),
-                    Some(Bucket { achieved: vec![(); 35], cum_achieve_time: tu, cum_loss_time: tu }),
+                    Some(Bucket { achieved: vec![(); 40], cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
+                    Some(Bucket { achieved: vec![(); 35], cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
                     None,
-                    Some(Bucket { achieved: vec![(); 25], cum_achieve_time: tu, cum_loss_time: tu }),
-                    Some(Bucket { achieved: vec![(); 20], cum_achieve_time: tu, cum_loss_time: tu }),
+                    Some(Bucket { achieved: vec![(); 25], cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
+                    Some(Bucket { achieved: vec![(); 20], cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
                 ],
                 [
-                    Some(Bucket { achieved: vec![(); 20], cum_achieve_time: tu, cum_loss_time: tu }),
-                    Some(Bucket { achieved: vec![(); 17], cum_achieve_time: tu, cum_loss_time: tu }),
+                    Some(Bucket { achieved: vec![(); 20], cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
+                    Some(Bucket { achieved: vec![(); 17], cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
                     None,
-                    Some(Bucket { achieved: vec![(); 12], cum_achieve_time: tu, cum_loss_time: tu }),
-                    Some(Bucket { achieved: vec![(); 10], cum_achieve_time: tu, cum_loss_time: tu }),
+                    Some(Bucket { achieved: vec![(); 12], cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
+                    Some(Bucket { achieved: vec![(); 10], cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
                 ],
             ],
+            max_achieve_time: None,
         };
 
         assert_eq!(
-            tracker.calc_stats(0, &[0, 1, 2]),
+            tracker.calc_stats(0, &[0, 1, 2]).unwrap(),
             CalcStatsResult {
                 achieved: vec![(); 80 + 40 + 20],
                 conversion_rate: Some(1.0),
                 average_time_to_achieve: tu * 3 / (80 + 40 + 20),
+                num_candidates: 80 + 40 + 20,
             }
         );
         assert_eq!(
-            tracker.calc_stats(1, &[0, 1, 2]),
+            tracker.calc_stats(1, &[0, 1, 2]).unwrap(),
             CalcStatsResult {
                 achieved: vec![(); 70 + 35 + 17],
                 conversion_rate: Some((70 + 35 + 17) as f64 / (80 + 40 + 20) as f64),
                 average_time_to_achieve: tu * 3 / (70 + 35 + 17),
+                num_candidates: 80 + 40 + 20,
             }
         );
         assert_eq!(
-            tracker.calc_stats(2, &[0]),
+            tracker.calc_stats(2, &[0]).unwrap(),
             CalcStatsResult {
                 achieved: vec![(); 60],
                 conversion_rate: Some(60.0 / 70.0),
                 average_time_to_achieve: tu / 60,
+                num_candidates: 70,
             }
         );
         assert_eq!(
-            tracker.calc_stats(3, &[0, 1]),
+            tracker.calc_stats(3, &[0, 1]).unwrap(),
             CalcStatsResult {
                 achieved: vec![(); 50 + 25],
                 conversion_rate: Some((50 + 25) as f64 / (60 + 35) as f64),
                 average_time_to_achieve: tu * 2 / (50 + 25),
+                num_candidates: 60 + 35,
             }
         );
         assert_eq!(
-            tracker.calc_stats(3, &[2]),
+            tracker.calc_stats(3, &[2]).unwrap(),
             CalcStatsResult {
                 achieved: vec![(); 12],
                 conversion_rate: Some(12.0 / 17.0),
                 average_time_to_achieve: tu / 12,
+                num_candidates: 17,
             }
         );
     }
 
     #[rustfmt::skip]
+    #[allow(clippy::zero_prefixed_literal)]
     #[test]
     fn calc_stats_of_loss() {
         let tu = TimeDelta::days(10);
@@ -315,34 +427,35 @@ mod test {
         let tracker = JobTracker {
             buckets: [
                 [
-                    Some(Bucket { achieved: uniques[000..080].to_owned(), cum_achieve_time: tu, cum_loss_time: tu }),
-                    Some(Bucket { achieved: uniques[000..070].to_owned(), cum_achieve_time: tu, cum_loss_time: tu }),
-                    Some(Bucket { achieved: uniques[000..060].to_owned(), cum_achieve_time: tu, cum_loss_time: tu }),
-                    Some(Bucket { achieved: uniques[000..050].to_owned(), cum_achieve_time: tu, cum_loss_time: tu }),
-                    Some(Bucket { achieved: uniques[000..040].to_owned(), cum_achieve_time: tu, cum_loss_time: tu }),
+                    Some(Bucket { achieved: uniques[000..080].to_owned(), cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
+                    Some(Bucket { achieved: uniques[000..070].to_owned(), cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
+                    Some(Bucket { achieved: uniques[000..060].to_owned(), cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
+                    Some(Bucket { achieved: uniques[000..050].to_owned(), cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
+                    Some(Bucket { achieved: uniques[000..040].to_owned(), cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
                 ],
                 [
-                    Some(Bucket { achieved: uniques[100..140].to_owned(), cum_achieve_time: tu, cum_loss_time: tu }),
-                    Some(Bucket { achieved: uniques[100..135].to_owned(), cum_achieve_time: tu, cum_loss_time: tu }),
+                    Some(Bucket { achieved: uniques[100..140].to_owned(), cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
+                    Some(Bucket { achieved: uniques[100..135].to_owned(), cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
                     None,
-                    Some(Bucket { achieved: uniques[100..125].to_owned(), cum_achieve_time: tu, cum_loss_time: tu }),
-                    Some(Bucket { achieved: uniques[100..120].to_owned(), cum_achieve_time: tu, cum_loss_time: tu }),
+                    Some(Bucket { achieved: uniques[100..125].to_owned(), cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
+                    Some(Bucket { achieved: uniques[100..120].to_owned(), cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
                 ],
                 [
-                    Some(Bucket { achieved: uniques[200..220].to_owned(), cum_achieve_time: tu, cum_loss_time: tu }),
-                    Some(Bucket { achieved: uniques[200..217].to_owned(), cum_achieve_time: tu, cum_loss_time: tu }),
+                    Some(Bucket { achieved: uniques[200..220].to_owned(), cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
+                    Some(Bucket { achieved: uniques[200..217].to_owned(), cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
                     None,
-                    Some(Bucket { achieved: uniques[200..212].to_owned(), cum_achieve_time: tu, cum_loss_time: tu }),
-                    Some(Bucket { achieved: uniques[200..210].to_owned(), cum_achieve_time: tu, cum_loss_time: tu }),
+                    Some(Bucket { achieved: uniques[200..212].to_owned(), cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
+                    Some(Bucket { achieved: uniques[200..210].to_owned(), cum_achieve_time: tu, cum_loss_time: tu, num_outliers: 0 }),
                 ],
             ],
+            max_achieve_time: None,
         };
 
         let mut all_lost = Vec::new();
         all_lost.extend(&uniques[40..70]);
         all_lost.extend(&uniques[120..135]);
         all_lost.extend(&uniques[210..217]);
-        assert_eq!(tracker.calc_stats_of_loss(), (all_lost, (tu * 7) / 52));
+        assert_eq!(tracker.calc_stats_of_loss().unwrap(), (all_lost, (tu * 7) / 52));
     }
 
     #[rustfmt::skip]
@@ -363,28 +476,133 @@ mod test {
             [true, true, false, true, true],
         ]);
 
-        tracker.add_job(&(), 0, &[None, Some(dt(1)), Some(dt(2)), Some(dt(4)), Some(dt(8))], None);
+        tracker.add_job(&(), 0, &[None, Some(dt(1)), Some(dt(2)), Some(dt(4)), Some(dt(8))], None).unwrap();
         assert_eq!(
             tracker.buckets[0],
             [
-                Some(Bucket { achieved: vec![(); 1], cum_achieve_time: td(0), cum_loss_time: td(0) }),
-                Some(Bucket { achieved: vec![(); 1], cum_achieve_time: td(0), cum_loss_time: td(0) }),
-                Some(Bucket { achieved: vec![(); 1], cum_achieve_time: td(1), cum_loss_time: td(0) }),
-                Some(Bucket { achieved: vec![(); 1], cum_achieve_time: td(2), cum_loss_time: td(0) }),
-                Some(Bucket { achieved: vec![(); 1], cum_achieve_time: td(4), cum_loss_time: td(0) }),
+                Some(Bucket { achieved: vec![(); 1], cum_achieve_time: td(0), cum_loss_time: td(0), num_outliers: 0 }),
+                Some(Bucket { achieved: vec![(); 1], cum_achieve_time: td(0), cum_loss_time: td(0), num_outliers: 0 }),
+                Some(Bucket { achieved: vec![(); 1], cum_achieve_time: td(1), cum_loss_time: td(0), num_outliers: 0 }),
+                Some(Bucket { achieved: vec![(); 1], cum_achieve_time: td(2), cum_loss_time: td(0), num_outliers: 0 }),
+                Some(Bucket { achieved: vec![(); 1], cum_achieve_time: td(4), cum_loss_time: td(0), num_outliers: 0 }),
             ]
         );
 
-        tracker.add_job(&(), 0, &[None, Some(dt(2)), None, Some(dt(10))], Some(dt(12)));
+        tracker.add_job(&(), 0, &[None, Some(dt(2)), None, Some(dt(10))], Some(dt(12))).unwrap();
         assert_eq!(
             tracker.buckets[0],
             [
-                Some(Bucket { achieved: vec![(); 2], cum_achieve_time: td(0), cum_loss_time: td(0) }),
-                Some(Bucket { achieved: vec![(); 2], cum_achieve_time: td(0), cum_loss_time: td(0) }),
-                Some(Bucket { achieved: vec![(); 2], cum_achieve_time: td(1), cum_loss_time: td(0) }),
-                Some(Bucket { achieved: vec![(); 2], cum_achieve_time: td(10), cum_loss_time: td(0) }),
-                Some(Bucket { achieved: vec![(); 1], cum_achieve_time: td(4), cum_loss_time: td(2) }),
+                Some(Bucket { achieved: vec![(); 2], cum_achieve_time: td(0), cum_loss_time: td(0), num_outliers: 0 }),
+                Some(Bucket { achieved: vec![(); 2], cum_achieve_time: td(0), cum_loss_time: td(0), num_outliers: 0 }),
+                Some(Bucket { achieved: vec![(); 2], cum_achieve_time: td(1), cum_loss_time: td(0), num_outliers: 0 }),
+                Some(Bucket { achieved: vec![(); 2], cum_achieve_time: td(10), cum_loss_time: td(0), num_outliers: 0 }),
+                Some(Bucket { achieved: vec![(); 1], cum_achieve_time: td(4), cum_loss_time: td(2), num_outliers: 0 }),
             ]
         );
     }
+
+    #[test]
+    fn funnel() {
+        let mut tracker = JobTracker::new([[true, true, true], [true, false, true]]);
+        tracker.add_job(&(), 0, &[None, None, None], None).unwrap();
+        tracker.add_job(&(), 0, &[None, None], Some(Timestamp::UNIX_EPOCH)).unwrap();
+        tracker.add_job(&(), 0, &[None], Some(Timestamp::UNIX_EPOCH)).unwrap();
+        tracker.add_job(&(), 1, &[None, None, None], None).unwrap();
+
+        assert_eq!(tracker.funnel(0), vec![(0, 3), (1, 2), (2, 1)]);
+        assert_eq!(tracker.funnel(1), vec![(0, 1), (2, 1)]);
+    }
+
+    #[test]
+    fn add_invalid_jobs() {
+        let mut tracker = JobTracker::new([[true, true, true], [true, false, true]]);
+        let ts = Some(Timestamp::UNIX_EPOCH);
+        assert_eq!(tracker.add_job(&(), 0, &[], ts), Err(JobTrackerError::NoMilestones));
+        assert_eq!(
+            tracker.add_job(&(), 0, &[None; 4], None),
+            Err(JobTrackerError::TooManyMilestones(4, 3))
+        );
+        assert_eq!(
+            tracker.add_job(&(), 1, &[None, ts], ts),
+            Err(JobTrackerError::InapplicableMilestone(1, 1))
+        );
+        assert_eq!(
+            tracker.add_job(&(), 0, &[None, None], None),
+            Err(JobTrackerError::NotLostOrFinished)
+        );
+        assert_eq!(
+            tracker.calc_stats(1, &[0, 1]),
+            Err(JobTrackerError::UnreachableMilestone(1, 1))
+        );
+
+        // rejected jobs are not added at all
+        assert_eq!(tracker.funnel(0), vec![(0, 0), (1, 0), (2, 0)]);
+        assert_eq!(tracker.funnel(1), vec![(0, 0), (2, 0)]);
+    }
+
+    #[test]
+    fn add_jobs_with_outliers() {
+        fn dt(days: i64) -> Timestamp {
+            Timestamp::UNIX_EPOCH + TimeDelta::days(days)
+        }
+
+        let mut tracker =
+            JobTracker::new([[true, true, true]]).with_max_achieve_time(TimeDelta::days(100));
+        assert_eq!(tracker.add_job(&(), 0, &[None, Some(dt(0)), Some(dt(10))], None), Ok(vec![]));
+        assert_eq!(
+            tracker.add_job(&(), 0, &[None, Some(dt(0)), Some(dt(365))], None),
+            Ok(vec![(2, TimeDelta::days(365))])
+        );
+
+        let bucket = tracker.get_bucket(0, 2).unwrap();
+        assert_eq!(bucket.achieved.len(), 2);
+        assert_eq!(bucket.num_outliers, 1);
+        assert_eq!(bucket.average_achieve_time(), TimeDelta::days(10));
+        assert_eq!(
+            tracker.calc_stats(2, &[0]).unwrap().average_time_to_achieve,
+            TimeDelta::days(10)
+        );
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn analyzed_jobs_are_lost_or_installed(
+            insurance: bool,
+            dates in proptest::array::uniform5(proptest::option::of(0..100i64)),
+        ) {
+            use crate::jobs::{analyze_job, test::make_job};
+
+            let [appointment, contingency, contract, install, loss] =
+                dates.map(|d| d.map(|d| Timestamp::UNIX_EPOCH + TimeDelta::days(d)));
+            let job = make_job(insurance, appointment, contingency, contract, install, loss);
+            let (analyzed, _) = analyze_job(job);
+            let Some(analysis) = analyzed.analysis else {
+                return Ok(());
+            };
+            if analysis.date_settled().is_none() {
+                return Ok(());
+            }
+
+            // the same milestones as the trackers of the KPI report
+            let mut tracker = JobTracker::new([
+                [true, true, true, true, true],
+                [true, true, false, true, true],
+                [true, true, false, true, true],
+            ]);
+            let kind = analysis.kind.into_int();
+            tracker.add_job(&(), kind, &analysis.timestamps, analysis.loss_timestamp).unwrap();
+
+            // a settled job is either installed or lost, never both
+            let installed = tracker.get_bucket(kind, 4).unwrap().achieved.len() == 1;
+            proptest::prop_assert_eq!(installed, analysis.timestamps.len() == 5);
+            proptest::prop_assert!(installed || analysis.loss_timestamp.is_some());
+            if installed {
+                for milestone in 0..5 {
+                    if let Some(bucket) = tracker.get_bucket(kind, milestone) {
+                        proptest::prop_assert_eq!(bucket.cum_loss_time, TimeDelta::zero());
+                    }
+                }
+            }
+        }
+    }
 }
@@ -1,7 +1,11 @@
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use std::{fmt::Display, ops::Index};
 use thiserror::Error;
 
+use crate::date_filter::{DateFilter, FilteredDate};
+use crate::job_tracker::JobTrackerError;
+
 const KEY_JNID: &str = "jnid";
 const KEY_SALES_REP: &str = "sales_rep_name";
 const KEY_INSURANCE_CHECKBOX: &str = "Insurance Job?";
@@ -9,12 +13,33 @@ const KEY_INSURANCE_COMPANY_NAME: &str = "Insurance Company";
 const KEY_INSURANCE_CLAIM_NUMBER: &str = "Claim #";
 const KEY_JOB_NUMBER: &str = "number";
 const KEY_JOB_NAME: &str = "name";
+const KEY_ADDRESS: &str = "address_line1";
+const KEY_CITY: &str = "city";
+const KEY_STATE: &str = "state_text";
+const KEY_ZIP: &str = "zip";
+const KEY_GEO: &str = "geo";
 const KEY_APPOINTMENT_DATE: &str = "Sales Appt Date";
+const KEY_APPOINTMENT_2_DATE: &str = "Appt #2 Date";
+const KEY_APPOINTMENT_3_DATE: &str = "Appt #3 Date";
 const KEY_CONTINGENCY_DATE: &str = "Signed Contingency Date";
 const KEY_CONTRACT_DATE: &str = "Signed Contract Date";
 const KEY_INSTALL_DATE: &str = "Install Date";
 const KEY_LOSS_DATE: &str = "Job Lost Date (Lost Status)";
+const KEY_LOSS_REASON: &str = "Loss Reason";
 const KEY_AMOUNT_RECEIVABLE: &str = "approved_invoice_due";
+const KEY_AMOUNT_ESTIMATE: &str = "approved_estimate_total";
+const KEY_BRANCH: &str = "Branch";
+const KEY_CREW: &str = "Crew";
+const KEY_TRADE: &str = "Trade";
+const KEY_WORK_TYPE: &str = "Work Type";
+const KEY_LEAD_SOURCE: &str = "source_name";
+const KEY_SUPPLEMENT_SUBMITTED_DATE: &str = "Supplement Submitted Date";
+const KEY_SUPPLEMENT_APPROVED_DATE: &str = "Supplement Approved Date";
+const KEY_SUPPLEMENT_AMOUNT_REQUESTED: &str = "Supplement Amount";
+const KEY_SUPPLEMENT_AMOUNT_APPROVED: &str = "Supplement Approved Amount";
+const KEY_PAYMENT_PLAN_INSTALLMENT: &str = "Payment Plan Installment";
+const KEY_PAYMENT_PLAN_NEXT_DUE_DATE: &str = "Payment Plan Next Due Date";
+const KEY_PAYMENT_PLAN_INTERVAL_DAYS: &str = "Payment Plan Interval (Days)";
 const KEY_STATUS_NAME: &str = "status_name";
 const KEY_STATUS_MOD_TIME: &str = "date_status_change";
 
@@ -158,8 +183,189 @@ pub struct Job {
     pub insurance_company_name: Option<String>,
     pub job_number: Option<String>,
     pub job_name: Option<String>,
+    /// The street address of the job site.
+    pub address: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub zip: Option<String>,
+    /// The coordinates of the job site, as geocoded by JobNimbus.
+    pub geo: Option<Geo>,
     /// The amount receivable on this job, in cents.
     pub amt_receivable: i32,
+    /// The total of all approved estimates on this job, in cents. This is the
+    /// best indication of the value of a job before it is invoiced.
+    pub amt_estimate: i32,
+    /// The branch office that the job belongs to.
+    pub branch: Option<String>,
+    /// The lead source of the job exactly as entered in JobNimbus. Since this
+    /// is free text, use a `LeadSourceMap` to normalize it before grouping or
+    /// filtering by it.
+    pub lead_source: Option<String>,
+    /// Why the job was lost, if it was lost and a reason was recorded.
+    pub loss_reason: Option<String>,
+    /// The installer or crew assigned to install the job.
+    pub crew: Option<String>,
+    /// The line of business of the job, e.g. roofing, siding, or gutters.
+    pub trade: Option<String>,
+    /// The dates that the sales appointment was rescheduled to, in order. The
+    /// date of the original appointment is in `milestone_dates`.
+    pub rescheduled_appointment_dates: Vec<Timestamp>,
+    /// Information about the supplement submitted to the insurance company for
+    /// this job, if any.
+    pub supplement: Supplement,
+    /// The payment plan that the customer agreed to for the amount receivable,
+    /// if any.
+    pub payment_plan: Option<PaymentPlan>,
+    /// The dates that were left out because they are implausible (see
+    /// `DateFilter`), which are reported as red flags.
+    pub rejected_dates: Vec<RejectedDate>,
+    /// The JSON object that this job was parsed from, including all the fields
+    /// that aren't parsed into the other fields (e.g. custom fields).
+    pub raw: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Job {
+    /// The full address of the job site on a single line, e.g. "123 Main St,
+    /// Springfield, IL 62701". `None` if no part of the address is known.
+    pub fn full_address(&self) -> Option<String> {
+        let state_zip = [&self.state, &self.zip]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(" ");
+        let parts = [self.address.as_deref(), self.city.as_deref(), Some(state_zip.as_str())]
+            .into_iter()
+            .flatten()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>();
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join(", "))
+        }
+    }
+
+    /// Whether the job belongs to the specified trade, ignoring case and
+    /// surrounding whitespace.
+    pub fn is_trade(&self, trade: &str) -> bool {
+        self.trade.as_deref().is_some_and(|t| t.trim().eq_ignore_ascii_case(trade.trim()))
+    }
+
+    /// The date of the sales appointment, taking rescheduling into account.
+    pub fn current_appointment_date(&self) -> Option<Timestamp> {
+        self.rescheduled_appointment_dates.last().copied().or(self.milestone_dates.appointment_date)
+    }
+
+    /// How long the sales appointment was delayed by rescheduling, i.e. the
+    /// time between the original appointment and the last rescheduled one.
+    /// `None` if the appointment was never rescheduled or if the date of the
+    /// original appointment is unknown.
+    pub fn reschedule_delay(&self) -> Option<TimeDelta> {
+        let original = self.milestone_dates.appointment_date?;
+        let last = self.rescheduled_appointment_dates.last()?;
+        Some(*last - original)
+    }
+
+    /// Returns the value of the specified field of the JobNimbus job, which may
+    /// be a custom field. `None` if the job doesn't have the field.
+    pub fn field(&self, key: &str) -> Option<&serde_json::Value> {
+        self.raw.get(key)
+    }
+
+    /// Like `field`, but renders the value as text for use in a report. Strings
+    /// are returned without quotes, and null values are treated as missing.
+    pub fn field_text(&self, key: &str) -> Option<String> {
+        match self.field(key)? {
+            serde_json::Value::Null => None,
+            serde_json::Value::String(s) => Some(s.clone()),
+            other => Some(other.to_string()),
+        }
+    }
+}
+
+impl From<Job> for serde_json::Value {
+    /// Converts the job back into the JSON object that it was parsed from.
+    fn from(job: Job) -> Self {
+        serde_json::Value::Object(job.raw)
+    }
+}
+
+/// A latitude and longitude, in degrees.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Geo {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+// coordinates are never NaN, so equality is reflexive
+impl Eq for Geo {}
+
+impl Geo {
+    /// The great-circle distance to `other`, in miles. This is a lower bound
+    /// on the driving distance.
+    pub fn distance_miles(&self, other: &Geo) -> f64 {
+        const EARTH_RADIUS_MILES: f64 = 3958.8;
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.lon - self.lon).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_MILES * a.sqrt().asin()
+    }
+}
+
+/// A supplement is a request to the insurance company for additional funds
+/// beyond the original claim, e.g. for damage discovered during the install.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Supplement {
+    pub submitted_date: Option<Timestamp>,
+    pub approved_date: Option<Timestamp>,
+    /// The amount requested in the supplement, in cents.
+    pub amt_requested: i32,
+    /// The amount approved by the insurance company, in cents.
+    pub amt_approved: i32,
+}
+
+/// An agreement to pay the amount receivable of a job in installments. The
+/// office moves the next due date forward whenever an installment is paid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentPlan {
+    /// The amount of each installment, in cents.
+    pub installment: i32,
+    /// The date that the next installment is due.
+    pub next_due_date: Timestamp,
+    /// The number of days between installments. Defaults to 30.
+    pub interval_days: i64,
+}
+
+impl PaymentPlan {
+    const DEFAULT_INTERVAL_DAYS: i64 = 30;
+
+    /// The number of installments that were due by `now` but haven't been
+    /// paid, i.e. the next installment if its due date passed, plus every
+    /// installment that came due after it.
+    pub fn missed_installments(&self, now: Timestamp) -> i64 {
+        if now <= self.next_due_date {
+            return 0;
+        }
+        (now - self.next_due_date).num_days() / self.interval_days + 1
+    }
+}
+
+/// A date of a job that was rejected by the `DateFilter` as implausible.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectedDate {
+    /// The JobNimbus field of the date.
+    pub field: &'static str,
+    /// The timestamp of the date, in seconds since the Unix epoch.
+    pub seconds: i64,
+}
+
+impl Supplement {
+    /// Whether a supplement was submitted but not yet approved.
+    pub fn is_outstanding(&self) -> bool {
+        self.submitted_date.is_some() && self.approved_date.is_none()
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -180,6 +386,16 @@ impl JobKind {
     }
 }
 
+impl Display for JobKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            JobKind::InsuranceWithContingency => write!(f, "Insurance with Contingency"),
+            JobKind::InsuranceWithoutContingency => write!(f, "Insurance without Contingency"),
+            JobKind::Retail => write!(f, "Retail"),
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct JobAnalysis {
     /// The kind of job that we have. This may not be totally accurate if the
@@ -202,6 +418,15 @@ pub struct AnalyzedJob {
     pub analysis: Option<JobAnalysis>,
 }
 
+impl AnalyzedJob {
+    /// Whether the job was abandoned, i.e. marked lost without a loss date, so
+    /// that it will never be settled.
+    pub fn is_abandoned(&self) -> bool {
+        self.job.status == Status::Lost
+            && self.analysis.as_ref().is_some_and(|analysis| analysis.date_settled().is_none())
+    }
+}
+
 impl JobAnalysis {
     /// Returns the date at which the job was settled, or `None` if the job is
     /// not settled.
@@ -220,7 +445,7 @@ impl JobAnalysis {
     }
 }
 
-#[derive(Debug, Error, PartialEq, Eq)]
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
 pub enum JobAnalysisError {
     #[error("This job has signed a contingency form, but is not an insurance job.")]
     ContingencyWithoutInsurance,
@@ -232,10 +457,61 @@ pub enum JobAnalysisError {
     SkippedDates(Milestone),
     #[error("This job has a loss date, but it has already been installed/contracted.")]
     InvalidLoss,
+    #[error("This job may be a duplicate of job(s) {0}.")]
+    PossibleDuplicate(String),
+    #[error("This job's sales rep \"{0}\" is not in the roster.")]
+    UnknownSalesRep(String),
+    #[error("This job's appointment was rescheduled {0} times, but it has no contract.")]
+    RepeatedReschedules(usize),
+    #[error("This job took {1} days to reach the milestone {0}, which is likely a typo.")]
+    OutlierDuration(Milestone, i64),
+    #[error("The date for {} ({}) is in the future.", .0.map(|stage| stage.to_string()).unwrap_or("Job Lost".to_owned()), .1.format("%Y-%m-%d"))]
+    FutureDate(Option<Milestone>, Timestamp),
+    #[error("This job's \"{0}\" ({}) is implausible, so it was ignored.", describe_timestamp(*.1))]
+    ImplausibleDate(&'static str, i64),
+    #[error("This job could not be included in the KPI stats. {0}")]
+    Untrackable(JobTrackerError),
+    #[error("This job's claim number \"{0}\" doesn't match the format of claims from {1}.")]
+    MalformedClaimNumber(String, String),
+}
+
+impl JobAnalysisError {
+    /// A name for the kind of the red flag, which doesn't change with the
+    /// details in its message, e.g. the number of days or the date.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            JobAnalysisError::ContingencyWithoutInsurance => "contingency_without_insurance",
+            JobAnalysisError::InconsistentInsuranceInfo => "inconsistent_insurance_info",
+            JobAnalysisError::OutOfOrderDates(_) => "out_of_order_dates",
+            JobAnalysisError::SkippedDates(_) => "skipped_dates",
+            JobAnalysisError::InvalidLoss => "invalid_loss",
+            JobAnalysisError::PossibleDuplicate(_) => "possible_duplicate",
+            JobAnalysisError::UnknownSalesRep(_) => "unknown_sales_rep",
+            JobAnalysisError::RepeatedReschedules(_) => "repeated_reschedules",
+            JobAnalysisError::OutlierDuration(_, _) => "outlier_duration",
+            JobAnalysisError::FutureDate(..) => "future_date",
+            JobAnalysisError::ImplausibleDate(..) => "implausible_date",
+            JobAnalysisError::Untrackable(_) => "untrackable",
+            JobAnalysisError::MalformedClaimNumber(..) => "malformed_claim_number",
+        }
+    }
+}
+
+/// The date of the timestamp (in seconds since the Unix epoch), or the
+/// timestamp itself if it is out of range.
+fn describe_timestamp(seconds: i64) -> String {
+    match Timestamp::from_timestamp(seconds, 0) {
+        Some(date) => date.format("%Y-%m-%d").to_string(),
+        None => format!("timestamp {}", seconds),
+    }
 }
 
 pub fn analyze_job(job: Job) -> (AnalyzedJob, Vec<JobAnalysisError>) {
-    let mut errors = Vec::new();
+    let mut errors: Vec<_> = job
+        .rejected_dates
+        .iter()
+        .map(|rejected| JobAnalysisError::ImplausibleDate(rejected.field, rejected.seconds))
+        .collect();
 
     'analysis: {
         // determine what kind of job this is. assume that insurance jobs require
@@ -247,9 +523,7 @@ pub fn analyze_job(job: Job) -> (AnalyzedJob, Vec<JobAnalysisError>) {
             if job.insurance_claim_number.is_some() {
                 // in the case of existing insurance info but unchecked box, log the
                 // inconsistency and proceed as if it was an insurance job
-
-                // TODO we might consider this an error in the future
-                // errors.push(JobAnalysisError::InconsistentInsuranceInfo);
+                errors.push(JobAnalysisError::InconsistentInsuranceInfo);
                 JobKind::InsuranceWithContingency
             } else {
                 JobKind::Retail
@@ -326,7 +600,7 @@ pub fn analyze_job(job: Job) -> (AnalyzedJob, Vec<JobAnalysisError>) {
                 analysis: Some(JobAnalysis {
                     kind,
                     timestamps: job.milestone_dates.timestamps_up_to(current_milestone),
-                    loss_timestamp: job.milestone_dates.loss_date.clone(),
+                    loss_timestamp: job.milestone_dates.loss_date,
                 }),
                 job,
             },
@@ -350,32 +624,86 @@ pub enum JobFromJsonError {
     StatusModTimeNotFound(serde_json::Map<String, serde_json::Value>),
 }
 
+/// The range of the timestamps (in seconds since the Unix epoch) that are
+/// accepted from JobNimbus, from 1900 to 2200. Timestamps outside of it are
+/// data entry errors (or in milliseconds), and are treated as missing so that
+/// date arithmetic on them can't overflow.
+const TIMESTAMP_RANGE: std::ops::RangeInclusive<i64> = -2_208_988_800..=7_258_118_400;
+
+/// The number in the JSON value, which JobNimbus (or a user editing a custom
+/// field) sometimes gives as a string.
+fn json_number(value: &serde_json::Value) -> Option<f64> {
+    match value {
+        serde_json::Value::Number(n) => n.as_f64(),
+        serde_json::Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+    .filter(|n: &f64| n.is_finite())
+}
+
 impl TryFrom<serde_json::Value> for Job {
     type Error = JobFromJsonError;
 
+    /// Parses a job as returned by the JobNimbus API, filtering its dates with
+    /// the default `DateFilter`. See [`Job::from_json`].
     fn try_from(value: serde_json::Value) -> Result<Self, JobFromJsonError> {
+        Job::from_json(value, &DateFilter::default())
+    }
+}
+
+impl Job {
+    /// Parses a job as returned by the JobNimbus API. Only a missing ID, status,
+    /// or status change time is an error; any other field that is missing or
+    /// has an unexpected type (e.g. a nested object) is treated as having no
+    /// value, so that one malformed job doesn't stop the others from loading.
+    /// The dates of the job are filtered with `date_filter`.
+    pub fn from_json(
+        value: serde_json::Value,
+        date_filter: &DateFilter,
+    ) -> Result<Self, JobFromJsonError> {
         let serde_json::Value::Object(map) = value else {
             return Err(JobFromJsonError::NotJsonObject(value));
         };
 
-        let Some(jnid) = map.get(KEY_JNID).and_then(|val| val.as_str()).map(str::to_owned) else {
-            return Err(JobFromJsonError::JnidNotFound(map));
-        };
-
         fn get_owned_nonempty(
             map: &serde_json::Map<String, serde_json::Value>,
             key: &str,
         ) -> Option<String> {
-            map.get(key).and_then(|val| val.as_str()).filter(|str| str.len() > 0).map(str::to_owned)
+            match map.get(key)? {
+                serde_json::Value::String(s) if !s.is_empty() => Some(s.clone()),
+                // e.g. a job number or zip code stored as a number
+                serde_json::Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            }
         }
 
+        let Some(jnid) = get_owned_nonempty(&map, KEY_JNID) else {
+            return Err(JobFromJsonError::JnidNotFound(map));
+        };
+
         let sales_rep = get_owned_nonempty(&map, KEY_SALES_REP);
-        let insurance_checkbox =
-            map.get(KEY_INSURANCE_CHECKBOX).and_then(|val| val.as_bool()).unwrap_or(false);
+        let insurance_checkbox = match map.get(KEY_INSURANCE_CHECKBOX) {
+            Some(serde_json::Value::Bool(b)) => *b,
+            Some(serde_json::Value::String(s)) => s.trim().eq_ignore_ascii_case("true"),
+            Some(serde_json::Value::Number(n)) => n.as_f64().is_some_and(|n| n != 0.0),
+            _ => false,
+        };
         let insurance_company_name = get_owned_nonempty(&map, KEY_INSURANCE_COMPANY_NAME);
         let insurance_claim_number = get_owned_nonempty(&map, KEY_INSURANCE_CLAIM_NUMBER);
         let job_number = get_owned_nonempty(&map, KEY_JOB_NUMBER);
         let job_name = get_owned_nonempty(&map, KEY_JOB_NAME);
+        let address = get_owned_nonempty(&map, KEY_ADDRESS);
+        let city = get_owned_nonempty(&map, KEY_CITY);
+        let state = get_owned_nonempty(&map, KEY_STATE);
+        let zip = get_owned_nonempty(&map, KEY_ZIP);
+        // JobNimbus uses (0, 0) for jobs that haven't been geocoded
+        let geo = map
+            .get(KEY_GEO)
+            .and_then(|geo| {
+                Some(Geo { lat: json_number(geo.get("lat")?)?, lon: json_number(geo.get("lon")?)? })
+            })
+            .filter(|geo| geo.lat.abs() <= 90.0 && geo.lon.abs() <= 180.0)
+            .filter(|geo| geo.lat != 0.0 || geo.lon != 0.0);
 
         let status: Status = if let Some(s) = map.get(KEY_STATUS_NAME).and_then(|v| v.as_str()) {
             s.into()
@@ -383,11 +711,24 @@ impl TryFrom<serde_json::Value> for Job {
             return Err(JobFromJsonError::StatusNotFound(map));
         };
 
-        let amt_receivable = map
-            .get(KEY_AMOUNT_RECEIVABLE)
-            .and_then(|val| val.as_f64())
-            .map(|val| val * 100.0)
-            .unwrap_or(0.0) as i32;
+        // amounts are given in dollars, but we store them in cents. amounts
+        // too large to store are treated as missing rather than saturated
+        fn get_cents(map: &serde_json::Map<String, serde_json::Value>, key: &str) -> i32 {
+            map.get(key)
+                .and_then(json_number)
+                .map(|val| val * 100.0)
+                .filter(|&cents| cents.abs() <= i32::MAX as f64)
+                .unwrap_or(0.0) as i32
+        }
+
+        let amt_receivable = get_cents(&map, KEY_AMOUNT_RECEIVABLE);
+        let amt_estimate = get_cents(&map, KEY_AMOUNT_ESTIMATE);
+        let branch = get_owned_nonempty(&map, KEY_BRANCH);
+        let lead_source = get_owned_nonempty(&map, KEY_LEAD_SOURCE);
+        let loss_reason = get_owned_nonempty(&map, KEY_LOSS_REASON);
+        let crew = get_owned_nonempty(&map, KEY_CREW);
+        let trade =
+            get_owned_nonempty(&map, KEY_TRADE).or_else(|| get_owned_nonempty(&map, KEY_WORK_TYPE));
 
         // the JobNimbus API sometimes returns a 0 timestamp for a date that has
         // no value, so we want to filter those out as if the value did not
@@ -397,17 +738,65 @@ impl TryFrom<serde_json::Value> for Job {
             key: &str,
         ) -> Option<Timestamp> {
             map.get(key)
-                .and_then(|value| value.as_i64())
-                .filter(|&val| val != 0)
+                .and_then(json_number)
+                .map(|secs| secs.trunc() as i64)
+                .filter(|&secs| secs != 0 && TIMESTAMP_RANGE.contains(&secs))
                 .and_then(|secs| DateTime::<Utc>::from_timestamp(secs, 0))
         }
 
+        // other placeholders for a missing date are configurable, as are the
+        // dates that are plausible. implausible dates (including the ones that
+        // date arithmetic could overflow on) are also treated as missing, but
+        // are kept to be reported as red flags
+        let mut rejected_dates = Vec::new();
+        let mut get_date = |key: &'static str| {
+            let seconds = map.get(key).and_then(json_number)?.trunc() as i64;
+            let filtered = if TIMESTAMP_RANGE.contains(&seconds) {
+                date_filter.filter(seconds)
+            } else {
+                FilteredDate::Rejected
+            };
+            match filtered {
+                FilteredDate::Accepted(date) => Some(date),
+                FilteredDate::Missing => None,
+                FilteredDate::Rejected => {
+                    rejected_dates.push(RejectedDate { field: key, seconds });
+                    None
+                }
+            }
+        };
+
         // extract all the milestone dates
-        let appointment_date = get_timestamp_nonzero(&map, KEY_APPOINTMENT_DATE);
-        let contingency_date = get_timestamp_nonzero(&map, KEY_CONTINGENCY_DATE);
-        let contract_date = get_timestamp_nonzero(&map, KEY_CONTRACT_DATE);
-        let install_date = get_timestamp_nonzero(&map, KEY_INSTALL_DATE);
-        let loss_date = get_timestamp_nonzero(&map, KEY_LOSS_DATE);
+        let appointment_date = get_date(KEY_APPOINTMENT_DATE);
+        let contingency_date = get_date(KEY_CONTINGENCY_DATE);
+        let contract_date = get_date(KEY_CONTRACT_DATE);
+        let install_date = get_date(KEY_INSTALL_DATE);
+        let loss_date = get_date(KEY_LOSS_DATE);
+        let rescheduled_appointment_dates = [KEY_APPOINTMENT_2_DATE, KEY_APPOINTMENT_3_DATE]
+            .into_iter()
+            .filter_map(&mut get_date)
+            .collect();
+
+        let supplement = Supplement {
+            submitted_date: get_date(KEY_SUPPLEMENT_SUBMITTED_DATE),
+            approved_date: get_date(KEY_SUPPLEMENT_APPROVED_DATE),
+            amt_requested: get_cents(&map, KEY_SUPPLEMENT_AMOUNT_REQUESTED),
+            amt_approved: get_cents(&map, KEY_SUPPLEMENT_AMOUNT_APPROVED),
+        };
+        // a payment plan needs both an installment and a date to pay it by
+        let installment = get_cents(&map, KEY_PAYMENT_PLAN_INSTALLMENT);
+        let payment_plan = get_date(KEY_PAYMENT_PLAN_NEXT_DUE_DATE)
+            .filter(|_| installment > 0)
+            .map(|next_due_date| PaymentPlan {
+                installment,
+                next_due_date,
+                interval_days: map
+                    .get(KEY_PAYMENT_PLAN_INTERVAL_DAYS)
+                    .and_then(json_number)
+                    .map(|days| days.round() as i64)
+                    .filter(|&days| days > 0)
+                    .unwrap_or(PaymentPlan::DEFAULT_INTERVAL_DAYS),
+            });
         let Some(status_mod_date) = get_timestamp_nonzero(&map, KEY_STATUS_MOD_TIME) else {
             return Err(JobFromJsonError::StatusModTimeNotFound(map));
         };
@@ -422,6 +811,11 @@ impl TryFrom<serde_json::Value> for Job {
             insurance_claim_number,
             job_number,
             job_name,
+            address,
+            city,
+            state,
+            zip,
+            geo,
             milestone_dates: MilestoneDates {
                 appointment_date,
                 contingency_date,
@@ -430,22 +824,70 @@ impl TryFrom<serde_json::Value> for Job {
                 loss_date,
             },
             amt_receivable,
+            amt_estimate,
+            branch,
+            lead_source,
+            loss_reason,
+            crew,
+            trade,
+            rescheduled_appointment_dates,
+            supplement,
+            payment_plan,
+            rejected_dates,
+            raw: map,
         })
     }
 }
 
 #[cfg(test)]
-mod test {
+pub(crate) mod test {
     use std::vec;
 
     use super::*;
 
+    /// The keys of all the fields that are parsed.
+    const KEYS: &[&str] = &[
+        KEY_JNID,
+        KEY_SALES_REP,
+        KEY_INSURANCE_CHECKBOX,
+        KEY_INSURANCE_COMPANY_NAME,
+        KEY_INSURANCE_CLAIM_NUMBER,
+        KEY_JOB_NUMBER,
+        KEY_JOB_NAME,
+        KEY_ADDRESS,
+        KEY_CITY,
+        KEY_STATE,
+        KEY_ZIP,
+        KEY_GEO,
+        KEY_APPOINTMENT_DATE,
+        KEY_APPOINTMENT_2_DATE,
+        KEY_APPOINTMENT_3_DATE,
+        KEY_CONTINGENCY_DATE,
+        KEY_CONTRACT_DATE,
+        KEY_INSTALL_DATE,
+        KEY_LOSS_DATE,
+        KEY_LOSS_REASON,
+        KEY_AMOUNT_RECEIVABLE,
+        KEY_AMOUNT_ESTIMATE,
+        KEY_BRANCH,
+        KEY_CREW,
+        KEY_TRADE,
+        KEY_WORK_TYPE,
+        KEY_LEAD_SOURCE,
+        KEY_SUPPLEMENT_SUBMITTED_DATE,
+        KEY_SUPPLEMENT_APPROVED_DATE,
+        KEY_SUPPLEMENT_AMOUNT_REQUESTED,
+        KEY_SUPPLEMENT_AMOUNT_APPROVED,
+        KEY_STATUS_NAME,
+        KEY_STATUS_MOD_TIME,
+    ];
+
     // date-time
     fn dt(seconds: i64) -> Timestamp {
         Timestamp::from_timestamp(seconds, 0).unwrap()
     }
 
-    fn make_job(
+    pub(crate) fn make_job(
         insurance: bool,
         date_1: Option<Timestamp>,
         date_2: Option<Timestamp>,
@@ -463,6 +905,11 @@ mod test {
             insurance_company_name: if insurance { Some("Gekko".to_owned()) } else { None },
             job_number: None,
             job_name: None,
+            address: None,
+            city: None,
+            state: None,
+            zip: None,
+            geo: None,
             milestone_dates: MilestoneDates {
                 appointment_date: date_1,
                 contingency_date: date_2,
@@ -471,6 +918,17 @@ mod test {
                 loss_date: date_5,
             },
             amt_receivable: 0,
+            amt_estimate: 0,
+            branch: None,
+            lead_source: None,
+            loss_reason: None,
+            crew: None,
+            trade: None,
+            rescheduled_appointment_dates: Vec::new(),
+            supplement: Supplement::default(),
+            payment_plan: None,
+            rejected_dates: Vec::new(),
+            raw: serde_json::Map::new(),
         }
     }
 
@@ -680,6 +1138,11 @@ mod test {
             insurance_company_name: Some("Gekko".to_owned()),
             job_number: None,
             job_name: None,
+            address: None,
+            city: None,
+            state: None,
+            zip: None,
+            geo: None,
             milestone_dates: MilestoneDates {
                 appointment_date: Some(dt(1)),
                 contingency_date: None,
@@ -688,6 +1151,17 @@ mod test {
                 loss_date: None,
             },
             amt_receivable: 0,
+            amt_estimate: 0,
+            branch: None,
+            lead_source: None,
+            loss_reason: None,
+            crew: None,
+            trade: None,
+            rescheduled_appointment_dates: Vec::new(),
+            supplement: Supplement::default(),
+            payment_plan: None,
+            rejected_dates: Vec::new(),
+            raw: serde_json::Map::new(),
         };
         assert_eq!(
             analyze_job(job.clone()),
@@ -704,4 +1178,268 @@ mod test {
             )
         );
     }
+
+    #[test]
+    fn job_from_json_address() {
+        let json = serde_json::json!({
+            "jnid": "abc",
+            "status_name": "Jobs In Progress",
+            "date_status_change": 1,
+            "address_line1": "123 Main St",
+            "city": "Springfield",
+            "state_text": "IL",
+            "zip": "62701",
+            "geo": { "lat": 39.78, "lon": -89.65 },
+        });
+        let job = Job::try_from(json).unwrap();
+        assert_eq!(job.full_address().as_deref(), Some("123 Main St, Springfield, IL 62701"));
+        assert_eq!(job.geo, Some(Geo { lat: 39.78, lon: -89.65 }));
+
+        let json = serde_json::json!({
+            "jnid": "abc",
+            "status_name": "Jobs In Progress",
+            "date_status_change": 1,
+            "zip": "62701",
+            "geo": { "lat": 0.0, "lon": 0.0 },
+        });
+        let job = Job::try_from(json).unwrap();
+        assert_eq!(job.full_address().as_deref(), Some("62701"));
+        assert_eq!(job.geo, None);
+    }
+
+    #[test]
+    fn job_from_json_trade() {
+        let json = serde_json::json!({
+            "jnid": "abc",
+            "status_name": "Jobs In Progress",
+            "date_status_change": 1,
+            "Work Type": "Roofing ",
+        });
+        let job = Job::try_from(json).unwrap();
+        assert!(job.is_trade("roofing"));
+        assert!(!job.is_trade("Siding"));
+    }
+
+    #[test]
+    fn geo_distance() {
+        let chicago = Geo { lat: 41.8781, lon: -87.6298 };
+        let springfield = Geo { lat: 39.7817, lon: -89.6501 };
+        assert_eq!(chicago.distance_miles(&chicago), 0.0);
+        let distance = chicago.distance_miles(&springfield);
+        assert!((distance - 179.3).abs() < 0.1, "distance was {}", distance);
+    }
+
+    #[test]
+    fn job_from_json_keeps_custom_fields() {
+        let json = serde_json::json!({
+            "jnid": "abc",
+            "status_name": "Jobs In Progress",
+            "date_status_change": 1,
+            "Roof Pitch": "8/12",
+            "Squares": 32.5,
+            "Gutters?": null,
+        });
+        let job = Job::try_from(json.clone()).unwrap();
+        assert_eq!(job.field_text("Roof Pitch").as_deref(), Some("8/12"));
+        assert_eq!(job.field_text("Squares").as_deref(), Some("32.5"));
+        assert_eq!(job.field_text("Gutters?"), None);
+        assert_eq!(job.field_text("Missing"), None);
+        assert_eq!(serde_json::Value::from(job), json);
+    }
+
+    #[test]
+    fn job_from_json_parses_payment_plan() {
+        let json = |installment: serde_json::Value, interval: serde_json::Value| {
+            serde_json::json!({
+                "jnid": "abc",
+                "status_name": "Pending Payments",
+                "date_status_change": 1,
+                "Payment Plan Installment": installment,
+                "Payment Plan Next Due Date": 1_700_000_000,
+                "Payment Plan Interval (Days)": interval,
+            })
+        };
+        let plan = Job::try_from(json("250.50".into(), 14.into())).unwrap().payment_plan.unwrap();
+        assert_eq!(plan.installment, 25050);
+        assert_eq!(plan.interval_days, 14);
+        let plan = Job::try_from(json(100.into(), 0.into())).unwrap().payment_plan.unwrap();
+        assert_eq!(plan.interval_days, 30);
+        assert_eq!(Job::try_from(json(0.into(), 14.into())).unwrap().payment_plan, None);
+
+        let due = plan.next_due_date;
+        assert_eq!(plan.missed_installments(due), 0);
+        assert_eq!(plan.missed_installments(due + TimeDelta::days(1)), 1);
+        assert_eq!(plan.missed_installments(due + TimeDelta::days(30)), 2);
+        assert_eq!(plan.missed_installments(due + TimeDelta::days(65)), 3);
+    }
+
+    #[test]
+    fn job_from_json_tolerates_unexpected_types() {
+        let json = serde_json::json!({
+            "jnid": 1234,
+            "status_name": "Jobs In Progress",
+            "date_status_change": "1700000000",
+            "number": 1001,
+            "name": { "first": "nested" },
+            "Insurance Job?": "true",
+            "approved_invoice_due": "1250.50",
+            "approved_estimate_total": 1e300,
+            "Sales Appt Date": 1.7e9,
+            "Signed Contract Date": 1_700_000_000_000i64,
+            "Install Date": [1_700_000_000],
+            "geo": { "lat": "39.78", "lon": 1000.0 },
+        });
+        let job = Job::try_from(json).unwrap();
+        assert_eq!(job.jnid, "1234");
+        assert_eq!(job.status_mod_date, dt(1_700_000_000));
+        assert_eq!(job.job_number.as_deref(), Some("1001"));
+        assert_eq!(job.job_name, None);
+        assert!(job.insurance_checkbox);
+        assert_eq!(job.amt_receivable, 125050);
+        assert_eq!(job.amt_estimate, 0);
+        assert_eq!(job.milestone_dates.appointment_date, Some(dt(1_700_000_000)));
+        assert_eq!(job.milestone_dates.contract_date, None);
+        assert_eq!(job.milestone_dates.install_date, None);
+        assert_eq!(job.geo, None);
+
+        let json = serde_json::json!({
+            "jnid": "abc",
+            "status_name": "Jobs In Progress",
+            "date_status_change": i64::MAX,
+        });
+        assert!(matches!(Job::try_from(json), Err(JobFromJsonError::StatusModTimeNotFound(_))));
+    }
+
+    #[test]
+    fn job_from_json_filters_dates() {
+        let json = serde_json::json!({
+            "jnid": "abc",
+            "status_name": "Jobs In Progress",
+            "date_status_change": 1,
+            "Sales Appt Date": -1,
+            "Signed Contract Date": 86400,
+            "Install Date": 946_684_799,
+            "Appt #2 Date": 1_700_000_000,
+        });
+        let job = Job::from_json(json, &DateFilter::default()).unwrap();
+        assert_eq!(job.milestone_dates.appointment_date, None);
+        assert_eq!(job.milestone_dates.contract_date, None);
+        assert_eq!(job.milestone_dates.install_date, None);
+        assert_eq!(job.rescheduled_appointment_dates, [dt(1_700_000_000)]);
+        assert_eq!(
+            job.rejected_dates,
+            [RejectedDate { field: KEY_INSTALL_DATE, seconds: 946_684_799 }]
+        );
+
+        let (_, errors) = analyze_job(job);
+        assert_eq!(errors, [JobAnalysisError::ImplausibleDate(KEY_INSTALL_DATE, 946_684_799)]);
+        assert_eq!(
+            errors[0].to_string(),
+            "This job's \"Install Date\" (1999-12-31) is implausible, so it was ignored."
+        );
+    }
+
+    /// Any JSON value without nesting deeper than a few levels.
+    fn arbitrary_json() -> impl proptest::strategy::Strategy<Value = serde_json::Value> {
+        use proptest::prelude::*;
+        let leaf = prop_oneof![
+            Just(serde_json::Value::Null),
+            any::<bool>().prop_map(serde_json::Value::from),
+            any::<i64>().prop_map(serde_json::Value::from),
+            // plausible timestamps, so that some jobs get analyzed
+            (0..2_000_000_000i64).prop_map(serde_json::Value::from),
+            any::<f64>().prop_map(serde_json::Value::from),
+            "[0-9. a-z-]{0,12}".prop_map(serde_json::Value::from),
+        ];
+        leaf.prop_recursive(3, 16, 4, |inner| {
+            prop_oneof![
+                proptest::collection::vec(inner.clone(), 0..4).prop_map(serde_json::Value::from),
+                proptest::collection::btree_map("[a-z]{1,3}", inner, 0..4)
+                    .prop_map(|map| serde_json::Value::Object(map.into_iter().collect())),
+            ]
+        })
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn job_from_json_never_panics(
+            values in proptest::collection::vec(arbitrary_json(), KEYS.len()),
+            present in proptest::collection::vec(proptest::bool::ANY, KEYS.len()),
+        ) {
+            // every known field, each with an arbitrary value or missing
+            let map = KEYS
+                .iter()
+                .zip(values)
+                .zip(present)
+                .filter(|(_, present)| *present)
+                .map(|((key, value), _)| (key.to_string(), value))
+                .collect();
+            if let Ok(job) = Job::try_from(serde_json::Value::Object(map)) {
+                let _ = analyze_job(job);
+            }
+        }
+
+        #[test]
+        fn in_order_milestones_are_accepted(
+            insurance: bool,
+            skip_contingency: bool,
+            reached in 0..=3usize,
+            lost: bool,
+            gaps in proptest::array::uniform5(0..100i64),
+        ) {
+            // walk the job through `reached` of the appointment, contract,
+            // and install milestones, with each date after the previous one
+            let mut latest = 0;
+            let mut next_date = |gap: i64| {
+                latest += gap;
+                Some(dt(latest))
+            };
+            let appointment = (reached >= 1).then(|| next_date(gaps[0])).flatten();
+            let contingency = (insurance && !skip_contingency && reached >= 1)
+                .then(|| next_date(gaps[1]))
+                .flatten();
+            let contract = (reached >= 2).then(|| next_date(gaps[2])).flatten();
+            let install = (reached >= 3).then(|| next_date(gaps[3])).flatten();
+            let loss = (lost && reached < 2).then(|| next_date(gaps[4])).flatten();
+            let job = make_job(insurance, appointment, contingency, contract, install, loss);
+
+            let (analyzed, errors) = analyze_job(job);
+            proptest::prop_assert_eq!(errors, vec![]);
+            let analysis = analyzed.analysis.unwrap();
+            let expected_kind = match (insurance, contingency, contract) {
+                (false, _, _) => JobKind::Retail,
+                (true, None, Some(_)) => JobKind::InsuranceWithoutContingency,
+                (true, _, _) => JobKind::InsuranceWithContingency,
+            };
+            proptest::prop_assert_eq!(analysis.kind, expected_kind);
+            let last_milestone = [appointment, contingency, contract, install]
+                .iter()
+                .rposition(Option::is_some)
+                .map_or(0, |i| i + 1);
+            proptest::prop_assert_eq!(analysis.timestamps.len(), last_milestone + 1);
+            proptest::prop_assert_eq!(analysis.loss_timestamp, loss);
+        }
+
+        #[test]
+        fn out_of_order_milestones_are_rejected(
+            insurance: bool,
+            dates in proptest::array::uniform5(0..100i64),
+        ) {
+            let [appointment, contingency, contract, install, loss] = dates.map(|d| Some(dt(d)));
+            let job = make_job(insurance, appointment, contingency, contract, install, loss);
+
+            let (analyzed, errors) = analyze_job(job);
+            let in_order = dates.windows(2).all(|pair| pair[0] <= pair[1]);
+            proptest::prop_assert_eq!(analyzed.analysis.is_some(), in_order);
+            if in_order {
+                // every date being present means that the job was lost after
+                // it was installed
+                proptest::prop_assert!(errors.contains(&JobAnalysisError::InvalidLoss));
+            } else {
+                proptest::prop_assert!(errors
+                    .iter()
+                    .any(|e| matches!(e, JobAnalysisError::OutOfOrderDates(_))));
+            }
+        }
+    }
 }
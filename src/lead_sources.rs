@@ -0,0 +1,144 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::BufReader,
+};
+
+use anyhow::Context as _;
+use tracing::warn;
+
+use crate::storage;
+
+const DEFAULT_MAP_FILE: &str = "lead_sources.json";
+
+/// Lead sources in JobNimbus are free text, so the same source is often spelled
+/// several different ways (e.g. "FB", "facebook", and "Facebook Ads"). This
+/// maps each of those spellings to a single canonical name.
+///
+/// The map is stored as a JSON object whose keys are the canonical names and
+/// whose values are lists of aliases, e.g.
+///
+/// ```json
+/// { "Facebook": ["FB", "facebook", "Facebook Ads"] }
+/// ```
+///
+/// Aliases are matched case-insensitively and ignoring surrounding whitespace.
+#[derive(Debug, Default)]
+pub struct LeadSourceMap {
+    /// The aliases of each canonical name, as stored in the map file.
+    canonical_to_aliases: BTreeMap<String, Vec<String>>,
+    /// Maps the lookup key of each alias (and each canonical name) to its
+    /// canonical name.
+    lookup: HashMap<String, String>,
+}
+
+impl LeadSourceMap {
+    /// Loads the lead source map from the default map file. Returns an empty
+    /// map if the file doesn't exist or can't be read.
+    pub fn load() -> Self {
+        let path = storage::config_path(DEFAULT_MAP_FILE);
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to open lead source map file: {}", e);
+                }
+                return Self::default();
+            }
+        };
+        match serde_json::from_reader(BufReader::new(file)) {
+            Ok(canonical_to_aliases) => Self::from_aliases(canonical_to_aliases),
+            Err(e) => {
+                warn!("Failed to deserialize lead source map file: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Applies `update` to the lead source map in the default map file and
+    /// saves the result. The file may be shared with other computers, so it is
+    /// locked while it is updated, so that changes made at the same time aren't
+    /// lost. Fails without saving if the file can't be read.
+    pub fn update(update: impl FnOnce(&mut Self)) -> anyhow::Result<()> {
+        storage::update_config(DEFAULT_MAP_FILE, |contents| {
+            let mut map = match contents {
+                Some(contents) => Self::from_aliases(
+                    serde_json::from_str(contents).context("Failed to read lead source map")?,
+                ),
+                None => Self::default(),
+            };
+            update(&mut map);
+            Ok(serde_json::to_string_pretty(&map.canonical_to_aliases)?)
+        })
+    }
+
+    pub fn from_aliases(canonical_to_aliases: BTreeMap<String, Vec<String>>) -> Self {
+        let mut map = Self::default();
+        for (canonical, aliases) in canonical_to_aliases {
+            map.add_aliases(&canonical, aliases);
+        }
+        map
+    }
+
+    /// Maps the specified aliases to the specified canonical name, replacing
+    /// any previous mappings of those aliases.
+    pub fn add_aliases(&mut self, canonical: &str, aliases: impl IntoIterator<Item = String>) {
+        let canonical = canonical.trim().to_owned();
+        self.lookup.insert(lookup_key(&canonical), canonical.clone());
+        for alias in aliases {
+            let key = lookup_key(&alias);
+            // remove the alias from whichever canonical name it used to map to
+            if let Some(previous) = self.lookup.insert(key.clone(), canonical.clone()) {
+                if let Some(previous_aliases) = self.canonical_to_aliases.get_mut(&previous) {
+                    previous_aliases.retain(|a| lookup_key(a) != key);
+                }
+            }
+            self.canonical_to_aliases.entry(canonical.clone()).or_default().push(alias);
+        }
+        self.canonical_to_aliases.entry(canonical).or_default();
+    }
+
+    /// Returns the canonical name of the specified lead source. Lead sources
+    /// that are not in the map are returned as-is (without surrounding
+    /// whitespace).
+    pub fn normalize<'a>(&'a self, lead_source: &'a str) -> &'a str {
+        self.lookup.get(&lookup_key(lead_source)).map(String::as_str).unwrap_or(lead_source.trim())
+    }
+}
+
+fn lookup_key(lead_source: &str) -> String {
+    lead_source.trim().to_lowercase()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn normalize() {
+        let map = LeadSourceMap::from_aliases(BTreeMap::from([
+            ("Facebook".to_owned(), vec!["FB".to_owned(), "Facebook Ads".to_owned()]),
+            ("Door Knocking".to_owned(), vec!["D2D".to_owned()]),
+        ]));
+
+        assert_eq!(map.normalize("FB"), "Facebook");
+        assert_eq!(map.normalize(" fb "), "Facebook");
+        assert_eq!(map.normalize("facebook"), "Facebook");
+        assert_eq!(map.normalize("FACEBOOK ADS"), "Facebook");
+        assert_eq!(map.normalize("d2d"), "Door Knocking");
+        assert_eq!(map.normalize(" Referral "), "Referral");
+    }
+
+    #[test]
+    fn add_aliases_moves_existing_alias() {
+        let mut map = LeadSourceMap::from_aliases(BTreeMap::from([(
+            "Facebook".to_owned(),
+            vec!["FB".to_owned(), "Social".to_owned()],
+        )]));
+        map.add_aliases("Social Media", ["social".to_owned()]);
+
+        assert_eq!(map.normalize("Social"), "Social Media");
+        assert_eq!(map.canonical_to_aliases["Facebook"], vec!["FB".to_owned()]);
+        assert_eq!(map.canonical_to_aliases["Social Media"], vec!["social".to_owned()]);
+    }
+}
@@ -0,0 +1,222 @@
+use std::{path::PathBuf, time::Instant};
+
+use apis::{google_sheets, http, job_nimbus};
+use clap::{CommandFactory as _, FromArgMatches as _, Parser};
+use subcommands::Subcommand;
+use tracing::warn;
+
+// the library API: the jobs, their analysis, and the results of the reports.
+// the rest of the crate is the command-line tool, which reads its settings and
+// keeps state for the run in ways that an embedding program can't control
+pub mod date_filter;
+pub mod job_tracker;
+pub mod jobs;
+pub mod report_builder;
+pub mod synthetic;
+
+pub(crate) mod apis;
+pub(crate) mod branches;
+pub(crate) mod claim_numbers;
+pub(crate) mod computed_columns;
+pub(crate) mod config_bundle;
+pub(crate) mod geocode_cache;
+pub(crate) mod lead_sources;
+pub(crate) mod red_flag_acks;
+pub(crate) mod report_profile;
+pub(crate) mod roster;
+pub(crate) mod run_summary;
+pub(crate) mod schedule;
+pub(crate) mod snapshots;
+pub(crate) mod storage;
+pub(crate) mod subcommands;
+pub(crate) mod templates;
+pub(crate) mod utils;
+pub(crate) mod webhook;
+
+/// The analyses behind the KPI and AR reports, and their results. The analyses
+/// only depend on their arguments, so they can be run any number of times with
+/// different options.
+pub mod analysis {
+    pub use crate::{
+        claim_numbers::ClaimNumberFormats,
+        roster::{RepInfo, Roster},
+        subcommands::{
+            acc_receivable::{
+                analyze as analyze_acc_receivable, AccRecvableCategory, AccRecvableJob,
+                AccRecvablePaymentPlan, AccRecvableReport, PriorityModel, DEFAULT_CATEGORIES,
+            },
+            kpi::{
+                analyze as analyze_kpi, processing, report, scoring::ScoreWeights, KpiOptions,
+                KpiSubject,
+            },
+        },
+    };
+}
+
+#[derive(Parser, Debug)]
+struct CliArgs {
+    /// The command to perform.
+    #[command(subcommand)]
+    command: Subcommand,
+
+    /// Whether to log debug messages, e.g. each request made to the Google
+    /// Sheets API.
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    /// The file to write a JSON summary of the run to, e.g. the number of jobs
+    /// fetched and the URLs of the Google Sheets written. A summary is always
+    /// printed to stderr at the end of the run.
+    #[arg(long, value_name = "PATH", global = true)]
+    summary_json: Option<String>,
+
+    /// How long a single request to JobNimbus, Google, or GitHub can take, in
+    /// seconds, before it fails. Overrides `request_timeout_secs` in
+    /// `http.json`. Unlimited by default.
+    #[arg(long, value_name = "SECONDS", global = true)]
+    request_timeout: Option<u64>,
+
+    /// How long an operation made of several requests (e.g. fetching all the
+    /// jobs, or uploading a spreadsheet) can take, in seconds, before it fails.
+    /// Overrides `deadline_secs` in `http.json`. Unlimited by default.
+    #[arg(long, value_name = "SECONDS", global = true)]
+    deadline: Option<u64>,
+
+    /// Use a built-in synthetic dataset instead of fetching the jobs from
+    /// JobNimbus, e.g. to try out the reports without an API key or real
+    /// customer data.
+    #[arg(long, global = true)]
+    demo: bool,
+
+    /// Read the jobs from a CSV or Excel file exported from the list of jobs in
+    /// JobNimbus instead of fetching them, e.g. to generate the reports without
+    /// an API key.
+    #[arg(long, value_name = "PATH", global = true, conflicts_with = "demo")]
+    import_jobs: Option<PathBuf>,
+}
+
+/// Exits with a usage error about options that can't be used together.
+fn argument_conflict(message: &str) -> ! {
+    CliArgs::command().error(clap::error::ErrorKind::ArgumentConflict, message).exit()
+}
+
+/// Runs ahitool with the command-line arguments of the process.
+pub fn main() -> anyhow::Result<()> {
+    let matches = CliArgs::command().get_matches();
+    let command_name = matches.subcommand_name().unwrap_or_default().to_owned();
+    let CliArgs { command, verbose, summary_json, request_timeout, deadline, demo, import_jobs } =
+        CliArgs::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    // set up tracing
+    let max_level = if verbose { tracing::Level::DEBUG } else { tracing::Level::INFO };
+    tracing_subscriber::fmt().with_max_level(max_level).init();
+
+    http::set_timeouts(request_timeout, deadline);
+    if demo {
+        job_nimbus::enable_demo_mode();
+    }
+    if let Some(path) = import_jobs {
+        job_nimbus::import_jobs_from(path);
+    }
+
+    let start = Instant::now();
+    let result = run(command);
+
+    let requests = google_sheets::request_count();
+    if requests > 0 {
+        run_summary::add_count("sheets_api_requests", requests);
+    }
+    let summary = run_summary::finish(&command_name, &result, start.elapsed());
+    if let Err(e) = summary.print_human(std::io::stderr()) {
+        warn!("Failed to print run summary: {}", e);
+    }
+    if let Some(path) = summary_json {
+        if let Err(e) = summary.write_json(&path) {
+            warn!("Failed to write run summary to {}: {}", path, e);
+        }
+    }
+
+    result
+}
+
+fn run(command: Subcommand) -> anyhow::Result<()> {
+    match command {
+        Subcommand::Kpi(job_kpi_args) => {
+            subcommands::kpi::main(job_kpi_args)?;
+        }
+        Subcommand::Ar(acc_recv_args) => {
+            subcommands::acc_receivable::main(acc_recv_args)?;
+        }
+        Subcommand::Calendar(calendar_args) => {
+            subcommands::calendar::main(calendar_args)?;
+        }
+        Subcommand::Backlog(backlog_args) => {
+            subcommands::backlog::main(backlog_args)?;
+        }
+        Subcommand::Carriers(carriers_args) => {
+            subcommands::carriers::main(carriers_args)?;
+        }
+        Subcommand::Supplements(supplements_args) => {
+            subcommands::supplements::main(supplements_args)?;
+        }
+        Subcommand::LeadSources(lead_sources_args) => {
+            subcommands::lead_sources::main(lead_sources_args)?;
+        }
+        Subcommand::Losses(losses_args) => {
+            subcommands::losses::main(losses_args)?;
+        }
+        Subcommand::Duplicates(duplicates_args) => {
+            subcommands::duplicates::main(duplicates_args)?;
+        }
+        Subcommand::Abandoned(abandoned_args) => {
+            subcommands::abandoned::main(abandoned_args)?;
+        }
+        Subcommand::Audit(audit_args) => {
+            subcommands::audit::main(audit_args)?;
+        }
+        Subcommand::Cohorts(cohorts_args) => {
+            subcommands::cohorts::main(cohorts_args)?;
+        }
+        Subcommand::Areas(areas_args) => {
+            subcommands::areas::main(areas_args)?;
+        }
+        Subcommand::Jobs(jobs_args) => {
+            subcommands::jobs::main(jobs_args)?;
+        }
+        Subcommand::Crews(crews_args) => {
+            subcommands::crews::main(crews_args)?;
+        }
+        Subcommand::Reschedules(reschedules_args) => {
+            subcommands::reschedules::main(reschedules_args)?;
+        }
+        Subcommand::Reassignments(reassignments_args) => {
+            subcommands::reassignments::main(reassignments_args)?;
+        }
+        Subcommand::Geo(geo_args) => {
+            subcommands::geo::main(geo_args)?;
+        }
+        Subcommand::BranchDistances(branch_distances_args) => {
+            subcommands::branch_distances::main(branch_distances_args)?;
+        }
+        Subcommand::Route(route_args) => {
+            subcommands::route::main(route_args)?;
+        }
+        Subcommand::Serve(serve_args) => {
+            subcommands::serve::main(serve_args)?;
+        }
+        Subcommand::ReportAll(report_all_args) => {
+            subcommands::report_all::main(report_all_args)?;
+        }
+        Subcommand::Config(config_args) => {
+            subcommands::config::main(config_args)?;
+        }
+        Subcommand::Cache(cache_args) => {
+            subcommands::cache::main(cache_args)?;
+        }
+        Subcommand::Update(update_args) => {
+            subcommands::update::main(update_args)?;
+        }
+    }
+
+    Ok(())
+}
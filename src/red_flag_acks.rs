@@ -0,0 +1,137 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::BufReader,
+};
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::storage;
+
+const DEFAULT_ACKS_FILE: &str = "red_flag_acks.json";
+
+/// A red flag of a job, identified by the ID of the job and the kind of the red
+/// flag (see `JobAnalysisError::kind`), so that a job with several red flags
+/// can have some of them acknowledged but not others. The message isn't part
+/// of the key, since it changes with the details of the job (e.g. the number
+/// of days), which would drop the acknowledgement.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RedFlagKey {
+    pub jnid: String,
+    pub kind: String,
+}
+
+/// The red flags that office staff looked into and acknowledged, so that they
+/// are left out of the KPI report. Flags are acknowledged by ticking their
+/// checkbox in the red flags sheet, which is read back before the next export.
+///
+/// The acknowledgements are stored as a JSON list, e.g.
+///
+/// ```json
+/// [{ "jnid": "abc123", "kind": "outlier_duration" }]
+/// ```
+#[derive(Debug, Default)]
+pub struct RedFlagAcks {
+    acknowledged: BTreeSet<RedFlagKey>,
+}
+
+impl RedFlagAcks {
+    /// Loads the acknowledgements from the default acknowledgements file.
+    /// Returns no acknowledgements if the file doesn't exist or can't be read.
+    pub fn load() -> Self {
+        let file = match File::open(storage::config_path(DEFAULT_ACKS_FILE)) {
+            Ok(file) => file,
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to open red flag acknowledgements file: {}", e);
+                }
+                return Self::default();
+            }
+        };
+        match serde_json::from_reader(BufReader::new(file)) {
+            Ok(acknowledged) => RedFlagAcks { acknowledged },
+            Err(e) => {
+                warn!("Failed to deserialize red flag acknowledgements file: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    #[cfg(test)]
+    pub fn from_acknowledged(acknowledged: impl IntoIterator<Item = RedFlagKey>) -> Self {
+        RedFlagAcks { acknowledged: acknowledged.into_iter().collect() }
+    }
+
+    /// Records whether each of the red flags is acknowledged in the default
+    /// acknowledgements file. The file may be shared with other computers, so
+    /// it is locked while it is updated. Returns the number of red flags whose
+    /// acknowledgement changed.
+    pub fn sync(observed: impl IntoIterator<Item = (RedFlagKey, bool)>) -> anyhow::Result<usize> {
+        let mut changed = 0;
+        storage::update_config(DEFAULT_ACKS_FILE, |contents| {
+            let mut acks = match contents {
+                Some(contents) => RedFlagAcks {
+                    acknowledged: serde_json::from_str(contents)
+                        .context("Failed to read red flag acknowledgements")?,
+                },
+                None => Self::default(),
+            };
+            changed = acks.apply(observed);
+            Ok(serde_json::to_string_pretty(&acks.acknowledged)?)
+        })?;
+        Ok(changed)
+    }
+
+    /// Acknowledges the red flags that are observed to be acknowledged and
+    /// unacknowledges the others. A job may have several red flags of the same
+    /// kind, which share an acknowledgement, so a red flag observed in a
+    /// different state than before changes the acknowledgement even if others
+    /// of the same kind weren't changed. Returns the number of red flags whose
+    /// acknowledgement changed.
+    fn apply(&mut self, observed: impl IntoIterator<Item = (RedFlagKey, bool)>) -> usize {
+        let changed: BTreeMap<_, _> = observed
+            .into_iter()
+            .filter(|(key, acknowledged)| self.acknowledged.contains(key) != *acknowledged)
+            .collect();
+        for (key, acknowledged) in &changed {
+            if *acknowledged {
+                self.acknowledged.insert(key.clone());
+            } else {
+                self.acknowledged.remove(key);
+            }
+        }
+        changed.len()
+    }
+
+    pub fn is_acknowledged(&self, jnid: &str, kind: &str) -> bool {
+        self.acknowledged.contains(&RedFlagKey { jnid: jnid.to_owned(), kind: kind.to_owned() })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn key(jnid: &str, kind: &str) -> RedFlagKey {
+        RedFlagKey { jnid: jnid.to_owned(), kind: kind.to_owned() }
+    }
+
+    #[test]
+    fn apply_observed_acknowledgements() {
+        let mut acks = RedFlagAcks::default();
+        let changed = acks.apply([(key("a", "late"), true), (key("b", "late"), false)]);
+        assert_eq!(changed, 1);
+        assert!(acks.is_acknowledged("a", "late"));
+        assert!(!acks.is_acknowledged("a", "early"));
+        assert!(!acks.is_acknowledged("b", "late"));
+
+        // ticking an already acknowledged flag changes nothing, and unticking
+        // one of several flags of the same kind unacknowledges all of them
+        assert_eq!(acks.apply([(key("a", "late"), true)]), 0);
+        let changed = acks.apply([(key("a", "late"), true), (key("a", "late"), false)]);
+        assert_eq!(changed, 1);
+        assert!(!acks.is_acknowledged("a", "late"));
+    }
+}
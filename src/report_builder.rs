@@ -0,0 +1,227 @@
+//! A facade for running the reports programmatically, e.g. from another crate,
+//! without going through the command line. For example:
+//!
+//! ```no_run
+//! use ahitool::report_builder::ReportBuilder;
+//!
+//! let jobs = ahitool::synthetic::generate_jobs(100, 0);
+//! let report = ReportBuilder::kpi().trade("Roofing").min_sample(10).run(jobs).unwrap();
+//! println!("{}", serde_json::to_string_pretty(&report).unwrap());
+//! ```
+
+use anyhow::Result;
+use chrono::Utc;
+
+use crate::apis::job_nimbus;
+use crate::claim_numbers::ClaimNumberFormats;
+use crate::jobs::{Job, Status, Timestamp};
+use crate::roster::Roster;
+use crate::subcommands::acc_receivable::{
+    self, AccRecvableReport, PriorityModel, DEFAULT_CATEGORIES,
+};
+use crate::subcommands::kpi::{
+    self, processing::FutureDates, report::KpiReport, scoring::ScoreWeights, KpiOptions,
+};
+
+/// An analysis that can be run by a `ReportBuilder`.
+pub trait ReportKind {
+    /// The results of the analysis.
+    type Report;
+
+    fn analyze(&self, jobs: Vec<Job>) -> Result<Self::Report>;
+}
+
+impl ReportKind for KpiOptions {
+    type Report = KpiReport;
+
+    fn analyze(&self, jobs: Vec<Job>) -> Result<KpiReport> {
+        kpi::analyze(jobs, self)
+    }
+}
+
+/// The options of the AR analysis.
+pub struct AccRecvableOptions {
+    /// The job statuses to include.
+    pub categories: Vec<Status>,
+    pub priority_model: PriorityModel,
+    /// The time up to which the days in status and the missed installments
+    /// are counted. `None` means the time at which the report is run.
+    pub as_of: Option<Timestamp>,
+}
+
+impl ReportKind for AccRecvableOptions {
+    type Report = AccRecvableReport;
+
+    fn analyze(&self, jobs: Vec<Job>) -> Result<AccRecvableReport> {
+        let now = self.as_of.unwrap_or_else(Utc::now);
+        Ok(acc_receivable::analyze(&jobs, self.categories.clone(), &self.priority_model, now))
+    }
+}
+
+/// A predicate that a job must satisfy to be included in a report.
+type JobFilter = Box<dyn Fn(&Job) -> bool>;
+
+/// Configures a report, then runs it on a set of jobs or on the jobs fetched
+/// from JobNimbus. The options that aren't set keep the same defaults as the
+/// corresponding subcommand.
+pub struct ReportBuilder<K> {
+    kind: K,
+    search_filter: Option<String>,
+    filters: Vec<JobFilter>,
+}
+
+impl<K: ReportKind> ReportBuilder<K> {
+    fn new(kind: K) -> Self {
+        ReportBuilder { kind, search_filter: None, filters: Vec::new() }
+    }
+
+    /// Only includes the jobs for which `predicate` returns true. Can be called
+    /// multiple times, in which case a job must satisfy every predicate.
+    pub fn filter(mut self, predicate: impl Fn(&Job) -> bool + 'static) -> Self {
+        self.filters.push(Box::new(predicate));
+        self
+    }
+
+    /// Only includes the jobs of the specified trade, ignoring case.
+    pub fn trade(self, trade: &str) -> Self {
+        let trade = trade.to_owned();
+        self.filter(move |job| job.is_trade(&trade))
+    }
+
+    /// The ElasticSearch query used to fetch the jobs from JobNimbus in
+    /// `fetch_and_run`. Has no effect on `run`.
+    pub fn search_filter(mut self, filter: impl Into<String>) -> Self {
+        self.search_filter = Some(filter.into());
+        self
+    }
+
+    /// Runs the report on the jobs that satisfy the filters.
+    pub fn run(&self, jobs: Vec<Job>) -> Result<K::Report> {
+        let jobs = jobs.into_iter().filter(|job| self.filters.iter().all(|f| f(job))).collect();
+        self.kind.analyze(jobs)
+    }
+
+    /// Fetches the jobs from JobNimbus, then runs the report on them. The
+    /// requests use the same network settings (`http.json`) as the command
+    /// line.
+    pub fn fetch_and_run(&self, jn_api_key: &str) -> Result<K::Report> {
+        let jobs =
+            job_nimbus::get_all_jobs_from_job_nimbus(jn_api_key, self.search_filter.as_deref())?;
+        self.run(jobs)
+    }
+}
+
+impl ReportBuilder<KpiOptions> {
+    /// A builder for the KPI report.
+    pub fn kpi() -> Self {
+        Self::new(KpiOptions::default())
+    }
+
+    /// Only counts the jobs settled within this range. `None` leaves that end
+    /// of the range open.
+    pub fn date_range(mut self, from: Option<Timestamp>, to: Option<Timestamp>) -> Self {
+        self.kind.from = from;
+        self.kind.to = to;
+        self
+    }
+
+    pub fn roster(mut self, roster: Roster) -> Self {
+        self.kind.roster = Some(roster);
+        self
+    }
+
+    /// Flags the jobs whose claim number doesn't match the format of their
+    /// carrier.
+    pub fn claim_number_formats(mut self, formats: ClaimNumberFormats) -> Self {
+        self.kind.claim_number_formats = Some(formats);
+        self
+    }
+
+    /// Whether to include the stats of the sales reps that are marked inactive
+    /// in the roster.
+    pub fn include_inactive(mut self, include_inactive: bool) -> Self {
+        self.kind.include_inactive = include_inactive;
+        self
+    }
+
+    pub fn min_sample(mut self, min_sample: usize) -> Self {
+        self.kind.min_sample = min_sample;
+        self
+    }
+
+    pub fn max_step_days(mut self, max_step_days: i64) -> Self {
+        self.kind.max_step_days = max_step_days;
+        self
+    }
+
+    /// Flags the jobs with milestone dates after `horizon`, and with `exclude`,
+    /// also leaves them out of the stats.
+    pub fn future_horizon(mut self, horizon: Timestamp, exclude: bool) -> Self {
+        self.kind.future_dates = Some(FutureDates { horizon, exclude });
+        self
+    }
+
+    pub fn score_weights(mut self, score_weights: ScoreWeights) -> Self {
+        self.kind.score_weights = score_weights;
+        self
+    }
+}
+
+impl ReportBuilder<AccRecvableOptions> {
+    /// A builder for the AR report. Unlike the `acc-receivable` subcommand, no
+    /// settings files are read: the categories and the priority model are the
+    /// defaults unless set with `categories` and `priority_model`.
+    pub fn acc_receivable() -> Self {
+        Self::new(AccRecvableOptions {
+            categories: DEFAULT_CATEGORIES.to_vec(),
+            priority_model: PriorityModel::default(),
+            as_of: None,
+        })
+    }
+
+    pub fn categories(mut self, categories: Vec<Status>) -> Self {
+        self.kind.categories = categories;
+        self
+    }
+
+    pub fn priority_model(mut self, priority_model: PriorityModel) -> Self {
+        self.kind.priority_model = priority_model;
+        self
+    }
+
+    pub fn as_of(mut self, as_of: Timestamp) -> Self {
+        self.kind.as_of = Some(as_of);
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::synthetic::generate_jobs;
+
+    #[test]
+    fn filters_are_applied_before_analysis() {
+        let jobs = generate_jobs(200, 3);
+        let roofing = jobs.iter().filter(|job| job.is_trade("roofing")).count();
+        let report = ReportBuilder::kpi().trade("Roofing").run(jobs.clone()).unwrap();
+        assert_eq!(report.reconciliation.total, roofing);
+
+        let report = ReportBuilder::kpi()
+            .trade("Roofing")
+            .filter(|job| job.insurance_claim_number.is_some())
+            .run(jobs)
+            .unwrap();
+        assert!(report.reconciliation.total <= roofing);
+    }
+
+    #[test]
+    fn acc_receivable_uses_categories() {
+        let jobs = generate_jobs(200, 3);
+        let report = ReportBuilder::acc_receivable()
+            .categories(vec![Status::PendingPayments])
+            .run(jobs)
+            .unwrap();
+        assert!(report.categories.iter().all(|category| category.status == "Pending Payments"));
+    }
+}
@@ -0,0 +1,16 @@
+/// Who a report is for, which decides what it includes, so that a report can be
+/// shared with someone without showing them what isn't theirs to see.
+#[derive(Debug, clap::ValueEnum, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReportProfile {
+    /// Everything.
+    #[default]
+    Manager,
+    /// Only what concerns the sales rep specified with `--rep`: in the KPI
+    /// report, their own stats and red flags next to the global stats, without
+    /// the leaderboard or the stats of other reps, teams, and trades; in the AR
+    /// report, their own jobs.
+    Rep,
+    /// The amounts owed, without conversion rates or how each sales rep is
+    /// doing. Only the AR report can be generated with this profile.
+    Accounting,
+}
@@ -0,0 +1,96 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::Context as _;
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+/// A single row of a roster file.
+#[derive(Debug, Deserialize)]
+struct RosterRecord {
+    name: String,
+    #[serde(default)]
+    hire_date: Option<String>,
+    #[serde(default)]
+    active: Option<String>,
+    #[serde(default)]
+    branch: Option<String>,
+    #[serde(default)]
+    team: Option<String>,
+}
+
+/// Information about a single sales rep.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepInfo {
+    /// The name of the rep, as it appears in JobNimbus.
+    pub name: String,
+    pub hire_date: Option<NaiveDate>,
+    /// Whether the rep still works for the company.
+    pub active: bool,
+    pub branch: Option<String>,
+    pub team: Option<String>,
+}
+
+/// Information about the sales reps, which is not stored in JobNimbus.
+///
+/// A roster is loaded from a CSV file with a header row. The `name` column is
+/// required, and the `hire_date` (of the form "%Y-%m-%d"), `active` (e.g.
+/// "yes" or "no", defaulting to active), `branch`, and `team` columns are
+/// optional.
+#[derive(Debug, Default)]
+pub struct Roster {
+    /// Maps the lookup key of each rep's name to the rep's info.
+    reps: HashMap<String, RepInfo>,
+}
+
+impl Roster {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let mut reader = csv::Reader::from_path(path)
+            .with_context(|| format!("Failed to open roster file {}", path.display()))?;
+        let mut reps = HashMap::new();
+        for (i, record) in reader.deserialize::<RosterRecord>().enumerate() {
+            // the header is line 1, so the first record is line 2
+            let line = i + 2;
+            let record = record.with_context(|| format!("Invalid roster record on line {line}"))?;
+            let hire_date = match record.hire_date.as_deref().map(str::trim) {
+                None | Some("") => None,
+                Some(date) => Some(
+                    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                        .with_context(|| format!("Invalid hire date on line {line}"))?,
+                ),
+            };
+            let active = match record.active.as_deref().map(|s| s.trim().to_lowercase()) {
+                None => true,
+                Some(active) => match active.as_str() {
+                    "" | "yes" | "y" | "true" | "1" => true,
+                    "no" | "n" | "false" | "0" => false,
+                    _ => anyhow::bail!("Invalid active flag on line {line}"),
+                },
+            };
+            let nonempty =
+                |s: Option<String>| s.map(|s| s.trim().to_owned()).filter(|s| !s.is_empty());
+            let name = record.name.trim().to_owned();
+            reps.insert(
+                lookup_key(&name),
+                RepInfo {
+                    name,
+                    hire_date,
+                    active,
+                    branch: nonempty(record.branch),
+                    team: nonempty(record.team),
+                },
+            );
+        }
+        Ok(Roster { reps })
+    }
+
+    /// Returns the info of the rep with the specified name. Names are matched
+    /// case-insensitively.
+    pub fn get(&self, name: &str) -> Option<&RepInfo> {
+        self.reps.get(&lookup_key(name))
+    }
+}
+
+fn lookup_key(name: &str) -> String {
+    name.trim().to_lowercase()
+}
@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tracing::{debug, info_span};
+
+/// A summary of what a run of ahitool did, printed at the end of every run so
+/// that wrapper scripts can verify that the run did what was expected.
+#[derive(Debug, Serialize)]
+pub struct RunSummary {
+    /// The subcommand that was run, e.g. "kpi".
+    pub command: String,
+    pub succeeded: bool,
+    /// The error that the run failed with, if any.
+    pub error: Option<String>,
+    /// Counts of things processed during the run, e.g. "jobs_fetched".
+    pub counts: BTreeMap<String, usize>,
+    /// The URLs of the Google Sheets written during the run.
+    pub spreadsheet_urls: Vec<String>,
+    /// Things that the run did differently than usual and that the user
+    /// should know about, e.g. creating a new spreadsheet because the saved
+    /// one was deleted.
+    pub notices: Vec<String>,
+    /// The time spent in each phase of the run, in the order in which the
+    /// phases first started.
+    pub phases: Vec<Phase>,
+    pub elapsed_seconds: f64,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Phase {
+    /// The name of the phase, e.g. "fetch".
+    pub name: String,
+    pub seconds: f64,
+}
+
+/// The parts of the summary collected while the run is in progress.
+struct Collected {
+    counts: BTreeMap<String, usize>,
+    spreadsheet_urls: Vec<String>,
+    notices: Vec<String>,
+    phases: Vec<Phase>,
+}
+
+static COLLECTED: Mutex<Collected> = Mutex::new(Collected {
+    counts: BTreeMap::new(),
+    spreadsheet_urls: Vec::new(),
+    notices: Vec::new(),
+    phases: Vec::new(),
+});
+
+/// Adds `n` to the count with the specified name.
+pub fn add_count(name: &str, n: usize) {
+    *COLLECTED.lock().unwrap().counts.entry(name.to_owned()).or_default() += n;
+}
+
+/// Records the URL of a Google Sheet that was written during the run.
+pub fn add_spreadsheet_url(url: &str) {
+    COLLECTED.lock().unwrap().spreadsheet_urls.push(url.to_owned());
+}
+
+/// Records something that the user should know about the run.
+pub fn add_notice(notice: &str) {
+    COLLECTED.lock().unwrap().notices.push(notice.to_owned());
+}
+
+/// Runs `f` within a tracing span for the phase with the specified name,
+/// adding the time it takes to the phase.
+pub fn time_phase<T>(name: &str, f: impl FnOnce() -> T) -> T {
+    let span = info_span!("phase", name);
+    let start = Instant::now();
+    let result = span.in_scope(f);
+    let seconds = start.elapsed().as_secs_f64();
+    debug!("The {} phase took {:.3}s", name, seconds);
+    let mut collected = COLLECTED.lock().unwrap();
+    add_phase(&mut collected.phases, name, seconds);
+    result
+}
+
+fn add_phase(phases: &mut Vec<Phase>, name: &str, seconds: f64) {
+    match phases.iter_mut().find(|phase| phase.name == name) {
+        Some(phase) => phase.seconds += seconds,
+        None => phases.push(Phase { name: name.to_owned(), seconds }),
+    }
+}
+
+/// Creates the summary of the run from everything collected so far.
+pub fn finish(command: &str, result: &anyhow::Result<()>, elapsed: Duration) -> RunSummary {
+    let mut collected = COLLECTED.lock().unwrap();
+    RunSummary {
+        command: command.to_owned(),
+        succeeded: result.is_ok(),
+        error: result.as_ref().err().map(|e| format!("{:#}", e)),
+        counts: std::mem::take(&mut collected.counts),
+        spreadsheet_urls: std::mem::take(&mut collected.spreadsheet_urls),
+        notices: std::mem::take(&mut collected.notices),
+        phases: std::mem::take(&mut collected.phases),
+        elapsed_seconds: elapsed.as_secs_f64(),
+    }
+}
+
+impl RunSummary {
+    pub fn print_human(&self, mut writer: impl Write) -> std::io::Result<()> {
+        let outcome = if self.succeeded { "succeeded" } else { "failed" };
+        writeln!(
+            writer,
+            "Run summary: `{}` {} in {:.1}s",
+            self.command, outcome, self.elapsed_seconds
+        )?;
+        if let Some(error) = &self.error {
+            writeln!(writer, "    error: {}", error)?;
+        }
+        for (name, count) in &self.counts {
+            writeln!(writer, "    {}: {}", name, count)?;
+        }
+        for Phase { name, seconds } in &self.phases {
+            writeln!(writer, "    {} phase: {:.1}s", name, seconds)?;
+        }
+        for url in &self.spreadsheet_urls {
+            writeln!(writer, "    spreadsheet: {}", url)?;
+        }
+        for notice in &self.notices {
+            writeln!(writer, "    notice: {}", notice)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_json(&self, path: &str) -> anyhow::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn repeated_phases_add_up() {
+        let mut phases = Vec::new();
+        add_phase(&mut phases, "fetch", 1.5);
+        add_phase(&mut phases, "upload", 2.0);
+        add_phase(&mut phases, "fetch", 0.5);
+        assert_eq!(
+            phases,
+            vec![
+                Phase { name: "fetch".to_owned(), seconds: 2.0 },
+                Phase { name: "upload".to_owned(), seconds: 2.0 },
+            ]
+        );
+    }
+}
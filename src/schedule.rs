@@ -0,0 +1,149 @@
+use std::{fs::File, io::BufReader};
+
+use chrono::{DateTime, Datelike as _, Local, NaiveTime, TimeDelta, TimeZone as _, Weekday};
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::storage;
+
+const DEFAULT_SCHEDULE_FILE: &str = "schedule.json";
+
+/// A command that is run automatically at a regular time, e.g. to export the
+/// KPI report to its spreadsheet every night. Scheduled exports are stored as
+/// a JSON array in the schedule file, e.g.
+///
+/// ```json
+/// [
+///     { "name": "Nightly KPI", "args": ["kpi", "--from", "ytd"], "every": "day", "at": "02:00" },
+///     { "name": "Weekly AR", "args": ["ar"], "every": "monday", "at": "06:30" }
+/// ]
+/// ```
+///
+/// `args` are the command-line arguments to run `ahitool` with, `every` is
+/// either "day" or a day of the week, and `at` is the local time of day.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledExport {
+    pub name: String,
+    pub args: Vec<String>,
+    pub frequency: Frequency,
+    pub time: NaiveTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly(Weekday),
+}
+
+#[derive(Deserialize)]
+struct ScheduleDef {
+    name: String,
+    args: Vec<String>,
+    every: String,
+    at: String,
+}
+
+impl ScheduledExport {
+    /// Loads the scheduled exports from the default schedule file. Returns no
+    /// exports if the file doesn't exist or can't be read, and skips exports
+    /// with an invalid frequency or time.
+    pub fn load_all() -> Vec<Self> {
+        let path = storage::config_path(DEFAULT_SCHEDULE_FILE);
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to open schedule file: {}", e);
+                }
+                return Vec::new();
+            }
+        };
+        let defs: Vec<ScheduleDef> = match serde_json::from_reader(BufReader::new(file)) {
+            Ok(defs) => defs,
+            Err(e) => {
+                warn!("Failed to deserialize schedule file: {}", e);
+                return Vec::new();
+            }
+        };
+        defs.into_iter()
+            .filter_map(|def| match Self::from_def(def) {
+                Ok(export) => Some(export),
+                Err(e) => {
+                    warn!("Skipping invalid scheduled export: {}", e);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn from_def(ScheduleDef { name, args, every, at }: ScheduleDef) -> Result<Self, String> {
+        let frequency = match every.trim().to_lowercase().as_str() {
+            "day" => Frequency::Daily,
+            day => Frequency::Weekly(
+                day.parse()
+                    .map_err(|_| format!("\"{}\" has an invalid day \"{}\"", name, every))?,
+            ),
+        };
+        let time = NaiveTime::parse_from_str(at.trim(), "%H:%M")
+            .map_err(|_| format!("\"{}\" has an invalid time \"{}\"", name, at))?;
+        Ok(Self { name, args, frequency, time })
+    }
+
+    /// The first time strictly after `now` that this export should run.
+    pub fn next_run(&self, now: DateTime<Local>) -> DateTime<Local> {
+        let mut date = now.date_naive();
+        loop {
+            let matches_day = match self.frequency {
+                Frequency::Daily => true,
+                Frequency::Weekly(weekday) => date.weekday() == weekday,
+            };
+            // times that don't exist because of daylight saving time are
+            // skipped, and ambiguous times run at the earlier one
+            if let Some(run) = Local.from_local_datetime(&date.and_time(self.time)).earliest() {
+                if matches_day && run > now {
+                    return run;
+                }
+            }
+            date += TimeDelta::days(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn export(every: &str, at: &str) -> Result<ScheduledExport, String> {
+        ScheduledExport::from_def(ScheduleDef {
+            name: "test".to_owned(),
+            args: vec!["kpi".to_owned()],
+            every: every.to_owned(),
+            at: at.to_owned(),
+        })
+    }
+
+    #[test]
+    fn parse_frequency_and_time() {
+        assert_eq!(export("day", "02:00").unwrap().frequency, Frequency::Daily);
+        assert_eq!(export("Monday", "02:00").unwrap().frequency, Frequency::Weekly(Weekday::Mon));
+        assert!(export("fortnight", "02:00").is_err());
+        assert!(export("day", "2am").is_err());
+    }
+
+    #[test]
+    fn next_run() {
+        let at = |date: &str, time: &str| {
+            let date = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+            let time = NaiveTime::parse_from_str(time, "%H:%M").unwrap();
+            Local.from_local_datetime(&date.and_time(time)).single().unwrap()
+        };
+
+        // 2024-06-05 is a Wednesday
+        let daily = export("day", "02:00").unwrap();
+        assert_eq!(daily.next_run(at("2024-06-05", "01:00")), at("2024-06-05", "02:00"));
+        assert_eq!(daily.next_run(at("2024-06-05", "02:00")), at("2024-06-06", "02:00"));
+
+        let weekly = export("monday", "06:30").unwrap();
+        assert_eq!(weekly.next_run(at("2024-06-05", "12:00")), at("2024-06-10", "06:30"));
+    }
+}
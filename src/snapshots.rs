@@ -0,0 +1,171 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context as _};
+use chrono::{NaiveDateTime, TimeZone as _, Utc};
+use tracing::{info, warn};
+
+use crate::apis::job_nimbus;
+use crate::date_filter::DateFilter;
+use crate::jobs::{Job, TimeDelta, Timestamp};
+use crate::run_summary;
+use crate::storage;
+
+/// The directory in the cache directory that snapshots are saved in. Snapshots
+/// are only saved if this directory exists, since every snapshot contains all
+/// of the jobs.
+const DEFAULT_SNAPSHOTS_DIR: &str = "snapshots";
+
+/// The format of the timestamp in the name of each snapshot file.
+const FILE_NAME_FORMAT: &str = "jobs-%Y%m%dT%H%M%SZ.json";
+
+/// Whether snapshots are being saved.
+pub fn enabled() -> bool {
+    dir().is_dir()
+}
+
+/// The directory that snapshots are saved in.
+pub fn dir() -> PathBuf {
+    storage::cache_path(DEFAULT_SNAPSHOTS_DIR)
+}
+
+/// Saves the jobs (as returned by JobNimbus) as a snapshot of all the jobs at
+/// the specified time.
+pub fn save(jobs: &[serde_json::Value], taken_at: Timestamp) -> anyhow::Result<()> {
+    let path = dir().join(taken_at.format(FILE_NAME_FORMAT).to_string());
+    serde_json::to_writer(BufWriter::new(File::create(&path)?), jobs)?;
+    info!("Saved a snapshot of {} jobs to {}", jobs.len(), path.display());
+    match prune() {
+        Ok(0) => {}
+        Ok(pruned) => run_summary::add_count("snapshots_pruned", pruned),
+        Err(e) => warn!("Failed to prune old snapshots: {}", e),
+    }
+    Ok(())
+}
+
+/// Removes the least recently used snapshots until the snapshots are within
+/// the cache limits. Returns the number of snapshots removed.
+pub fn prune() -> anyhow::Result<usize> {
+    let dir = dir();
+    if !dir.is_dir() {
+        return Ok(0);
+    }
+    let mut snapshots = Vec::new();
+    for entry in std::fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if taken_at(&path).is_some() {
+            snapshots.push(path);
+        }
+    }
+    let limits = storage::CacheLimits::load();
+    let max_bytes = limits.max_snapshots_mb.map(|mb| mb.saturating_mul(1024 * 1024));
+    let removed = storage::prune_lru(snapshots, limits.max_snapshots, max_bytes)?;
+    for path in &removed {
+        info!("Removed the snapshot {} to stay within the cache limits", path.display());
+    }
+    Ok(removed.len())
+}
+
+/// Loads the latest snapshot taken at or before `as_of`, returning the time it
+/// was taken along with its jobs.
+pub fn load_as_of(as_of: Timestamp) -> anyhow::Result<(Timestamp, Vec<Job>)> {
+    let dir = dir();
+    if !dir.is_dir() {
+        bail!("There is no {} directory to load snapshots from", dir.display());
+    }
+    let mut snapshots = Vec::new();
+    for entry in std::fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if let Some(taken_at) = taken_at(&path) {
+            snapshots.push((taken_at, path));
+        }
+    }
+    let Some((taken_at, path)) = latest_as_of(snapshots, as_of) else {
+        bail!("There is no snapshot from before {}", as_of);
+    };
+
+    info!("Loading the snapshot taken at {} from {}", taken_at, path.display());
+    // marks the snapshot as recently used, so that it is pruned last
+    if let Err(e) = File::options()
+        .write(true)
+        .open(&path)
+        .and_then(|file| file.set_modified(std::time::SystemTime::now()))
+    {
+        warn!("Failed to mark the snapshot {} as used: {}", path.display(), e);
+    }
+    let file = File::open(&path)?;
+    let values: Vec<serde_json::Value> = serde_json::from_reader(BufReader::new(file))
+        .with_context(|| format!("Failed to read snapshot {}", path.display()))?;
+    let jobs = job_nimbus::parse_jobs(values, &DateFilter::load());
+    run_summary::add_count("jobs_loaded_from_snapshot", jobs.len());
+    Ok((taken_at, jobs))
+}
+
+/// Warns, in the log and in the run summary, if the jobs were fetched more than
+/// `max_age` before the time that a report is generated as of, e.g. because
+/// the latest snapshot before `--as-of` is old. Returns whether they were.
+pub fn warn_if_stale(fetched_at: Timestamp, as_of: Timestamp, max_age: TimeDelta) -> bool {
+    let age = as_of - fetched_at;
+    if age <= max_age {
+        return false;
+    }
+    let notice = format!(
+        "The report is based on data fetched at {}, {} hours before the time it is as of",
+        fetched_at.format("%Y-%m-%d %H:%M UTC"),
+        age.num_hours()
+    );
+    warn!("{}", notice);
+    run_summary::add_notice(&notice);
+    true
+}
+
+/// The time at which the snapshot at `path` was taken, if it is a snapshot.
+fn taken_at(path: &Path) -> Option<Timestamp> {
+    let name = path.file_name()?.to_str()?;
+    let taken_at = NaiveDateTime::parse_from_str(name, FILE_NAME_FORMAT).ok()?;
+    Some(Utc.from_utc_datetime(&taken_at))
+}
+
+fn latest_as_of(
+    snapshots: Vec<(Timestamp, PathBuf)>,
+    as_of: Timestamp,
+) -> Option<(Timestamp, PathBuf)> {
+    snapshots.into_iter().filter(|(taken_at, _)| *taken_at <= as_of).max_by_key(|(t, _)| *t)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn latest_snapshot_as_of() {
+        let at = |date: &str| {
+            Utc.from_utc_datetime(
+                &NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S").unwrap(),
+            )
+        };
+        let path = |date: &str| PathBuf::from(at(date).format(FILE_NAME_FORMAT).to_string());
+
+        let jan = path("2024-01-31 23:00:00");
+        let feb = path("2024-02-29 23:00:00");
+        assert_eq!(taken_at(&jan), Some(at("2024-01-31 23:00:00")));
+        assert_eq!(taken_at(Path::new("notes.txt")), None);
+
+        let snapshots =
+            || vec![(taken_at(&feb).unwrap(), feb.clone()), (taken_at(&jan).unwrap(), jan.clone())];
+        assert_eq!(latest_as_of(snapshots(), at("2024-02-15 00:00:00")).unwrap().1, jan);
+        assert_eq!(latest_as_of(snapshots(), at("2024-03-01 00:00:00")).unwrap().1, feb);
+        assert_eq!(latest_as_of(snapshots(), at("2024-01-01 00:00:00")), None);
+    }
+
+    #[test]
+    fn stale_data_is_flagged() {
+        let fetched_at = Timestamp::UNIX_EPOCH;
+        let max_age = TimeDelta::hours(24);
+        assert!(!warn_if_stale(fetched_at, fetched_at + TimeDelta::hours(24), max_age));
+        assert!(warn_if_stale(fetched_at, fetched_at + TimeDelta::hours(25), max_age));
+    }
+}
@@ -0,0 +1,427 @@
+//! Where `ahitool` keeps its files. Settings and credentials are small, so they
+//! are kept in the settings directory, which is the platform's directory for
+//! settings. Settings can also be shared by several computers through a shared
+//! directory (e.g. on a network drive), which is used for each settings file
+//! that isn't in the settings directory. Data that can be regenerated and may
+//! grow large (e.g. the geocode cache and the snapshots of the jobs) is kept in
+//! the platform's cache directory instead, so that it isn't mixed in with the
+//! settings or backed up along with them. Older versions of `ahitool` kept
+//! everything in the current working directory, so files found there are moved
+//! into the right directory the next time they are used.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufReader, Write as _},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context as _;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+/// The environment variable that overrides the cache directory.
+const CACHE_DIR_VAR: &str = "AHITOOL_CACHE_DIR";
+
+/// The environment variable that overrides the settings directory.
+const CONFIG_DIR_VAR: &str = "AHITOOL_CONFIG_DIR";
+
+/// The environment variable that sets the shared settings directory.
+const SHARED_CONFIG_DIR_VAR: &str = "AHITOOL_SHARED_CONFIG_DIR";
+
+/// How long to wait for another run to finish updating a settings file.
+const CONFIG_LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a settings file can be locked before the lock is assumed to have
+/// been left behind by a run that was killed, and is taken over.
+const STALE_CONFIG_LOCK_AGE: Duration = Duration::from_secs(60);
+
+const DEFAULT_LIMITS_FILE: &str = "cache_limits.json";
+
+/// How much of the disk the cache directory may take up, so that it doesn't
+/// fill up a small drive over time. When the limits are exceeded, the least
+/// recently used entries are removed.
+///
+/// The limits are stored as a JSON object in the settings, e.g.
+///
+/// ```json
+/// { "max_snapshots": null, "max_snapshots_mb": 1024 }
+/// ```
+///
+/// `null` means no limit. Fields that are left out keep the defaults, which are
+/// the values above.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheLimits {
+    /// The maximum number of snapshots to keep.
+    pub max_snapshots: Option<usize>,
+    /// The maximum total size of the snapshots, in megabytes.
+    pub max_snapshots_mb: Option<u64>,
+}
+
+impl Default for CacheLimits {
+    fn default() -> Self {
+        CacheLimits { max_snapshots: None, max_snapshots_mb: Some(1024) }
+    }
+}
+
+impl CacheLimits {
+    /// Loads the limits from the default limits file. Returns the default
+    /// limits if the file doesn't exist or can't be read.
+    pub fn load() -> Self {
+        let file = match File::open(config_path(DEFAULT_LIMITS_FILE)) {
+            Ok(file) => file,
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to open cache limits file: {}", e);
+                }
+                return Self::default();
+            }
+        };
+        match serde_json::from_reader(BufReader::new(file)) {
+            Ok(limits) => limits,
+            Err(e) => {
+                warn!("Failed to deserialize cache limits file: {}", e);
+                Self::default()
+            }
+        }
+    }
+}
+
+/// The directory of the settings shared by several computers, set by
+/// `AHITOOL_SHARED_CONFIG_DIR`, if any.
+pub fn shared_config_dir() -> Option<PathBuf> {
+    std::env::var_os(SHARED_CONFIG_DIR_VAR).filter(|dir| !dir.is_empty()).map(PathBuf::from)
+}
+
+/// The settings directory: `AHITOOL_CONFIG_DIR` if it is set, otherwise
+/// `ahitool` in the platform's settings directory (e.g. `~/.config/ahitool` on
+/// Linux, `~/Library/Application Support/ahitool` on macOS, and
+/// `%APPDATA%\ahitool` on Windows). Falls back to the current working directory
+/// if the platform has no settings directory. The directory may not exist yet.
+pub fn config_dir() -> PathBuf {
+    match std::env::var_os(CONFIG_DIR_VAR).filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => dirs::config_dir().map(|dir| dir.join("ahitool")).unwrap_or_else(|| ".".into()),
+    }
+}
+
+/// The path of a settings file (or directory, e.g. of templates). The file in
+/// the settings directory is used if it exists, which overrides the whole file
+/// in the shared settings directory; otherwise, the file in the shared settings
+/// directory is used if there is one. If older versions of `ahitool` left the
+/// file in the current working directory, then it is moved into the settings
+/// directory first, or keeps being used where it is if it can't be moved.
+pub fn config_path(name: &str) -> PathBuf {
+    config_path_in(Path::new("."), &config_dir(), shared_config_dir().as_deref(), name)
+}
+
+fn config_path_in(
+    legacy_dir: &Path,
+    config_dir: &Path,
+    shared_dir: Option<&Path>,
+    name: &str,
+) -> PathBuf {
+    let path = move_from_legacy_dir(legacy_dir, config_dir, "settings", name);
+    match shared_dir {
+        Some(shared_dir) if !path.exists() => shared_dir.join(name),
+        _ => path,
+    }
+}
+
+/// The path of a credentials file (e.g. an API key). Credentials are kept in
+/// the settings directory like other settings, but are never read from or
+/// written to the shared settings directory. If older versions of `ahitool`
+/// left the file in the current working directory, then it is moved into the
+/// settings directory first, or keeps being used where it is if it can't be
+/// moved.
+pub fn credentials_path(name: &str) -> PathBuf {
+    move_from_legacy_dir(Path::new("."), &config_dir(), "settings", name)
+}
+
+/// Writes a credentials file (see [`credentials_path`]), creating the settings
+/// directory if necessary.
+pub fn write_credentials(name: &str, contents: &str) -> anyhow::Result<()> {
+    let path = credentials_path(name);
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    fs::write(&path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Updates a settings file: `update` is given the current contents of the file
+/// (if it exists) and returns the new contents. The file is locked while it is
+/// updated, so that updates made at the same time (possibly by other computers
+/// sharing the settings) are applied one after the other instead of
+/// overwriting each other, and is replaced all at once, so that it is never
+/// read half-written.
+pub fn update_config(
+    name: &str,
+    update: impl FnOnce(Option<&str>) -> anyhow::Result<String>,
+) -> anyhow::Result<()> {
+    update_config_at(&config_path(name), update)
+}
+
+fn update_config_at(
+    path: &Path,
+    update: impl FnOnce(Option<&str>) -> anyhow::Result<String>,
+) -> anyhow::Result<()> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let _lock = ConfigLock::acquire(path)?;
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => Some(contents),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    let contents = update(contents.as_deref())?;
+
+    let mut file = tempfile::NamedTempFile::new_in(dir)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    file.write_all(contents.as_bytes())?;
+    file.persist(path).with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+/// A lock on a settings file, which is a file next to it that is removed when
+/// the lock is dropped.
+struct ConfigLock {
+    path: PathBuf,
+}
+
+impl ConfigLock {
+    fn acquire(config_path: &Path) -> anyhow::Result<Self> {
+        let mut path = config_path.as_os_str().to_owned();
+        path.push(".lock");
+        let path = PathBuf::from(path);
+        let start = Instant::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(ConfigLock { path }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let age = fs::metadata(&path)
+                        .and_then(|metadata| metadata.modified())
+                        .ok()
+                        .and_then(|modified| modified.elapsed().ok());
+                    if age.is_some_and(|age| age > STALE_CONFIG_LOCK_AGE) {
+                        warn!("Taking over the stale lock file {}", path.display());
+                        let _ = fs::remove_file(&path);
+                        continue;
+                    }
+                    if start.elapsed() > CONFIG_LOCK_TIMEOUT {
+                        anyhow::bail!(
+                            "{} is being updated by another run. Try again later, or delete {} \
+                             if that run was interrupted.",
+                            config_path.display(),
+                            path.display()
+                        );
+                    }
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("Failed to create the lock file {}", path.display())
+                    })
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ConfigLock {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("Failed to remove the lock file {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// The cache directory: `AHITOOL_CACHE_DIR` if it is set, otherwise `ahitool`
+/// in the platform's cache directory (e.g. `~/.cache/ahitool` on Linux,
+/// `~/Library/Caches/ahitool` on macOS, and `%LOCALAPPDATA%\ahitool` on
+/// Windows). Falls back to the current working directory if the platform has no
+/// cache directory. The directory may not exist yet.
+pub fn cache_dir() -> PathBuf {
+    match std::env::var_os(CACHE_DIR_VAR).filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => dirs::cache_dir().map(|dir| dir.join("ahitool")).unwrap_or_else(|| ".".into()),
+    }
+}
+
+/// The path of a file or directory in the cache directory. If older versions of
+/// `ahitool` left it in the current working directory, then it is moved into
+/// the cache directory first, or keeps being used where it is if it can't be
+/// moved.
+pub fn cache_path(name: &str) -> PathBuf {
+    move_from_legacy_dir(Path::new("."), &cache_dir(), "cache", name)
+}
+
+/// The path of a file or directory in `dir`, after moving it there from
+/// `legacy_dir` if it is only in `legacy_dir`. Returns the path in
+/// `legacy_dir` if it can't be moved. `kind` describes `dir` in the log, e.g.
+/// "cache".
+fn move_from_legacy_dir(legacy_dir: &Path, dir: &Path, kind: &str, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    let legacy_path = legacy_dir.join(name);
+    if legacy_dir == dir || !legacy_path.exists() || path.exists() {
+        return path;
+    }
+    match fs::create_dir_all(dir).and_then(|()| fs::rename(&legacy_path, &path)) {
+        Ok(()) => {
+            info!("Moved {} into the {} directory {}", name, kind, dir.display());
+            path
+        }
+        Err(e) => {
+            warn!("Failed to move {} into the {} directory {}: {}", name, kind, dir.display(), e);
+            legacy_path
+        }
+    }
+}
+
+/// The size in bytes of a file, or of all the files in a directory.
+pub fn size_of(path: &Path) -> std::io::Result<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        size += size_of(&entry?.path())?;
+    }
+    Ok(size)
+}
+
+/// Removes the least recently used of the files (by modification time) until
+/// there are at most `max_files` of them taking up at most `max_bytes`. The
+/// most recently used file is always kept. Returns the removed files.
+pub fn prune_lru(
+    paths: impl IntoIterator<Item = PathBuf>,
+    max_files: Option<usize>,
+    max_bytes: Option<u64>,
+) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        let metadata = fs::metadata(&path)?;
+        files.push((metadata.modified()?, metadata.len(), path));
+    }
+    files.sort();
+
+    let mut count = files.len();
+    let mut bytes: u64 = files.iter().map(|(_, len, _)| len).sum();
+    let mut removed = Vec::new();
+    files.pop();
+    for (_, len, path) in files {
+        let over_limit = max_files.is_some_and(|max_files| count > max_files)
+            || max_bytes.is_some_and(|max_bytes| bytes > max_bytes);
+        if !over_limit {
+            break;
+        }
+        fs::remove_file(&path)?;
+        count -= 1;
+        bytes -= len;
+        removed.push(path);
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+
+    #[test]
+    fn legacy_files_are_moved_into_the_cache_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join("cache");
+        fs::write(dir.path().join("geocode_cache.json"), "{}").unwrap();
+        fs::create_dir(dir.path().join("snapshots")).unwrap();
+        fs::write(dir.path().join("snapshots").join("a.json"), "[1, 2]").unwrap();
+
+        let path = move_from_legacy_dir(dir.path(), &cache_dir, "cache", "geocode_cache.json");
+        assert_eq!(path, cache_dir.join("geocode_cache.json"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{}");
+        assert!(!dir.path().join("geocode_cache.json").exists());
+
+        let path = move_from_legacy_dir(dir.path(), &cache_dir, "cache", "snapshots");
+        assert_eq!(size_of(&path).unwrap(), 6);
+        assert_eq!(size_of(&cache_dir).unwrap(), 8);
+        let path = move_from_legacy_dir(dir.path(), &cache_dir, "cache", "missing");
+        assert_eq!(path, cache_dir.join("missing"));
+    }
+
+    #[test]
+    fn least_recently_used_files_are_pruned() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<_> = (0..4).map(|i| dir.path().join(format!("{}.json", i))).collect();
+        for (i, path) in paths.iter().enumerate() {
+            fs::write(path, "0123456789").unwrap();
+            // 0 is the most recently used, 3 the least
+            let modified = SystemTime::now() - Duration::from_secs(60 * i as u64);
+            File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+        }
+
+        assert_eq!(prune_lru(paths.clone(), None, None).unwrap(), Vec::<PathBuf>::new());
+        assert_eq!(
+            prune_lru(paths.clone(), Some(3), Some(25)).unwrap(),
+            vec![paths[3].clone(), paths[2].clone()]
+        );
+        // the most recently used file is kept even if it is over the limit
+        assert_eq!(prune_lru(paths[..2].to_vec(), None, Some(5)).unwrap(), vec![paths[1].clone()]);
+        assert!(paths[0].exists());
+    }
+
+    #[test]
+    fn local_settings_override_shared_settings() {
+        let legacy = tempfile::tempdir().unwrap();
+        let local = tempfile::tempdir().unwrap();
+        let shared = tempfile::tempdir().unwrap();
+        fs::write(local.path().join("branches.json"), "[]").unwrap();
+
+        let path = |name| config_path_in(legacy.path(), local.path(), Some(shared.path()), name);
+        assert_eq!(path("branches.json"), local.path().join("branches.json"));
+        assert_eq!(path("lead_sources.json"), shared.path().join("lead_sources.json"));
+        let path = config_path_in(legacy.path(), local.path(), None, "lead_sources.json");
+        assert_eq!(path, local.path().join("lead_sources.json"));
+    }
+
+    #[test]
+    fn legacy_settings_are_moved_into_the_config_dir() {
+        let legacy = tempfile::tempdir().unwrap();
+        let config_dir = legacy.path().join("config");
+        let shared = tempfile::tempdir().unwrap();
+        fs::write(legacy.path().join("branches.json"), "[]").unwrap();
+        fs::write(shared.path().join("branches.json"), "[1]").unwrap();
+
+        // a legacy file is still local, so it overrides the shared one
+        let path = config_path_in(legacy.path(), &config_dir, Some(shared.path()), "branches.json");
+        assert_eq!(path, config_dir.join("branches.json"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[]");
+        assert!(!legacy.path().join("branches.json").exists());
+    }
+
+    #[test]
+    fn concurrent_config_updates_are_not_lost() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lead_sources.json");
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let path = &path;
+                scope.spawn(move || {
+                    update_config_at(path, |contents| {
+                        let mut values: Vec<i32> =
+                            contents.map_or(Ok(Vec::new()), serde_json::from_str)?;
+                        values.push(i);
+                        Ok(serde_json::to_string(&values)?)
+                    })
+                    .unwrap()
+                });
+            }
+        });
+        let mut values: Vec<i32> =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        values.sort();
+        assert_eq!(values, (0..8).collect::<Vec<_>>());
+        assert!(!dir.path().join("lead_sources.json.lock").exists());
+    }
+}
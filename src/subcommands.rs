@@ -1,7 +1,32 @@
+pub mod abandoned;
 pub mod acc_receivable;
+pub mod areas;
+pub mod audit;
+pub mod backlog;
+pub mod branch_distances;
+pub mod cache;
+pub mod calendar;
+pub mod carriers;
+pub mod cohorts;
+pub mod config;
+pub mod crews;
+pub mod duplicates;
+pub mod geo;
+pub mod jobs;
 pub mod kpi;
+pub mod lead_sources;
+pub mod losses;
+pub mod output;
+pub mod reassignments;
+pub mod report_all;
+pub mod reschedules;
+pub mod route;
+pub mod serve;
+pub mod supplements;
 pub mod update;
 
+// the arguments are only parsed once, so the size of the variants doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(clap::Subcommand, Debug)]
 pub enum Subcommand {
     /// Update the executable to the latest version.
@@ -10,4 +35,51 @@ pub enum Subcommand {
     Kpi(kpi::Args),
     /// Generate a report for all accounts receivable.
     Ar(acc_receivable::Args),
+    /// Export upcoming installs as a calendar.
+    Calendar(calendar::Args),
+    /// Generate a report of signed jobs that have not been installed yet.
+    Backlog(backlog::Args),
+    /// Generate a report of insurance jobs grouped by insurance carrier.
+    Carriers(carriers::Args),
+    /// Generate a report of insurance supplements and their approval.
+    Supplements(supplements::Args),
+    /// View and edit the normalization of lead sources.
+    LeadSources(lead_sources::Args),
+    /// Generate a report of lost jobs and where they were lost.
+    Losses(losses::Args),
+    /// Generate a report of jobs that are likely duplicates of each other.
+    Duplicates(duplicates::Args),
+    /// List jobs that were marked lost without a loss date, and optionally
+    /// move them out of the pipeline in JobNimbus.
+    Abandoned(abandoned::Args),
+    /// Generate a report of jobs with missing required fields.
+    Audit(audit::Args),
+    /// Generate a report comparing sales reps by how long they have been hired.
+    Cohorts(cohorts::Args),
+    /// Generate a report of jobs grouped by zip code or city.
+    Areas(areas::Args),
+    /// Search for jobs in JobNimbus.
+    Jobs(jobs::Args),
+    /// Generate a report of installs grouped by crew.
+    Crews(crews::Args),
+    /// Generate a report of rescheduled appointments grouped by sales rep.
+    Reschedules(reschedules::Args),
+    /// Generate a report of jobs whose sales rep was changed mid-pipeline and
+    /// how that affects conversion.
+    Reassignments(reassignments::Args),
+    /// Geocode jobs and export their locations.
+    Geo(geo::Args),
+    /// Report the distance of jobs from their branch offices.
+    BranchDistances(branch_distances::Args),
+    /// Plan a route through a sales rep's upcoming appointments.
+    Route(route::Args),
+    /// Run a server that exposes KPI metrics to Prometheus and analyzes jobs on
+    /// request.
+    Serve(serve::Args),
+    /// Generate the KPI and AR reports and the list of all jobs from a single fetch of the jobs.
+    ReportAll(report_all::Args),
+    /// Export the settings to a single file, or import them from one.
+    Config(config::Args),
+    /// Show where cached data is kept and how much space it takes up.
+    Cache(cache::Args),
 }
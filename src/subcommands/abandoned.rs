@@ -0,0 +1,269 @@
+use std::io::Write;
+
+use clap::CommandFactory as _;
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::{
+    apis::job_nimbus,
+    jobs::{self, Job},
+    run_summary,
+    subcommands::output,
+    CliArgs,
+};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The JobNimbus API key. This key will be cached.
+    #[arg(long, default_value = None, global = true, env)]
+    jn_api_key: Option<String>,
+
+    /// The filter to use when query JobNimbus for jobs, using ElasticSearch
+    /// syntax.
+    #[arg(short, long = "filter", default_value = None)]
+    filter_filename: Option<String>,
+
+    /// The status to move abandoned jobs to, e.g. "Archived".
+    #[arg(long, value_name = "STATUS", default_value = None)]
+    set_status: Option<String>,
+
+    /// The tag to add to abandoned jobs, e.g. "abandoned".
+    #[arg(long, value_name = "TAG", default_value = None)]
+    add_tag: Option<String>,
+
+    /// Whether to actually make the changes in JobNimbus. Without this option,
+    /// the changes are only previewed. Requires `--set-status` or `--add-tag`.
+    #[arg(long)]
+    apply: bool,
+
+    /// The format in which to print the output.
+    #[arg(long, value_enum, default_value = "human")]
+    format: OutputFormat,
+
+    /// The file to write the output to. "-" or unspecified will write to
+    /// stdout.
+    #[arg(short, long, default_value = None)]
+    output: Option<String>,
+}
+
+#[derive(Debug, clap::ValueEnum, Clone, Copy, Eq, PartialEq)]
+enum OutputFormat {
+    /// Prints a human-readable report into the output file.
+    Human,
+    /// Prints a CSV file into the output file.
+    Csv,
+}
+
+/// The changes to make to each abandoned job to take it out of the pipeline.
+#[derive(Debug, Default)]
+pub struct Cleanup {
+    pub status: Option<String>,
+    pub tag: Option<String>,
+}
+
+impl Cleanup {
+    pub fn is_empty(&self) -> bool {
+        self.status.is_none() && self.tag.is_none()
+    }
+
+    /// The fields to update in JobNimbus to clean up the job, or `None` if the
+    /// job has already been cleaned up.
+    pub fn fields(&self, job: &Job) -> Option<serde_json::Value> {
+        let mut fields = serde_json::Map::new();
+        if let Some(status) = &self.status {
+            if !job.status.to_string().eq_ignore_ascii_case(status.trim()) {
+                fields.insert("status_name".to_owned(), json!(status.trim()));
+            }
+        }
+        if let Some(tag) = &self.tag {
+            let mut tags = tags(job);
+            if !tags.iter().any(|t| t.trim().eq_ignore_ascii_case(tag.trim())) {
+                tags.push(tag.trim());
+                fields.insert("tags".to_owned(), json!(tags));
+            }
+        }
+        if fields.is_empty() {
+            None
+        } else {
+            Some(serde_json::Value::Object(fields))
+        }
+    }
+
+    /// A description of the changes to make to the job, e.g. "set status to
+    /// Archived, add tag abandoned".
+    fn describe(&self, job: &Job) -> String {
+        let Some(fields) = self.fields(job) else {
+            return "already cleaned up".to_owned();
+        };
+        let mut changes = Vec::new();
+        if let Some(status) = fields.get("status_name").and_then(|s| s.as_str()) {
+            changes.push(format!("set status to {}", status));
+        }
+        if fields.get("tags").is_some() {
+            changes.push(format!("add tag {}", self.tag.as_deref().unwrap_or_default().trim()));
+        }
+        changes.join(", ")
+    }
+}
+
+/// The tags of the job in JobNimbus.
+fn tags(job: &Job) -> Vec<&str> {
+    job.raw
+        .get("tags")
+        .and_then(|tags| tags.as_array())
+        .map(|tags| tags.iter().filter_map(|tag| tag.as_str()).collect())
+        .unwrap_or_default()
+}
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let Args { jn_api_key, filter_filename, set_status, add_tag, apply, format, output } = args;
+
+    let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+
+    let cleanup = Cleanup { status: set_status, tag: add_tag };
+    if apply && cleanup.is_empty() {
+        CliArgs::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "The `--apply` option requires `--set-status` or `--add-tag`",
+            )
+            .exit();
+    }
+
+    let jobs = job_nimbus::get_all_jobs_with_filter_file(&jn_api_key, filter_filename.as_deref())?;
+    let abandoned = find_abandoned(jobs);
+
+    let output_writer = output::writer(output.as_deref())?;
+    match format {
+        OutputFormat::Human => print_human(&abandoned, &cleanup, apply, output_writer)?,
+        OutputFormat::Csv => print_csv(&abandoned, &cleanup, output_writer)?,
+    }
+
+    if apply {
+        write_back_cleanup(&jn_api_key, &abandoned, &cleanup);
+    }
+
+    Ok(())
+}
+
+/// Finds the abandoned jobs, i.e. the jobs that are marked lost but were never
+/// given a loss date, so they are never settled.
+pub fn find_abandoned(jobs: impl IntoIterator<Item = Job>) -> Vec<Job> {
+    jobs.into_iter()
+        .map(|job| jobs::analyze_job(job).0)
+        .filter(|analyzed| analyzed.is_abandoned())
+        .map(|analyzed| analyzed.job)
+        .collect()
+}
+
+fn job_number(job: &Job) -> &str {
+    job.job_number.as_deref().unwrap_or(&job.jnid)
+}
+
+fn print_human(
+    jobs: &[Job],
+    cleanup: &Cleanup,
+    apply: bool,
+    mut writer: impl Write,
+) -> std::io::Result<()> {
+    writeln!(writer, "Abandoned jobs (lost without a loss date): {}", jobs.len())?;
+    for job in jobs {
+        write!(
+            writer,
+            "    - {} (#{}), assigned to {}, lost since {}",
+            job.job_name.as_deref().unwrap_or(""),
+            job_number(job),
+            job.sales_rep.as_deref().unwrap_or("Unknown Sales Rep"),
+            job.status_mod_date.format("%Y-%m-%d"),
+        )?;
+        if !cleanup.is_empty() {
+            write!(writer, ": {}", cleanup.describe(job))?;
+        }
+        writeln!(writer)?;
+    }
+    if !cleanup.is_empty() && !apply {
+        writeln!(writer, "This is a preview; run again with `--apply` to make these changes.")?;
+    }
+    Ok(())
+}
+
+fn print_csv(jobs: &[Job], cleanup: &Cleanup, writer: impl Write) -> std::io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record([
+        "Job Number",
+        "Job Name",
+        "Sales Rep",
+        "Lost Since",
+        "Cleanup",
+        "Link",
+    ])?;
+    for job in jobs {
+        let changes = if cleanup.is_empty() { String::new() } else { cleanup.describe(job) };
+        writer.write_record([
+            job_number(job),
+            job.job_name.as_deref().unwrap_or(""),
+            job.sales_rep.as_deref().unwrap_or("Unknown Sales Rep"),
+            &job.status_mod_date.format("%Y-%m-%d").to_string(),
+            &changes,
+            &job_nimbus::job_url(&job.jnid),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Makes the cleanup changes to the abandoned jobs in JobNimbus. Failures are
+/// logged and don't stop the remaining updates.
+fn write_back_cleanup(jn_api_key: &str, jobs: &[Job], cleanup: &Cleanup) {
+    let updates: Vec<_> = jobs.iter().filter_map(|job| Some((job, cleanup.fields(job)?))).collect();
+    info!("Cleaning up {} abandoned jobs in JobNimbus", updates.len());
+    let mut num_updated = 0;
+    for (job, fields) in updates {
+        match job_nimbus::update_job(jn_api_key, &job.jnid, &fields) {
+            Ok(()) => num_updated += 1,
+            Err(e) => warn!("Failed to clean up job {}: {}", job.jnid, e),
+        }
+    }
+    run_summary::add_count("jobs_updated", num_updated);
+    info!("Cleaned up {} abandoned jobs in JobNimbus", num_updated);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jobs::{test::make_job, Status, TimeDelta, Timestamp};
+
+    #[test]
+    fn only_lost_jobs_without_loss_date_are_abandoned() {
+        let day = |days| Some(Timestamp::UNIX_EPOCH + TimeDelta::days(days));
+        let mut abandoned = make_job(false, day(1), None, None, None, None);
+        abandoned.status = Status::Lost;
+        abandoned.jnid = "a".to_owned();
+        let mut lost = make_job(false, day(1), None, None, None, day(2));
+        lost.status = Status::Lost;
+        let open = make_job(false, day(1), None, None, None, None);
+
+        let found = find_abandoned([abandoned, lost, open]);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].jnid, "a");
+    }
+
+    #[test]
+    fn cleanup_skips_jobs_already_cleaned_up() {
+        let mut job = make_job(false, None, None, None, None, None);
+        job.status = Status::Lost;
+        job.raw.insert("tags".to_owned(), json!(["Storm"]));
+        let cleanup =
+            Cleanup { status: Some("Archived".to_owned()), tag: Some("abandoned".to_owned()) };
+        assert_eq!(
+            cleanup.fields(&job),
+            Some(json!({ "status_name": "Archived", "tags": ["Storm", "abandoned"] }))
+        );
+        assert_eq!(cleanup.describe(&job), "set status to Archived, add tag abandoned");
+
+        job.status = Status::Other("archived".to_owned());
+        job.raw.insert("tags".to_owned(), json!(["Storm", "Abandoned"]));
+        assert_eq!(cleanup.fields(&job), None);
+        assert_eq!(cleanup.describe(&job), "already cleaned up");
+    }
+}
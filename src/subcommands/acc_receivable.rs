@@ -1,21 +1,35 @@
-use std::{collections::HashMap, io::Write};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Write},
+};
 
 use chrono::Utc;
 use clap::CommandFactory as _;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
 
 use crate::{
     apis::{
         google_sheets::{
             self,
             spreadsheet::{
-                CellData, ExtendedValue, GridData, RowData, Sheet, SheetProperties, Spreadsheet,
+                mk_row, ExtendedValue, GridData, Sheet, SheetProperties, Spreadsheet,
                 SpreadsheetProperties,
             },
+            TargetRange,
         },
         job_nimbus,
     },
-    jobs::{Job, Status},
-    utils, CliArgs,
+    argument_conflict,
+    computed_columns::ComputedColumn,
+    jobs::{Job, Status, Timestamp},
+    report_profile::ReportProfile,
+    storage,
+    subcommands::output::{OutputArgs, OutputFormat},
+    utils,
+    webhook::{self, ReportSummary},
+    CliArgs,
 };
 
 #[derive(clap::Args, Debug)]
@@ -28,30 +42,64 @@ pub struct Args {
     #[arg(long, value_enum, default_value = "google-sheets")]
     format: OutputFormat,
 
-    /// The file to write the output to. "-" or unspecified will write to
-    /// stdout. This option is ignored with `--format google-sheets`.
-    #[arg(short, long, default_value = None)]
-    output: Option<String>,
+    #[command(flatten)]
+    output_args: OutputArgs,
 
-    /// Only valid with `--format google-sheets`. Whether to always create a new
-    /// Google Sheet. If not specified, then updates the existing Google Sheet
-    /// for this command if it exists.
+    /// Whether to include the address of each job in the report.
     #[arg(long)]
-    new: bool,
-}
+    addresses: bool,
+
+    /// Only include jobs of the specified trade (e.g. "Roofing"), from the
+    /// "Trade" or "Work Type" field of each job.
+    #[arg(long, default_value = None)]
+    trade: Option<String>,
+
+    /// Only include jobs assigned to the sales rep with this name (ignoring
+    /// case). Required with `--profile rep`.
+    #[arg(long, value_name = "NAME")]
+    rep: Option<String>,
+
+    /// Who the report is for, which decides what it includes.
+    #[arg(long, value_enum, default_value = "manager")]
+    profile: ReportProfile,
+
+    /// The filter to use when query JobNimbus for jobs, using ElasticSearch
+    /// syntax.
+    #[arg(short, long = "filter", default_value = None)]
+    filter_filename: Option<String>,
+
+    /// The URL to send a JSON summary of the report to once it has been
+    /// generated, e.g. a Zapier or Make webhook.
+    #[arg(long, value_name = "URL", default_value = None)]
+    post_summary_to: Option<String>,
 
-#[derive(Debug, clap::ValueEnum, Clone, Copy, Eq, PartialEq)]
-enum OutputFormat {
-    /// Prints a human-readable report into the output file.
-    Human,
-    /// Prints a CSV file into the output file.
-    Csv,
-    /// Outputs a Google Sheet on the user's Google Drive (requires OAuth
-    /// authorization).
-    GoogleSheets,
+    /// Only valid with `--format google-sheets`. Writes a single sheet of the
+    /// report into an existing spreadsheet, starting at the specified cell,
+    /// instead of uploading the whole report. The range is of the form
+    /// `SPREADSHEET_ID:TAB!CELL` (e.g. `1aBcD...:KPI!A1`), and the rest of
+    /// that spreadsheet is left untouched.
+    #[arg(long, value_name = "RANGE", value_parser = google_sheets::parse_target_range)]
+    target_range: Option<TargetRange>,
+
+    /// The sheet of the report to write with `--target-range`.
+    #[arg(
+        long,
+        value_name = "TITLE",
+        default_value = "Accounts Receivable",
+        requires = "target_range"
+    )]
+    target_sheet: String,
 }
 
-const CATEGORIES_WE_CARE_ABOUT: &[Status] = &[
+/// The file listing the job statuses to include in the report, in the order in
+/// which to display them. This is a JSON array of status names, e.g.
+/// `["Pending Payments", "Collections"]`. If it doesn't exist, then
+/// `DEFAULT_CATEGORIES` is used.
+const CATEGORIES_FILE: &str = "ar_categories.json";
+
+/// The job statuses included in the report by default, in the order in which
+/// they are displayed.
+pub const DEFAULT_CATEGORIES: &[Status] = &[
     Status::PendingPayments,
     Status::PostInstallSupplementPending,
     Status::JobsInProgress,
@@ -62,87 +110,656 @@ const CATEGORIES_WE_CARE_ABOUT: &[Status] = &[
     Status::Collections,
 ];
 
+/// The file configuring how the collection priority of each job is calculated
+/// (see `PriorityModel`). If it doesn't exist, then the default model is used.
+const PRIORITY_FILE: &str = "ar_priority.json";
+
+/// How urgently a job's receivable should be collected: its amount receivable
+/// in dollars, times the number of `age_unit_days` that it has been in its
+/// status, times the weight of its status. E.g. with the default model, $1,000
+/// that has been in Collections for two months has a priority of 4,000.
+///
+/// Jobs on a payment plan that is current have a priority of 0, since nothing
+/// is due from them yet.
+///
+/// The model is stored as a JSON object with any of the fields, e.g.
+///
+/// ```json
+/// { "age_unit_days": 7, "status_weights": { "Collections": 3, "Job Completed": 1.5 } }
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PriorityModel {
+    /// The number of days in status that count as one unit of age.
+    pub age_unit_days: f64,
+    /// The weight of each status, by status name. Statuses that aren't listed
+    /// have the `default_status_weight`.
+    pub status_weights: HashMap<String, f64>,
+    pub default_status_weight: f64,
+}
+
+impl Default for PriorityModel {
+    fn default() -> Self {
+        PriorityModel {
+            age_unit_days: 30.0,
+            status_weights: HashMap::from([
+                (Status::Collections.to_string(), 2.0),
+                (Status::PendingPayments.to_string(), 1.5),
+                // the work isn't done yet, so there is usually nothing to chase
+                (Status::JobsInProgress.to_string(), 0.5),
+            ]),
+            default_status_weight: 1.0,
+        }
+    }
+}
+
+impl PriorityModel {
+    /// Loads the model from the priority file, falling back to the default
+    /// model if the file doesn't exist or can't be read.
+    pub fn load() -> Self {
+        let file = match File::open(storage::config_path(PRIORITY_FILE)) {
+            Ok(file) => file,
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to open AR priority file: {}", e);
+                }
+                return Self::default();
+            }
+        };
+        match serde_json::from_reader::<_, Self>(BufReader::new(file)) {
+            Ok(model) if model.age_unit_days > 0.0 => model,
+            Ok(_) => {
+                warn!("The `age_unit_days` in the AR priority file must be positive");
+                Self::default()
+            }
+            Err(e) => {
+                warn!("Failed to deserialize AR priority file: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    fn status_weight(&self, status: &Status) -> f64 {
+        self.status_weights
+            .iter()
+            .find(|(name, _)| Status::from(name.trim()) == *status)
+            .map_or(self.default_status_weight, |(_, &weight)| weight)
+    }
+
+    /// The collection priority of the job. Jobs that are owed nothing (or that
+    /// overpaid) have a priority of 0.
+    pub fn priority(&self, job: &Job, now: Timestamp) -> f64 {
+        if is_on_current_plan(job, now) {
+            return 0.0;
+        }
+        let dollars = job.amt_receivable.max(0) as f64 / 100.0;
+        let age = days_in_status(job, now).max(0) as f64 / self.age_unit_days;
+        dollars * age * self.status_weight(&job.status)
+    }
+}
+
 struct AccRecvableData<'a> {
+    /// The time up to which the days that jobs have been in their status are
+    /// counted.
+    now: Timestamp,
     total: i32,
-    categorized_jobs: HashMap<Status, (i32, Vec<&'a Job>)>,
+    /// The total and jobs of each category, from the highest total priority to
+    /// the lowest, each with its jobs from the highest priority to the lowest.
+    categorized_jobs: Vec<(Status, (i32, Vec<&'a Job>))>,
+    priority_model: PriorityModel,
 }
 
-pub fn main(args: Args) -> anyhow::Result<()> {
-    let Args { jn_api_key, output, format, new } = args;
+impl AccRecvableData<'_> {
+    fn priority(&self, job: &Job) -> f64 {
+        self.priority_model.priority(job, self.now)
+    }
 
-    let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+    fn total_priority(&self, jobs: &[&Job]) -> f64 {
+        jobs.iter().map(|job| self.priority(job)).sum()
+    }
 
-    if format == OutputFormat::GoogleSheets && output.is_some() {
-        CliArgs::command()
-            .error(
-                clap::error::ErrorKind::ArgumentConflict,
-                "The `--output` option cannot be used with `--format google-sheets`",
-            )
-            .exit();
+    /// The total receivable of the jobs that are on a current payment plan.
+    fn current_plan_total(&self, jobs: &[&Job]) -> i32 {
+        jobs.iter()
+            .filter(|job| is_on_current_plan(job, self.now))
+            .map(|job| job.amt_receivable)
+            .sum()
     }
-    if format != OutputFormat::GoogleSheets && new {
-        CliArgs::command()
-            .error(
-                clap::error::ErrorKind::ArgumentConflict,
-                "The `--new` option can only be used with `--format google-sheets`",
-            )
-            .exit();
+
+    /// The total receivable of all the jobs that are on a current payment
+    /// plan.
+    fn current_plan_receivable(&self) -> i32 {
+        self.categorized_jobs.iter().map(|(_, (_, jobs))| self.current_plan_total(jobs)).sum()
     }
+}
 
-    let jobs = job_nimbus::get_all_jobs_from_job_nimbus(&jn_api_key, None)?;
+/// Whether the job is on a payment plan without any missed installments.
+fn is_on_current_plan(job: &Job, now: Timestamp) -> bool {
+    job.payment_plan.as_ref().is_some_and(|plan| plan.missed_installments(now) == 0)
+}
 
-    let mut results = AccRecvableData { total: 0, categorized_jobs: HashMap::new() };
-    for category in CATEGORIES_WE_CARE_ABOUT {
-        results.categorized_jobs.insert(category.clone(), (0, Vec::new()));
+/// The state of the job's payment plan for the report, e.g. "current, next due
+/// 2025-06-15" or "missed 2 installments". Empty if it has no payment plan.
+fn payment_plan_text(job: &Job, now: Timestamp) -> String {
+    let Some(plan) = &job.payment_plan else {
+        return String::new();
+    };
+    match plan.missed_installments(now) {
+        0 => format!("current, next due {}", plan.next_due_date.format("%Y-%m-%d")),
+        1 => "missed 1 installment".to_owned(),
+        missed => format!("missed {} installments", missed),
     }
+}
+
+/// Sorts the jobs into the categories, leaving out jobs whose status is not one
+/// of the categories, then sorts the categories and their jobs by priority.
+/// Categories with the same total priority (e.g. empty ones) keep their order.
+fn categorize(
+    jobs: &[Job],
+    categories: Vec<Status>,
+    priority_model: PriorityModel,
+    now: Timestamp,
+) -> AccRecvableData<'_> {
+    let categorized_jobs =
+        categories.into_iter().map(|category| (category, (0, Vec::new()))).collect();
+    let mut results = AccRecvableData { now, total: 0, categorized_jobs, priority_model };
 
-    for job in &jobs {
+    for job in jobs {
         let amt = job.amt_receivable;
 
-        if let Some((category_total, category_jobs)) = results.categorized_jobs.get_mut(&job.status)
+        if let Some((_, (category_total, category_jobs))) =
+            results.categorized_jobs.iter_mut().find(|(status, _)| *status == job.status)
         {
             results.total += amt;
             *category_total += amt;
-            category_jobs.push(&job);
+            category_jobs.push(job);
         }
     }
 
-    let output_writer: Box<dyn Write> = match output.as_deref() {
-        Some("-") | None => Box::new(std::io::stdout()),
-        Some(path) => Box::new(std::fs::File::create(path)?),
+    let priority = |job: &Job| results.priority_model.priority(job, now);
+    for (_, (_, jobs)) in &mut results.categorized_jobs {
+        jobs.sort_by(|a, b| priority(b).total_cmp(&priority(a)));
+    }
+    let total_priority = |jobs: &[&Job]| jobs.iter().map(|job| priority(job)).sum::<f64>();
+    results
+        .categorized_jobs
+        .sort_by(|(_, (_, a)), (_, (_, b))| total_priority(b).total_cmp(&total_priority(a)));
+    results
+}
+
+/// The version of the schema of `AccRecvableReport`. It is incremented whenever
+/// a field is removed or changes meaning; fields may be added without
+/// incrementing it.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// The results of the AR report in a stable, serializable form, for using
+/// ahitool as a library (see `analyze`). Amounts are in cents.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccRecvableReport {
+    /// The `SCHEMA_VERSION` that the report was generated with.
+    pub schema_version: u32,
+    pub total_receivable_cents: i64,
+    /// The part of the total receivable that is on payment plans without any
+    /// missed installments, and so isn't due yet.
+    #[serde(default)]
+    pub current_plan_receivable_cents: i64,
+    /// The categories from the highest total priority to the lowest.
+    pub categories: Vec<AccRecvableCategory>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccRecvableCategory {
+    /// The status of the jobs in the category, e.g. "Pending Payments".
+    pub status: String,
+    pub total_receivable_cents: i64,
+    /// The part of the total receivable that is on current payment plans.
+    #[serde(default)]
+    pub current_plan_receivable_cents: i64,
+    /// The sum of the collection priorities of the jobs.
+    #[serde(default)]
+    pub total_priority: f64,
+    /// The median days that the jobs have been in their status. `None` if there
+    /// are no jobs.
+    pub median_days_in_status: Option<i64>,
+    /// The 90th percentile days that the jobs have been in their status.
+    /// `None` if there are no jobs.
+    pub p90_days_in_status: Option<i64>,
+    /// The number of jobs in each aging bucket, along with the bucket's label
+    /// (e.g. "0-30 days").
+    pub aging_buckets: Vec<(String, usize)>,
+    /// The jobs from the highest priority to the lowest.
+    pub jobs: Vec<AccRecvableJob>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccRecvableJob {
+    pub jnid: String,
+    pub job_number: Option<String>,
+    pub job_name: Option<String>,
+    pub sales_rep: Option<String>,
+    pub receivable_cents: i32,
+    pub days_in_status: i64,
+    /// The collection priority of the job (see `PriorityModel`).
+    #[serde(default)]
+    pub priority: f64,
+    /// The payment plan of the job, if any.
+    #[serde(default)]
+    pub payment_plan: Option<AccRecvablePaymentPlan>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccRecvablePaymentPlan {
+    pub installment_cents: i32,
+    /// The date that the next installment is due, e.g. "2025-06-15".
+    pub next_due_date: String,
+    pub interval_days: i64,
+    /// The number of installments that are past due. The plan is current if
+    /// this is 0.
+    pub missed_installments: i64,
+}
+
+/// Analyzes the jobs like the AR report does, returning the results instead of
+/// outputting them anywhere. Only jobs whose status is one of the categories
+/// are included. The days in status and missed installments are counted up to
+/// `now`.
+pub fn analyze(
+    jobs: &[Job],
+    categories: Vec<Status>,
+    priority_model: &PriorityModel,
+    now: Timestamp,
+) -> AccRecvableReport {
+    let results = categorize(jobs, categories, priority_model.clone(), now);
+    let categories = results
+        .categorized_jobs
+        .iter()
+        .map(|(status, (total, jobs))| {
+            let aging = aging_stats(jobs, results.now);
+            AccRecvableCategory {
+                status: status.to_string(),
+                total_receivable_cents: *total as i64,
+                current_plan_receivable_cents: results.current_plan_total(jobs) as i64,
+                total_priority: results.total_priority(jobs),
+                median_days_in_status: aging.median_days,
+                p90_days_in_status: aging.p90_days,
+                aging_buckets: AgingStats::bucket_labels()
+                    .into_iter()
+                    .zip(aging.bucket_counts)
+                    .collect(),
+                jobs: jobs
+                    .iter()
+                    .map(|job| AccRecvableJob {
+                        jnid: job.jnid.clone(),
+                        job_number: job.job_number.clone(),
+                        job_name: job.job_name.clone(),
+                        sales_rep: job.sales_rep.clone(),
+                        receivable_cents: job.amt_receivable,
+                        days_in_status: days_in_status(job, results.now),
+                        priority: results.priority(job),
+                        payment_plan: job.payment_plan.as_ref().map(|plan| {
+                            AccRecvablePaymentPlan {
+                                installment_cents: plan.installment,
+                                next_due_date: plan.next_due_date.format("%Y-%m-%d").to_string(),
+                                interval_days: plan.interval_days,
+                                missed_installments: plan.missed_installments(results.now),
+                            }
+                        }),
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    AccRecvableReport {
+        schema_version: SCHEMA_VERSION,
+        total_receivable_cents: results.total as i64,
+        current_plan_receivable_cents: results.current_plan_receivable() as i64,
+        categories,
+    }
+}
+
+/// Loads the job statuses to include in the report from the categories file,
+/// falling back to the default categories if the file doesn't exist or can't be
+/// read.
+pub fn load_categories() -> Vec<Status> {
+    let path = storage::config_path(CATEGORIES_FILE);
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!("Failed to open AR categories file: {}", e);
+            }
+            return DEFAULT_CATEGORIES.to_vec();
+        }
+    };
+    match serde_json::from_reader::<_, Vec<String>>(BufReader::new(file)) {
+        Ok(names) => parse_categories(&names),
+        Err(e) => {
+            warn!("Failed to deserialize AR categories file: {}", e);
+            DEFAULT_CATEGORIES.to_vec()
+        }
+    }
+}
+
+/// Converts the status names into statuses, ignoring duplicates.
+fn parse_categories(names: &[String]) -> Vec<Status> {
+    let mut categories = Vec::new();
+    for name in names {
+        let status = Status::from(name.trim());
+        if !categories.contains(&status) {
+            categories.push(status);
+        }
+    }
+    categories
+}
+
+/// The upper bounds (inclusive) of the aging buckets, in days in status. Jobs
+/// that have been in their status for longer than the last bound fall in a
+/// final, unbounded bucket.
+const AGING_BUCKET_BOUNDS: [i64; 4] = [30, 60, 90, 180];
+
+/// How long the jobs in a category have been in their status.
+#[derive(Debug, PartialEq, Eq)]
+struct AgingStats {
+    /// The median days in status. `None` if there are no jobs.
+    median_days: Option<i64>,
+    /// The 90th percentile days in status. `None` if there are no jobs.
+    p90_days: Option<i64>,
+    /// The number of jobs in each aging bucket (see `AGING_BUCKET_BOUNDS`).
+    bucket_counts: [usize; AGING_BUCKET_BOUNDS.len() + 1],
+}
+
+impl AgingStats {
+    fn from_days(mut days: Vec<i64>) -> Self {
+        days.sort_unstable();
+        let mut bucket_counts = [0; AGING_BUCKET_BOUNDS.len() + 1];
+        for &d in &days {
+            let bucket = AGING_BUCKET_BOUNDS
+                .iter()
+                .position(|&bound| d <= bound)
+                .unwrap_or(AGING_BUCKET_BOUNDS.len());
+            bucket_counts[bucket] += 1;
+        }
+        AgingStats {
+            median_days: percentile(&days, 0.5),
+            p90_days: percentile(&days, 0.9),
+            bucket_counts,
+        }
+    }
+
+    fn bucket_labels() -> Vec<String> {
+        let mut labels = Vec::new();
+        let mut lower = 0;
+        for bound in AGING_BUCKET_BOUNDS {
+            labels.push(format!("{}-{} days", lower, bound));
+            lower = bound + 1;
+        }
+        labels.push(format!("{}+ days", lower));
+        labels
+    }
+}
+
+/// The nearest-rank percentile of the sorted values, or `None` if there are no
+/// values.
+fn percentile(sorted: &[i64], p: f64) -> Option<i64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+fn days_in_status(job: &Job, now: Timestamp) -> i64 {
+    now.signed_duration_since(job.status_mod_date).num_days()
+}
+
+fn aging_stats(jobs: &[&Job], now: Timestamp) -> AgingStats {
+    AgingStats::from_days(jobs.iter().map(|job| days_in_status(job, now)).collect())
+}
+
+const ADDRESS_HEADERS: [&str; 4] = ["Address", "City", "State", "Zip"];
+
+/// The address columns of the job, or no columns if addresses aren't included.
+fn address_fields(job: &Job, include_addresses: bool) -> Vec<String> {
+    if !include_addresses {
+        return Vec::new();
+    }
+    [&job.address, &job.city, &job.state, &job.zip]
+        .into_iter()
+        .map(|field| field.clone().unwrap_or_default())
+        .collect()
+}
+
+/// The values of the computed columns for the job. Columns that can't be
+/// evaluated for the job are `None`.
+fn computed_fields(
+    job: &Job,
+    computed_columns: &[ComputedColumn],
+    now: Timestamp,
+) -> Vec<Option<f64>> {
+    computed_columns.iter().map(|column| column.evaluate(job, now)).collect()
+}
+
+fn days_or_na(days: Option<i64>) -> String {
+    days.map(|d| d.to_string()).unwrap_or_else(|| "N/A".to_owned())
+}
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    run(args, None)
+}
+
+/// Generates the report. If `prefetched` is specified, then the report is
+/// generated from those jobs (fetched at the specified time) instead of
+/// fetching them from JobNimbus, and the API key and filter are ignored.
+pub fn run(args: Args, prefetched: Option<(Timestamp, Vec<Job>)>) -> anyhow::Result<()> {
+    let Args {
+        jn_api_key,
+        output_args,
+        format,
+        addresses,
+        trade,
+        rep,
+        profile,
+        filter_filename,
+        post_summary_to,
+        target_range,
+        target_sheet,
+    } = args;
+    output_args.validate(format == OutputFormat::GoogleSheets);
+
+    if target_range.is_some() && format != OutputFormat::GoogleSheets {
+        argument_conflict(
+            "The `--target-range` option can only be used with `--format google-sheets`",
+        );
+    }
+    if target_range.is_some() && output_args.new {
+        argument_conflict("The `--target-range` option cannot be used with `--new`");
+    }
+    if profile == ReportProfile::Rep && rep.is_none() {
+        CliArgs::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "The `--profile rep` option requires `--rep`",
+            )
+            .exit();
+    }
+
+    let (fetched_at, mut jobs) = match prefetched {
+        Some(prefetched) => prefetched,
+        None => {
+            let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+            let fetched_at = Utc::now();
+            (
+                fetched_at,
+                job_nimbus::get_all_jobs_with_filter_file(&jn_api_key, filter_filename.as_deref())?,
+            )
+        }
     };
+    if let Some(trade) = &trade {
+        jobs.retain(|job| job.is_trade(trade));
+    }
+    // every amount in the report is for accounting, so only the rep profile
+    // leaves anything out
+    if let Some(rep) = &rep {
+        let is_rep = |name: &str| name.trim().eq_ignore_ascii_case(rep.trim());
+        jobs.retain(|job| job.sales_rep.as_deref().is_some_and(is_rep));
+    }
+    let computed_columns = ComputedColumn::load_all();
+
+    let results = categorize(&jobs, load_categories(), PriorityModel::load(), Utc::now());
 
+    let output_writer = output_args.writer()?;
+
+    let mut spreadsheet_url = None;
     match format {
-        OutputFormat::Human => print_human(&results, output_writer)?,
-        OutputFormat::Csv => print_csv(&results, output_writer)?,
+        OutputFormat::Human => print_human(&results, addresses, fetched_at, output_writer)?,
+        OutputFormat::Csv => print_csv(&results, addresses, &computed_columns, output_writer)?,
         OutputFormat::GoogleSheets => {
-            generate_report_google_sheets(&results, !new)?;
+            let destination = match &target_range {
+                Some(range) => google_sheets::Destination::Range(range, &target_sheet),
+                None if output_args.new => google_sheets::Destination::New,
+                None => google_sheets::Destination::Existing,
+            };
+            spreadsheet_url = Some(generate_report_google_sheets(
+                &results,
+                addresses,
+                fetched_at,
+                &computed_columns,
+                destination,
+            )?);
+        }
+    }
+
+    if let Some(url) = post_summary_to {
+        let mut summary = ReportSummary::new("ar", Utc::now(), fetched_at);
+        summary.spreadsheet_url = spreadsheet_url;
+        summary.totals.insert("receivable".to_owned(), results.total as f64 / 100.0);
+        let current_plan_total = results.current_plan_receivable() as f64 / 100.0;
+        summary.totals.insert("current_payment_plans".to_owned(), current_plan_total);
+        for (status, (total, jobs)) in &results.categorized_jobs {
+            summary.counts.insert(status.to_string(), jobs.len());
+            summary.totals.insert(status.to_string(), *total as f64 / 100.0);
         }
+        let num_jobs = results.categorized_jobs.iter().map(|(_, (_, jobs))| jobs.len()).sum();
+        summary.counts.insert("jobs".to_owned(), num_jobs);
+        summary.counts.insert("sheets_api_requests".to_owned(), google_sheets::request_count());
+        webhook::post_summary_or_warn(&url, &summary);
     }
 
     Ok(())
 }
 
-fn print_human(results: &AccRecvableData, mut writer: impl Write) -> std::io::Result<()> {
+fn print_human(
+    results: &AccRecvableData,
+    include_addresses: bool,
+    fetched_at: Timestamp,
+    mut writer: impl Write,
+) -> std::io::Result<()> {
     let mut zero_amt_jobs = Vec::new();
+    let mut current_plan_jobs = Vec::new();
 
+    writeln!(writer, "Based on data fetched at {}", fetched_at.format("%Y-%m-%d %H:%M UTC"))?;
     writeln!(writer, "Total: ${}", results.total as f64 / 100.0)?;
+    // the receivables on current payment plans aren't due yet, so they are
+    // listed separately from the overdue ones
+    let current_plan_total = results.current_plan_receivable();
+    if current_plan_total != 0 {
+        writeln!(
+            writer,
+            "On a current payment plan: ${}, overdue: ${}",
+            current_plan_total as f64 / 100.0,
+            (results.total - current_plan_total) as f64 / 100.0
+        )?;
+    }
     for (status, (category_total, jobs)) in &results.categorized_jobs {
-        writeln!(writer, "    - {}: total ${}", status, *category_total as f64 / 100.0)?;
+        writeln!(
+            writer,
+            "    - {}: total ${}, priority {:.0}",
+            status,
+            *category_total as f64 / 100.0,
+            results.total_priority(jobs)
+        )?;
+        let category_plan_total = results.current_plan_total(jobs);
+        if category_plan_total != 0 {
+            writeln!(
+                writer,
+                "      ${} on a current payment plan",
+                category_plan_total as f64 / 100.0
+            )?;
+        }
+        let aging = aging_stats(jobs, results.now);
+        writeln!(
+            writer,
+            "      median {} days, 90th percentile {} days in status",
+            days_or_na(aging.median_days),
+            days_or_na(aging.p90_days)
+        )?;
+        let distribution = AgingStats::bucket_labels()
+            .iter()
+            .zip(aging.bucket_counts)
+            .map(|(label, count)| format!("{}: {}", label, count))
+            .collect::<Vec<_>>();
+        writeln!(writer, "      {}", distribution.join(" | "))?;
         for job in jobs {
             if job.amt_receivable == 0 {
                 zero_amt_jobs.push(job);
                 continue;
             }
+            if is_on_current_plan(job, results.now) {
+                current_plan_jobs.push(job);
+                continue;
+            }
 
             let name = job.job_name.as_deref().unwrap_or("");
             let number = job.job_number.as_deref().unwrap_or("Unknown Job Number");
             let amount_receivable = job.amt_receivable as f64 / 100.0;
-            let days_in_status = Utc::now().signed_duration_since(job.status_mod_date).num_days();
+            let days_in_status = days_in_status(job, results.now);
+            let payment_plan = match payment_plan_text(job, results.now) {
+                text if text.is_empty() => text,
+                text => format!(", payment plan {}", text),
+            };
+            writeln!(
+                writer,
+                "        - {} (#{}): ${:.2} ({} days, priority {:.0}{}, assigned to {})",
+                name,
+                number,
+                amount_receivable,
+                days_in_status,
+                results.priority(job),
+                payment_plan,
+                job.sales_rep.as_deref().unwrap_or("Unknown Sales Rep")
+            )?;
+            if include_addresses {
+                writeln!(
+                    writer,
+                    "          at {}",
+                    job.full_address().as_deref().unwrap_or("unknown address")
+                )?;
+            }
+        }
+    }
+
+    if !current_plan_jobs.is_empty() {
+        writeln!(writer, "Jobs on a current payment plan:")?;
+    }
+    for job in current_plan_jobs {
+        let Some(plan) = &job.payment_plan else {
+            continue;
+        };
+        writeln!(
+            writer,
+            "    - {} (#{}): ${:.2} ({}, next ${:.2} due {}, assigned to {})",
+            job.job_name.as_deref().unwrap_or(""),
+            job.job_number.as_deref().unwrap_or("Unknown Job Number"),
+            job.amt_receivable as f64 / 100.0,
+            job.status,
+            plan.installment as f64 / 100.0,
+            plan.next_due_date.format("%Y-%m-%d"),
+            job.sales_rep.as_deref().unwrap_or("Unknown Sales Rep")
+        )?;
+        if include_addresses {
             writeln!(
                 writer,
-                "        - {} (#{}): ${:.2} ({} days, assigned to {})",
-                name, number, amount_receivable, days_in_status, job.sales_rep.as_deref().unwrap_or("Unknown Sales Rep")
+                "        at {}",
+                job.full_address().as_deref().unwrap_or("unknown address")
             )?;
         }
     }
@@ -151,21 +768,49 @@ fn print_human(results: &AccRecvableData, mut writer: impl Write) -> std::io::Re
     for job in zero_amt_jobs {
         let name = job.job_name.as_deref().unwrap_or("");
         let number = job.job_number.as_deref().unwrap_or("Unknown Job Number");
-        let days_in_status = Utc::now().signed_duration_since(job.status_mod_date).num_days();
+        let days_in_status = days_in_status(job, results.now);
         writeln!(
             writer,
             "    - {} (#{}): ({} for {} days, assigned to {})",
             name, number, job.status, days_in_status, job.sales_rep.as_deref().unwrap_or("Unknown Sales Rep")
         )?;
+        if include_addresses {
+            writeln!(
+                writer,
+                "        at {}",
+                job.full_address().as_deref().unwrap_or("unknown address")
+            )?;
+        }
     }
 
     Ok(())
 }
 
-fn print_csv(results: &AccRecvableData, writer: impl Write) -> std::io::Result<()> {
-    let mut writer = csv::Writer::from_writer(writer);
+fn print_csv(
+    results: &AccRecvableData,
+    include_addresses: bool,
+    computed_columns: &[ComputedColumn],
+    writer: impl Write,
+) -> std::io::Result<()> {
+    // the aging summary is a second table with different columns
+    let mut writer = csv::WriterBuilder::new().flexible(true).from_writer(writer);
+    let address_headers: &[&str] = if include_addresses { &ADDRESS_HEADERS } else { &[] };
     writer
-        .write_record(&["Job Name", "Sales Rep", "Job Number", "Job Status", "Amount", "Days In Status"])
+        .write_record(
+            [
+                "Job Name",
+                "Sales Rep",
+                "Job Number",
+                "Job Status",
+                "Amount",
+                "Days In Status",
+                "Priority",
+                "Payment Plan",
+            ]
+            .into_iter()
+            .chain(address_headers.iter().copied())
+            .chain(computed_columns.iter().map(|column| column.name.as_str())),
+        )
         .unwrap();
     for (_status, (_category_total, jobs)) in &results.categorized_jobs {
         for job in jobs {
@@ -174,45 +819,96 @@ fn print_csv(results: &AccRecvableData, writer: impl Write) -> std::io::Result<(
             let number = job.job_number.as_deref().unwrap_or("Unknown Job Number");
             let status = format!("{}", job.status);
             let amount_receivable = (job.amt_receivable as f64) / 100.0;
-            let days_in_status = Utc::now().signed_duration_since(job.status_mod_date).num_days();
+            let days_in_status = days_in_status(job, results.now);
+            let address = address_fields(job, include_addresses);
+            let computed = computed_fields(job, computed_columns, results.now)
+                .into_iter()
+                .map(|value| value.map(|v| v.to_string()).unwrap_or_default())
+                .collect::<Vec<_>>();
             writer
-                .write_record(&[
-                    name,
-                    sales_rep,
-                    number,
-                    &status,
-                    &amount_receivable.to_string(),
-                    &days_in_status.to_string(),
-                ])
+                .write_record(
+                    [
+                        name,
+                        sales_rep,
+                        number,
+                        &status,
+                        &amount_receivable.to_string(),
+                        &days_in_status.to_string(),
+                        &format!("{:.0}", results.priority(job)),
+                        &payment_plan_text(job, results.now),
+                    ]
+                    .into_iter()
+                    .chain(address.iter().map(String::as_str))
+                    .chain(computed.iter().map(String::as_str)),
+                )
                 .unwrap();
         }
     }
+
+    writer.write_record([""])?;
+    let mut header =
+        vec!["Job Status".to_owned(), "Median Days".to_owned(), "90th Percentile Days".to_owned()];
+    header.extend(AgingStats::bucket_labels());
+    writer.write_record(&header)?;
+    for (status, (_category_total, jobs)) in &results.categorized_jobs {
+        let aging = aging_stats(jobs, results.now);
+        let mut record =
+            vec![status.to_string(), days_or_na(aging.median_days), days_or_na(aging.p90_days)];
+        record.extend(aging.bucket_counts.iter().map(|count| count.to_string()));
+        writer.write_record(&record)?;
+    }
     writer.flush().unwrap();
     Ok(())
 }
 
 fn generate_report_google_sheets(
     results: &AccRecvableData<'_>,
-    update: bool,
-) -> anyhow::Result<()> {
-    fn mk_row(cells: impl IntoIterator<Item = ExtendedValue>) -> RowData {
-        RowData {
-            values: cells
-                .into_iter()
-                .map(|cell| CellData { user_entered_value: Some(cell) })
-                .collect(),
-        }
-    }
+    include_addresses: bool,
+    fetched_at: Timestamp,
+    computed_columns: &[ComputedColumn],
+    destination: google_sheets::Destination<'_>,
+) -> anyhow::Result<String> {
+    let spreadsheet =
+        report_spreadsheet(results, include_addresses, fetched_at, computed_columns);
+    let url = google_sheets::upload_to_destination_blocking(
+        google_sheets::SheetNickname::AccReceivable,
+        spreadsheet,
+        destination,
+    )?;
+    utils::open_url(url.as_str());
+    Ok(url)
+}
 
+/// Builds the spreadsheet of the report.
+fn report_spreadsheet(
+    results: &AccRecvableData<'_>,
+    include_addresses: bool,
+    fetched_at: Timestamp,
+    computed_columns: &[ComputedColumn],
+) -> Spreadsheet {
+    let address_headers: &[&str] = if include_addresses { &ADDRESS_HEADERS } else { &[] };
     let mut rows = Vec::new();
-    rows.push(mk_row([
-        ExtendedValue::StringValue("Job Name".to_string()),
-        ExtendedValue::StringValue("Job Salesman".to_string()),
-        ExtendedValue::StringValue("Job Number".to_string()),
-        ExtendedValue::StringValue("Job Status".to_string()),
-        ExtendedValue::StringValue("Amount".to_string()),
-        ExtendedValue::StringValue("Days In Status".to_string()),
-    ]));
+    rows.push(mk_row(
+        [
+            ExtendedValue::StringValue("Job Name".to_string()),
+            ExtendedValue::StringValue("Job Salesman".to_string()),
+            ExtendedValue::StringValue("Job Number".to_string()),
+            ExtendedValue::StringValue("Job Status".to_string()),
+            ExtendedValue::StringValue("Amount".to_string()),
+            ExtendedValue::StringValue("Days In Status".to_string()),
+            ExtendedValue::StringValue("Priority".to_string()),
+            ExtendedValue::StringValue("Payment Plan".to_string()),
+        ]
+        .into_iter()
+        .chain(address_headers.iter().map(|header| ExtendedValue::StringValue(header.to_string())))
+        .chain(
+            computed_columns.iter().map(|column| ExtendedValue::StringValue(column.name.clone())),
+        )
+        .chain([ExtendedValue::StringValue("Job ID".to_string())]),
+    ));
+    // the job ID identifies each row, so that the columns that users add to the
+    // right of the report stay with their job
+    let key_column = rows[0].values.len() - 1;
     for (_status, (_category_total, jobs)) in &results.categorized_jobs {
         for job in jobs {
             let name = job.job_name.as_deref().unwrap_or("");
@@ -220,21 +916,41 @@ fn generate_report_google_sheets(
             let number = job.job_number.as_deref().unwrap_or("Unknown Job Number");
             let status = job.status.to_string();
             let amount_receivable = (job.amt_receivable as f64) / 100.0;
-            let days_in_status = Utc::now().signed_duration_since(job.status_mod_date).num_days();
-            rows.push(mk_row([
-                ExtendedValue::StringValue(name.to_owned()),
-                ExtendedValue::StringValue(sales_rep.to_owned()),
-                ExtendedValue::StringValue(number.to_owned()),
-                ExtendedValue::StringValue(status),
-                ExtendedValue::NumberValue(amount_receivable),
-                ExtendedValue::NumberValue(days_in_status as f64),
-            ]));
+            let days_in_status = days_in_status(job, results.now);
+            rows.push(mk_row(
+                [
+                    ExtendedValue::StringValue(name.to_owned()),
+                    ExtendedValue::StringValue(sales_rep.to_owned()),
+                    ExtendedValue::StringValue(number.to_owned()),
+                    ExtendedValue::StringValue(status),
+                    ExtendedValue::NumberValue(amount_receivable),
+                    ExtendedValue::NumberValue(days_in_status as f64),
+                    ExtendedValue::NumberValue(results.priority(job).round()),
+                    ExtendedValue::StringValue(payment_plan_text(job, results.now)),
+                ]
+                .into_iter()
+                .chain(
+                    address_fields(job, include_addresses)
+                        .into_iter()
+                        .map(ExtendedValue::StringValue),
+                )
+                .chain(computed_fields(job, computed_columns, results.now).into_iter().map(
+                    |value| match value {
+                        Some(value) => ExtendedValue::NumberValue(value),
+                        None => ExtendedValue::StringValue("".to_owned()),
+                    },
+                ))
+                .chain([ExtendedValue::StringValue(job.jnid.clone())]),
+            ));
         }
     }
 
-    let spreadsheet = Spreadsheet {
+    let mut spreadsheet = Spreadsheet {
         properties: SpreadsheetProperties {
-            title: Some(format!("Accounts Receivable Report ({})", Utc::now())),
+            title: Some(format!(
+                "Accounts Receivable Report (data fetched {})",
+                fetched_at.format("%Y-%m-%d %H:%M UTC")
+            )),
         },
         sheets: Some(vec![Sheet {
             properties: SheetProperties {
@@ -243,35 +959,228 @@ fn generate_report_google_sheets(
             },
             data: Some(GridData { start_row: 1, start_column: 1, row_data: rows }),
         }]),
+        key_columns: HashMap::from([("Accounts Receivable".to_string(), key_column)]),
         ..Default::default()
     };
+    spreadsheet.sheets.as_mut().unwrap().push(aging_sheet(results));
+    spreadsheet
+}
 
-    let url = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(
-        google_sheets::run_with_credentials(|token| {
-            // FIXME cloning the token is a workaround because I can't
-            // get lifetimes to work correctly in run_with_credentials
-            let token = token.clone();
-            let spreadsheet = &spreadsheet;
-            async move {
-                let spreadsheet = spreadsheet.clone();
-                if update {
-                    google_sheets::create_or_write_spreadsheet(
-                        &token,
-                        google_sheets::SheetNickname::AccReceivable,
-                        spreadsheet,
-                    )
-                    .await
-                } else {
-                    google_sheets::create_spreadsheet(
-                        &token,
-                        google_sheets::SheetNickname::AccReceivable,
-                        spreadsheet,
-                    )
-                    .await
-                }
+/// Creates a sheet summarizing how long the jobs in each category have been in
+/// their status.
+fn aging_sheet(results: &AccRecvableData<'_>) -> Sheet {
+    fn days_cell(days: Option<i64>) -> ExtendedValue {
+        match days {
+            Some(days) => ExtendedValue::NumberValue(days as f64),
+            None => ExtendedValue::StringValue("N/A".to_string()),
+        }
+    }
+
+    let mut rows = Vec::new();
+    let mut header = vec![
+        ExtendedValue::StringValue("Job Status".to_string()),
+        ExtendedValue::StringValue("Median Days".to_string()),
+        ExtendedValue::StringValue("90th Percentile Days".to_string()),
+    ];
+    header.extend(AgingStats::bucket_labels().into_iter().map(ExtendedValue::StringValue));
+    rows.push(mk_row(header));
+    for (status, (_category_total, jobs)) in &results.categorized_jobs {
+        let aging = aging_stats(jobs, results.now);
+        let mut row = vec![
+            ExtendedValue::StringValue(status.to_string()),
+            days_cell(aging.median_days),
+            days_cell(aging.p90_days),
+        ];
+        row.extend(
+            aging.bucket_counts.iter().map(|&count| ExtendedValue::NumberValue(count as f64)),
+        );
+        rows.push(mk_row(row));
+    }
+
+    Sheet {
+        properties: SheetProperties { title: Some("Aging".to_string()), ..Default::default() },
+        data: Some(GridData { start_row: 1, start_column: 1, row_data: rows }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jobs::PaymentPlan;
+
+    #[test]
+    fn parse_categories_keeps_order() {
+        let names = ["Collections", " Pending Payments ", "Collections", "Custom Status"]
+            .map(str::to_owned);
+        assert_eq!(
+            parse_categories(&names),
+            vec![
+                Status::Collections,
+                Status::PendingPayments,
+                Status::Other("Custom Status".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn aging_stats_from_days() {
+        let aging = AgingStats::from_days(vec![400, 5, 20, 45, 10]);
+        assert_eq!(
+            aging,
+            AgingStats {
+                median_days: Some(20),
+                p90_days: Some(400),
+                bucket_counts: [3, 1, 0, 0, 1]
             }
-        }),
-    )?;
-    utils::open_url(url.as_str());
-    Ok(())
+        );
+
+        let empty = AgingStats::from_days(vec![]);
+        assert_eq!(empty.median_days, None);
+        assert_eq!(empty.bucket_counts, [0; 5]);
+    }
+
+    #[test]
+    fn priority_of_jobs() {
+        let now: Timestamp = "2025-06-01T00:00:00Z".parse().unwrap();
+        let job = |status, amt_receivable, days| Job {
+            status,
+            amt_receivable,
+            status_mod_date: now - chrono::Duration::days(days),
+            ..crate::jobs::test::make_job(false, None, None, None, None, None)
+        };
+        let model: PriorityModel = serde_json::from_str(
+            r#"{ "age_unit_days": 10, "status_weights": { " Collections ": 3 } }"#,
+        )
+        .unwrap();
+        assert_eq!(model.priority(&job(Status::Collections, 10000, 20), now), 600.0);
+        assert_eq!(model.priority(&job(Status::PendingPayments, 10000, 20), now), 200.0);
+        assert_eq!(model.priority(&job(Status::Collections, -500, 20), now), 0.0);
+        assert!(serde_json::from_str::<PriorityModel>(r#"{ "age": 10 }"#).is_err());
+    }
+
+    #[test]
+    fn current_payment_plans_are_listed_separately() {
+        let now: Timestamp = "2025-06-01T00:00:00Z".parse().unwrap();
+        let job = |number: &str, amt_receivable, next_due_days: Option<i64>| Job {
+            job_number: Some(number.to_owned()),
+            status: Status::PendingPayments,
+            amt_receivable,
+            status_mod_date: now - chrono::Duration::days(60),
+            payment_plan: next_due_days.map(|days| PaymentPlan {
+                installment: 2500,
+                next_due_date: now + chrono::Duration::days(days),
+                interval_days: 30,
+            }),
+            ..crate::jobs::test::make_job(false, None, None, None, None, None)
+        };
+        let jobs = [job("1", 10000, Some(5)), job("2", 5000, Some(-40)), job("3", 1000, None)];
+        let results =
+            categorize(&jobs, vec![Status::PendingPayments], PriorityModel::default(), now);
+        assert_eq!(results.current_plan_receivable(), 10000);
+        assert_eq!(results.priority(&jobs[0]), 0.0);
+        assert_eq!(payment_plan_text(&jobs[1], now), "missed 2 installments");
+
+        let mut out = Vec::new();
+        print_human(&results, false, now, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(lines[2], "On a current payment plan: $100, overdue: $60");
+        assert!(lines.contains(
+            &"        -  (#2): $50.00 (60 days, priority 150, payment plan missed 2 installments, \
+              assigned to Unknown Sales Rep)"
+        ));
+        let current = lines.iter().position(|line| *line == "Jobs on a current payment plan:");
+        assert_eq!(
+            lines[current.unwrap() + 1],
+            "    -  (#1): $100.00 (Pending Payments, next $25.00 due 2025-06-06, assigned to \
+             Unknown Sales Rep)"
+        );
+    }
+
+    #[test]
+    fn analyze_round_trips() {
+        let job = |jnid: &str, status, amt_receivable| Job {
+            jnid: jnid.to_owned(),
+            status,
+            amt_receivable,
+            status_mod_date: Utc::now() - chrono::Duration::days(60),
+            ..crate::jobs::test::make_job(false, None, None, None, None, None)
+        };
+        let jobs = [
+            job("a", Status::PendingPayments, 1000),
+            job("b", Status::Collections, 250),
+            job("c", Status::Lost, 500),
+        ];
+
+        let report = analyze(
+            &jobs,
+            vec![Status::Collections, Status::PendingPayments],
+            &PriorityModel::default(),
+            Utc::now(),
+        );
+        assert_eq!(report.schema_version, SCHEMA_VERSION);
+        assert_eq!(report.total_receivable_cents, 1250);
+        let categories: Vec<_> = report
+            .categories
+            .iter()
+            .map(|category| (category.status.as_str(), category.jobs[0].jnid.as_str()))
+            .collect();
+        // $10 pending for two months outranks $2.50 in collections
+        assert_eq!(categories, [("Pending Payments", "a"), ("Collections", "b")]);
+        assert_eq!(report.categories[0].jobs[0].priority, 30.0);
+        assert_eq!(report.categories[1].total_priority, 10.0);
+
+        let json = serde_json::to_string(&report).unwrap();
+        assert_eq!(serde_json::from_str::<AccRecvableReport>(&json).unwrap(), report);
+    }
+
+    // The golden tests below pin the exact output of each format for a fixed
+    // synthetic data set, since spreadsheets built on top of the report depend
+    // on its column order and formatting. Accept intended changes with `cargo
+    // insta review`.
+
+    fn snapshot_jobs() -> Vec<Job> {
+        crate::synthetic::generate_jobs(40, 1)
+    }
+
+    fn snapshot_data(jobs: &[Job]) -> AccRecvableData<'_> {
+        categorize(
+            jobs,
+            DEFAULT_CATEGORIES.to_vec(),
+            PriorityModel::default(),
+            "2025-06-01T00:00:00Z".parse().unwrap(),
+        )
+    }
+
+    fn snapshot_columns() -> Vec<ComputedColumn> {
+        vec![ComputedColumn::new("Days Since Install".to_owned(), "today - install_date").unwrap()]
+    }
+
+    #[test]
+    fn human_output_is_unchanged() {
+        let jobs = snapshot_jobs();
+        let mut out = Vec::new();
+        print_human(&snapshot_data(&jobs), true, Timestamp::UNIX_EPOCH, &mut out).unwrap();
+        insta::assert_snapshot!(String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn csv_output_is_unchanged() {
+        let jobs = snapshot_jobs();
+        let mut out = Vec::new();
+        print_csv(&snapshot_data(&jobs), true, &snapshot_columns(), &mut out).unwrap();
+        insta::assert_snapshot!(String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn google_sheets_payload_is_unchanged() {
+        let jobs = snapshot_jobs();
+        let spreadsheet = report_spreadsheet(
+            &snapshot_data(&jobs),
+            true,
+            Timestamp::UNIX_EPOCH,
+            &snapshot_columns(),
+        );
+        insta::assert_snapshot!(serde_json::to_string_pretty(&spreadsheet).unwrap());
+    }
 }
@@ -0,0 +1,289 @@
+use std::{collections::BTreeMap, io::Write};
+
+use chrono::Utc;
+
+use crate::{
+    apis::{
+        google_sheets::{
+            self,
+            spreadsheet::{
+                mk_row, ExtendedValue, GridData, Sheet, SheetProperties, Spreadsheet,
+                SpreadsheetProperties,
+            },
+        },
+        job_nimbus,
+    },
+    jobs::{self, AnalyzedJob, Job, Milestone},
+    subcommands::output::{OutputArgs, OutputFormat},
+    utils::{self, percent_or_na},
+};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The JobNimbus API key. This key will be cached.
+    #[arg(long, default_value = None, global = true, env)]
+    jn_api_key: Option<String>,
+
+    /// The filter to use when query JobNimbus for jobs, using ElasticSearch
+    /// syntax.
+    #[arg(short, long = "filter", default_value = None)]
+    filter_filename: Option<String>,
+
+    /// How to group the jobs into areas.
+    #[arg(long, value_enum, default_value = "zip")]
+    by: AreaKind,
+
+    /// The format in which to print the output.
+    #[arg(long, value_enum, default_value = "google-sheets")]
+    format: OutputFormat,
+
+    #[command(flatten)]
+    output_args: OutputArgs,
+}
+
+#[derive(Debug, clap::ValueEnum, Clone, Copy, Eq, PartialEq)]
+enum AreaKind {
+    /// Groups jobs by zip code.
+    Zip,
+    /// Groups jobs by city and state.
+    City,
+}
+
+impl AreaKind {
+    fn header(self) -> &'static str {
+        match self {
+            AreaKind::Zip => "Zip Code",
+            AreaKind::City => "City",
+        }
+    }
+
+    /// The area that the job is in, or `None` if the job's address doesn't say.
+    fn area_of(self, job: &Job) -> Option<String> {
+        match self {
+            AreaKind::Zip => job.zip.as_deref().map(|zip| zip.trim().to_owned()),
+            AreaKind::City => {
+                let city = job.city.as_deref()?.trim();
+                Some(match job.state.as_deref() {
+                    Some(state) => format!("{}, {}", city, state.trim()),
+                    None => city.to_owned(),
+                })
+            }
+        }
+    }
+}
+
+/// Statistics about the jobs in a single area.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct AreaStats {
+    /// The number of jobs in this area.
+    pub num_jobs: usize,
+    /// The number of jobs that had an appointment.
+    pub num_appointments: usize,
+    /// The number of jobs that signed a contract.
+    pub num_contracts: usize,
+    /// The total estimate amount of jobs that signed a contract, in cents.
+    pub revenue: i64,
+}
+
+impl AreaStats {
+    /// The rate at which appointments turn into contracts. `None` if no jobs
+    /// had an appointment.
+    pub fn close_rate(&self) -> Option<f64> {
+        if self.num_appointments == 0 {
+            None
+        } else {
+            Some(self.num_contracts as f64 / self.num_appointments as f64)
+        }
+    }
+}
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let Args { jn_api_key, filter_filename, by, format, output_args } = args;
+
+    let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+    output_args.validate(format == OutputFormat::GoogleSheets);
+
+    let jobs = job_nimbus::get_all_jobs_with_filter_file(&jn_api_key, filter_filename.as_deref())?;
+
+    let analyzed: Vec<AnalyzedJob> = jobs.into_iter().map(|job| jobs::analyze_job(job).0).collect();
+    let results = calculate_area_stats(&analyzed, by);
+
+    let output_writer = output_args.writer()?;
+
+    match format {
+        OutputFormat::Human => print_human(&results, output_writer)?,
+        OutputFormat::Csv => print_csv(&results, by, output_writer)?,
+        OutputFormat::GoogleSheets => {
+            generate_report_google_sheets(&results, by, !output_args.new)?
+        }
+    }
+
+    Ok(())
+}
+
+/// Groups the jobs by area and calculates statistics for each area, ordered
+/// from the area with the most jobs to the area with the fewest. Jobs whose
+/// area is unknown are grouped under `None`. Jobs that failed analysis only
+/// count towards the number of jobs.
+fn calculate_area_stats<'a>(
+    jobs: impl IntoIterator<Item = &'a AnalyzedJob>,
+    by: AreaKind,
+) -> Vec<(Option<String>, AreaStats)> {
+    let appointment = Milestone::AppointmentMade.into_int();
+    let contract = Milestone::ContractSigned.into_int();
+
+    let mut results: BTreeMap<Option<String>, AreaStats> = BTreeMap::new();
+    for AnalyzedJob { job, analysis } in jobs {
+        let stats = results.entry(by.area_of(job)).or_default();
+        stats.num_jobs += 1;
+        let Some(analysis) = analysis else {
+            continue;
+        };
+        if analysis.timestamps.len() > appointment {
+            stats.num_appointments += 1;
+        }
+        if analysis.timestamps.len() > contract {
+            stats.num_contracts += 1;
+            stats.revenue += job.amt_estimate as i64;
+        }
+    }
+
+    let mut results: Vec<_> = results.into_iter().collect();
+    // stable sort, so areas with the same number of jobs stay in name order
+    results.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.num_jobs));
+    results
+}
+
+fn area_name(area: &Option<String>) -> &str {
+    area.as_deref().unwrap_or("Unknown")
+}
+
+fn print_human(
+    results: &[(Option<String>, AreaStats)],
+    mut writer: impl Write,
+) -> std::io::Result<()> {
+    for (area, stats) in results {
+        writeln!(
+            writer,
+            "{}: {} jobs | {} appointments | {} contracts | Close Rate {} | Revenue ${:.2}",
+            area_name(area),
+            stats.num_jobs,
+            stats.num_appointments,
+            stats.num_contracts,
+            percent_or_na(stats.close_rate()),
+            stats.revenue as f64 / 100.0,
+        )?;
+    }
+    Ok(())
+}
+
+fn print_csv(
+    results: &[(Option<String>, AreaStats)],
+    by: AreaKind,
+    writer: impl Write,
+) -> std::io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record([
+        by.header(),
+        "Jobs",
+        "Appointments",
+        "Contracts",
+        "Close Rate",
+        "Revenue",
+    ])?;
+    for (area, stats) in results {
+        writer.write_record([
+            area_name(area),
+            &stats.num_jobs.to_string(),
+            &stats.num_appointments.to_string(),
+            &stats.num_contracts.to_string(),
+            &percent_or_na(stats.close_rate()),
+            &(stats.revenue as f64 / 100.0).to_string(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn generate_report_google_sheets(
+    results: &[(Option<String>, AreaStats)],
+    by: AreaKind,
+    update: bool,
+) -> anyhow::Result<()> {
+    let mut rows = Vec::new();
+    rows.push(mk_row([
+        ExtendedValue::StringValue(by.header().to_string()),
+        ExtendedValue::StringValue("Jobs".to_string()),
+        ExtendedValue::StringValue("Appointments".to_string()),
+        ExtendedValue::StringValue("Contracts".to_string()),
+        ExtendedValue::StringValue("Close Rate".to_string()),
+        ExtendedValue::StringValue("Revenue".to_string()),
+    ]));
+    for (area, stats) in results {
+        rows.push(mk_row([
+            ExtendedValue::StringValue(area_name(area).to_owned()),
+            ExtendedValue::NumberValue(stats.num_jobs as f64),
+            ExtendedValue::NumberValue(stats.num_appointments as f64),
+            ExtendedValue::NumberValue(stats.num_contracts as f64),
+            ExtendedValue::StringValue(percent_or_na(stats.close_rate())),
+            ExtendedValue::NumberValue(stats.revenue as f64 / 100.0),
+        ]));
+    }
+
+    let spreadsheet = Spreadsheet {
+        properties: SpreadsheetProperties { title: Some(format!("Area Report ({})", Utc::now())) },
+        sheets: Some(vec![Sheet {
+            properties: SheetProperties {
+                title: Some(format!("By {}", by.header())),
+                ..Default::default()
+            },
+            data: Some(GridData { start_row: 0, start_column: 0, row_data: rows }),
+        }]),
+        ..Default::default()
+    };
+
+    let url = google_sheets::upload_spreadsheet_blocking(
+        google_sheets::SheetNickname::Areas,
+        spreadsheet,
+        update,
+    )?;
+    utils::open_url(url.as_str());
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jobs::{test::make_job, Timestamp};
+
+    #[test]
+    fn area_stats_by_city() {
+        fn job(city: Option<&str>, contracted: bool, amt_estimate: i32) -> AnalyzedJob {
+            let date = Some(Timestamp::UNIX_EPOCH);
+            let mut job =
+                make_job(false, date, None, contracted.then_some(date).flatten(), None, None);
+            job.city = city.map(str::to_owned);
+            job.state = Some("IL".to_owned());
+            job.amt_estimate = amt_estimate;
+            jobs::analyze_job(job).0
+        }
+
+        let jobs = [
+            job(Some("Springfield"), true, 1000),
+            job(Some("Peoria"), false, 500),
+            job(Some("Springfield"), false, 700),
+            job(None, true, 300),
+        ];
+        let results = calculate_area_stats(&jobs, AreaKind::City);
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].0.as_deref(), Some("Springfield, IL"));
+        assert_eq!(
+            results[0].1,
+            AreaStats { num_jobs: 2, num_appointments: 2, num_contracts: 1, revenue: 1000 }
+        );
+        assert_eq!(results[0].1.close_rate(), Some(0.5));
+        assert_eq!(results[1].0, None);
+        assert_eq!(results[2].0.as_deref(), Some("Peoria, IL"));
+    }
+}
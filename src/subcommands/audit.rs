@@ -0,0 +1,339 @@
+use std::{collections::BTreeMap, fmt::Display, io::Write};
+
+use chrono::Utc;
+
+use crate::{
+    apis::{
+        google_sheets::{
+            self,
+            spreadsheet::{
+                mk_row, ExtendedValue, GridData, RowData, Sheet, SheetProperties, Spreadsheet,
+                SpreadsheetProperties,
+            },
+        },
+        job_nimbus,
+    },
+    jobs::{Job, Milestone},
+    subcommands::output::OutputArgs,
+    utils::{self, percent_or_na},
+};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The JobNimbus API key. This key will be cached.
+    #[arg(long, default_value = None, global = true, env)]
+    jn_api_key: Option<String>,
+
+    /// The filter to use when query JobNimbus for jobs, using ElasticSearch
+    /// syntax.
+    #[arg(short, long = "filter", default_value = None)]
+    filter_filename: Option<String>,
+
+    /// The format in which to print the output.
+    #[arg(long, value_enum, default_value = "google-sheets")]
+    format: OutputFormat,
+
+    #[command(flatten)]
+    output_args: OutputArgs,
+}
+
+#[derive(Debug, clap::ValueEnum, Clone, Copy, Eq, PartialEq)]
+enum OutputFormat {
+    /// Prints a human-readable report into the output file.
+    Human,
+    /// Prints a CSV file of all incomplete jobs into the output file.
+    Csv,
+    /// Outputs a Google Sheet on the user's Google Drive (requires OAuth
+    /// authorization).
+    GoogleSheets,
+}
+
+/// A required field (or set of fields) that a job is checked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Check {
+    SalesRep,
+    JobNumber,
+    /// At least one milestone date must be set.
+    Dates,
+    /// Insurance jobs must have a claim number.
+    ClaimNumber,
+}
+impl Check {
+    const ALL: [Check; 4] = [Check::SalesRep, Check::JobNumber, Check::Dates, Check::ClaimNumber];
+
+    /// Whether the check applies to the specified job.
+    fn applies_to(self, job: &Job) -> bool {
+        match self {
+            Check::ClaimNumber => job.insurance_checkbox,
+            _ => true,
+        }
+    }
+
+    /// Whether the specified job passes the check.
+    fn passes(self, job: &Job) -> bool {
+        match self {
+            Check::SalesRep => job.sales_rep.is_some(),
+            Check::JobNumber => job.job_number.is_some(),
+            Check::Dates => {
+                Milestone::ordered_iter().skip(1).any(|ms| job.milestone_dates[ms].is_some())
+                    || job.milestone_dates.loss_date.is_some()
+            }
+            Check::ClaimNumber => job.insurance_claim_number.is_some(),
+        }
+    }
+}
+impl Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Check::SalesRep => write!(f, "Missing Sales Rep"),
+            Check::JobNumber => write!(f, "Missing Job Number"),
+            Check::Dates => write!(f, "Missing All Dates"),
+            Check::ClaimNumber => write!(f, "Missing Claim Number"),
+        }
+    }
+}
+
+struct JobAudit<'a> {
+    job: &'a Job,
+    num_applicable: usize,
+    failed: Vec<Check>,
+}
+
+impl JobAudit<'_> {
+    fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+fn audit_job(job: &Job) -> JobAudit<'_> {
+    let applicable = Check::ALL.into_iter().filter(|check| check.applies_to(job));
+    let mut num_applicable = 0;
+    let mut failed = Vec::new();
+    for check in applicable {
+        num_applicable += 1;
+        if !check.passes(job) {
+            failed.push(check);
+        }
+    }
+    JobAudit { job, num_applicable, failed }
+}
+
+/// Completeness statistics for a group of jobs.
+#[derive(Debug, Default)]
+struct AuditStats {
+    num_jobs: usize,
+    num_complete_jobs: usize,
+    num_checks: usize,
+    num_passed_checks: usize,
+}
+
+impl AuditStats {
+    fn add_job(&mut self, audit: &JobAudit) {
+        self.num_jobs += 1;
+        if audit.is_complete() {
+            self.num_complete_jobs += 1;
+        }
+        self.num_checks += audit.num_applicable;
+        self.num_passed_checks += audit.num_applicable - audit.failed.len();
+    }
+
+    /// The fraction of all applicable checks that passed.
+    fn field_completeness(&self) -> Option<f64> {
+        if self.num_checks == 0 {
+            None
+        } else {
+            Some(self.num_passed_checks as f64 / self.num_checks as f64)
+        }
+    }
+
+    /// The fraction of jobs that passed all checks.
+    fn job_completeness(&self) -> Option<f64> {
+        if self.num_jobs == 0 {
+            None
+        } else {
+            Some(self.num_complete_jobs as f64 / self.num_jobs as f64)
+        }
+    }
+}
+
+struct AuditData<'a> {
+    /// The audits of the jobs that failed at least one check.
+    incomplete: Vec<JobAudit<'a>>,
+    total: AuditStats,
+    by_rep: BTreeMap<Option<&'a str>, AuditStats>,
+}
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let Args { jn_api_key, filter_filename, format, output_args } = args;
+
+    let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+    output_args.validate(format == OutputFormat::GoogleSheets);
+
+    let jobs = job_nimbus::get_all_jobs_with_filter_file(&jn_api_key, filter_filename.as_deref())?;
+
+    let mut results =
+        AuditData { incomplete: Vec::new(), total: Default::default(), by_rep: BTreeMap::new() };
+    for job in &jobs {
+        let audit = audit_job(job);
+        results.total.add_job(&audit);
+        results.by_rep.entry(job.sales_rep.as_deref()).or_default().add_job(&audit);
+        if !audit.is_complete() {
+            results.incomplete.push(audit);
+        }
+    }
+
+    let output_writer = output_args.writer()?;
+
+    match format {
+        OutputFormat::Human => print_human(&results, output_writer)?,
+        OutputFormat::Csv => print_csv(&results, output_writer)?,
+        OutputFormat::GoogleSheets => generate_report_google_sheets(&results, !output_args.new)?,
+    }
+
+    Ok(())
+}
+
+fn failed_checks(audit: &JobAudit) -> String {
+    audit.failed.iter().map(|check| check.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+fn print_human(results: &AuditData, mut writer: impl Write) -> std::io::Result<()> {
+    let write_stats = |writer: &mut dyn Write, name: &str, stats: &AuditStats| {
+        writeln!(
+            writer,
+            "{}: {} of {} jobs complete ({}) | {} of fields complete",
+            name,
+            stats.num_complete_jobs,
+            stats.num_jobs,
+            percent_or_na(stats.job_completeness()),
+            percent_or_na(stats.field_completeness()),
+        )
+    };
+
+    write_stats(&mut writer, "Total", &results.total)?;
+    writeln!(writer, "By sales rep:")?;
+    for (rep, stats) in &results.by_rep {
+        write_stats(&mut writer, &format!("    - {}", rep.unwrap_or("Unknown Sales Rep")), stats)?;
+    }
+
+    writeln!(writer, "Incomplete jobs:")?;
+    for audit in &results.incomplete {
+        writeln!(
+            writer,
+            "    - {} (#{}): {}",
+            audit.job.job_name.as_deref().unwrap_or(""),
+            audit.job.job_number.as_deref().unwrap_or(&audit.job.jnid),
+            failed_checks(audit)
+        )?;
+    }
+
+    Ok(())
+}
+
+fn print_csv(results: &AuditData, writer: impl Write) -> std::io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record(["Job Name", "Sales Rep", "Job Number", "JNID", "Problems"])?;
+    for audit in &results.incomplete {
+        let job = audit.job;
+        writer.write_record([
+            job.job_name.as_deref().unwrap_or(""),
+            job.sales_rep.as_deref().unwrap_or("Unknown Sales Rep"),
+            job.job_number.as_deref().unwrap_or(""),
+            &job.jnid,
+            &failed_checks(audit),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn generate_report_google_sheets(results: &AuditData, update: bool) -> anyhow::Result<()> {
+    fn mk_stats_row(name: &str, stats: &AuditStats) -> RowData {
+        mk_row([
+            ExtendedValue::StringValue(name.to_owned()),
+            ExtendedValue::NumberValue(stats.num_jobs as f64),
+            ExtendedValue::NumberValue(stats.num_complete_jobs as f64),
+            ExtendedValue::StringValue(percent_or_na(stats.job_completeness())),
+            ExtendedValue::StringValue(percent_or_na(stats.field_completeness())),
+        ])
+    }
+
+    let mut rep_rows = vec![mk_row([
+        ExtendedValue::StringValue("Sales Rep".to_string()),
+        ExtendedValue::StringValue("Jobs".to_string()),
+        ExtendedValue::StringValue("Complete Jobs".to_string()),
+        ExtendedValue::StringValue("Job Completeness".to_string()),
+        ExtendedValue::StringValue("Field Completeness".to_string()),
+    ])];
+    for (rep, stats) in &results.by_rep {
+        rep_rows.push(mk_stats_row(rep.unwrap_or("Unknown Sales Rep"), stats));
+    }
+    rep_rows.push(mk_stats_row("Total", &results.total));
+
+    let mut job_rows = vec![mk_row([
+        ExtendedValue::StringValue("Job Name".to_string()),
+        ExtendedValue::StringValue("Sales Rep".to_string()),
+        ExtendedValue::StringValue("Job Number".to_string()),
+        ExtendedValue::StringValue("JNID".to_string()),
+        ExtendedValue::StringValue("Problems".to_string()),
+    ])];
+    for audit in &results.incomplete {
+        let job = audit.job;
+        job_rows.push(mk_row([
+            ExtendedValue::StringValue(job.job_name.clone().unwrap_or_default()),
+            ExtendedValue::StringValue(
+                job.sales_rep.clone().unwrap_or_else(|| "Unknown Sales Rep".to_owned()),
+            ),
+            ExtendedValue::StringValue(job.job_number.clone().unwrap_or_default()),
+            ExtendedValue::StringValue(job.jnid.clone()),
+            ExtendedValue::StringValue(failed_checks(audit)),
+        ]));
+    }
+
+    let sheets = [("By Rep", rep_rows), ("Incomplete Jobs", job_rows)]
+        .into_iter()
+        .map(|(title, rows)| Sheet {
+            properties: SheetProperties { title: Some(title.to_string()), ..Default::default() },
+            data: Some(GridData { start_row: 0, start_column: 0, row_data: rows }),
+        })
+        .collect();
+
+    let spreadsheet = Spreadsheet {
+        properties: SpreadsheetProperties {
+            title: Some(format!("Data Completeness Audit ({})", Utc::now())),
+        },
+        sheets: Some(sheets),
+        ..Default::default()
+    };
+
+    let url = google_sheets::upload_spreadsheet_blocking(
+        google_sheets::SheetNickname::Audit,
+        spreadsheet,
+        update,
+    )?;
+    utils::open_url(url.as_str());
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jobs::{test::make_job, Timestamp};
+
+    #[test]
+    fn audit_jobs() {
+        let mut job = make_job(true, None, None, None, None, None);
+        job.sales_rep = Some("Alice".to_owned());
+        job.job_number = Some("100".to_owned());
+        job.insurance_claim_number = None;
+        let audit = audit_job(&job);
+        assert_eq!(audit.num_applicable, 4);
+        assert_eq!(audit.failed, vec![Check::Dates, Check::ClaimNumber]);
+
+        let mut job = make_job(false, None, Some(Timestamp::UNIX_EPOCH), None, None, None);
+        job.sales_rep = Some("Alice".to_owned());
+        let audit = audit_job(&job);
+        assert_eq!(audit.num_applicable, 3);
+        assert_eq!(audit.failed, vec![Check::JobNumber]);
+    }
+}
@@ -0,0 +1,324 @@
+use std::{collections::BTreeMap, io::Write};
+
+use chrono::{Datelike as _, NaiveDate, TimeDelta, Utc};
+use tracing::warn;
+
+use crate::{
+    apis::{
+        google_sheets::{
+            self,
+            spreadsheet::{
+                mk_row, ExtendedValue, GridData, Sheet, SheetProperties, Spreadsheet,
+                SpreadsheetProperties,
+            },
+        },
+        job_nimbus,
+    },
+    jobs::{Job, Timestamp},
+    subcommands::output::OutputArgs,
+    utils,
+};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The JobNimbus API key. This key will be cached.
+    #[arg(long, default_value = None, global = true, env)]
+    jn_api_key: Option<String>,
+
+    /// The filter to use when query JobNimbus for jobs, using ElasticSearch
+    /// syntax.
+    #[arg(short, long = "filter", default_value = None)]
+    filter_filename: Option<String>,
+
+    /// The maximum number of installs that the crews can handle in a single
+    /// week. Weeks with more scheduled installs than this are flagged. If
+    /// unspecified, no weeks are flagged.
+    #[arg(long)]
+    crew_capacity: Option<usize>,
+
+    /// The format in which to print the output.
+    #[arg(long, value_enum, default_value = "google-sheets")]
+    format: OutputFormat,
+
+    #[command(flatten)]
+    output_args: OutputArgs,
+}
+
+#[derive(Debug, clap::ValueEnum, Clone, Copy, Eq, PartialEq)]
+enum OutputFormat {
+    /// Prints a human-readable report into the output file.
+    Human,
+    /// Prints a CSV file of all backlogged jobs into the output file.
+    Csv,
+    /// Outputs a Google Sheet on the user's Google Drive (requires OAuth
+    /// authorization).
+    GoogleSheets,
+}
+
+/// Jobs that have signed a contract but have not been installed yet.
+struct BacklogData<'a> {
+    /// All backlogged jobs, in order of install date. Jobs without an install
+    /// date come last.
+    jobs: Vec<&'a Job>,
+    /// The number and total value (in cents) of backlogged jobs, keyed by the
+    /// Monday of the week in which they are scheduled to be installed. `None`
+    /// holds the jobs that don't have an install date yet.
+    by_week: BTreeMap<Option<NaiveDate>, (usize, i64)>,
+    /// The number and total value (in cents) of backlogged jobs, keyed by
+    /// branch.
+    by_branch: BTreeMap<Option<&'a str>, (usize, i64)>,
+    /// The weekly install capacity that the schedule was checked against.
+    crew_capacity: Option<usize>,
+}
+
+impl BacklogData<'_> {
+    fn over_capacity(&self, scheduled: usize) -> bool {
+        self.crew_capacity.is_some_and(|capacity| scheduled > capacity)
+    }
+}
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let Args { jn_api_key, filter_filename, crew_capacity, format, output_args } = args;
+
+    let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+    output_args.validate(format == OutputFormat::GoogleSheets);
+
+    let jobs = job_nimbus::get_all_jobs_with_filter_file(&jn_api_key, filter_filename.as_deref())?;
+
+    let results = calculate_backlog(&jobs, Utc::now(), crew_capacity);
+    for (week, (scheduled, _)) in &results.by_week {
+        if let Some(week) = week {
+            if results.over_capacity(*scheduled) {
+                warn!(
+                    "{} installs are scheduled for the week of {}, which exceeds the crew \
+                     capacity of {}",
+                    scheduled,
+                    week,
+                    crew_capacity.expect("a week can only be over capacity if there is a capacity")
+                );
+            }
+        }
+    }
+
+    let output_writer = output_args.writer()?;
+
+    match format {
+        OutputFormat::Human => print_human(&results, output_writer)?,
+        OutputFormat::Csv => print_csv(&results, output_writer)?,
+        OutputFormat::GoogleSheets => generate_report_google_sheets(&results, !output_args.new)?,
+    }
+
+    Ok(())
+}
+
+fn calculate_backlog(
+    jobs: &[Job],
+    now: Timestamp,
+    crew_capacity: Option<usize>,
+) -> BacklogData<'_> {
+    let mut backlog: Vec<&Job> = jobs
+        .iter()
+        .filter(|job| {
+            let dates = &job.milestone_dates;
+            dates.contract_date.is_some()
+                && dates.loss_date.is_none()
+                && dates.install_date.is_none_or(|date| date > now)
+        })
+        .collect();
+    // sort by install date, putting the jobs without an install date last
+    backlog.sort_by_key(|job| {
+        let install_date = job.milestone_dates.install_date;
+        (install_date.is_none(), install_date)
+    });
+
+    let mut by_week = BTreeMap::new();
+    let mut by_branch = BTreeMap::new();
+    for job in &backlog {
+        let week = job.milestone_dates.install_date.map(|date| week_of(date.date_naive()));
+        let (count, value) = by_week.entry(week).or_insert((0, 0));
+        *count += 1;
+        *value += job.amt_estimate as i64;
+
+        let (count, value) = by_branch.entry(job.branch.as_deref()).or_insert((0, 0));
+        *count += 1;
+        *value += job.amt_estimate as i64;
+    }
+
+    BacklogData { jobs: backlog, by_week, by_branch, crew_capacity }
+}
+
+/// Returns the Monday of the week containing the specified date.
+fn week_of(date: NaiveDate) -> NaiveDate {
+    date - TimeDelta::days(date.weekday().num_days_from_monday().into())
+}
+
+fn week_name(week: Option<NaiveDate>) -> String {
+    match week {
+        Some(week) => format!("Week of {}", week),
+        None => "Unscheduled".to_owned(),
+    }
+}
+
+fn print_human(results: &BacklogData, mut writer: impl Write) -> std::io::Result<()> {
+    let total_value: i64 = results.by_week.values().map(|(_, value)| value).sum();
+    writeln!(writer, "Backlog: {} jobs, ${:.2}", results.jobs.len(), total_value as f64 / 100.0)?;
+
+    writeln!(writer, "By scheduled install week:")?;
+    for (week, (count, value)) in &results.by_week {
+        let warning =
+            if week.is_some() && results.over_capacity(*count) { " (OVER CAPACITY)" } else { "" };
+        writeln!(
+            writer,
+            "    - {}: {} jobs, ${:.2}{}",
+            week_name(*week),
+            count,
+            *value as f64 / 100.0,
+            warning
+        )?;
+    }
+
+    writeln!(writer, "By branch:")?;
+    for (branch, (count, value)) in &results.by_branch {
+        writeln!(
+            writer,
+            "    - {}: {} jobs, ${:.2}",
+            branch.unwrap_or("Unknown Branch"),
+            count,
+            *value as f64 / 100.0
+        )?;
+    }
+
+    writeln!(writer, "Jobs:")?;
+    for job in &results.jobs {
+        let name = job.job_name.as_deref().unwrap_or("");
+        let number = job.job_number.as_deref().unwrap_or("Unknown Job Number");
+        let install_date = job
+            .milestone_dates
+            .install_date
+            .map(|date| date.date_naive().to_string())
+            .unwrap_or_else(|| "unscheduled".to_owned());
+        writeln!(
+            writer,
+            "    - {} (#{}): ${:.2} (install {}, branch {}, assigned to {})",
+            name,
+            number,
+            job.amt_estimate as f64 / 100.0,
+            install_date,
+            job.branch.as_deref().unwrap_or("unknown"),
+            job.sales_rep.as_deref().unwrap_or("Unknown Sales Rep")
+        )?;
+    }
+
+    Ok(())
+}
+
+fn print_csv(results: &BacklogData, writer: impl Write) -> std::io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record([
+        "Job Name",
+        "Sales Rep",
+        "Job Number",
+        "Branch",
+        "Contract Date",
+        "Install Week",
+        "Install Date",
+        "Value",
+    ])?;
+    for job in &results.jobs {
+        let dates = &job.milestone_dates;
+        writer.write_record([
+            job.job_name.as_deref().unwrap_or(""),
+            job.sales_rep.as_deref().unwrap_or("Unknown Salesman"),
+            job.job_number.as_deref().unwrap_or("Unknown Job Number"),
+            job.branch.as_deref().unwrap_or("Unknown Branch"),
+            &dates.contract_date.map(|date| date.date_naive().to_string()).unwrap_or_default(),
+            &week_name(dates.install_date.map(|date| week_of(date.date_naive()))),
+            &dates.install_date.map(|date| date.date_naive().to_string()).unwrap_or_default(),
+            &(job.amt_estimate as f64 / 100.0).to_string(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn generate_report_google_sheets(results: &BacklogData, update: bool) -> anyhow::Result<()> {
+    let mut week_rows = vec![mk_row([
+        ExtendedValue::StringValue("Week".to_string()),
+        ExtendedValue::StringValue("Jobs".to_string()),
+        ExtendedValue::StringValue("Value".to_string()),
+        ExtendedValue::StringValue("Over Capacity".to_string()),
+    ])];
+    for (week, (count, value)) in &results.by_week {
+        week_rows.push(mk_row([
+            ExtendedValue::StringValue(week_name(*week)),
+            ExtendedValue::NumberValue(*count as f64),
+            ExtendedValue::NumberValue(*value as f64 / 100.0),
+            ExtendedValue::BoolValue(week.is_some() && results.over_capacity(*count)),
+        ]));
+    }
+
+    let mut branch_rows = vec![mk_row([
+        ExtendedValue::StringValue("Branch".to_string()),
+        ExtendedValue::StringValue("Jobs".to_string()),
+        ExtendedValue::StringValue("Value".to_string()),
+    ])];
+    for (branch, (count, value)) in &results.by_branch {
+        branch_rows.push(mk_row([
+            ExtendedValue::StringValue(branch.unwrap_or("Unknown Branch").to_owned()),
+            ExtendedValue::NumberValue(*count as f64),
+            ExtendedValue::NumberValue(*value as f64 / 100.0),
+        ]));
+    }
+
+    let mut job_rows = vec![mk_row([
+        ExtendedValue::StringValue("Job Name".to_string()),
+        ExtendedValue::StringValue("Job Salesman".to_string()),
+        ExtendedValue::StringValue("Job Number".to_string()),
+        ExtendedValue::StringValue("Branch".to_string()),
+        ExtendedValue::StringValue("Install Date".to_string()),
+        ExtendedValue::StringValue("Value".to_string()),
+    ])];
+    for job in &results.jobs {
+        job_rows.push(mk_row([
+            ExtendedValue::StringValue(job.job_name.clone().unwrap_or_default()),
+            ExtendedValue::StringValue(
+                job.sales_rep.clone().unwrap_or_else(|| "Unknown Salesman".to_owned()),
+            ),
+            ExtendedValue::StringValue(
+                job.job_number.clone().unwrap_or_else(|| "Unknown Job Number".to_owned()),
+            ),
+            ExtendedValue::StringValue(
+                job.branch.clone().unwrap_or_else(|| "Unknown Branch".to_owned()),
+            ),
+            match job.milestone_dates.install_date {
+                Some(date) => ExtendedValue::DateValue(date.date_naive()),
+                None => ExtendedValue::StringValue("Unscheduled".to_owned()),
+            },
+            ExtendedValue::NumberValue(job.amt_estimate as f64 / 100.0),
+        ]));
+    }
+
+    let sheets = [("By Week", week_rows), ("By Branch", branch_rows), ("Jobs", job_rows)]
+        .into_iter()
+        .map(|(title, rows)| Sheet {
+            properties: SheetProperties { title: Some(title.to_string()), ..Default::default() },
+            data: Some(GridData { start_row: 0, start_column: 0, row_data: rows }),
+        })
+        .collect();
+
+    let spreadsheet = Spreadsheet {
+        properties: SpreadsheetProperties {
+            title: Some(format!("Production Backlog Report ({})", Utc::now())),
+        },
+        sheets: Some(sheets),
+        ..Default::default()
+    };
+
+    let url = google_sheets::upload_spreadsheet_blocking(
+        google_sheets::SheetNickname::Backlog,
+        spreadsheet,
+        update,
+    )?;
+    utils::open_url(url.as_str());
+    Ok(())
+}
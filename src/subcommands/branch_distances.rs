@@ -0,0 +1,288 @@
+use std::{collections::BTreeMap, io::Write};
+
+use chrono::Utc;
+
+use crate::{
+    apis::{
+        google_sheets::{
+            self,
+            spreadsheet::{
+                mk_row, ExtendedValue, GridData, Sheet, SheetProperties, Spreadsheet,
+                SpreadsheetProperties,
+            },
+        },
+        job_nimbus,
+    },
+    branches::Branches,
+    geocode_cache::GeocodeCache,
+    jobs::{Geo, Job},
+    subcommands::geo,
+    subcommands::output::{OutputArgs, OutputFormat},
+    utils,
+};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The JobNimbus API key. This key will be cached.
+    #[arg(long, default_value = None, global = true, env)]
+    jn_api_key: Option<String>,
+
+    /// The distance from its branch office, in miles, beyond which a job is
+    /// flagged as outside the service radius.
+    #[arg(long, default_value = "50")]
+    radius: f64,
+
+    /// The filter to use when query JobNimbus for jobs, using ElasticSearch
+    /// syntax.
+    #[arg(short, long = "filter", default_value = None)]
+    filter_filename: Option<String>,
+
+    /// The format in which to print the output.
+    #[arg(long, value_enum, default_value = "google-sheets")]
+    format: OutputFormat,
+
+    #[command(flatten)]
+    output_args: OutputArgs,
+}
+
+/// The distance of a job from its branch office.
+#[derive(Debug, PartialEq)]
+pub struct JobDistance<'a> {
+    pub job: &'a Job,
+    pub branch: &'a str,
+    pub miles: f64,
+    pub outside_radius: bool,
+}
+
+/// Statistics about the distances of a single sales rep's jobs from their
+/// branch offices.
+#[derive(Debug, Default, PartialEq)]
+pub struct RepDistanceStats {
+    /// The number of jobs whose distance is known.
+    pub num_jobs: usize,
+    /// The cumulative distance of the jobs from their branch offices, in miles.
+    pub cum_miles: f64,
+    /// The number of jobs outside the service radius.
+    pub num_outside_radius: usize,
+}
+
+impl RepDistanceStats {
+    /// The average distance of the jobs from their branch offices, in miles.
+    /// Zero if there are no jobs.
+    pub fn average_miles(&self) -> f64 {
+        if self.num_jobs == 0 {
+            0.0
+        } else {
+            self.cum_miles / self.num_jobs as f64
+        }
+    }
+}
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let Args { jn_api_key, radius, filter_filename, format, output_args } = args;
+
+    let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+    output_args.validate(format == OutputFormat::GoogleSheets);
+
+    let branches = Branches::load();
+    if branches.is_empty() {
+        anyhow::bail!("No branch offices are configured; add their coordinates to branches.json");
+    }
+
+    let jobs = job_nimbus::get_all_jobs_with_filter_file(&jn_api_key, filter_filename.as_deref())?;
+
+    let locations = geo::locate_jobs(&jobs, &GeocodeCache::load());
+    let distances = calculate_distances(&locations, &branches, radius);
+    let rep_stats = calculate_rep_stats(&distances);
+
+    let output_writer = output_args.writer()?;
+
+    match format {
+        OutputFormat::Human => print_human(&distances, &rep_stats, radius, output_writer)?,
+        OutputFormat::Csv => print_csv(&distances, output_writer)?,
+        OutputFormat::GoogleSheets => {
+            let spreadsheet = Spreadsheet {
+                properties: SpreadsheetProperties {
+                    title: Some(format!("Branch Distance Report ({})", Utc::now())),
+                },
+                sheets: Some(vec![rep_stats_sheet(&rep_stats), distances_sheet(&distances)]),
+                ..Default::default()
+            };
+            let url = google_sheets::upload_spreadsheet_blocking(
+                google_sheets::SheetNickname::BranchDistances,
+                spreadsheet,
+                !output_args.new,
+            )?;
+            utils::open_url(url.as_str());
+        }
+    }
+
+    Ok(())
+}
+
+/// Calculates the distance of each job from its branch office. Jobs without
+/// coordinates, without a branch, or whose branch's location is unknown are
+/// skipped.
+fn calculate_distances<'a>(
+    locations: &[(&'a Job, Option<Geo>)],
+    branches: &Branches,
+    radius: f64,
+) -> Vec<JobDistance<'a>> {
+    locations
+        .iter()
+        .filter_map(|&(job, geo)| {
+            let branch = job.branch.as_deref()?;
+            let miles = geo?.distance_miles(&branches.location(branch)?);
+            Some(JobDistance { job, branch, miles, outside_radius: miles > radius })
+        })
+        .collect()
+}
+
+/// Groups the job distances by sales rep. Jobs without a sales rep are grouped
+/// under `None`.
+fn calculate_rep_stats(distances: &[JobDistance]) -> BTreeMap<Option<String>, RepDistanceStats> {
+    let mut results: BTreeMap<Option<String>, RepDistanceStats> = BTreeMap::new();
+    for distance in distances {
+        let stats = results.entry(distance.job.sales_rep.clone()).or_default();
+        stats.num_jobs += 1;
+        stats.cum_miles += distance.miles;
+        if distance.outside_radius {
+            stats.num_outside_radius += 1;
+        }
+    }
+    results
+}
+
+fn rep_name(rep: &Option<String>) -> &str {
+    rep.as_deref().unwrap_or("Unknown Sales Rep")
+}
+
+fn print_human(
+    distances: &[JobDistance],
+    rep_stats: &BTreeMap<Option<String>, RepDistanceStats>,
+    radius: f64,
+    mut writer: impl Write,
+) -> std::io::Result<()> {
+    for (rep, stats) in rep_stats {
+        writeln!(
+            writer,
+            "{}: Jobs {} | Avg Distance {:.1} mi | Outside Radius {}",
+            rep_name(rep),
+            stats.num_jobs,
+            stats.average_miles(),
+            stats.num_outside_radius,
+        )?;
+    }
+    writeln!(writer)?;
+    writeln!(writer, "Jobs more than {} miles from their branch:", radius)?;
+    for distance in distances.iter().filter(|distance| distance.outside_radius) {
+        writeln!(
+            writer,
+            "    {} ({}): {:.1} mi from {}",
+            distance.job.job_number.as_deref().unwrap_or("unknown job number"),
+            distance.job.job_name.as_deref().unwrap_or("unknown job name"),
+            distance.miles,
+            distance.branch,
+        )?;
+    }
+    Ok(())
+}
+
+const HEADERS: [&str; 6] =
+    ["Job Number", "Job Name", "Sales Rep", "Branch", "Distance (mi)", "Outside Radius"];
+
+fn print_csv(distances: &[JobDistance], writer: impl Write) -> std::io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record(HEADERS)?;
+    for distance in distances {
+        writer.write_record([
+            distance.job.job_number.as_deref().unwrap_or_default(),
+            distance.job.job_name.as_deref().unwrap_or_default(),
+            distance.job.sales_rep.as_deref().unwrap_or_default(),
+            distance.branch,
+            &format!("{:.1}", distance.miles),
+            if distance.outside_radius { "yes" } else { "no" },
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn rep_stats_sheet(rep_stats: &BTreeMap<Option<String>, RepDistanceStats>) -> Sheet {
+    let mut rows = Vec::new();
+    rows.push(mk_row([
+        ExtendedValue::StringValue("Sales Rep".to_string()),
+        ExtendedValue::StringValue("Jobs".to_string()),
+        ExtendedValue::StringValue("Avg Distance (mi)".to_string()),
+        ExtendedValue::StringValue("Outside Radius".to_string()),
+    ]));
+    for (rep, stats) in rep_stats {
+        rows.push(mk_row([
+            ExtendedValue::StringValue(rep_name(rep).to_owned()),
+            ExtendedValue::NumberValue(stats.num_jobs as f64),
+            ExtendedValue::NumberValue(stats.average_miles()),
+            ExtendedValue::NumberValue(stats.num_outside_radius as f64),
+        ]));
+    }
+
+    Sheet {
+        properties: SheetProperties { title: Some("Sales Reps".to_string()), ..Default::default() },
+        data: Some(GridData { start_row: 0, start_column: 0, row_data: rows }),
+    }
+}
+
+fn distances_sheet(distances: &[JobDistance]) -> Sheet {
+    let mut rows = Vec::new();
+    rows.push(mk_row(HEADERS.map(|header| ExtendedValue::StringValue(header.to_owned()))));
+    for distance in distances {
+        rows.push(mk_row([
+            ExtendedValue::StringValue(distance.job.job_number.clone().unwrap_or_default()),
+            ExtendedValue::StringValue(distance.job.job_name.clone().unwrap_or_default()),
+            ExtendedValue::StringValue(distance.job.sales_rep.clone().unwrap_or_default()),
+            ExtendedValue::StringValue(distance.branch.to_owned()),
+            ExtendedValue::NumberValue(distance.miles),
+            ExtendedValue::BoolValue(distance.outside_radius),
+        ]));
+    }
+
+    Sheet {
+        properties: SheetProperties { title: Some("Jobs".to_string()), ..Default::default() },
+        data: Some(GridData { start_row: 0, start_column: 0, row_data: rows }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jobs::test::make_job;
+
+    #[test]
+    fn distances_by_rep() {
+        let office = Geo { lat: 40.0, lon: -89.0 };
+        let branches = Branches::from_offices(BTreeMap::from([("Central".to_owned(), office)]));
+        fn job(branch: Option<&str>) -> Job {
+            let mut job = make_job(false, None, None, None, None, None);
+            job.branch = branch.map(str::to_owned);
+            job.sales_rep = Some("Jo".to_owned());
+            job
+        }
+        let jobs = [job(Some(" central")), job(Some("Central")), job(Some("North")), job(None)];
+        let locations = [
+            (&jobs[0], Some(office)),
+            (&jobs[1], Some(Geo { lat: 41.0, lon: -89.0 })),
+            (&jobs[2], Some(office)),
+            (&jobs[3], Some(office)),
+        ];
+
+        let distances = calculate_distances(&locations, &branches, 50.0);
+        assert_eq!(distances.len(), 2);
+        assert_eq!(distances[0].miles, 0.0);
+        assert!(!distances[0].outside_radius);
+        assert!(distances[1].outside_radius);
+
+        let stats = &calculate_rep_stats(&distances)[&Some("Jo".to_owned())];
+        assert_eq!(stats.num_jobs, 2);
+        assert_eq!(stats.num_outside_radius, 1);
+        assert_eq!(stats.average_miles(), distances[1].miles / 2.0);
+    }
+}
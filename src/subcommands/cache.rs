@@ -0,0 +1,99 @@
+use std::path::Path;
+
+use crate::{geocode_cache::GeocodeCache, run_summary, snapshots, storage};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Remove the least recently used snapshots until the cache is within its
+    /// limits (which also happens every time a snapshot is saved).
+    #[arg(long)]
+    prune: bool,
+}
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let Args { prune } = args;
+
+    // moves anything left in the working directory by older versions into the
+    // cache directory, so that it is included below
+    GeocodeCache::path();
+    snapshots::dir();
+
+    if prune {
+        let pruned = snapshots::prune()?;
+        println!("Removed {} snapshots", pruned);
+        run_summary::add_count("snapshots_pruned", pruned);
+    }
+
+    let dir = storage::cache_dir();
+    println!("Cache directory: {}", dir.display());
+    if !dir.is_dir() {
+        println!("The cache directory is empty");
+        return Ok(());
+    }
+    let mut entries = std::fs::read_dir(&dir)?
+        .map(|entry| {
+            entry.map(|entry| (entry.file_name().to_string_lossy().into_owned(), entry.path()))
+        })
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    let mut total = 0;
+    for (name, path) in entries {
+        let size = storage::size_of(&path)?;
+        total += size;
+        println!("{:>12}  {}", format_size(size), describe(&name, &path));
+    }
+    println!("{:>12}  total", format_size(total));
+
+    let limits = storage::CacheLimits::load();
+    let max_count = match limits.max_snapshots {
+        Some(max) => format!("{} snapshots", max),
+        None => "any number of snapshots".to_string(),
+    };
+    let max_size = match limits.max_snapshots_mb {
+        Some(mb) => format_size(mb.saturating_mul(1024 * 1024)),
+        None => "any size".to_string(),
+    };
+    println!("Snapshots are limited to {} totaling {}", max_count, max_size);
+    Ok(())
+}
+
+fn describe(name: &str, path: &Path) -> String {
+    if path.is_dir() {
+        let count = std::fs::read_dir(path).map(|entries| entries.count()).unwrap_or(0);
+        format!("{}/ ({} {})", name, count, if count == 1 { "file" } else { "files" })
+    } else {
+        name.to_string()
+    }
+}
+
+/// Formats a number of bytes with a binary unit, e.g. `1.5 MiB`.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    for next_unit in &UNITS[1..] {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next_unit;
+    }
+    format!("{:.1} {}", size, unit)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sizes_are_formatted_with_binary_units() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024 * 1024), "5.0 GiB");
+    }
+}
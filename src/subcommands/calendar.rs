@@ -0,0 +1,255 @@
+use std::io::Write;
+
+use chrono::{Local, NaiveDate, TimeDelta, Utc};
+
+use crate::{
+    apis::{
+        google_sheets::{
+            self,
+            spreadsheet::{
+                mk_row, ExtendedValue, GridData, Sheet, SheetProperties, Spreadsheet,
+                SpreadsheetProperties,
+            },
+        },
+        job_nimbus,
+    },
+    jobs::{Job, Timestamp},
+    subcommands::output::OutputArgs,
+    utils,
+};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The JobNimbus API key. This key will be cached.
+    #[arg(long, default_value = None, global = true, env)]
+    jn_api_key: Option<String>,
+
+    /// The filter to use when query JobNimbus for jobs, using ElasticSearch
+    /// syntax.
+    #[arg(short, long = "filter", default_value = None)]
+    filter_filename: Option<String>,
+
+    /// The format in which to print the output.
+    #[arg(long, value_enum, default_value = "ics")]
+    format: OutputFormat,
+
+    #[command(flatten)]
+    output_args: OutputArgs,
+}
+
+#[derive(Debug, clap::ValueEnum, Clone, Copy, Eq, PartialEq)]
+enum OutputFormat {
+    /// Prints an iCalendar (.ics) file into the output file, which can be
+    /// imported into or subscribed to from Google Calendar.
+    Ics,
+    /// Outputs a Google Sheet on the user's Google Drive (requires OAuth
+    /// authorization).
+    GoogleSheets,
+}
+
+/// The product identifier written into generated calendars, as required by
+/// RFC 5545.
+const PRODUCT_ID: &str = "-//andrechen77//ahitool//EN";
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let Args { jn_api_key, filter_filename, format, output_args } = args;
+
+    let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+    output_args.validate(format == OutputFormat::GoogleSheets);
+
+    let jobs = job_nimbus::get_all_jobs_with_filter_file(&jn_api_key, filter_filename.as_deref())?;
+
+    let now = Utc::now();
+    let installs = upcoming_installs(&jobs, Local::now().date_naive());
+
+    match format {
+        OutputFormat::Ics => {
+            let output_writer = output_args.writer()?;
+            print_ics(&installs, now, output_writer)?;
+        }
+        OutputFormat::GoogleSheets => generate_report_google_sheets(&installs, !output_args.new)?,
+    }
+
+    Ok(())
+}
+
+/// The jobs with an install date on or after `today` (in local time), along
+/// with the install date, sorted by install date. Installs scheduled earlier
+/// today are still upcoming.
+fn upcoming_installs(jobs: &[Job], today: NaiveDate) -> Vec<(Timestamp, &Job)> {
+    let mut installs: Vec<(Timestamp, &Job)> = jobs
+        .iter()
+        .filter_map(|job| job.milestone_dates.install_date.map(|date| (date, job)))
+        .filter(|(date, _)| date.with_timezone(&Local).date_naive() >= today)
+        .collect();
+    installs.sort_by_key(|(date, _)| *date);
+    installs
+}
+
+fn print_ics(
+    installs: &[(Timestamp, &Job)],
+    now: Timestamp,
+    mut writer: impl Write,
+) -> std::io::Result<()> {
+    let date_stamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+
+    write_ics_line(&mut writer, "BEGIN:VCALENDAR")?;
+    write_ics_line(&mut writer, "VERSION:2.0")?;
+    write_ics_line(&mut writer, &format!("PRODID:{}", PRODUCT_ID))?;
+    write_ics_line(&mut writer, "CALSCALE:GREGORIAN")?;
+    write_ics_line(&mut writer, "X-WR-CALNAME:Upcoming Installs")?;
+    for (install_date, job) in installs {
+        // installs are all-day events, so the end date is the following day
+        // (the end date is exclusive)
+        let start = install_date.date_naive();
+        let end = start + TimeDelta::days(1);
+
+        let name = job.job_name.as_deref().unwrap_or("Unnamed Job");
+        let number = job.job_number.as_deref().unwrap_or("Unknown Job Number");
+        let sales_rep = job.sales_rep.as_deref().unwrap_or("Unknown Sales Rep");
+
+        write_ics_line(&mut writer, "BEGIN:VEVENT")?;
+        write_ics_line(&mut writer, &format!("UID:install-{}@ahitool", job.jnid))?;
+        write_ics_line(&mut writer, &format!("DTSTAMP:{}", date_stamp))?;
+        write_ics_line(&mut writer, &format!("DTSTART;VALUE=DATE:{}", start.format("%Y%m%d")))?;
+        write_ics_line(&mut writer, &format!("DTEND;VALUE=DATE:{}", end.format("%Y%m%d")))?;
+        write_ics_line(
+            &mut writer,
+            &format!("SUMMARY:{}", escape_ics_text(&format!("Install: {} (#{})", name, number))),
+        )?;
+        write_ics_line(
+            &mut writer,
+            &format!(
+                "DESCRIPTION:{}",
+                escape_ics_text(&format!("Job #{}\nSales rep: {}", number, sales_rep))
+            ),
+        )?;
+        write_ics_line(&mut writer, "TRANSP:TRANSPARENT")?;
+        write_ics_line(&mut writer, "END:VEVENT")?;
+    }
+    write_ics_line(&mut writer, "END:VCALENDAR")?;
+    writer.flush()
+}
+
+/// Escapes the characters that have special meaning in iCalendar text values.
+fn escape_ics_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => (),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Writes a single content line, folding it so that no physical line is longer
+/// than 75 octets (not counting the CRLF), as required by RFC 5545.
+fn write_ics_line(writer: &mut impl Write, line: &str) -> std::io::Result<()> {
+    const MAX_LINE_OCTETS: usize = 75;
+
+    let mut line_octets = 0;
+    for c in line.chars() {
+        // never split a multi-byte character across physical lines
+        if line_octets + c.len_utf8() > MAX_LINE_OCTETS {
+            // continuation lines start with a single space, which counts
+            // towards the limit
+            writer.write_all(b"\r\n ")?;
+            line_octets = 1;
+        }
+        let mut buf = [0; 4];
+        writer.write_all(c.encode_utf8(&mut buf).as_bytes())?;
+        line_octets += c.len_utf8();
+    }
+    writer.write_all(b"\r\n")
+}
+
+fn generate_report_google_sheets(
+    installs: &[(Timestamp, &Job)],
+    update: bool,
+) -> anyhow::Result<()> {
+    let mut rows = Vec::new();
+    rows.push(mk_row([
+        ExtendedValue::StringValue("Install Date".to_string()),
+        ExtendedValue::StringValue("Job Name".to_string()),
+        ExtendedValue::StringValue("Job Number".to_string()),
+        ExtendedValue::StringValue("Sales Rep".to_string()),
+    ]));
+    for (install_date, job) in installs {
+        rows.push(mk_row([
+            ExtendedValue::DateValue(install_date.date_naive()),
+            ExtendedValue::StringValue(job.job_name.clone().unwrap_or_default()),
+            ExtendedValue::StringValue(
+                job.job_number.clone().unwrap_or_else(|| "Unknown Job Number".to_owned()),
+            ),
+            ExtendedValue::StringValue(
+                job.sales_rep.clone().unwrap_or_else(|| "Unknown Sales Rep".to_owned()),
+            ),
+        ]));
+    }
+
+    let spreadsheet = Spreadsheet {
+        properties: SpreadsheetProperties {
+            title: Some(format!("Install Calendar ({})", Utc::now())),
+        },
+        sheets: Some(vec![Sheet {
+            properties: SheetProperties {
+                title: Some("Upcoming Installs".to_string()),
+                ..Default::default()
+            },
+            data: Some(GridData { start_row: 0, start_column: 0, row_data: rows }),
+        }]),
+        ..Default::default()
+    };
+
+    let url = google_sheets::upload_spreadsheet_blocking(
+        google_sheets::SheetNickname::InstallCalendar,
+        spreadsheet,
+        update,
+    )?;
+    utils::open_url(url.as_str());
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone as _;
+
+    use super::*;
+    use crate::jobs::test::make_job;
+
+    #[test]
+    fn upcoming_installs_include_earlier_today() {
+        let local = |day, hour| Local.with_ymd_and_hms(2024, 6, day, hour, 0, 0).unwrap().to_utc();
+        let install_on = |date| make_job(false, None, None, None, Some(date), None);
+        let jobs = [install_on(local(3, 8)), install_on(local(2, 8)), install_on(local(1, 23))];
+
+        let today = NaiveDate::from_ymd_opt(2024, 6, 2).unwrap();
+        let installs: Vec<_> =
+            upcoming_installs(&jobs, today).into_iter().map(|(date, _)| date).collect();
+        assert_eq!(installs, [local(2, 8), local(3, 8)]);
+    }
+
+    #[test]
+    fn escape_text() {
+        assert_eq!(
+            escape_ics_text("Smith, John; roof\\gutters\nnote"),
+            "Smith\\, John\\; roof\\\\gutters\\nnote"
+        );
+    }
+
+    #[test]
+    fn fold_long_lines() {
+        let mut out = Vec::new();
+        write_ics_line(&mut out, &"a".repeat(80)).unwrap();
+        assert_eq!(out, format!("{}\r\n {}\r\n", "a".repeat(75), "a".repeat(5)).into_bytes());
+
+        let mut out = Vec::new();
+        write_ics_line(&mut out, "short").unwrap();
+        assert_eq!(out, b"short\r\n");
+    }
+}
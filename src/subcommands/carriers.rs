@@ -0,0 +1,269 @@
+use std::{collections::BTreeMap, io::Write};
+
+use chrono::Utc;
+
+use crate::{
+    apis::{
+        google_sheets::{
+            self,
+            spreadsheet::{
+                mk_row, ExtendedValue, GridData, Sheet, SheetProperties, Spreadsheet,
+                SpreadsheetProperties,
+            },
+        },
+        job_nimbus,
+    },
+    jobs::{self, AnalyzedJob, JobKind, Milestone, TimeDelta},
+    subcommands::output::{OutputArgs, OutputFormat},
+    utils::{self, into_days, percent_or_na},
+};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The JobNimbus API key. This key will be cached.
+    #[arg(long, default_value = None, global = true, env)]
+    jn_api_key: Option<String>,
+
+    /// The filter to use when query JobNimbus for jobs, using ElasticSearch
+    /// syntax.
+    #[arg(short, long = "filter", default_value = None)]
+    filter_filename: Option<String>,
+
+    /// The format in which to print the output.
+    #[arg(long, value_enum, default_value = "google-sheets")]
+    format: OutputFormat,
+
+    #[command(flatten)]
+    output_args: OutputArgs,
+}
+
+/// Statistics about the insurance jobs of a single insurance carrier.
+#[derive(Debug, Default)]
+pub struct CarrierStats {
+    /// The number of insurance jobs with this carrier.
+    pub num_jobs: usize,
+    /// The number of jobs that signed a contingency.
+    pub num_contingencies: usize,
+    /// The number of jobs that signed a contract after signing a contingency,
+    /// i.e. the number of claims that the carrier approved.
+    pub num_approvals: usize,
+    /// The number of jobs that were installed.
+    pub num_installs: usize,
+    /// The cumulative time between signing the contingency and signing the
+    /// contract, over all approved jobs.
+    pub cum_approval_time: TimeDelta,
+    /// The cumulative time between signing the contract and installing, over
+    /// all installed jobs.
+    pub cum_install_time: TimeDelta,
+    /// The total amount receivable on jobs with this carrier, in cents.
+    pub amt_receivable: i64,
+}
+
+impl CarrierStats {
+    /// The rate at which contingencies turn into contracts. `None` if no jobs
+    /// signed a contingency.
+    pub fn approval_rate(&self) -> Option<f64> {
+        if self.num_contingencies == 0 {
+            None
+        } else {
+            Some(self.num_approvals as f64 / self.num_contingencies as f64)
+        }
+    }
+
+    /// The average time between signing the contingency and signing the
+    /// contract. Zero if no claims were approved.
+    pub fn average_time_to_approve(&self) -> TimeDelta {
+        if self.num_approvals == 0 {
+            TimeDelta::zero()
+        } else {
+            self.cum_approval_time / self.num_approvals.try_into().unwrap()
+        }
+    }
+
+    /// The average time between signing the contract and installing. Zero if
+    /// no jobs were installed.
+    pub fn average_time_to_install(&self) -> TimeDelta {
+        if self.num_installs == 0 {
+            TimeDelta::zero()
+        } else {
+            self.cum_install_time / self.num_installs.try_into().unwrap()
+        }
+    }
+}
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let Args { jn_api_key, filter_filename, format, output_args } = args;
+
+    let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+    output_args.validate(format == OutputFormat::GoogleSheets);
+
+    let jobs = job_nimbus::get_all_jobs_with_filter_file(&jn_api_key, filter_filename.as_deref())?;
+
+    let analyzed: Vec<AnalyzedJob> = jobs.into_iter().map(|job| jobs::analyze_job(job).0).collect();
+    let results = calculate_carrier_stats(&analyzed);
+
+    let output_writer = output_args.writer()?;
+
+    match format {
+        OutputFormat::Human => print_human(&results, output_writer)?,
+        OutputFormat::Csv => print_csv(&results, output_writer)?,
+        OutputFormat::GoogleSheets => {
+            let spreadsheet = Spreadsheet {
+                properties: SpreadsheetProperties {
+                    title: Some(format!("Insurance Carrier Report ({})", Utc::now())),
+                },
+                sheets: Some(vec![carrier_stats_sheet(&results)]),
+                ..Default::default()
+            };
+            let url = google_sheets::upload_spreadsheet_blocking(
+                google_sheets::SheetNickname::Carriers,
+                spreadsheet,
+                !output_args.new,
+            )?;
+            utils::open_url(url.as_str());
+        }
+    }
+
+    Ok(())
+}
+
+/// Groups the insurance jobs by carrier and calculates statistics for each
+/// carrier. Jobs without a carrier are grouped under `None`. Jobs that failed
+/// analysis or that are not insurance jobs are ignored.
+pub fn calculate_carrier_stats<'a>(
+    jobs: impl IntoIterator<Item = &'a AnalyzedJob>,
+) -> BTreeMap<Option<String>, CarrierStats> {
+    let contingency = Milestone::ContingencySigned.into_int();
+    let contract = Milestone::ContractSigned.into_int();
+    let install = Milestone::Installed.into_int();
+
+    let mut results: BTreeMap<Option<String>, CarrierStats> = BTreeMap::new();
+    for AnalyzedJob { job, analysis } in jobs {
+        let Some(analysis) = analysis else {
+            continue;
+        };
+        if analysis.kind == JobKind::Retail {
+            continue;
+        }
+
+        let stats = results.entry(job.insurance_company_name.clone()).or_default();
+        stats.num_jobs += 1;
+        stats.amt_receivable += job.amt_receivable as i64;
+
+        let timestamp = |milestone: usize| analysis.timestamps.get(milestone).copied().flatten();
+        if analysis.kind == JobKind::InsuranceWithContingency
+            && analysis.timestamps.len() > contingency
+        {
+            stats.num_contingencies += 1;
+            if analysis.timestamps.len() > contract {
+                stats.num_approvals += 1;
+                if let (Some(start), Some(end)) = (timestamp(contingency), timestamp(contract)) {
+                    stats.cum_approval_time += end - start;
+                }
+            }
+        }
+        if analysis.timestamps.len() > install {
+            stats.num_installs += 1;
+            if let (Some(start), Some(end)) = (timestamp(contract), timestamp(install)) {
+                stats.cum_install_time += end - start;
+            }
+        }
+    }
+    results
+}
+
+fn carrier_name(carrier: &Option<String>) -> &str {
+    carrier.as_deref().unwrap_or("Unknown Carrier")
+}
+
+fn print_human(
+    results: &BTreeMap<Option<String>, CarrierStats>,
+    mut writer: impl Write,
+) -> std::io::Result<()> {
+    for (carrier, stats) in results {
+        writeln!(writer, "{}: ================", carrier_name(carrier))?;
+        writeln!(
+            writer,
+            "Jobs {} | Contingencies {} | Approved {} | Installed {}",
+            stats.num_jobs, stats.num_contingencies, stats.num_approvals, stats.num_installs
+        )?;
+        writeln!(
+            writer,
+            "Approval Rate {} | Avg Time to Approve {:.2} days | Avg Time to Install {:.2} days",
+            percent_or_na(stats.approval_rate()),
+            into_days(stats.average_time_to_approve()),
+            into_days(stats.average_time_to_install()),
+        )?;
+        writeln!(writer, "Receivable ${:.2}", stats.amt_receivable as f64 / 100.0)?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+fn print_csv(
+    results: &BTreeMap<Option<String>, CarrierStats>,
+    writer: impl Write,
+) -> std::io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record([
+        "Carrier",
+        "Jobs",
+        "Contingencies",
+        "Approved",
+        "Approval Rate",
+        "Avg Days to Approve",
+        "Installed",
+        "Avg Days to Install",
+        "Receivable",
+    ])?;
+    for (carrier, stats) in results {
+        writer.write_record([
+            carrier_name(carrier),
+            &stats.num_jobs.to_string(),
+            &stats.num_contingencies.to_string(),
+            &stats.num_approvals.to_string(),
+            &percent_or_na(stats.approval_rate()),
+            &into_days(stats.average_time_to_approve()).to_string(),
+            &stats.num_installs.to_string(),
+            &into_days(stats.average_time_to_install()).to_string(),
+            &(stats.amt_receivable as f64 / 100.0).to_string(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Creates a sheet containing a table of the specified carrier stats, with one
+/// row per carrier.
+pub fn carrier_stats_sheet(results: &BTreeMap<Option<String>, CarrierStats>) -> Sheet {
+    let mut rows = Vec::new();
+    rows.push(mk_row([
+        ExtendedValue::StringValue("Carrier".to_string()),
+        ExtendedValue::StringValue("Jobs".to_string()),
+        ExtendedValue::StringValue("Contingencies".to_string()),
+        ExtendedValue::StringValue("Approved".to_string()),
+        ExtendedValue::StringValue("Approval Rate".to_string()),
+        ExtendedValue::StringValue("Avg Days to Approve".to_string()),
+        ExtendedValue::StringValue("Installed".to_string()),
+        ExtendedValue::StringValue("Avg Days to Install".to_string()),
+        ExtendedValue::StringValue("Receivable".to_string()),
+    ]));
+    for (carrier, stats) in results {
+        rows.push(mk_row([
+            ExtendedValue::StringValue(carrier_name(carrier).to_owned()),
+            ExtendedValue::NumberValue(stats.num_jobs as f64),
+            ExtendedValue::NumberValue(stats.num_contingencies as f64),
+            ExtendedValue::NumberValue(stats.num_approvals as f64),
+            ExtendedValue::StringValue(percent_or_na(stats.approval_rate())),
+            ExtendedValue::NumberValue(into_days(stats.average_time_to_approve())),
+            ExtendedValue::NumberValue(stats.num_installs as f64),
+            ExtendedValue::NumberValue(into_days(stats.average_time_to_install())),
+            ExtendedValue::NumberValue(stats.amt_receivable as f64 / 100.0),
+        ]));
+    }
+
+    Sheet {
+        properties: SheetProperties { title: Some("Carriers".to_string()), ..Default::default() },
+        data: Some(GridData { start_row: 0, start_column: 0, row_data: rows }),
+    }
+}
@@ -0,0 +1,335 @@
+use std::{collections::BTreeMap, fmt::Display, io::Write};
+
+use chrono::{NaiveDate, Utc};
+
+use crate::{
+    apis::{
+        google_sheets::{
+            self,
+            spreadsheet::{
+                mk_row, ExtendedValue, GridData, Sheet, SheetProperties, Spreadsheet,
+                SpreadsheetProperties,
+            },
+        },
+        job_nimbus,
+    },
+    jobs::{self, AnalyzedJob, Milestone, TimeDelta, Timestamp},
+    roster::Roster,
+    subcommands::output::OutputArgs,
+    utils::{self, into_days, percent_or_na},
+};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The JobNimbus API key. This key will be cached.
+    #[arg(long, default_value = None, global = true, env)]
+    jn_api_key: Option<String>,
+
+    /// The filter to use when query JobNimbus for jobs, using ElasticSearch
+    /// syntax.
+    #[arg(short, long = "filter", default_value = None)]
+    filter_filename: Option<String>,
+
+    /// The roster file containing the hire date of each sales rep. This is a
+    /// CSV file with a header row containing the columns "name" and
+    /// "hire_date" (of the form "%Y-%m-%d").
+    #[arg(long)]
+    roster: String,
+
+    /// The format in which to print the output.
+    #[arg(long, value_enum, default_value = "google-sheets")]
+    format: OutputFormat,
+
+    #[command(flatten)]
+    output_args: OutputArgs,
+}
+
+#[derive(Debug, clap::ValueEnum, Clone, Copy, Eq, PartialEq)]
+enum OutputFormat {
+    /// Prints a human-readable report into the output file.
+    Human,
+    /// Prints a CSV file of the stats of each rep in each cohort into the
+    /// output file.
+    Csv,
+    /// Outputs a Google Sheet on the user's Google Drive (requires OAuth
+    /// authorization).
+    GoogleSheets,
+}
+
+/// How long a rep had been with the company when they ran the appointment for
+/// a job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Cohort {
+    FirstNinetyDays,
+    ThreeToTwelveMonths,
+    OverOneYear,
+    /// The rep's hire date or the job's appointment date is unknown, or the
+    /// appointment was before the hire date.
+    Unknown,
+}
+impl Display for Cohort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Cohort::FirstNinetyDays => write!(f, "First 90 Days"),
+            Cohort::ThreeToTwelveMonths => write!(f, "3-12 Months"),
+            Cohort::OverOneYear => write!(f, "1+ Years"),
+            Cohort::Unknown => write!(f, "Unknown"),
+        }
+    }
+}
+
+fn cohort_of(hire_date: Option<NaiveDate>, appointment_date: Option<Timestamp>) -> Cohort {
+    let (Some(hire_date), Some(appointment_date)) = (hire_date, appointment_date) else {
+        return Cohort::Unknown;
+    };
+    match (appointment_date.date_naive() - hire_date).num_days() {
+        ..0 => Cohort::Unknown,
+        0..90 => Cohort::FirstNinetyDays,
+        90..365 => Cohort::ThreeToTwelveMonths,
+        _ => Cohort::OverOneYear,
+    }
+}
+
+#[derive(Debug, Default)]
+struct CohortStats {
+    num_appts: usize,
+    num_contracts: usize,
+    num_installs: usize,
+    /// The cumulative time between the appointment and the install, over all
+    /// installed jobs with known dates.
+    cum_cycle_time: TimeDelta,
+    num_cycles: usize,
+}
+
+impl CohortStats {
+    fn add_job(&mut self, timestamps: &[Option<Timestamp>]) {
+        let appt = Milestone::AppointmentMade.into_int();
+        let contract = Milestone::ContractSigned.into_int();
+        let install = Milestone::Installed.into_int();
+
+        self.num_appts += 1;
+        if timestamps.len() > contract {
+            self.num_contracts += 1;
+        }
+        if timestamps.len() > install {
+            self.num_installs += 1;
+            if let (Some(start), Some(end)) = (timestamps[appt], timestamps[install]) {
+                self.cum_cycle_time += end - start;
+                self.num_cycles += 1;
+            }
+        }
+    }
+
+    /// The fraction of appointments that turned into contracts.
+    fn close_rate(&self) -> Option<f64> {
+        if self.num_appts == 0 {
+            None
+        } else {
+            Some(self.num_contracts as f64 / self.num_appts as f64)
+        }
+    }
+
+    /// The average time between the appointment and the install. Zero if no
+    /// installs have known dates.
+    fn average_cycle_time(&self) -> TimeDelta {
+        if self.num_cycles == 0 {
+            TimeDelta::zero()
+        } else {
+            self.cum_cycle_time / self.num_cycles.try_into().unwrap()
+        }
+    }
+}
+
+struct CohortData<'a> {
+    by_cohort: BTreeMap<Cohort, CohortStats>,
+    by_rep: BTreeMap<(Option<&'a str>, Cohort), CohortStats>,
+}
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let Args { jn_api_key, filter_filename, roster, format, output_args } = args;
+
+    let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+    output_args.validate(format == OutputFormat::GoogleSheets);
+
+    let roster = Roster::load(roster)?;
+
+    let jobs = job_nimbus::get_all_jobs_with_filter_file(&jn_api_key, filter_filename.as_deref())?;
+
+    let analyzed: Vec<AnalyzedJob> = jobs.into_iter().map(|job| jobs::analyze_job(job).0).collect();
+    let mut results = CohortData { by_cohort: BTreeMap::new(), by_rep: BTreeMap::new() };
+    for AnalyzedJob { job, analysis } in &analyzed {
+        let Some(analysis) = analysis else {
+            continue;
+        };
+        // only consider jobs that have had an appointment
+        if analysis.timestamps.len() <= Milestone::AppointmentMade.into_int() {
+            continue;
+        }
+
+        let hire_date = job
+            .sales_rep
+            .as_deref()
+            .and_then(|rep| roster.get(rep))
+            .and_then(|rep_info| rep_info.hire_date);
+        let cohort = cohort_of(hire_date, job.milestone_dates.appointment_date);
+        results.by_cohort.entry(cohort).or_default().add_job(&analysis.timestamps);
+        results
+            .by_rep
+            .entry((job.sales_rep.as_deref(), cohort))
+            .or_default()
+            .add_job(&analysis.timestamps);
+    }
+
+    let output_writer = output_args.writer()?;
+
+    match format {
+        OutputFormat::Human => print_human(&results, output_writer)?,
+        OutputFormat::Csv => print_csv(&results, output_writer)?,
+        OutputFormat::GoogleSheets => generate_report_google_sheets(&results, !output_args.new)?,
+    }
+
+    Ok(())
+}
+
+fn print_human(results: &CohortData, mut writer: impl Write) -> std::io::Result<()> {
+    let write_stats = |writer: &mut dyn Write, name: &str, stats: &CohortStats| {
+        writeln!(
+            writer,
+            "{}: Appts {} | Contracts {} | Close Rate {} | Installs {} | Avg Cycle Time {:.2} days",
+            name,
+            stats.num_appts,
+            stats.num_contracts,
+            percent_or_na(stats.close_rate()),
+            stats.num_installs,
+            into_days(stats.average_cycle_time()),
+        )
+    };
+
+    writeln!(writer, "By cohort:")?;
+    for (cohort, stats) in &results.by_cohort {
+        write_stats(&mut writer, &format!("    - {}", cohort), stats)?;
+    }
+    writeln!(writer, "By sales rep:")?;
+    for ((rep, cohort), stats) in &results.by_rep {
+        write_stats(
+            &mut writer,
+            &format!("    - {} ({})", rep.unwrap_or("Unknown Sales Rep"), cohort),
+            stats,
+        )?;
+    }
+    Ok(())
+}
+
+fn print_csv(results: &CohortData, writer: impl Write) -> std::io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record([
+        "Sales Rep",
+        "Cohort",
+        "Appts",
+        "Contracts",
+        "Close Rate",
+        "Installs",
+        "Avg Cycle Time (days)",
+    ])?;
+    for ((rep, cohort), stats) in &results.by_rep {
+        writer.write_record([
+            rep.unwrap_or("Unknown Sales Rep"),
+            &cohort.to_string(),
+            &stats.num_appts.to_string(),
+            &stats.num_contracts.to_string(),
+            &percent_or_na(stats.close_rate()),
+            &stats.num_installs.to_string(),
+            &into_days(stats.average_cycle_time()).to_string(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn generate_report_google_sheets(results: &CohortData, update: bool) -> anyhow::Result<()> {
+    fn mk_stats_cells(stats: &CohortStats) -> [ExtendedValue; 5] {
+        [
+            ExtendedValue::NumberValue(stats.num_appts as f64),
+            ExtendedValue::NumberValue(stats.num_contracts as f64),
+            ExtendedValue::StringValue(percent_or_na(stats.close_rate())),
+            ExtendedValue::NumberValue(stats.num_installs as f64),
+            ExtendedValue::NumberValue(into_days(stats.average_cycle_time())),
+        ]
+    }
+    let stats_headers = ["Appts", "Contracts", "Close Rate", "Installs", "Avg Cycle Time (days)"]
+        .map(|header| ExtendedValue::StringValue(header.to_string()));
+
+    let mut cohort_rows = vec![mk_row(
+        [ExtendedValue::StringValue("Cohort".to_string())].into_iter().chain(stats_headers.clone()),
+    )];
+    for (cohort, stats) in &results.by_cohort {
+        cohort_rows.push(mk_row(
+            [ExtendedValue::StringValue(cohort.to_string())]
+                .into_iter()
+                .chain(mk_stats_cells(stats)),
+        ));
+    }
+
+    let mut rep_rows = vec![mk_row(
+        [
+            ExtendedValue::StringValue("Sales Rep".to_string()),
+            ExtendedValue::StringValue("Cohort".to_string()),
+        ]
+        .into_iter()
+        .chain(stats_headers),
+    )];
+    for ((rep, cohort), stats) in &results.by_rep {
+        rep_rows.push(mk_row(
+            [
+                ExtendedValue::StringValue(rep.unwrap_or("Unknown Sales Rep").to_owned()),
+                ExtendedValue::StringValue(cohort.to_string()),
+            ]
+            .into_iter()
+            .chain(mk_stats_cells(stats)),
+        ));
+    }
+
+    let sheets = [("By Cohort", cohort_rows), ("By Rep", rep_rows)]
+        .into_iter()
+        .map(|(title, rows)| Sheet {
+            properties: SheetProperties { title: Some(title.to_string()), ..Default::default() },
+            data: Some(GridData { start_row: 0, start_column: 0, row_data: rows }),
+        })
+        .collect();
+
+    let spreadsheet = Spreadsheet {
+        properties: SpreadsheetProperties {
+            title: Some(format!("Rep Cohort Report ({})", Utc::now())),
+        },
+        sheets: Some(sheets),
+        ..Default::default()
+    };
+
+    let url = google_sheets::upload_spreadsheet_blocking(
+        google_sheets::SheetNickname::Cohorts,
+        spreadsheet,
+        update,
+    )?;
+    utils::open_url(url.as_str());
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cohorts() {
+        let hire_date = NaiveDate::from_ymd_opt(2024, 1, 1);
+        let appt = |y, m, d| {
+            Some(NaiveDate::from_ymd_opt(y, m, d).unwrap().and_hms_opt(12, 0, 0).unwrap().and_utc())
+        };
+        assert_eq!(cohort_of(hire_date, appt(2024, 1, 1)), Cohort::FirstNinetyDays);
+        assert_eq!(cohort_of(hire_date, appt(2024, 3, 30)), Cohort::FirstNinetyDays);
+        assert_eq!(cohort_of(hire_date, appt(2024, 3, 31)), Cohort::ThreeToTwelveMonths);
+        assert_eq!(cohort_of(hire_date, appt(2025, 1, 1)), Cohort::OverOneYear);
+        assert_eq!(cohort_of(hire_date, appt(2023, 12, 31)), Cohort::Unknown);
+        assert_eq!(cohort_of(None, appt(2024, 1, 1)), Cohort::Unknown);
+        assert_eq!(cohort_of(hire_date, None), Cohort::Unknown);
+    }
+}
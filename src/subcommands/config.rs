@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+
+use tracing::info;
+
+use crate::{config_bundle::ConfigBundle, storage};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The action to perform on the settings.
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Bundle the settings in the settings directory into a single file,
+    /// leaving out the API keys and other credentials.
+    Export {
+        /// The file to write the bundle to.
+        #[arg(default_value = "ahitool_config.json")]
+        path: PathBuf,
+    },
+    /// Write the settings in a bundle into the settings directory.
+    Import {
+        /// The bundle to import.
+        path: PathBuf,
+
+        /// Replace settings files that already exist with different contents.
+        #[arg(long)]
+        overwrite: bool,
+    },
+}
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let Args { command } = args;
+
+    match command {
+        Command::Export { path } => {
+            ConfigBundle::move_legacy_settings();
+            let bundle = ConfigBundle::export_from(&storage::config_dir())?;
+            bundle.save(&path)?;
+            for name in bundle.files.keys() {
+                println!("{}", name);
+            }
+            info!("Exported {} settings files to {}", bundle.files.len(), path.display());
+        }
+        Command::Import { path, overwrite } => {
+            let bundle = ConfigBundle::load(&path)?;
+            ConfigBundle::move_legacy_settings();
+            let written = bundle.import_into(&storage::config_dir(), overwrite)?;
+            for name in &written {
+                println!("{}", name);
+            }
+            info!(
+                "Imported {} settings files ({} already up to date)",
+                written.len(),
+                bundle.files.len() - written.len()
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,247 @@
+use std::{collections::BTreeMap, io::Write};
+
+use chrono::Utc;
+
+use crate::{
+    apis::{
+        google_sheets::{
+            self,
+            spreadsheet::{
+                mk_row, ExtendedValue, GridData, Sheet, SheetProperties, Spreadsheet,
+                SpreadsheetProperties,
+            },
+        },
+        job_nimbus,
+    },
+    jobs::{self, AnalyzedJob, Milestone, TimeDelta, Timestamp},
+    subcommands::output::{OutputArgs, OutputFormat},
+    utils::{self, into_days},
+};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The JobNimbus API key. This key will be cached.
+    #[arg(long, default_value = None, global = true, env)]
+    jn_api_key: Option<String>,
+
+    /// The filter to use when query JobNimbus for jobs, using ElasticSearch
+    /// syntax.
+    #[arg(short, long = "filter", default_value = None)]
+    filter_filename: Option<String>,
+
+    /// The format in which to print the output.
+    #[arg(long, value_enum, default_value = "google-sheets")]
+    format: OutputFormat,
+
+    #[command(flatten)]
+    output_args: OutputArgs,
+}
+
+/// Statistics about the jobs installed by a single crew.
+#[derive(Debug, Default)]
+pub struct CrewStats {
+    /// The number of jobs that the crew installed.
+    pub num_installs: usize,
+    /// The number of installed jobs whose contract date is known, i.e. the
+    /// number of jobs that `cum_cycle_time` is calculated over.
+    pub num_timed: usize,
+    /// The cumulative time between signing the contract and installing.
+    pub cum_cycle_time: TimeDelta,
+    /// The date of the crew's earliest install.
+    pub first_install: Option<Timestamp>,
+    /// The date of the crew's latest install.
+    pub last_install: Option<Timestamp>,
+}
+
+impl CrewStats {
+    /// The average time between signing the contract and installing. Zero if
+    /// no installed jobs have a known contract date.
+    pub fn average_cycle_time(&self) -> TimeDelta {
+        if self.num_timed == 0 {
+            TimeDelta::zero()
+        } else {
+            self.cum_cycle_time / self.num_timed.try_into().unwrap()
+        }
+    }
+
+    /// The average number of installs per week between the crew's first and
+    /// last install. Crews whose installs all happened within a week are
+    /// treated as having worked for one week.
+    pub fn installs_per_week(&self) -> f64 {
+        let weeks = match (self.first_install, self.last_install) {
+            (Some(first), Some(last)) => into_days(last - first) / 7.0,
+            _ => 0.0,
+        };
+        self.num_installs as f64 / weeks.max(1.0)
+    }
+}
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let Args { jn_api_key, filter_filename, format, output_args } = args;
+
+    let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+    output_args.validate(format == OutputFormat::GoogleSheets);
+
+    let jobs = job_nimbus::get_all_jobs_with_filter_file(&jn_api_key, filter_filename.as_deref())?;
+
+    let analyzed: Vec<AnalyzedJob> = jobs.into_iter().map(|job| jobs::analyze_job(job).0).collect();
+    let results = calculate_crew_stats(&analyzed);
+
+    let output_writer = output_args.writer()?;
+
+    match format {
+        OutputFormat::Human => print_human(&results, output_writer)?,
+        OutputFormat::Csv => print_csv(&results, output_writer)?,
+        OutputFormat::GoogleSheets => {
+            let spreadsheet = Spreadsheet {
+                properties: SpreadsheetProperties {
+                    title: Some(format!("Crew Report ({})", Utc::now())),
+                },
+                sheets: Some(vec![crew_stats_sheet(&results)]),
+                ..Default::default()
+            };
+            let url = google_sheets::upload_spreadsheet_blocking(
+                google_sheets::SheetNickname::Crews,
+                spreadsheet,
+                !output_args.new,
+            )?;
+            utils::open_url(url.as_str());
+        }
+    }
+
+    Ok(())
+}
+
+/// Groups the installed jobs by crew and calculates statistics for each crew.
+/// Jobs without a crew are grouped under `None`. Jobs that failed analysis or
+/// that have not been installed are ignored.
+pub fn calculate_crew_stats<'a>(
+    jobs: impl IntoIterator<Item = &'a AnalyzedJob>,
+) -> BTreeMap<Option<String>, CrewStats> {
+    let contract = Milestone::ContractSigned.into_int();
+    let install = Milestone::Installed.into_int();
+
+    let mut results: BTreeMap<Option<String>, CrewStats> = BTreeMap::new();
+    for AnalyzedJob { job, analysis } in jobs {
+        let Some(analysis) = analysis else {
+            continue;
+        };
+        let Some(&Some(install_date)) = analysis.timestamps.get(install) else {
+            continue;
+        };
+
+        let stats = results.entry(job.crew.clone()).or_default();
+        stats.num_installs += 1;
+        if let Some(contract_date) = analysis.timestamps[contract] {
+            stats.num_timed += 1;
+            stats.cum_cycle_time += install_date - contract_date;
+        }
+        stats.first_install =
+            Some(stats.first_install.map_or(install_date, |d| d.min(install_date)));
+        stats.last_install = Some(stats.last_install.map_or(install_date, |d| d.max(install_date)));
+    }
+    results
+}
+
+fn crew_name(crew: &Option<String>) -> &str {
+    crew.as_deref().unwrap_or("Unknown Crew")
+}
+
+fn print_human(
+    results: &BTreeMap<Option<String>, CrewStats>,
+    mut writer: impl Write,
+) -> std::io::Result<()> {
+    for (crew, stats) in results {
+        writeln!(
+            writer,
+            "{}: Installed {} | {:.2} installs/week | Avg Contract to Install {:.2} days",
+            crew_name(crew),
+            stats.num_installs,
+            stats.installs_per_week(),
+            into_days(stats.average_cycle_time()),
+        )?;
+    }
+    Ok(())
+}
+
+fn print_csv(
+    results: &BTreeMap<Option<String>, CrewStats>,
+    writer: impl Write,
+) -> std::io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record([
+        "Crew",
+        "Installed",
+        "Installs per Week",
+        "Avg Days Contract to Install",
+    ])?;
+    for (crew, stats) in results {
+        writer.write_record([
+            crew_name(crew),
+            &stats.num_installs.to_string(),
+            &stats.installs_per_week().to_string(),
+            &into_days(stats.average_cycle_time()).to_string(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Creates a sheet containing a table of the specified crew stats, with one row
+/// per crew.
+pub fn crew_stats_sheet(results: &BTreeMap<Option<String>, CrewStats>) -> Sheet {
+    let mut rows = Vec::new();
+    rows.push(mk_row([
+        ExtendedValue::StringValue("Crew".to_string()),
+        ExtendedValue::StringValue("Installed".to_string()),
+        ExtendedValue::StringValue("Installs per Week".to_string()),
+        ExtendedValue::StringValue("Avg Days Contract to Install".to_string()),
+    ]));
+    for (crew, stats) in results {
+        rows.push(mk_row([
+            ExtendedValue::StringValue(crew_name(crew).to_owned()),
+            ExtendedValue::NumberValue(stats.num_installs as f64),
+            ExtendedValue::NumberValue(stats.installs_per_week()),
+            ExtendedValue::NumberValue(into_days(stats.average_cycle_time())),
+        ]));
+    }
+
+    Sheet {
+        properties: SheetProperties { title: Some("Crews".to_string()), ..Default::default() },
+        data: Some(GridData { start_row: 0, start_column: 0, row_data: rows }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jobs::test::make_job;
+
+    #[test]
+    fn crew_stats() {
+        fn day(days: i64) -> Option<Timestamp> {
+            Some(Timestamp::UNIX_EPOCH + TimeDelta::days(days))
+        }
+        fn job(crew: &str, contract: Option<Timestamp>, install: Option<Timestamp>) -> AnalyzedJob {
+            let mut job = make_job(false, day(0), None, contract, install, None);
+            job.crew = Some(crew.to_owned());
+            jobs::analyze_job(job).0
+        }
+
+        let jobs = [
+            job("A", day(10), day(20)),
+            job("A", day(10), day(40)),
+            job("A", day(10), None),
+            job("B", day(5), day(6)),
+        ];
+        let results = calculate_crew_stats(&jobs);
+
+        let a = &results[&Some("A".to_owned())];
+        assert_eq!(a.num_installs, 2);
+        assert_eq!(a.average_cycle_time(), TimeDelta::days(20));
+        assert_eq!(a.installs_per_week(), 2.0 / (20.0 / 7.0));
+        let b = &results[&Some("B".to_owned())];
+        assert_eq!(b.num_installs, 1);
+        assert_eq!(b.installs_per_week(), 1.0);
+    }
+}
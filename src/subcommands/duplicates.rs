@@ -0,0 +1,287 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    io::Write,
+};
+
+use chrono::Utc;
+
+use crate::{
+    apis::{
+        google_sheets::{
+            self,
+            spreadsheet::{
+                mk_row, ExtendedValue, GridData, Sheet, SheetProperties, Spreadsheet,
+                SpreadsheetProperties,
+            },
+        },
+        job_nimbus,
+    },
+    jobs::{Job, JobAnalysisError},
+    subcommands::output::{OutputArgs, OutputFormat},
+    utils,
+};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The JobNimbus API key. This key will be cached.
+    #[arg(long, default_value = None, global = true, env)]
+    jn_api_key: Option<String>,
+
+    /// The filter to use when query JobNimbus for jobs, using ElasticSearch
+    /// syntax.
+    #[arg(short, long = "filter", default_value = None)]
+    filter_filename: Option<String>,
+
+    /// The format in which to print the output.
+    #[arg(long, value_enum, default_value = "google-sheets")]
+    format: OutputFormat,
+
+    #[command(flatten)]
+    output_args: OutputArgs,
+}
+
+/// Why a group of jobs are likely duplicates of each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateReason {
+    NameAndAddress,
+    ClaimNumber,
+}
+impl Display for DuplicateReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DuplicateReason::NameAndAddress => write!(f, "Same Name and Address"),
+            DuplicateReason::ClaimNumber => write!(f, "Same Claim Number"),
+        }
+    }
+}
+
+/// A group of (at least two) jobs that are likely duplicates of each other.
+#[derive(Debug)]
+pub struct DuplicateGroup<'a> {
+    pub reason: DuplicateReason,
+    pub jobs: Vec<&'a Job>,
+}
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let Args { jn_api_key, filter_filename, format, output_args } = args;
+
+    let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+    output_args.validate(format == OutputFormat::GoogleSheets);
+
+    let jobs = job_nimbus::get_all_jobs_with_filter_file(&jn_api_key, filter_filename.as_deref())?;
+
+    let groups = find_duplicates(&jobs);
+
+    let output_writer = output_args.writer()?;
+
+    match format {
+        OutputFormat::Human => print_human(&groups, output_writer)?,
+        OutputFormat::Csv => print_csv(&groups, output_writer)?,
+        OutputFormat::GoogleSheets => generate_report_google_sheets(&groups, !output_args.new)?,
+    }
+
+    Ok(())
+}
+
+/// Finds groups of jobs that are likely duplicates of each other, i.e. jobs
+/// that have the same name and address, or the same insurance claim number.
+/// Names, addresses, and claim numbers are compared ignoring case, whitespace,
+/// and punctuation. A job may appear in more than one group.
+pub fn find_duplicates<'a>(jobs: impl IntoIterator<Item = &'a Job>) -> Vec<DuplicateGroup<'a>> {
+    let mut by_name_and_address: BTreeMap<(String, String), Vec<&Job>> = BTreeMap::new();
+    let mut by_claim_number: BTreeMap<String, Vec<&Job>> = BTreeMap::new();
+    for job in jobs {
+        if let (Some(name), Some(address)) = (&job.job_name, &job.address) {
+            let key = (normalize(name), normalize(address));
+            if !key.0.is_empty() && !key.1.is_empty() {
+                by_name_and_address.entry(key).or_default().push(job);
+            }
+        }
+        if let Some(claim_number) = &job.insurance_claim_number {
+            let key = normalize(claim_number);
+            if !key.is_empty() {
+                by_claim_number.entry(key).or_default().push(job);
+            }
+        }
+    }
+
+    let name_and_address_groups = by_name_and_address
+        .into_values()
+        .map(|jobs| DuplicateGroup { reason: DuplicateReason::NameAndAddress, jobs });
+    let claim_number_groups = by_claim_number
+        .into_values()
+        .map(|jobs| DuplicateGroup { reason: DuplicateReason::ClaimNumber, jobs });
+    name_and_address_groups
+        .chain(claim_number_groups)
+        .filter(|group| group.jobs.len() > 1)
+        .collect()
+}
+
+/// Returns a red flag for every job that is likely a duplicate of another job,
+/// keyed by the jnid of the job.
+pub fn duplicate_red_flags<'a>(
+    jobs: impl IntoIterator<Item = &'a Job>,
+) -> HashMap<String, Vec<JobAnalysisError>> {
+    let mut red_flags: HashMap<String, Vec<JobAnalysisError>> = HashMap::new();
+    for group in find_duplicates(jobs) {
+        for job in &group.jobs {
+            let others = group
+                .jobs
+                .iter()
+                .filter(|other| other.jnid != job.jnid)
+                .map(|other| job_number(other))
+                .collect::<Vec<_>>()
+                .join(", ");
+            red_flags
+                .entry(job.jnid.clone())
+                .or_default()
+                .push(JobAnalysisError::PossibleDuplicate(others));
+        }
+    }
+    red_flags
+}
+
+/// Lowercases the string and removes everything except letters and digits, so
+/// that e.g. "123 Main St." and "123 main st" compare equal.
+fn normalize(s: &str) -> String {
+    s.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+fn job_number(job: &Job) -> &str {
+    job.job_number.as_deref().unwrap_or(&job.jnid)
+}
+
+fn print_human(groups: &[DuplicateGroup], mut writer: impl Write) -> std::io::Result<()> {
+    writeln!(writer, "Possible duplicate groups: {}", groups.len())?;
+    for group in groups {
+        writeln!(writer, "{}:", group.reason)?;
+        for job in &group.jobs {
+            writeln!(
+                writer,
+                "    - {} (#{}) at {}, claim #{} (assigned to {})",
+                job.job_name.as_deref().unwrap_or(""),
+                job_number(job),
+                job.address.as_deref().unwrap_or("unknown address"),
+                job.insurance_claim_number.as_deref().unwrap_or("N/A"),
+                job.sales_rep.as_deref().unwrap_or("Unknown Sales Rep")
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn print_csv(groups: &[DuplicateGroup], writer: impl Write) -> std::io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record([
+        "Group",
+        "Reason",
+        "Job Number",
+        "Job Name",
+        "Address",
+        "Claim Number",
+        "Sales Rep",
+    ])?;
+    for (i, group) in groups.iter().enumerate() {
+        for job in &group.jobs {
+            writer.write_record([
+                &(i + 1).to_string(),
+                &group.reason.to_string(),
+                job_number(job),
+                job.job_name.as_deref().unwrap_or(""),
+                job.address.as_deref().unwrap_or(""),
+                job.insurance_claim_number.as_deref().unwrap_or(""),
+                job.sales_rep.as_deref().unwrap_or("Unknown Sales Rep"),
+            ])?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn generate_report_google_sheets(groups: &[DuplicateGroup], update: bool) -> anyhow::Result<()> {
+    let mut rows = vec![mk_row([
+        ExtendedValue::StringValue("Group".to_string()),
+        ExtendedValue::StringValue("Reason".to_string()),
+        ExtendedValue::StringValue("Job Number".to_string()),
+        ExtendedValue::StringValue("Job Name".to_string()),
+        ExtendedValue::StringValue("Address".to_string()),
+        ExtendedValue::StringValue("Claim Number".to_string()),
+        ExtendedValue::StringValue("Sales Rep".to_string()),
+    ])];
+    for (i, group) in groups.iter().enumerate() {
+        for job in &group.jobs {
+            rows.push(mk_row([
+                ExtendedValue::NumberValue((i + 1) as f64),
+                ExtendedValue::StringValue(group.reason.to_string()),
+                ExtendedValue::StringValue(job_number(job).to_owned()),
+                ExtendedValue::StringValue(job.job_name.clone().unwrap_or_default()),
+                ExtendedValue::StringValue(job.address.clone().unwrap_or_default()),
+                ExtendedValue::StringValue(job.insurance_claim_number.clone().unwrap_or_default()),
+                ExtendedValue::StringValue(
+                    job.sales_rep.clone().unwrap_or_else(|| "Unknown Sales Rep".to_owned()),
+                ),
+            ]));
+        }
+    }
+
+    let spreadsheet = Spreadsheet {
+        properties: SpreadsheetProperties {
+            title: Some(format!("Duplicate Jobs Report ({})", Utc::now())),
+        },
+        sheets: Some(vec![Sheet {
+            properties: SheetProperties {
+                title: Some("Duplicates".to_string()),
+                ..Default::default()
+            },
+            data: Some(GridData { start_row: 0, start_column: 0, row_data: rows }),
+        }]),
+        ..Default::default()
+    };
+
+    let url = google_sheets::upload_spreadsheet_blocking(
+        google_sheets::SheetNickname::Duplicates,
+        spreadsheet,
+        update,
+    )?;
+    utils::open_url(url.as_str());
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jobs::test::make_job;
+
+    fn job(jnid: &str, name: &str, address: &str, claim_number: Option<&str>) -> Job {
+        let mut job = make_job(false, None, None, None, None, None);
+        job.jnid = jnid.to_owned();
+        job.job_number = Some(jnid.to_owned());
+        job.job_name = Some(name.to_owned());
+        job.address = Some(address.to_owned());
+        job.insurance_claim_number = claim_number.map(str::to_owned);
+        job
+    }
+
+    #[test]
+    fn find_duplicate_groups() {
+        let jobs = [
+            job("1", "John Smith", "123 Main St.", None),
+            job("2", "john smith", "123 main st", Some("AB-100")),
+            job("3", "Jane Doe", "123 Main St.", Some("ab100")),
+            job("4", "Jane Doe", "456 Oak Ave", None),
+        ];
+        let groups = find_duplicates(&jobs);
+        let groups: Vec<(DuplicateReason, Vec<&str>)> = groups
+            .iter()
+            .map(|group| (group.reason, group.jobs.iter().map(|job| job.jnid.as_str()).collect()))
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                (DuplicateReason::NameAndAddress, vec!["1", "2"]),
+                (DuplicateReason::ClaimNumber, vec!["2", "3"]),
+            ]
+        );
+    }
+}
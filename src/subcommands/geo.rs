@@ -0,0 +1,589 @@
+use std::{
+    io::Write,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use clap::CommandFactory as _;
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::{
+    apis::{
+        google_maps::{self, LookupError},
+        google_sheets::{
+            self,
+            spreadsheet::{
+                mk_row, ExtendedValue, GridData, Sheet, SheetProperties, Spreadsheet,
+                SpreadsheetProperties,
+            },
+            TargetRange,
+        },
+        http, job_nimbus,
+    },
+    argument_conflict,
+    geocode_cache::GeocodeCache,
+    jobs::{Geo, Job},
+    subcommands::output::OutputArgs,
+    utils, CliArgs,
+};
+
+/// How many addresses to look up between progress messages and saves of the
+/// geocode cache.
+const PROGRESS_INTERVAL: usize = 25;
+
+/// How many times to retry a lookup that was rate-limited before giving up.
+const MAX_RETRIES: u32 = 5;
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The JobNimbus API key. This key will be cached.
+    #[arg(long, default_value = None, global = true, env)]
+    jn_api_key: Option<String>,
+
+    /// The Google Maps API key, used to geocode jobs that JobNimbus has no
+    /// coordinates for. This key will be cached. If no key is available, only
+    /// coordinates from JobNimbus and the geocode cache are used.
+    #[arg(long, default_value = None, env)]
+    gm_api_key: Option<String>,
+
+    /// The maximum number of Google Maps lookups to send per second.
+    #[arg(long, default_value = "10")]
+    qps: f64,
+
+    /// Whether to save the coordinates of jobs that JobNimbus had no
+    /// coordinates for back into JobNimbus.
+    #[arg(long)]
+    write_back: bool,
+
+    /// The filter to use when query JobNimbus for jobs, using ElasticSearch
+    /// syntax.
+    #[arg(short, long = "filter", default_value = None)]
+    filter_filename: Option<String>,
+
+    /// The format in which to print the output.
+    #[arg(long, value_enum, default_value = "google-sheets")]
+    format: OutputFormat,
+
+    #[command(flatten)]
+    output_args: OutputArgs,
+
+    /// Only valid with `--format google-sheets`. Writes the locations into an
+    /// existing spreadsheet, starting at the specified cell, instead of
+    /// uploading a spreadsheet of their own. The range is of the form
+    /// `SPREADSHEET_ID:TAB!CELL` (e.g. `1aBcD...:Locations!A1`), and the rest of
+    /// that spreadsheet is left untouched.
+    #[arg(long, value_name = "RANGE", value_parser = google_sheets::parse_target_range)]
+    target_range: Option<TargetRange>,
+
+    /// The sheet of the report to write with `--target-range`. The report only
+    /// has the "Locations" sheet.
+    #[arg(long, value_name = "TITLE", default_value = "Locations", requires = "target_range")]
+    target_sheet: String,
+}
+
+#[derive(Debug, clap::ValueEnum, Clone, Copy, Eq, PartialEq)]
+enum OutputFormat {
+    /// Prints a human-readable report into the output file.
+    Human,
+    /// Prints a CSV file into the output file.
+    Csv,
+    /// Prints a GeoJSON feature collection of the located jobs into the output
+    /// file.
+    GeoJson,
+    /// Outputs a Google Sheet on the user's Google Drive (requires OAuth
+    /// authorization).
+    GoogleSheets,
+}
+
+/// Where the coordinates of the jobs came from.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct GeocodeStats {
+    /// The number of jobs that already had coordinates in JobNimbus.
+    pub from_job_nimbus: usize,
+    /// The number of jobs whose coordinates were found in the geocode cache.
+    pub from_cache: usize,
+    /// The number of jobs that were geocoded using Google Maps.
+    pub geocoded: usize,
+    /// The number of jobs whose address Google Maps couldn't find.
+    pub not_found: usize,
+    /// The number of jobs whose lookup failed for any other reason, or that
+    /// weren't looked up because there was no Google Maps API key.
+    pub failed: usize,
+    /// The number of jobs without an address.
+    pub no_address: usize,
+}
+
+impl GeocodeStats {
+    /// The number of jobs that have coordinates.
+    pub fn num_located(&self) -> usize {
+        self.from_job_nimbus + self.from_cache + self.geocoded
+    }
+
+    /// The number of jobs that lack coordinates.
+    pub fn num_missing(&self) -> usize {
+        self.not_found + self.failed + self.no_address
+    }
+}
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let Args {
+        jn_api_key,
+        gm_api_key,
+        qps,
+        write_back,
+        filter_filename,
+        format,
+        output_args,
+        target_range,
+        target_sheet,
+    } = args;
+
+    let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+    let gm_api_key = match google_maps::get_api_key(gm_api_key) {
+        Ok(key) => Some(key),
+        Err(e) => {
+            warn!("Jobs without coordinates will not be geocoded: {}", e);
+            None
+        }
+    };
+
+    if qps.is_nan() || qps <= 0.0 {
+        CliArgs::command()
+            .error(clap::error::ErrorKind::InvalidValue, "The `--qps` option must be positive")
+            .exit();
+    }
+    output_args.validate(format == OutputFormat::GoogleSheets);
+    if target_range.is_some() && format != OutputFormat::GoogleSheets {
+        argument_conflict(
+            "The `--target-range` option can only be used with `--format google-sheets`",
+        );
+    }
+    if target_range.is_some() && output_args.new {
+        argument_conflict("The `--target-range` option cannot be used with `--new`");
+    }
+
+    let jobs = job_nimbus::get_all_jobs_with_filter_file(&jn_api_key, filter_filename.as_deref())?;
+
+    let mut cache = GeocodeCache::load();
+    let (locations, stats) = if let Some(gm_api_key) = gm_api_key {
+        let mut geocoder = BatchGeocoder::new(gm_api_key, qps)?;
+        geocode_jobs(&jobs, &mut cache, Some(|address: &str| geocoder.lookup(address)))
+    } else {
+        geocode_jobs(&jobs, &mut cache, None::<fn(&str) -> Result<Geo, LookupError>>)
+    };
+    if let Err(e) = cache.save() {
+        warn!("Failed to save geocode cache: {}", e);
+    }
+    info!(
+        "Located {} of {} jobs ({} from JobNimbus, {} from cache, {} geocoded); {} lack coordinates",
+        stats.num_located(),
+        jobs.len(),
+        stats.from_job_nimbus,
+        stats.from_cache,
+        stats.geocoded,
+        stats.num_missing(),
+    );
+
+    if write_back {
+        write_back_locations(&jn_api_key, &locations);
+    }
+
+    let output_writer = output_args.writer()?;
+
+    match format {
+        OutputFormat::Human => print_human(&locations, &stats, output_writer)?,
+        OutputFormat::Csv => print_csv(&locations, output_writer)?,
+        OutputFormat::GeoJson => {
+            serde_json::to_writer_pretty(output_writer, &geo_json(&locations))?;
+        }
+        OutputFormat::GoogleSheets => {
+            let spreadsheet = Spreadsheet {
+                properties: SpreadsheetProperties {
+                    title: Some(format!("Job Locations ({})", Utc::now())),
+                },
+                sheets: Some(vec![locations_sheet(&locations)]),
+                ..Default::default()
+            };
+            let destination = match &target_range {
+                Some(range) => google_sheets::Destination::Range(range, &target_sheet),
+                None if output_args.new => google_sheets::Destination::New,
+                None => google_sheets::Destination::Existing,
+            };
+            let url = google_sheets::upload_to_destination_blocking(
+                google_sheets::SheetNickname::Geo,
+                spreadsheet,
+                destination,
+            )?;
+            utils::open_url(url.as_str());
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds the coordinates of each job from JobNimbus or the geocode cache,
+/// without looking up any addresses.
+pub fn locate_jobs<'a>(jobs: &'a [Job], cache: &GeocodeCache) -> Vec<(&'a Job, Option<Geo>)> {
+    jobs.iter().map(|job| (job, job.geo.or_else(|| cache.get(&job.full_address()?)))).collect()
+}
+
+/// Looks up addresses using Google Maps, sending at most `qps` requests per
+/// second and backing off and retrying when rate-limited.
+struct BatchGeocoder {
+    runtime: tokio::runtime::Runtime,
+    client: reqwest::Client,
+    api_key: String,
+    throttle: Throttle,
+}
+
+impl BatchGeocoder {
+    fn new(api_key: String, qps: f64) -> anyhow::Result<Self> {
+        Ok(Self {
+            runtime: tokio::runtime::Builder::new_multi_thread().enable_all().build()?,
+            client: http::client()?,
+            api_key,
+            throttle: Throttle::new(Duration::from_secs_f64(1.0 / qps)),
+        })
+    }
+
+    fn lookup(&mut self, address: &str) -> Result<Geo, LookupError> {
+        let mut backoff = Duration::from_secs(1);
+        let mut retries = 0;
+        loop {
+            self.throttle.wait();
+            let lookup = google_maps::lookup(self.client.clone(), &self.api_key, address);
+            match self.runtime.block_on(lookup) {
+                Ok(location) => return Ok(Geo { lat: location.latitude, lon: location.longitude }),
+                Err(LookupError::TooFast) if retries < MAX_RETRIES => {
+                    warn!("Rate-limited by Google Maps; retrying in {:?}", backoff);
+                    std::thread::sleep(backoff);
+                    backoff *= 2;
+                    retries += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Spaces out events so that they happen at most once per interval.
+struct Throttle {
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl Throttle {
+    fn new(interval: Duration) -> Self {
+        Self { interval, last: None }
+    }
+
+    /// How long to wait at time `now` before the next event may happen.
+    fn delay(&self, now: Instant) -> Duration {
+        match self.last {
+            Some(last) => (last + self.interval).saturating_duration_since(now),
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Blocks until the next event may happen, and records that it happened.
+    fn wait(&mut self) {
+        std::thread::sleep(self.delay(Instant::now()));
+        self.last = Some(Instant::now());
+    }
+}
+
+/// Finds the coordinates of each job, preferring the coordinates from
+/// JobNimbus, then those in the cache, and finally calling `lookup` on the
+/// job's address (if there is a `lookup`). Successful lookups are added to the
+/// cache, which is saved periodically so that an interrupted run can resume
+/// where it left off. If a lookup is still rate-limited after retrying, no
+/// further lookups are made.
+fn geocode_jobs<'a>(
+    jobs: &'a [Job],
+    cache: &mut GeocodeCache,
+    mut lookup: Option<impl FnMut(&str) -> Result<Geo, LookupError>>,
+) -> (Vec<(&'a Job, Option<Geo>)>, GeocodeStats) {
+    let mut stats = GeocodeStats::default();
+    let mut num_lookups = 0;
+    let locations = jobs
+        .iter()
+        .map(|job| {
+            if let Some(geo) = job.geo {
+                stats.from_job_nimbus += 1;
+                return (job, Some(geo));
+            }
+            let Some(address) = job.full_address() else {
+                stats.no_address += 1;
+                return (job, None);
+            };
+            if let Some(geo) = cache.get(&address) {
+                stats.from_cache += 1;
+                return (job, Some(geo));
+            }
+
+            let Some(lookup_address) = &mut lookup else {
+                stats.failed += 1;
+                return (job, None);
+            };
+            num_lookups += 1;
+            if num_lookups % PROGRESS_INTERVAL == 0 {
+                info!("Looked up {} addresses so far", num_lookups);
+                if let Err(e) = cache.save() {
+                    warn!("Failed to save geocode cache: {}", e);
+                }
+            }
+            match lookup_address(&address) {
+                Ok(geo) => {
+                    stats.geocoded += 1;
+                    cache.insert(&address, geo);
+                    (job, Some(geo))
+                }
+                Err(LookupError::NotFound) => {
+                    stats.not_found += 1;
+                    (job, None)
+                }
+                Err(LookupError::TooFast) => {
+                    warn!("Still rate-limited by Google Maps; skipping the remaining lookups");
+                    stats.failed += 1;
+                    lookup = None;
+                    (job, None)
+                }
+                Err(e) => {
+                    warn!("Failed to geocode \"{}\": {}", address, e);
+                    stats.failed += 1;
+                    (job, None)
+                }
+            }
+        })
+        .collect();
+    (locations, stats)
+}
+
+/// The jobs that JobNimbus has no coordinates for but that have been located
+/// otherwise, along with their coordinates.
+fn missing_from_job_nimbus<'a>(locations: &[(&'a Job, Option<Geo>)]) -> Vec<(&'a Job, Geo)> {
+    locations
+        .iter()
+        .filter(|(job, _)| job.geo.is_none())
+        .filter_map(|(job, geo)| Some((*job, (*geo)?)))
+        .collect()
+}
+
+/// Saves the coordinates of jobs that JobNimbus has no coordinates for into
+/// JobNimbus. Failures are logged and don't stop the remaining updates.
+fn write_back_locations(jn_api_key: &str, locations: &[(&Job, Option<Geo>)]) {
+    let updates = missing_from_job_nimbus(locations);
+    info!("Saving the coordinates of {} jobs to JobNimbus", updates.len());
+    let mut num_updated = 0;
+    for (job, Geo { lat, lon }) in updates {
+        let fields = json!({ "geo": { "lat": lat, "lon": lon } });
+        match job_nimbus::update_job(jn_api_key, &job.jnid, &fields) {
+            Ok(()) => num_updated += 1,
+            Err(e) => warn!("Failed to save the coordinates of job {}: {}", job.jnid, e),
+        }
+    }
+    info!("Saved the coordinates of {} jobs to JobNimbus", num_updated);
+}
+
+const HEADERS: [&str; 7] =
+    ["Job Number", "Job Name", "Status", "Sales Rep", "Address", "Latitude", "Longitude"];
+
+fn job_fields(job: &Job) -> [String; 5] {
+    [
+        job.job_number.clone().unwrap_or_default(),
+        job.job_name.clone().unwrap_or_default(),
+        job.status.to_string(),
+        job.sales_rep.clone().unwrap_or_default(),
+        job.full_address().unwrap_or_default(),
+    ]
+}
+
+fn print_human(
+    locations: &[(&Job, Option<Geo>)],
+    stats: &GeocodeStats,
+    mut writer: impl Write,
+) -> std::io::Result<()> {
+    writeln!(writer, "Located {} of {} jobs", stats.num_located(), locations.len())?;
+    writeln!(writer, "    From JobNimbus: {}", stats.from_job_nimbus)?;
+    writeln!(writer, "    From cache: {}", stats.from_cache)?;
+    writeln!(writer, "    Geocoded: {}", stats.geocoded)?;
+    writeln!(writer, "Missing coordinates for {} jobs", stats.num_missing())?;
+    writeln!(writer, "    No address: {}", stats.no_address)?;
+    writeln!(writer, "    Address not found: {}", stats.not_found)?;
+    writeln!(writer, "    Lookup failed: {}", stats.failed)?;
+    writeln!(writer)?;
+    for (job, geo) in locations {
+        let location = match geo {
+            Some(Geo { lat, lon }) => format!("{:.6}, {:.6}", lat, lon),
+            None => "no coordinates".to_owned(),
+        };
+        writeln!(
+            writer,
+            "{} ({}): {}",
+            job.job_number.as_deref().unwrap_or("unknown job number"),
+            job.full_address().as_deref().unwrap_or("no address"),
+            location,
+        )?;
+    }
+    Ok(())
+}
+
+fn print_csv(locations: &[(&Job, Option<Geo>)], writer: impl Write) -> std::io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record(HEADERS)?;
+    for (job, geo) in locations {
+        let (lat, lon) = match geo {
+            Some(Geo { lat, lon }) => (lat.to_string(), lon.to_string()),
+            None => (String::new(), String::new()),
+        };
+        writer.write_record(job_fields(job).into_iter().chain([lat, lon]))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Creates a GeoJSON feature collection containing a point for each job that
+/// has coordinates.
+fn geo_json(locations: &[(&Job, Option<Geo>)]) -> serde_json::Value {
+    let features: Vec<_> = locations
+        .iter()
+        .filter_map(|(job, geo)| {
+            let Geo { lat, lon } = (*geo)?;
+            Some(json!({
+                "type": "Feature",
+                // GeoJSON puts longitude first
+                "geometry": { "type": "Point", "coordinates": [lon, lat] },
+                "properties": {
+                    "job_number": job.job_number,
+                    "job_name": job.job_name,
+                    "status": job.status.to_string(),
+                    "sales_rep": job.sales_rep,
+                    "address": job.full_address(),
+                },
+            }))
+        })
+        .collect();
+    json!({ "type": "FeatureCollection", "features": features })
+}
+
+fn locations_sheet(locations: &[(&Job, Option<Geo>)]) -> Sheet {
+    let mut rows = Vec::new();
+    rows.push(mk_row(HEADERS.map(|header| ExtendedValue::StringValue(header.to_owned()))));
+    for (job, geo) in locations {
+        let coordinates = match geo {
+            Some(Geo { lat, lon }) => {
+                [ExtendedValue::NumberValue(*lat), ExtendedValue::NumberValue(*lon)]
+            }
+            None => [
+                ExtendedValue::StringValue(String::new()),
+                ExtendedValue::StringValue(String::new()),
+            ],
+        };
+        rows.push(mk_row(
+            job_fields(job).into_iter().map(ExtendedValue::StringValue).chain(coordinates),
+        ));
+    }
+
+    Sheet {
+        properties: SheetProperties { title: Some("Locations".to_string()), ..Default::default() },
+        data: Some(GridData { start_row: 0, start_column: 0, row_data: rows }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jobs::test::make_job;
+
+    #[test]
+    fn geocode_jobs_prefers_existing_coordinates() {
+        fn job(address: Option<&str>, geo: Option<Geo>) -> Job {
+            let mut job = make_job(false, None, None, None, None, None);
+            job.address = address.map(str::to_owned);
+            job.geo = geo;
+            job
+        }
+        let here = Geo { lat: 1.0, lon: 2.0 };
+
+        let mut cache = GeocodeCache::default();
+        cache.insert("2 Cached Rd", here);
+        let jobs = [
+            job(Some("1 Known St"), Some(here)),
+            job(Some("2 Cached Rd"), None),
+            job(Some("3 Lookup Ave"), None),
+            job(Some("4 Nowhere Ln"), None),
+            job(None, None),
+        ];
+        let mut looked_up = Vec::new();
+        let lookup = |address: &str| {
+            looked_up.push(address.to_owned());
+            match address {
+                "3 Lookup Ave" => Ok(here),
+                _ => Err(LookupError::NotFound),
+            }
+        };
+        let (locations, stats) = geocode_jobs(&jobs, &mut cache, Some(lookup));
+
+        assert_eq!(looked_up, ["3 Lookup Ave", "4 Nowhere Ln"]);
+        assert_eq!(
+            stats,
+            GeocodeStats {
+                from_job_nimbus: 1,
+                from_cache: 1,
+                geocoded: 1,
+                not_found: 1,
+                failed: 0,
+                no_address: 1,
+            }
+        );
+        assert_eq!(locations.iter().filter(|(_, geo)| geo.is_some()).count(), 3);
+        assert_eq!(cache.get("3 lookup ave"), Some(here));
+    }
+
+    #[test]
+    fn geocode_jobs_stops_when_rate_limited() {
+        let jobs: Vec<_> = ["1 A St", "2 B St", "3 C St"]
+            .into_iter()
+            .map(|address| {
+                let mut job = make_job(false, None, None, None, None, None);
+                job.address = Some(address.to_owned());
+                job
+            })
+            .collect();
+        let mut num_lookups = 0;
+        let lookup = |_: &str| {
+            num_lookups += 1;
+            Err(LookupError::TooFast)
+        };
+        let (_, stats) = geocode_jobs(&jobs, &mut GeocodeCache::default(), Some(lookup));
+
+        assert_eq!(num_lookups, 1);
+        assert_eq!(stats.failed, 3);
+    }
+
+    #[test]
+    fn missing_from_job_nimbus_skips_known_and_unlocated() {
+        let here = Geo { lat: 1.0, lon: 2.0 };
+        let mut known = make_job(false, None, None, None, None, None);
+        known.geo = Some(here);
+        let found = make_job(false, None, None, None, None, None);
+        let lost = make_job(false, None, None, None, None, None);
+        let locations = [(&known, Some(here)), (&found, Some(here)), (&lost, None)];
+
+        let missing = missing_from_job_nimbus(&locations);
+        assert_eq!(missing.len(), 1);
+        assert!(std::ptr::eq(missing[0].0, &found));
+    }
+
+    #[test]
+    fn throttle_delay() {
+        let start = Instant::now();
+        let mut throttle = Throttle::new(Duration::from_millis(100));
+        assert_eq!(throttle.delay(start), Duration::ZERO);
+
+        throttle.last = Some(start);
+        assert_eq!(throttle.delay(start + Duration::from_millis(30)), Duration::from_millis(70));
+        assert_eq!(throttle.delay(start + Duration::from_millis(150)), Duration::ZERO);
+    }
+}
@@ -0,0 +1,228 @@
+use std::io::Write;
+
+use chrono::Utc;
+
+use crate::{apis::job_nimbus, argument_conflict, jobs::Job, subcommands::output};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The JobNimbus API key. This key will be cached.
+    #[arg(long, default_value = None, global = true, env)]
+    jn_api_key: Option<String>,
+
+    /// The action to perform on the jobs.
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// List the jobs that match a filter.
+    Search {
+        /// A condition that jobs must meet, of the form `FIELD=VALUE`, where
+        /// `FIELD` is the name of a JobNimbus field (e.g.
+        /// `status_name=Collections`). May be specified multiple times, in which
+        /// case jobs must meet all of the conditions.
+        #[arg(
+            short = 'w',
+            long = "where",
+            value_name = "FIELD=VALUE",
+            value_parser = parse_condition
+        )]
+        conditions: Vec<(String, String)>,
+
+        /// The filter to use when query JobNimbus for jobs, using ElasticSearch
+        /// syntax. Cannot be used with `--where`.
+        #[arg(short, long = "filter", default_value = None)]
+        filter_filename: Option<String>,
+
+        /// The format in which to print the output.
+        #[arg(long, value_enum, default_value = "table")]
+        format: OutputFormat,
+
+        /// The file to write the output to. "-" or unspecified will write to
+        /// stdout.
+        #[arg(short, long, default_value = None)]
+        output: Option<String>,
+    },
+    /// Print the JobNimbus record of a single job as JSON, including all of its
+    /// fields.
+    Show {
+        /// The job number or JobNimbus ID of the job.
+        job: String,
+
+        /// Only print the fields whose names contain this text (ignoring
+        /// case).
+        #[arg(long, default_value = None)]
+        search: Option<String>,
+
+        /// The file to write the output to. "-" or unspecified will write to
+        /// stdout.
+        #[arg(short, long, default_value = None)]
+        output: Option<String>,
+    },
+}
+
+#[derive(Debug, clap::ValueEnum, Clone, Copy, Eq, PartialEq)]
+enum OutputFormat {
+    /// Prints a human-readable table into the output file.
+    Table,
+    /// Prints a CSV file into the output file.
+    Csv,
+    /// Prints the jobs exactly as returned by JobNimbus, as a JSON array.
+    Json,
+    /// Prints the jobs exactly as returned by JobNimbus, as one JSON object per
+    /// line.
+    Jsonl,
+}
+
+fn parse_condition(condition: &str) -> Result<(String, String), String> {
+    let (field, value) = condition
+        .split_once('=')
+        .ok_or_else(|| format!("expected `FIELD=VALUE`, but found \"{}\"", condition))?;
+    Ok((field.trim().to_owned(), value.trim().to_owned()))
+}
+
+const HEADERS: [&str; 7] =
+    ["Job Number", "Job Name", "Sales Rep", "Status", "Days In Status", "Receivable", "Address"];
+
+fn job_fields(job: &Job) -> [String; 7] {
+    let days_in_status = Utc::now().signed_duration_since(job.status_mod_date).num_days();
+    [
+        job.job_number.clone().unwrap_or_default(),
+        job.job_name.clone().unwrap_or_default(),
+        job.sales_rep.clone().unwrap_or_default(),
+        job.status.to_string(),
+        days_in_status.to_string(),
+        format!("{:.2}", job.amt_receivable as f64 / 100.0),
+        job.full_address().unwrap_or_default(),
+    ]
+}
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let Args { jn_api_key, command } = args;
+
+    match command {
+        Command::Search { conditions, filter_filename, format, output } => {
+            if !conditions.is_empty() && filter_filename.is_some() {
+                argument_conflict("The `--where` option cannot be used with `--filter`");
+            }
+
+            let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+            let filter = if filter_filename.is_some() {
+                job_nimbus::read_filter_file(filter_filename.as_deref())?
+            } else if !conditions.is_empty() {
+                Some(job_nimbus::build_filter(
+                    conditions.iter().map(|(field, value)| (field.as_str(), value.as_str())),
+                ))
+            } else {
+                None
+            };
+            let jobs = job_nimbus::get_all_jobs_from_job_nimbus(&jn_api_key, filter.as_deref())?;
+
+            let output_writer = output::writer(output.as_deref())?;
+
+            match format {
+                OutputFormat::Table => print_table(&jobs, output_writer)?,
+                OutputFormat::Csv => print_csv(&jobs, output_writer)?,
+                OutputFormat::Json => {
+                    let jobs: Vec<serde_json::Value> = jobs.into_iter().map(Into::into).collect();
+                    serde_json::to_writer_pretty(output_writer, &jobs)?;
+                }
+                OutputFormat::Jsonl => {
+                    let mut output_writer = output_writer;
+                    for job in jobs {
+                        serde_json::to_writer(&mut output_writer, &serde_json::Value::from(job))?;
+                        writeln!(output_writer)?;
+                    }
+                }
+            }
+        }
+        Command::Show { job, search, output } => {
+            let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+            let filter = job_nimbus::build_filter([(job_field(&job), job.as_str())]);
+            let jobs = job_nimbus::get_all_jobs_from_job_nimbus(&jn_api_key, Some(&filter))?;
+            let Some(job) = jobs.into_iter().next() else {
+                anyhow::bail!("No job found with number or ID \"{}\"", job);
+            };
+
+            let mut record = job.raw;
+            if let Some(search) = search {
+                let search = search.to_lowercase();
+                record.retain(|key, _| key.to_lowercase().contains(&search));
+            }
+
+            let mut output_writer = output::writer(output.as_deref())?;
+            serde_json::to_writer_pretty(&mut output_writer, &record)?;
+            writeln!(output_writer)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The JobNimbus field to look up a job by. Job numbers are short and numeric,
+/// whereas JobNimbus IDs are long and alphanumeric.
+fn job_field(job: &str) -> &'static str {
+    if job.chars().all(|c| c.is_ascii_digit() || c == '-') {
+        "number"
+    } else {
+        "jnid"
+    }
+}
+
+pub fn print_table(jobs: &[Job], mut writer: impl Write) -> std::io::Result<()> {
+    let rows: Vec<_> = jobs.iter().map(job_fields).collect();
+    let mut widths = HEADERS.map(|header| header.chars().count());
+    for row in &rows {
+        for (width, field) in widths.iter_mut().zip(row) {
+            *width = (*width).max(field.chars().count());
+        }
+    }
+
+    let mut write_row = |fields: &[&str]| {
+        let cells: Vec<_> = fields
+            .iter()
+            .zip(widths)
+            .map(|(field, width)| format!("{:width$}", field, width = width))
+            .collect();
+        writeln!(writer, "{}", cells.join(" | ").trim_end())
+    };
+    write_row(&HEADERS)?;
+    for row in &rows {
+        write_row(&row.each_ref().map(String::as_str))?;
+    }
+    Ok(())
+}
+
+pub fn print_csv(jobs: &[Job], writer: impl Write) -> std::io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record(HEADERS)?;
+    for job in jobs {
+        writer.write_record(job_fields(job))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_conditions() {
+        assert_eq!(
+            parse_condition("sales_rep_name = John Smith"),
+            Ok(("sales_rep_name".to_owned(), "John Smith".to_owned()))
+        );
+        assert_eq!(parse_condition("number=a=b"), Ok(("number".to_owned(), "a=b".to_owned())));
+        assert!(parse_condition("Collections").is_err());
+    }
+
+    #[test]
+    fn job_field_by_format() {
+        assert_eq!(job_field("1234"), "number");
+        assert_eq!(job_field("2023-1234"), "number");
+        assert_eq!(job_field("m1abc2def3ghi4"), "jnid");
+    }
+}
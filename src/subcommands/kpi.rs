@@ -1,20 +1,38 @@
 use std::collections::BTreeMap;
 use std::fmt::Display;
 use std::path::Path;
+use std::rc::Rc;
 
+use crate::apis::google_sheets::{self, TargetRange};
 use crate::apis::job_nimbus;
-use crate::CliArgs;
+use crate::argument_conflict;
+use crate::claim_numbers::ClaimNumberFormats;
+use crate::job_tracker::JobTrackerError;
+use crate::jobs::{AnalyzedJob, Job, JobAnalysisError, TimeDelta, Timestamp};
+use crate::red_flag_acks::RedFlagAcks;
+use crate::report_profile::ReportProfile;
+use crate::roster::Roster;
+use crate::run_summary;
+use crate::snapshots;
+use crate::subcommands::carriers;
+use crate::subcommands::crews;
+use crate::webhook::{self, ReportSummary};
 use anyhow::Context;
 use anyhow::Result;
+use chrono::DateTime;
 use chrono::Datelike as _;
+use chrono::Months;
 use chrono::NaiveDate;
 use chrono::NaiveDateTime;
 use chrono::NaiveTime;
 use chrono::TimeZone as _;
 use chrono::Utc;
-use clap::CommandFactory as _;
+use processing::{JobTrackerStats, Reconciliation};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use tracing::warn;
 
-#[derive(clap::Args, Debug)]
+#[derive(clap::Args, Debug, Clone)]
 pub struct Args {
     /// The JobNimbus API key to use. This key will be cached.
     #[arg(long, default_value = None, global = true, env)]
@@ -41,6 +59,130 @@ pub struct Args {
     #[arg(long = "to", default_value = "today")]
     to_date: String,
 
+    /// Whether to compare each stat with the same stat over the same date
+    /// range one year earlier. Requires both `--from` and `--to` to be
+    /// actual dates (i.e. not "forever").
+    #[arg(long)]
+    compare_yoy: bool,
+
+    /// The minimum number of jobs that a conversion rate must be calculated
+    /// from. Conversion rates calculated from fewer jobs are not shown, since
+    /// they are likely to be misleading.
+    #[arg(long, default_value_t = 5)]
+    min_sample: usize,
+
+    /// The maximum number of days that it can take a job to reach a milestone
+    /// from the previous one. Longer durations are most likely caused by typos
+    /// in dates, so they are excluded from the average times and reported as
+    /// red flags instead.
+    #[arg(long, default_value_t = 365)]
+    max_step_days: i64,
+
+    /// The number of days past now (or `--as-of`) that a milestone date can
+    /// be. Later dates, e.g. an install date typed with next year, make a job
+    /// look further along than it is, so they are reported as red flags.
+    #[arg(
+        long,
+        value_name = "DAYS",
+        default_value_t = processing::FutureDates::DEFAULT_HORIZON_DAYS
+    )]
+    future_horizon_days: i64,
+
+    /// Whether to leave the jobs with milestone dates past the future horizon
+    /// out of the stats, instead of only flagging them.
+    #[arg(long)]
+    exclude_future_dated: bool,
+
+    /// The roster file containing information about each sales rep. This is a
+    /// CSV file with a header row containing the column "name" and optionally
+    /// the columns "active", "branch", and "team". If specified, then inactive
+    /// reps are excluded from the report (although their jobs still count
+    /// towards the global stats), reps are grouped into teams, and jobs
+    /// assigned to reps that are not in the roster are flagged.
+    #[arg(long, default_value = None)]
+    roster: Option<String>,
+
+    /// Only valid with `--roster`. Whether to include inactive reps in the
+    /// report.
+    #[arg(long)]
+    include_inactive: bool,
+
+    /// The JSON file containing the weights used to calculate the composite
+    /// score of each sales rep, e.g. `{"close_rate": 0.4, "install_rate": 0.2,
+    /// "volume": 0.2, "time_to_close": 0.2}`. Weights that are not specified
+    /// are zero. If this option is not specified, then the weights in the
+    /// example are used.
+    #[arg(long, default_value = None)]
+    scoring: Option<String>,
+
+    /// Only include jobs of the specified trade (e.g. "Roofing"), from the
+    /// "Trade" or "Work Type" field of each job.
+    #[arg(long, default_value = None)]
+    trade: Option<String>,
+
+    /// The URL to send a JSON summary of the report to once it has been
+    /// generated, e.g. a Zapier or Make webhook.
+    #[arg(long, value_name = "URL", default_value = None)]
+    post_summary_to: Option<String>,
+
+    /// Only valid with `--format human`. Instead of the full report, print a
+    /// side-by-side comparison of the stats of these sales reps, highlighting
+    /// the best and worst value of each stat. Specify this option 2 to 4
+    /// times; "Global" refers to the global stats.
+    #[arg(long, value_name = "REP")]
+    compare: Vec<String>,
+
+    /// Only valid with `--format human`. List the jobs that contributed to
+    /// each conversion on their own lines, with the date they were settled
+    /// and a link to the job in JobNimbus, including for the global stats.
+    #[arg(long)]
+    list_jobs: bool,
+
+    /// Only valid with `--format human`. Instead of the full report, explain
+    /// how the job with this job number was counted: which sales rep it
+    /// belongs to, whether it was settled within the date range, which
+    /// conversions it counted toward, and its red flags.
+    #[arg(long, value_name = "JOB_NUMBER")]
+    find_job: Option<String>,
+
+    /// Only valid with `--format human` or `--profile rep`. Instead of the
+    /// full report, print only the tracker of the sales rep with this name
+    /// (ignoring case), e.g. to prepare for a one-on-one. With `--profile rep`
+    /// and another format, the report only includes this rep.
+    #[arg(long, value_name = "NAME")]
+    rep: Option<String>,
+
+    /// Who the report is for, which decides what it includes.
+    #[arg(long, value_enum, default_value = "manager")]
+    profile: ReportProfile,
+
+    /// Generate the report as it would have appeared at this time, from the
+    /// latest snapshot of the jobs taken at or before it (see the README),
+    /// ignoring later edits to jobs. "today" and "ytd" are also relative to
+    /// this time. Either an RFC 3339 timestamp or a date in the format
+    /// `%Y-%m-%d`. Cannot be used with `--filter`.
+    #[arg(long, value_name = "TIMESTAMP", default_value = None)]
+    as_of: Option<String>,
+
+    /// Warn if the jobs were fetched more than this many hours before the
+    /// time that the report is generated as of, i.e. if the latest snapshot
+    /// before `--as-of` is this old.
+    #[arg(long, value_name = "HOURS", default_value = "24")]
+    max_data_age: i64,
+
+    /// Only valid with `--format human`. Instead of generating the report
+    /// once, keep printing it to the terminal, clearing the screen and
+    /// fetching the jobs updated since the previous fetch at this interval,
+    /// e.g. "15m", "1h", or "90s". Runs until interrupted with Ctrl+C.
+    #[arg(long, value_name = "INTERVAL", value_parser = watch::parse_interval)]
+    watch: Option<std::time::Duration>,
+
+    /// Only valid with `--format google-sheets`. Where to export the red
+    /// flags, e.g. to share them with data-entry staff separately from the
+    /// stats.
+    #[arg(long = "red-flags", value_enum, default_value = "include")]
+    red_flags_export: RedFlagsExport,
+
     /// The format in which to print the output.
     #[arg(long, value_enum, default_value = "google-sheets")]
     format: OutputFormat,
@@ -56,6 +198,18 @@ pub struct Args {
     /// for this command if it exists.
     #[arg(long)]
     new: bool,
+
+    /// Only valid with `--format google-sheets`. Writes a single sheet of the
+    /// report into an existing spreadsheet, starting at the specified cell,
+    /// instead of uploading the whole report. The range is of the form
+    /// `SPREADSHEET_ID:TAB!CELL` (e.g. `1aBcD...:KPI!A1`), and the rest of
+    /// that spreadsheet is left untouched.
+    #[arg(long, value_name = "RANGE", value_parser = google_sheets::parse_target_range)]
+    target_range: Option<TargetRange>,
+
+    /// The sheet of the report to write with `--target-range`.
+    #[arg(long, value_name = "TITLE", default_value = "Leaderboard", requires = "target_range")]
+    target_sheet: String,
 }
 
 #[derive(Debug, clap::ValueEnum, Clone, Copy, Eq, PartialEq)]
@@ -70,47 +224,194 @@ enum OutputFormat {
     /// Outputs a Google Sheet on the user's Google Drive (requires OAuth
     /// authorization).
     GoogleSheets,
+    /// Prints a single HTML page into the output directory (or into stdout),
+    /// laid out by the `kpi.html` template. See the README for how to
+    /// customize it.
+    Html,
+}
+
+#[derive(Debug, clap::ValueEnum, Clone, Copy, Eq, PartialEq)]
+enum RedFlagsExport {
+    /// Includes the red flags as a tab of the KPI spreadsheet.
+    Include,
+    /// Exports the red flags to their own spreadsheet, separate from the KPI
+    /// spreadsheet.
+    Separate,
+    /// Exports only the red flags to their own spreadsheet, without updating
+    /// the KPI spreadsheet, e.g. to refresh them more often than the stats.
+    Only,
 }
 
 pub fn main(args: Args) -> Result<()> {
-    let Args { jn_api_key, filter_filename, from_date, to_date, format, output, new } = args;
+    let Some(interval) = args.watch else {
+        return run(args, None);
+    };
+    if args.format != OutputFormat::Human {
+        argument_conflict("The `--watch` option can only be used with `--format human`");
+    }
+    if args.output.as_deref().is_some_and(|output| output != "-") {
+        argument_conflict(
+            "The `--watch` option prints to the terminal, so it cannot be used with `--output`",
+        );
+    }
+    if args.as_of.is_some() {
+        argument_conflict("The `--watch` option cannot be used with `--as-of`");
+    }
+    if args.post_summary_to.is_some() {
+        // every refresh would send the summary again
+        argument_conflict("The `--watch` option cannot be used with `--post-summary-to`");
+    }
+    watch::run(args, interval)
+}
 
-    let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+/// Generates the report. If `prefetched` is specified, then the report is
+/// generated from those jobs (fetched at the specified time) instead of
+/// fetching them from JobNimbus, and the API key and filter are ignored.
+pub fn run(args: Args, prefetched: Option<(Timestamp, Vec<Job>)>) -> Result<()> {
+    let Args {
+        jn_api_key,
+        filter_filename,
+        from_date,
+        to_date,
+        compare_yoy,
+        min_sample,
+        max_step_days,
+        future_horizon_days,
+        exclude_future_dated,
+        roster,
+        include_inactive,
+        scoring,
+        trade,
+        post_summary_to,
+        compare,
+        list_jobs,
+        find_job,
+        rep,
+        profile,
+        as_of,
+        max_data_age,
+        watch: _,
+        red_flags_export,
+        format,
+        output,
+        new,
+        target_range,
+        target_sheet,
+    } = args;
 
-    if format == OutputFormat::GoogleSheets && output.is_some() {
-        CliArgs::command()
-            .error(
-                clap::error::ErrorKind::ArgumentConflict,
-                "The `--output` option cannot be used with `--format google-sheets`",
-            )
-            .exit();
+    let max_achieve_time = TimeDelta::days(max_step_days);
+
+    crate::subcommands::output::validate(
+        format == OutputFormat::GoogleSheets,
+        output.is_some(),
+        new,
+    );
+
+    if target_range.is_some() && format != OutputFormat::GoogleSheets {
+        argument_conflict(
+            "The `--target-range` option can only be used with `--format google-sheets`",
+        );
     }
-    if format != OutputFormat::GoogleSheets && new {
-        CliArgs::command()
-            .error(
-                clap::error::ErrorKind::ArgumentConflict,
-                "The `--new` option can only be used with `--format google-sheets`",
-            )
-            .exit();
+    if red_flags_export != RedFlagsExport::Include && format != OutputFormat::GoogleSheets {
+        argument_conflict(
+            "The `--red-flags` option can only be used with `--format google-sheets`",
+        );
+    }
+    if target_range.is_some() && new {
+        argument_conflict("The `--target-range` option cannot be used with `--new`");
     }
 
-    let filter = if let Some(filter_filename) = filter_filename {
-        Some(std::fs::read_to_string(filter_filename)?)
-    } else {
-        None
+    if !compare.is_empty() && format != OutputFormat::Human {
+        argument_conflict("The `--compare` option can only be used with `--format human`");
+    }
+    if list_jobs && format != OutputFormat::Human {
+        argument_conflict("The `--list-jobs` option can only be used with `--format human`");
+    }
+    if find_job.is_some() && format != OutputFormat::Human {
+        argument_conflict("The `--find-job` option can only be used with `--format human`");
+    }
+    if rep.is_some() && format != OutputFormat::Human && profile != ReportProfile::Rep {
+        argument_conflict(
+            "The `--rep` option can only be used with `--format human` or `--profile rep`",
+        );
+    }
+    if profile == ReportProfile::Rep && rep.is_none() {
+        argument_conflict("The `--profile rep` option requires `--rep`");
+    }
+    if profile == ReportProfile::Accounting {
+        argument_conflict(
+            "The KPI report is made of conversion details, which the `accounting` profile \
+                 leaves out; use `ahitool ar` instead",
+        );
+    }
+    let modes = [!compare.is_empty(), find_job.is_some(), rep.is_some()];
+    if modes.into_iter().filter(|&mode| mode).count() > 1 {
+        argument_conflict(
+            "Only one of `--compare`, `--find-job`, and `--rep` can be used at a time",
+        );
+    }
+    if !compare.is_empty() && !(2..=4).contains(&compare.len()) {
+        argument_conflict("The `--compare` option must be specified 2 to 4 times");
+    }
+
+    if as_of.is_some() && filter_filename.is_some() {
+        argument_conflict("The `--as-of` option cannot be used with `--filter`");
+    }
+    let as_of = as_of
+        .map(|as_of| {
+            DateTime::parse_from_rfc3339(&as_of)
+                .map(|dt| dt.to_utc())
+                .or_else(|_| {
+                    NaiveDate::parse_from_str(&as_of, "%Y-%m-%d").map(|date| {
+                        Utc.from_utc_datetime(&NaiveDateTime::new(date, NaiveTime::MIN))
+                    })
+                })
+                .context("Invalid timestamp. Use RFC 3339 or '%Y-%m-%d'.")
+        })
+        .transpose()?;
+    let now = as_of.unwrap_or_else(Utc::now);
+    let future_dates = processing::FutureDates {
+        horizon: now + TimeDelta::days(future_horizon_days),
+        exclude: exclude_future_dated,
+    };
+
+    if roster.is_none() && include_inactive {
+        argument_conflict("The `--include-inactive` option can only be used with `--roster`");
+    }
+    let roster = roster.map(Roster::load).transpose()?;
+    let score_weights = scoring.map(scoring::ScoreWeights::load).transpose()?.unwrap_or_default();
+
+    let (fetched_at, mut jobs) = match prefetched {
+        Some(prefetched) => prefetched,
+        None if as_of.is_some() => {
+            let (taken_at, jobs) = snapshots::load_as_of(now)?;
+            info!("Generating the report as of {} from the snapshot taken at {}", now, taken_at);
+            (taken_at, jobs)
+        }
+        None => {
+            let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+            let fetched_at = Utc::now();
+            (
+                fetched_at,
+                job_nimbus::get_all_jobs_with_filter_file(&jn_api_key, filter_filename.as_deref())?,
+            )
+        }
     };
-    let jobs = job_nimbus::get_all_jobs_from_job_nimbus(&jn_api_key, filter.as_deref())?;
+    snapshots::warn_if_stale(fetched_at, now, TimeDelta::hours(max_data_age));
+    if let Some(trade) = &trade {
+        jobs.retain(|job| job.is_trade(trade));
+    }
 
     let from_date = match from_date.as_str() {
         "forever" => None,
         "ytd" => Some(
             Utc.from_utc_datetime(&NaiveDateTime::new(
-                NaiveDate::from_ymd_opt(Utc::now().year(), 1, 1)
+                NaiveDate::from_ymd_opt(now.year(), 1, 1)
                     .expect("Jan 1 should always be valid in the current year."),
                 NaiveTime::MIN,
             )),
         ),
-        "today" => Some(Utc::now()),
+        "today" => Some(now),
         date_string => Some(
             NaiveDate::parse_from_str(date_string, "%Y-%m-%d")
                 .map(|date| Utc.from_utc_datetime(&NaiveDateTime::new(date, NaiveTime::MIN)))
@@ -119,7 +420,7 @@ pub fn main(args: Args) -> Result<()> {
     };
     let to_date = match to_date.as_str() {
         "forever" => None,
-        "today" => Some(Utc::now()),
+        "today" => Some(now),
         date_string => Some(
             NaiveDate::parse_from_str(date_string, "%Y-%m-%d")
                 .map(|date| Utc.from_utc_datetime(&NaiveDateTime::new(date, NaiveTime::MIN)))
@@ -127,29 +428,455 @@ pub fn main(args: Args) -> Result<()> {
         ),
     };
 
-    let (trackers, red_flags) = processing::process_jobs(jobs.into_iter(), (from_date, to_date));
-    let tracker_stats = trackers
-        .into_iter()
-        .map(|(rep, tracker)| (rep, processing::calculate_job_tracker_stats(&tracker)))
-        .filter(|(_, stats)| stats.appt_count > 0)
-        .collect::<BTreeMap<_, _>>();
+    let found_job = find_job.as_deref().map(|job_number| {
+        jobs.iter()
+            .find(|job| {
+                job.job_number.as_deref().is_some_and(|num| num.trim() == job_number.trim())
+            })
+            .cloned()
+    });
+
+    let prev_tracker_stats = if compare_yoy {
+        let (Some(from_date), Some(to_date)) = (from_date, to_date) else {
+            argument_conflict(
+                "The `--compare-yoy` option requires both `--from` and `--to` to be dates",
+            );
+        };
+        let one_year = Months::new(12);
+        let prev_range = (
+            from_date.checked_sub_months(one_year).context("Date out of range")?,
+            to_date.checked_sub_months(one_year).context("Date out of range")?,
+        );
+        let prev_data = processing::process_jobs(
+            jobs.clone().into_iter(),
+            (Some(prev_range.0), Some(prev_range.1)),
+            roster.as_ref(),
+            None,
+            max_achieve_time,
+            Some(future_dates),
+        );
+        Some(
+            prev_data
+                .trackers
+                .into_iter()
+                .map(|(rep, tracker)| {
+                    Ok((rep, processing::calculate_job_tracker_stats(&tracker, min_sample)?))
+                })
+                .collect::<Result<BTreeMap<_, _>, JobTrackerError>>()?,
+        )
+    } else {
+        None
+    };
+
+    run_summary::add_count("jobs_analyzed", jobs.len());
+    let options = KpiOptions {
+        from: from_date,
+        to: to_date,
+        roster,
+        claim_number_formats: Some(ClaimNumberFormats::load()),
+        include_inactive,
+        min_sample,
+        max_step_days,
+        future_dates: Some(future_dates),
+        score_weights,
+    };
+    let mut analysis = run_summary::time_phase("analyze", || analyze_jobs(jobs, &options))?;
+    let mut prev_tracker_stats = prev_tracker_stats;
+    if let (ReportProfile::Rep, Some(name)) = (profile, &rep) {
+        let Some(subject) = find_subject(&analysis.tracker_stats, name).cloned() else {
+            anyhow::bail!("No stats found for sales rep \"{}\"", name);
+        };
+        analysis.restrict_to(&subject);
+        if let Some(prev_tracker_stats) = &mut prev_tracker_stats {
+            prev_tracker_stats.retain(|other, _| *other == subject || *other == KpiSubject::Global);
+        }
+    }
+    // the red flags that were ticked in the red flags sheet since the last
+    // export are read back first, so that the export doesn't untick them
+    let red_flags_nickname = match red_flags_export {
+        _ if format != OutputFormat::GoogleSheets => None,
+        RedFlagsExport::Separate => Some(google_sheets::SheetNickname::RedFlags),
+        RedFlagsExport::Only if target_range.is_none() => {
+            Some(google_sheets::SheetNickname::RedFlags)
+        }
+        RedFlagsExport::Include if target_range.is_none() => {
+            Some(google_sheets::SheetNickname::Kpi)
+        }
+        _ => None,
+    };
+    if let Some(nickname) = red_flags_nickname {
+        sync_red_flag_acks(nickname);
+    }
+    let acks = RedFlagAcks::load();
+    let is_acknowledged = |(job, err): &(Rc<AnalyzedJob>, JobAnalysisError)| {
+        acks.is_acknowledged(&job.job.jnid, err.kind())
+    };
+    let num_acknowledged: usize =
+        analysis.red_flags.values().flatten().filter(|flag| is_acknowledged(flag)).count();
+    let num_red_flags = analysis.red_flags.values().map(Vec::len).sum::<usize>() - num_acknowledged;
+    if format != OutputFormat::GoogleSheets {
+        // acknowledged red flags are only listed in the sheet, where they can
+        // be unticked
+        for red_flags in analysis.red_flags.values_mut() {
+            red_flags.retain(|flag| !is_acknowledged(flag));
+        }
+        analysis.red_flags.retain(|_, red_flags| !red_flags.is_empty());
+    }
+    let parameters = run_parameters(
+        (from_date, to_date),
+        filter_filename.as_deref(),
+        trade.as_deref(),
+        as_of,
+        fetched_at,
+        min_sample,
+        max_step_days,
+        future_dates,
+    );
+    let report = (format == OutputFormat::Html).then(|| report::KpiReport {
+        parameters: parameters.clone(),
+        ..report::KpiReport::new(&analysis, &options)
+    });
+    let parameters = output::parameters_line(&parameters);
+    let Analysis { tracker_stats, leaderboard, red_flags, reconciliation, settled_jobs } = analysis;
+    run_summary::add_count("red_flags", num_red_flags);
+    if num_acknowledged > 0 {
+        run_summary::add_count("red_flags_acknowledged", num_acknowledged);
+    }
+    run_summary::add_count("jobs_excluded", reconciliation.excluded.len());
+    let carrier_stats = carriers::calculate_carrier_stats(settled_jobs.iter().map(Rc::as_ref));
+    let crew_stats = crews::calculate_crew_stats(settled_jobs.iter().map(Rc::as_ref));
 
     let output = output.filter(|s| s != "-");
-    let output = output.as_deref().map(|path| Path::new(path));
+    let output = output.as_deref().map(Path::new);
+    if let Some(found_job) = found_job {
+        let Some(job) = found_job else {
+            anyhow::bail!(
+                "No job with job number \"{}\" was found; it may have been left out by the \
+                 filter or `--trade`",
+                find_job.unwrap_or_default()
+            );
+        };
+        let out: Box<dyn std::io::Write> = match output {
+            Some(path) => Box::new(std::fs::File::create(path)?),
+            None => Box::new(std::io::stdout()),
+        };
+        output::print_job_lookup(&job, (from_date, to_date), &tracker_stats, &red_flags, out)?;
+        return Ok(());
+    }
+    if let Some(name) = rep.as_ref().filter(|_| format == OutputFormat::Human) {
+        let Some(subject) = find_subject(&tracker_stats, name) else {
+            anyhow::bail!("No stats found for sales rep \"{}\"", name);
+        };
+        let out: Box<dyn std::io::Write> = match output {
+            Some(path) => Box::new(std::fs::File::create(path)?),
+            None => Box::new(std::io::stdout()),
+        };
+        let prev_stats = prev_tracker_stats.as_ref().map(|prev| prev.get(subject));
+        output::print_tracker_human(subject, &tracker_stats[subject], prev_stats, list_jobs, out)?;
+        return Ok(());
+    }
+    if !compare.is_empty() {
+        let mut subjects = Vec::new();
+        for name in &compare {
+            let Some(subject) = find_subject(&tracker_stats, name) else {
+                anyhow::bail!("No stats found for sales rep \"{}\"", name);
+            };
+            subjects.push((subject, &tracker_stats[subject]));
+        }
+        let out: Box<dyn std::io::Write> = match output {
+            Some(path) => Box::new(std::fs::File::create(path)?),
+            None => Box::new(std::io::stdout()),
+        };
+        output::print_comparison(&subjects, out)?;
+        return Ok(());
+    }
+    let mut spreadsheet_url = None;
     match format {
-        OutputFormat::Human => output::print_report_human(&tracker_stats, &red_flags, output)?,
-        OutputFormat::Csv => output::print_report_csv(&tracker_stats, &red_flags, output)?,
+        OutputFormat::Human => output::print_report_human(
+            &tracker_stats,
+            prev_tracker_stats.as_ref(),
+            &leaderboard,
+            &red_flags,
+            &reconciliation,
+            &parameters,
+            list_jobs,
+            output,
+        )?,
+        OutputFormat::Csv => output::print_report_csv(
+            &tracker_stats,
+            prev_tracker_stats.as_ref(),
+            &leaderboard,
+            &red_flags,
+            &reconciliation,
+            &parameters,
+            output,
+        )?,
+        OutputFormat::Html => {
+            output::print_report_html(&report.expect("the report is built for HTML"), output)?
+        }
         OutputFormat::GoogleSheets => {
-            output::generate_report_google_sheets(&tracker_stats, &red_flags, !new)?
+            let destination = match &target_range {
+                Some(range) => google_sheets::Destination::Range(range, &target_sheet),
+                None if new => google_sheets::Destination::New,
+                None => google_sheets::Destination::Existing,
+            };
+            let red_flags_sheet = output::red_flags_sheet(&red_flags, &acks);
+            let reconciliation_sheet = output::reconciliation_sheet(&reconciliation);
+            let excluded_jobs_sheet = output::excluded_jobs_sheet(&reconciliation.excluded);
+            spreadsheet_url = Some(match red_flags_export {
+                RedFlagsExport::Include => output::generate_report_google_sheets(
+                    &tracker_stats,
+                    prev_tracker_stats.as_ref(),
+                    &leaderboard,
+                    [
+                        red_flags_sheet,
+                        excluded_jobs_sheet,
+                        reconciliation_sheet,
+                        output::definitions_sheet(),
+                    ],
+                    &carrier_stats,
+                    &crew_stats,
+                    &parameters,
+                    destination,
+                )?,
+                RedFlagsExport::Separate => {
+                    // the red flags never go to the target range, which is
+                    // meant for the stats
+                    let red_flags_destination = if new {
+                        google_sheets::Destination::New
+                    } else {
+                        google_sheets::Destination::Existing
+                    };
+                    output::generate_red_flags_google_sheets(
+                        red_flags_sheet,
+                        &parameters,
+                        red_flags_destination,
+                    )?;
+                    output::generate_report_google_sheets(
+                        &tracker_stats,
+                        prev_tracker_stats.as_ref(),
+                        &leaderboard,
+                        [excluded_jobs_sheet, reconciliation_sheet, output::definitions_sheet()],
+                        &carrier_stats,
+                        &crew_stats,
+                        &parameters,
+                        destination,
+                    )?
+                }
+                RedFlagsExport::Only => output::generate_red_flags_google_sheets(
+                    red_flags_sheet,
+                    &parameters,
+                    destination,
+                )?,
+            });
+        }
+    }
+
+    if let Some(url) = post_summary_to {
+        let mut summary = ReportSummary::new("kpi", Utc::now(), fetched_at);
+        summary.spreadsheet_url = spreadsheet_url;
+        if let Some(global) = tracker_stats.get(&KpiSubject::Global) {
+            summary.counts.insert("appointments".to_owned(), global.appt_count);
+            summary.counts.insert("contracts".to_owned(), global.contract_count());
+            summary.counts.insert("installs".to_owned(), global.install_count);
         }
+        summary.counts.insert("red_flags".to_owned(), num_red_flags);
+        summary.counts.insert("excluded_jobs".to_owned(), reconciliation.excluded.len());
+        summary.counts.insert("sheets_api_requests".to_owned(), google_sheets::request_count());
+        webhook::post_summary_or_warn(&url, &summary);
     }
 
     Ok(())
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
-enum KpiSubject {
+/// Records which red flags are ticked in the red flags sheet of the spreadsheet
+/// saved under the nickname, if it exists. Failing to read the sheet only means
+/// that the acknowledgements made since the last export are lost, so it is not
+/// an error.
+fn sync_red_flag_acks(nickname: google_sheets::SheetNickname) {
+    let rows = match google_sheets::read_sheet_blocking(nickname, "Red Flags") {
+        Ok(Some(rows)) => rows,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Failed to read the acknowledged red flags from the red flags sheet: {}", e);
+            return;
+        }
+    };
+    match RedFlagAcks::sync(output::red_flags_sheet_acks(&rows)) {
+        Ok(0) => {}
+        Ok(changed) => {
+            info!("Updated the acknowledgements of {} red flags from the sheet", changed)
+        }
+        Err(e) => warn!("Failed to save the acknowledged red flags: {}", e),
+    }
+}
+
+/// The options of the KPI analysis, independent of how the report is output.
+pub struct KpiOptions {
+    /// Only jobs settled at or after this time are counted. `None` means the
+    /// beginning of time.
+    pub from: Option<Timestamp>,
+    /// Only jobs settled at or before this time are counted. `None` means the
+    /// end of time.
+    pub to: Option<Timestamp>,
+    pub roster: Option<Roster>,
+    /// If specified, then jobs whose claim number doesn't match the format of
+    /// their carrier are flagged.
+    pub claim_number_formats: Option<ClaimNumberFormats>,
+    /// Whether to include the stats of the sales reps that are marked inactive
+    /// in the roster.
+    pub include_inactive: bool,
+    /// Conversion rates calculated from fewer jobs than this are marked as
+    /// small samples.
+    pub min_sample: usize,
+    /// Jobs that took longer than this many days to reach a milestone from the
+    /// previous one are left out of the average times and flagged.
+    pub max_step_days: i64,
+    /// How to handle milestone dates in the future. `None` doesn't check them.
+    pub future_dates: Option<processing::FutureDates>,
+    pub score_weights: scoring::ScoreWeights,
+}
+
+impl Default for KpiOptions {
+    fn default() -> Self {
+        KpiOptions {
+            from: None,
+            to: None,
+            roster: None,
+            claim_number_formats: None,
+            include_inactive: false,
+            min_sample: 5,
+            max_step_days: 365,
+            future_dates: None,
+            score_weights: scoring::ScoreWeights::default(),
+        }
+    }
+}
+
+/// The results of analyzing the jobs, before they are output.
+struct Analysis {
+    tracker_stats: BTreeMap<KpiSubject, JobTrackerStats>,
+    leaderboard: Vec<scoring::RepScore>,
+    red_flags: BTreeMap<KpiSubject, Vec<(Rc<AnalyzedJob>, JobAnalysisError)>>,
+    reconciliation: Reconciliation,
+    /// The jobs settled within the date range.
+    settled_jobs: Vec<Rc<AnalyzedJob>>,
+}
+
+impl Analysis {
+    /// Leaves out everything but the stats, red flags, and jobs of the subject
+    /// (along with the global stats to compare them to), e.g. for a report that
+    /// is shared with a sales rep.
+    fn restrict_to(&mut self, subject: &KpiSubject) {
+        let is_subject = |job: &AnalyzedJob| match (subject, &job.job.sales_rep) {
+            (KpiSubject::SalesRep(name), Some(sales_rep)) => name == sales_rep,
+            (KpiSubject::UnknownSalesRep, None) => true,
+            _ => false,
+        };
+        self.tracker_stats.retain(|other, _| other == subject || *other == KpiSubject::Global);
+        self.leaderboard.clear();
+        self.red_flags.retain(|other, _| other == subject);
+        self.reconciliation.excluded.retain(|(job, _)| is_subject(job));
+        self.settled_jobs.retain(|job| is_subject(job));
+    }
+}
+
+fn analyze_jobs(jobs: Vec<Job>, options: &KpiOptions) -> Result<Analysis> {
+    let processing::KpiData { trackers, red_flags, reconciliation } = processing::process_jobs(
+        jobs.into_iter(),
+        (options.from, options.to),
+        options.roster.as_ref(),
+        options.claim_number_formats.as_ref(),
+        TimeDelta::days(options.max_step_days),
+        options.future_dates,
+    );
+    let settled_jobs = match trackers.get(&KpiSubject::Global) {
+        Some(tracker) => processing::all_jobs(tracker)?,
+        None => Vec::new(),
+    };
+    let mut tracker_stats = BTreeMap::new();
+    for (rep, tracker) in trackers {
+        let stats = processing::calculate_job_tracker_stats(&tracker, options.min_sample)?;
+        if stats.appt_count > 0
+            && (options.include_inactive || !is_inactive(&rep, options.roster.as_ref()))
+        {
+            tracker_stats.insert(rep, stats);
+        }
+    }
+    let leaderboard = scoring::rank_reps(&tracker_stats, &options.score_weights);
+    Ok(Analysis { tracker_stats, leaderboard, red_flags, reconciliation, settled_jobs })
+}
+
+/// Analyzes the jobs like the KPI report does, returning the results instead
+/// of outputting them anywhere.
+pub fn analyze(jobs: Vec<Job>, options: &KpiOptions) -> Result<report::KpiReport> {
+    let analysis = analyze_jobs(jobs, options)?;
+    Ok(report::KpiReport::new(&analysis, options))
+}
+
+/// The names and values of the parameters that the report is generated with,
+/// which are echoed in every output format so that e.g. a CSV file still says
+/// which date range it covers after it has been renamed.
+#[allow(clippy::too_many_arguments)]
+fn run_parameters(
+    (from, to): (Option<Timestamp>, Option<Timestamp>),
+    filter_filename: Option<&str>,
+    trade: Option<&str>,
+    as_of: Option<Timestamp>,
+    fetched_at: Timestamp,
+    min_sample: usize,
+    max_step_days: i64,
+    future_dates: processing::FutureDates,
+) -> Vec<(String, String)> {
+    let date = |timestamp: Option<Timestamp>| match timestamp {
+        Some(timestamp) => timestamp.format("%Y-%m-%d").to_string(),
+        None => "forever".to_owned(),
+    };
+    let mut parameters = vec![
+        ("Settled from".to_owned(), date(from)),
+        ("Settled to".to_owned(), date(to)),
+        ("Filter".to_owned(), filter_filename.unwrap_or("none").to_owned()),
+        ("Trade".to_owned(), trade.unwrap_or("all").to_owned()),
+    ];
+    if let Some(as_of) = as_of {
+        parameters.push(("As of".to_owned(), as_of.format("%Y-%m-%d %H:%M UTC").to_string()));
+    }
+    parameters
+        .push(("Data fetched".to_owned(), fetched_at.format("%Y-%m-%d %H:%M UTC").to_string()));
+    parameters.push(("Min sample".to_owned(), min_sample.to_string()));
+    parameters.push(("Max step days".to_owned(), max_step_days.to_string()));
+    parameters.push((
+        if future_dates.exclude { "Excluding dates after" } else { "Flagging dates after" }
+            .to_owned(),
+        future_dates.horizon.format("%Y-%m-%d %H:%M UTC").to_string(),
+    ));
+    parameters
+}
+
+/// Finds the subject with stats whose name matches `name`, ignoring case.
+/// "Global" refers to the global stats.
+fn find_subject<'a, T>(stats: &'a BTreeMap<KpiSubject, T>, name: &str) -> Option<&'a KpiSubject> {
+    let name = name.trim();
+    stats.keys().find(|subject| match subject {
+        KpiSubject::Global => name.eq_ignore_ascii_case("global"),
+        KpiSubject::SalesRep(rep) => rep.trim().eq_ignore_ascii_case(name),
+        _ => false,
+    })
+}
+
+/// Whether the subject is a sales rep that is marked inactive in the roster.
+fn is_inactive(subject: &KpiSubject, roster: Option<&Roster>) -> bool {
+    let (KpiSubject::SalesRep(name), Some(roster)) = (subject, roster) else {
+        return false;
+    };
+    roster.get(name).is_some_and(|rep_info| !rep_info.active)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum KpiSubject {
     Global,
+    Team(String),
+    Trade(String),
     SalesRep(String),
     UnknownSalesRep,
 }
@@ -157,77 +884,355 @@ impl Display for KpiSubject {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             KpiSubject::Global => write!(f, "[Global]"),
+            KpiSubject::Team(name) => write!(f, "[Team {}]", name),
+            KpiSubject::Trade(name) => write!(f, "[Trade {}]", name),
             KpiSubject::SalesRep(name) => write!(f, "{}", name),
             KpiSubject::UnknownSalesRep => write!(f, "[Unknown]"),
         }
     }
 }
 
-mod processing {
-    use std::{collections::HashMap, rc::Rc};
+/// Keeps the human-readable report on the terminal up to date, as a cheap live
+/// dashboard.
+mod watch {
+    use std::io::Write as _;
+    use std::time::Duration;
+
+    use anyhow::Result;
+    use chrono::{Local, Utc};
+    use tracing::warn;
+
+    use crate::apis::job_nimbus;
+    use crate::jobs::{Job, TimeDelta};
+
+    /// Clears the terminal and moves the cursor to the top left corner.
+    const CLEAR_SCREEN: &str = "\x1B[2J\x1B[H";
+
+    /// How far before the previous fetch to look for updated jobs, so that a job
+    /// updated while it was in progress isn't missed.
+    const FETCH_OVERLAP: TimeDelta = TimeDelta::minutes(1);
+
+    /// Parses an interval such as "15m", "1h", or "90s".
+    pub fn parse_interval(interval: &str) -> Result<Duration, String> {
+        let interval = interval.trim();
+        let unit_start = interval.find(|c: char| !c.is_ascii_digit()).unwrap_or(interval.len());
+        let (amount, unit) = interval.split_at(unit_start);
+        let amount: u64 =
+            amount.parse().map_err(|_| format!("\"{}\" doesn't start with a number", interval))?;
+        let secs_per_unit = match unit.trim() {
+            "s" => 1,
+            "m" | "" => 60,
+            "h" => 60 * 60,
+            unit => return Err(format!("Unknown unit \"{}\"; use \"s\", \"m\", or \"h\"", unit)),
+        };
+        if amount == 0 {
+            return Err("The interval must be longer than zero".to_owned());
+        }
+        Ok(Duration::from_secs(amount * secs_per_unit))
+    }
+
+    /// Prints the report, then at every interval fetches the jobs that were
+    /// updated since the previous fetch and prints the report again. Jobs
+    /// deleted from JobNimbus stay in the report until it is restarted. A
+    /// failed refresh is logged and the report is printed from the jobs that
+    /// were fetched before.
+    pub fn run(args: super::Args, interval: Duration) -> Result<()> {
+        let jn_api_key = job_nimbus::get_api_key(args.jn_api_key.clone())?;
+        let filter = job_nimbus::read_filter_file(args.filter_filename.as_deref())?;
+        let mut fetched_at = Utc::now();
+        let mut jobs = job_nimbus::get_all_jobs_from_job_nimbus(&jn_api_key, filter.as_deref())?;
+        loop {
+            print!("{}", CLEAR_SCREEN);
+            if let Err(e) = super::run(args.clone(), Some((fetched_at, jobs.clone()))) {
+                warn!("Failed to generate the report: {:#}", e);
+            }
+            let next_refresh = Local::now() + interval;
+            println!(
+                "\nNext refresh at {} (press Ctrl+C to stop)",
+                next_refresh.format("%H:%M:%S")
+            );
+            std::io::stdout().flush()?;
+            std::thread::sleep(interval);
+
+            let fetch_start = Utc::now();
+            let since = fetched_at - FETCH_OVERLAP;
+            match job_nimbus::get_jobs_updated_since(&jn_api_key, filter.as_deref(), since) {
+                Ok(updated) => {
+                    merge_updated_jobs(&mut jobs, updated);
+                    fetched_at = fetch_start;
+                }
+                Err(e) => warn!("Failed to refresh the jobs: {:#}", e),
+            }
+        }
+    }
+
+    /// Replaces the jobs that were updated (by JobNimbus ID) and adds the ones
+    /// that are new.
+    fn merge_updated_jobs(jobs: &mut Vec<Job>, updated: Vec<Job>) {
+        for job in updated {
+            match jobs.iter_mut().find(|other| other.jnid == job.jnid) {
+                Some(other) => *other = job,
+                None => jobs.push(job),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+        use crate::synthetic;
+
+        #[test]
+        fn parse_interval_units() {
+            assert_eq!(parse_interval("15m"), Ok(Duration::from_secs(15 * 60)));
+            assert_eq!(parse_interval("1h"), Ok(Duration::from_secs(60 * 60)));
+            assert_eq!(parse_interval("90s"), Ok(Duration::from_secs(90)));
+            assert_eq!(parse_interval("5"), Ok(Duration::from_secs(5 * 60)));
+            assert!(parse_interval("0m").is_err());
+            assert!(parse_interval("15d").is_err());
+            assert!(parse_interval("m").is_err());
+        }
+
+        #[test]
+        fn merge_replaces_and_adds_jobs() {
+            let mut jobs = synthetic::generate_jobs(3, 0);
+            let mut updated = synthetic::generate_jobs(2, 1);
+            updated[0].jnid = jobs[1].jnid.clone();
+            updated[1].jnid = "new".to_owned();
+            let replacement = updated[0].clone();
+
+            merge_updated_jobs(&mut jobs, updated);
+            assert_eq!(jobs.len(), 4);
+            assert_eq!(jobs[1], replacement);
+            assert_eq!(jobs[3].jnid, "new");
+        }
+    }
+}
+
+pub mod processing {
+    use std::{
+        collections::{BTreeMap, HashMap},
+        rc::Rc,
+    };
 
     use tracing::info;
 
     use crate::{
-        job_tracker::{self, CalcStatsResult, JobTracker},
+        claim_numbers::ClaimNumberFormats,
+        job_tracker::{CalcStatsResult, JobTracker, JobTrackerError},
         jobs::{
             self, AnalyzedJob, Job, JobAnalysisError, JobKind, Milestone, TimeDelta, Timestamp,
         },
+        roster::Roster,
+        subcommands::duplicates,
     };
 
     use super::KpiSubject;
 
-    pub type TrackersAndFlags = (
-        HashMap<KpiSubject, JobTracker3x5>,
-        HashMap<KpiSubject, Vec<(Rc<AnalyzedJob>, JobAnalysisError)>>,
-    );
+    /// The number of times that a job's appointment can be rescheduled without
+    /// a contract before the job is flagged.
+    const MIN_FLAGGED_RESCHEDULES: usize = 2;
+
+    /// How to handle milestone dates past a horizon, which are usually typos
+    /// in the year. Such dates make a job look further along (e.g. settled)
+    /// than it is, and skew the times between milestones.
+    #[derive(Debug, Clone, Copy)]
+    pub struct FutureDates {
+        /// The latest date that a milestone can have been reached.
+        pub horizon: Timestamp,
+        /// Whether to exclude the jobs with later milestone dates, instead of
+        /// only flagging them.
+        pub exclude: bool,
+    }
+
+    impl FutureDates {
+        /// The default number of days past now that a milestone date can be.
+        /// Installs are routinely scheduled weeks or months ahead, so only
+        /// dates far enough out to be typos are flagged.
+        pub const DEFAULT_HORIZON_DAYS: i64 = 365;
+
+        /// A red flag for each milestone date of the job past the horizon.
+        fn check(&self, job: &Job) -> Vec<JobAnalysisError> {
+            let dates = &job.milestone_dates;
+            Milestone::ordered_iter()
+                .map(|milestone| (Some(milestone), dates[milestone]))
+                .chain([(None, dates.loss_date)])
+                .filter_map(|(milestone, date)| {
+                    date.filter(|&date| date > self.horizon)
+                        .map(|date| JobAnalysisError::FutureDate(milestone, date))
+                })
+                .collect()
+        }
+    }
+
+    /// The results of processing the jobs for the KPI report.
+    pub struct KpiData {
+        pub trackers: HashMap<KpiSubject, JobTracker3x5>,
+        pub red_flags: BTreeMap<KpiSubject, Vec<(Rc<AnalyzedJob>, JobAnalysisError)>>,
+        pub reconciliation: Reconciliation,
+    }
+
+    /// Accounts for every processed job, so that the stats can be reconciled
+    /// with the number of jobs. Every job is counted in exactly one category.
+    #[derive(Debug, Default)]
+    pub struct Reconciliation {
+        /// Jobs that were settled within the date range, i.e. the jobs that the
+        /// stats are calculated from.
+        pub settled_in_range: usize,
+        pub settled_out_of_range: usize,
+        /// Jobs that are marked lost in JobNimbus, but have no loss date, so
+        /// they were never settled.
+        pub abandoned: usize,
+        /// Jobs that have not reached any milestone.
+        pub milestoneless: usize,
+        /// Jobs that reached a milestone but have not been settled yet.
+        pub unsettled: usize,
+        /// The jobs that were left out of every tracker because they could not
+        /// be analyzed or tracked, along with the reason. These jobs are also
+        /// listed among the red flags.
+        pub excluded: Vec<(Rc<AnalyzedJob>, JobAnalysisError)>,
+    }
+
+    impl Reconciliation {
+        /// The total number of jobs processed.
+        pub fn total(&self) -> usize {
+            self.counts().iter().map(|(_, count)| count).sum()
+        }
+
+        /// Each category along with its count, in the order in which they
+        /// should be displayed.
+        pub fn counts(&self) -> [(&'static str, usize); 6] {
+            [
+                ("Settled within range", self.settled_in_range),
+                ("Settled out of range", self.settled_out_of_range),
+                ("Unsettled", self.unsettled),
+                ("Abandoned (lost without a loss date)", self.abandoned),
+                ("No milestones", self.milestoneless),
+                ("Excluded", self.excluded.len()),
+            ]
+        }
+    }
 
     pub fn process_jobs(
         jobs: impl Iterator<Item = Job>,
         (from_dt, to_dt): (Option<Timestamp>, Option<Timestamp>),
-    ) -> TrackersAndFlags {
+        roster: Option<&Roster>,
+        claim_number_formats: Option<&ClaimNumberFormats>,
+        max_achieve_time: TimeDelta,
+        future_dates: Option<FutureDates>,
+    ) -> KpiData {
         info!(
             "Processing jobs settled between {} and {}",
             from_dt.map(|dt| dt.to_string()).as_deref().unwrap_or("the beginning of time"),
             to_dt.map(|dt| dt.to_string()).as_deref().unwrap_or("the end of time")
         );
 
+        // duplicates can only be found by looking at all the jobs at once
+        let jobs: Vec<Job> = jobs.collect();
+        let mut duplicate_red_flags = duplicates::duplicate_red_flags(&jobs);
+
+        let build_job_tracker = || build_job_tracker().with_max_achieve_time(max_achieve_time);
         let mut trackers = HashMap::new();
-        let mut red_flags = HashMap::new();
+        let mut red_flags = BTreeMap::new();
+        let mut reconciliation = Reconciliation::default();
         for job in jobs {
-            let (analyzed, errors) = jobs::analyze_job(job);
+            let extra_errors = duplicate_red_flags.remove(&job.jnid).unwrap_or_default();
+            let (analyzed, mut errors) = jobs::analyze_job(job);
+            // the analysis stops at the first error that it can't recover
+            // from, which is the reason that the job is excluded
+            let mut exclusion =
+                if analyzed.analysis.is_none() { errors.last().cloned() } else { None };
+            errors.extend(extra_errors);
+            let num_reschedules = analyzed.job.rescheduled_appointment_dates.len();
+            if num_reschedules >= MIN_FLAGGED_RESCHEDULES
+                && analyzed.job.milestone_dates.contract_date.is_none()
+            {
+                errors.push(JobAnalysisError::RepeatedReschedules(num_reschedules));
+            }
+            errors.extend(claim_number_formats.and_then(|formats| formats.check(&analyzed.job)));
+            if let Some(future_dates) = future_dates {
+                let future_errors = future_dates.check(&analyzed.job);
+                if future_dates.exclude && exclusion.is_none() {
+                    exclusion = future_errors.first().cloned();
+                }
+                errors.extend(future_errors);
+            }
+            let rep_info =
+                analyzed.job.sales_rep.as_deref().zip(roster).and_then(|(name, roster)| {
+                    let rep_info = roster.get(name);
+                    if rep_info.is_none() {
+                        errors.push(JobAnalysisError::UnknownSalesRep(name.to_owned()));
+                    }
+                    rep_info
+                });
+            let team = rep_info.and_then(|rep_info| rep_info.team.clone()).map(KpiSubject::Team);
+            let trade =
+                analyzed.job.trade.as_deref().map(|t| KpiSubject::Trade(t.trim().to_owned()));
             let analyzed = Rc::new(analyzed);
             let target = match analyzed.job.sales_rep.clone() {
                 Some(name) => KpiSubject::SalesRep(name),
                 None => KpiSubject::UnknownSalesRep,
             };
             if let AnalyzedJob { analysis: Some(analysis), .. } = analyzed.as_ref() {
-                // only add jobs that were settled
-                if let Some(date_settled) = analysis.date_settled() {
+                if exclusion.is_some() {
+                    // the job is excluded for a future date, so it is only
+                    // counted among the excluded jobs
+                } else if let Some(date_settled) = analysis.date_settled() {
                     // only add jobs that were settled within the date range
                     if (from_dt.is_none() || date_settled >= from_dt.unwrap())
                         && (to_dt.is_none() || date_settled <= to_dt.unwrap())
                     {
                         let kind = analysis.kind.into_int();
-                        trackers
-                            .entry(KpiSubject::Global)
-                            .or_insert_with(build_job_tracker)
-                            .add_job(
-                                &analyzed,
-                                kind,
-                                &analysis.timestamps,
-                                analysis.loss_timestamp,
-                            );
-                        trackers.entry(target.clone()).or_insert_with(build_job_tracker).add_job(
-                            &analyzed,
-                            kind,
-                            &analysis.timestamps,
-                            analysis.loss_timestamp,
-                        );
+                        // every tracker has the same milestones, so a job that
+                        // the global tracker rejects is rejected by all of them
+                        let subjects =
+                            [Some(KpiSubject::Global), Some(target.clone()), team, trade];
+                        for (i, subject) in subjects.into_iter().flatten().enumerate() {
+                            let added =
+                                trackers.entry(subject).or_insert_with(build_job_tracker).add_job(
+                                    &analyzed,
+                                    kind,
+                                    &analysis.timestamps,
+                                    analysis.loss_timestamp,
+                                );
+                            match added {
+                                // the same outliers are found by every
+                                // tracker, so only report them once
+                                Ok(outliers) if i == 0 => {
+                                    for (milestone, duration) in outliers {
+                                        errors.push(JobAnalysisError::OutlierDuration(
+                                            Milestone::ordered_iter().nth(milestone).unwrap(),
+                                            duration.num_days(),
+                                        ));
+                                    }
+                                }
+                                Ok(_) => {}
+                                Err(e) => {
+                                    let error = JobAnalysisError::Untrackable(e);
+                                    exclusion = Some(error.clone());
+                                    errors.push(error);
+                                    break;
+                                }
+                            }
+                        }
+                        if exclusion.is_none() {
+                            reconciliation.settled_in_range += 1;
+                        }
+                    } else {
+                        reconciliation.settled_out_of_range += 1;
                     }
+                } else if analyzed.is_abandoned() {
+                    reconciliation.abandoned += 1;
+                } else if analysis.timestamps.iter().all(Option::is_none) {
+                    reconciliation.milestoneless += 1;
+                } else {
+                    reconciliation.unsettled += 1;
                 }
             }
 
+            if let Some(reason) = exclusion {
+                reconciliation.excluded.push((analyzed.clone(), reason));
+            }
             if !errors.is_empty() {
                 let sales_rep_errors: &mut Vec<_> = red_flags.entry(target).or_default();
                 for error in errors {
@@ -236,10 +1241,10 @@ mod processing {
             }
         }
 
-        (trackers, red_flags)
+        KpiData { trackers, red_flags, reconciliation }
     }
 
-    type JobTracker3x5 =
+    pub type JobTracker3x5 =
         JobTracker<{ JobKind::NUM_VARIANTS }, { Milestone::NUM_VARIANTS }, Rc<AnalyzedJob>>;
 
     fn build_job_tracker() -> JobTracker3x5 {
@@ -261,8 +1266,168 @@ mod processing {
         pub appt_contract_retail_conv: ConversionStats,
         pub install_insure_conv: ConversionStats,
         pub install_retail_conv: ConversionStats,
+        /// The sales funnel of each kind of job.
+        pub funnels: Vec<(JobKind, Vec<FunnelStage>)>,
+    }
+
+    impl JobTrackerStats {
+        /// The number of jobs that signed a contract.
+        pub fn contract_count(&self) -> usize {
+            self.close_conversions().iter().map(|conv| conv.achieved.len()).sum()
+        }
+
+        /// The average time of all the conversions into signing a contract.
+        /// Zero if no jobs signed a contract.
+        pub fn average_time_to_close(&self) -> TimeDelta {
+            let contract_count = self.contract_count();
+            if contract_count == 0 {
+                return TimeDelta::zero();
+            }
+            let cum_time: TimeDelta = self
+                .close_conversions()
+                .iter()
+                .map(|conv| conv.average_time_to_achieve * conv.achieved.len().try_into().unwrap())
+                .sum();
+            cum_time / contract_count.try_into().unwrap()
+        }
+
+        /// The conversions that end in signing a contract, which together
+        /// include every job that signed a contract exactly once.
+        fn close_conversions(&self) -> [&ConversionStats; 3] {
+            [
+                &self.appt_contract_insure_conv,
+                &self.continge_contract_conv,
+                &self.appt_contract_retail_conv,
+            ]
+        }
+
+        /// Returns all the conversion stats along with their names, in the
+        /// order of `CONVERSION_DEFINITIONS`.
+        pub fn conversions(&self) -> [(&'static str, &ConversionStats); 7] {
+            CONVERSION_DEFINITIONS
+                .each_ref()
+                .map(|definition| (definition.name, (definition.stats)(self)))
+        }
+    }
+
+    /// Explains which jobs a conversion counts, so that the readers of the
+    /// report don't have to guess what e.g. "(I) Appt to Contract" means.
+    #[derive(Debug)]
+    pub struct ConversionDefinition {
+        pub name: &'static str,
+        /// The kinds of jobs that are counted as making the conversion.
+        pub job_kinds: &'static [JobKind],
+        pub from: Milestone,
+        /// The milestone that the jobs reach to make the conversion, or `None`
+        /// if the conversion counts the jobs that were lost.
+        pub to: Option<Milestone>,
+        /// The jobs that the conversion rate is calculated out of.
+        pub denominator: &'static str,
+        /// The stats of the conversion among the stats of a subject.
+        pub stats: fn(&JobTrackerStats) -> &ConversionStats,
+    }
+
+    impl ConversionDefinition {
+        /// A description of the conversion in plain words, e.g. "Retail jobs
+        /// that went from Contract Signed to Installed, out of retail jobs with
+        /// a signed contract."
+        pub fn describe(&self) -> String {
+            let kinds = if self.job_kinds.len() == JobKind::NUM_VARIANTS {
+                "All".to_owned()
+            } else {
+                self.job_kinds.iter().map(JobKind::to_string).collect::<Vec<_>>().join(" or ")
+            };
+            let reached = match self.to {
+                Some(to) => format!("went from {} to {}", self.from, to),
+                None => format!("were lost after {}", self.from),
+            };
+            format!("{} jobs that {}, out of {}.", kinds, reached, self.denominator.to_lowercase())
+        }
     }
 
+    /// The definitions of the conversions, in the order in which they should be
+    /// displayed.
+    pub const CONVERSION_DEFINITIONS: [ConversionDefinition; 7] = {
+        use JobKind::*;
+        use Milestone::*;
+        [
+            ConversionDefinition {
+                name: "All Losses",
+                job_kinds: &[InsuranceWithContingency, InsuranceWithoutContingency, Retail],
+                from: AppointmentMade,
+                to: None,
+                denominator: "All jobs with an appointment",
+                stats: |stats| &stats.loss_conv,
+            },
+            ConversionDefinition {
+                name: "(I) Appt to Contingency",
+                job_kinds: &[InsuranceWithContingency],
+                from: AppointmentMade,
+                to: Some(ContingencySigned),
+                denominator: "Insurance jobs with an appointment",
+                stats: |stats| &stats.appt_continge_conv,
+            },
+            ConversionDefinition {
+                name: "(I) Appt to Contract",
+                job_kinds: &[InsuranceWithoutContingency],
+                from: AppointmentMade,
+                to: Some(ContractSigned),
+                denominator: "Insurance jobs with an appointment",
+                stats: |stats| &stats.appt_contract_insure_conv,
+            },
+            ConversionDefinition {
+                name: "(I) Contingency to Contract",
+                job_kinds: &[InsuranceWithContingency],
+                from: ContingencySigned,
+                to: Some(ContractSigned),
+                denominator: "Insurance jobs with a signed contingency",
+                stats: |stats| &stats.continge_contract_conv,
+            },
+            ConversionDefinition {
+                name: "(R) Appt to Contract",
+                job_kinds: &[Retail],
+                from: AppointmentMade,
+                to: Some(ContractSigned),
+                denominator: "Retail jobs with an appointment",
+                stats: |stats| &stats.appt_contract_retail_conv,
+            },
+            ConversionDefinition {
+                name: "(I) Contract to Installation",
+                job_kinds: &[InsuranceWithContingency, InsuranceWithoutContingency],
+                from: ContractSigned,
+                to: Some(Installed),
+                denominator: "Insurance jobs with a signed contract",
+                stats: |stats| &stats.install_insure_conv,
+            },
+            ConversionDefinition {
+                name: "(R) Contract to Installation",
+                job_kinds: &[Retail],
+                from: ContractSigned,
+                to: Some(Installed),
+                denominator: "Retail jobs with a signed contract",
+                stats: |stats| &stats.install_retail_conv,
+            },
+        ]
+    };
+
+    /// The definitions of the other terms used in the report, along with their
+    /// names.
+    pub const OTHER_DEFINITIONS: [(&str, &str); 4] = [
+        (
+            "Rate",
+            "The number of jobs that made the conversion, out of the jobs in its denominator. \
+            Only jobs settled (installed or lost) within the date range are counted.",
+        ),
+        (
+            "Avg Time (days)",
+            "The average number of days between the two milestones of the conversion, or \
+            between the last milestone reached and the loss date for losses. Steps that took \
+            longer than `--max-step-days` are left out.",
+        ),
+        ("Appts", "The number of settled jobs of any kind with an appointment."),
+        ("Installed", "The number of settled jobs of any kind that were installed."),
+    ];
+
     #[derive(Debug)]
     pub struct ConversionStats {
         /// All the jobs that made the conversion.
@@ -272,89 +1437,172 @@ mod processing {
         /// The average amount of time for a successful conversion. Zero if no
         /// jobs made the conversion.
         pub average_time_to_achieve: TimeDelta,
+        /// The number of jobs that could have made the conversion, i.e. the
+        /// denominator of the conversion rate.
+        pub num_candidates: usize,
+        /// Whether there were too few candidates for the conversion rate to
+        /// be meaningful.
+        pub small_sample: bool,
+    }
+
+    #[derive(Debug)]
+    pub struct FunnelStage {
+        pub milestone: Milestone,
+        /// The number of jobs that reached this milestone.
+        pub count: usize,
+        /// The fraction of jobs that reached the previous milestone but not
+        /// this one. `None` for the first milestone, or if no jobs reached the
+        /// previous milestone.
+        pub drop_off: Option<f64>,
+    }
+
+    /// Calculates the sales funnel of the specified kind of job, i.e. how many
+    /// jobs reached each milestone and how many dropped off between
+    /// consecutive milestones.
+    pub fn calculate_funnel(tracker: &JobTracker3x5, kind: JobKind) -> Vec<FunnelStage> {
+        let milestones: Vec<Milestone> = Milestone::ordered_iter().collect();
+        let mut prev_count = None;
+        tracker
+            .funnel(kind.into_int())
+            .into_iter()
+            .map(|(milestone, count)| {
+                let drop_off = prev_count
+                    .filter(|&prev_count| prev_count > 0)
+                    .map(|prev_count| 1.0 - count as f64 / prev_count as f64);
+                prev_count = Some(count);
+                FunnelStage { milestone: milestones[milestone], count, drop_off }
+            })
+            .collect()
+    }
+
+    /// Returns all the jobs that were added to the tracker.
+    pub fn all_jobs(tracker: &JobTracker3x5) -> Result<Vec<Rc<AnalyzedJob>>, JobTrackerError> {
+        let kinds = [
+            JobKind::InsuranceWithContingency.into_int(),
+            JobKind::InsuranceWithoutContingency.into_int(),
+            JobKind::Retail.into_int(),
+        ];
+        Ok(tracker.calc_stats(Milestone::LeadAcquired.into_int(), &kinds)?.achieved)
     }
 
-    pub fn calculate_job_tracker_stats(tracker: &JobTracker3x5) -> JobTrackerStats {
+    /// Calculates the stats of the tracker. Conversion rates with fewer than
+    /// `min_sample` candidates are marked as small samples.
+    pub fn calculate_job_tracker_stats(
+        tracker: &JobTracker3x5,
+        min_sample: usize,
+    ) -> Result<JobTrackerStats, JobTrackerError> {
         let iwc = JobKind::InsuranceWithContingency.into_int(); // "insurance with contingency"
         let iwo = JobKind::InsuranceWithoutContingency.into_int(); // "insurance without contingency"
         let ret = JobKind::Retail.into_int(); // "retail"
 
         // some basic stats
         let appt_count = tracker
-            .calc_stats(Milestone::AppointmentMade.into_int(), &[iwc, iwo, ret])
+            .calc_stats(Milestone::AppointmentMade.into_int(), &[iwc, iwo, ret])?
             .achieved
             .len();
         let install_count =
-            tracker.calc_stats(Milestone::Installed.into_int(), &[iwc, iwo, ret]).achieved.len();
+            tracker.calc_stats(Milestone::Installed.into_int(), &[iwc, iwo, ret])?.achieved.len();
+
+        let mk_conv_stats =
+            |achieved, conversion_rate, average_time_to_achieve, num_candidates| ConversionStats {
+                achieved,
+                conversion_rate,
+                average_time_to_achieve,
+                num_candidates,
+                small_sample: num_candidates < min_sample,
+            };
 
         let loss_conv = {
-            let (achieved, average_time_to_achieve) = tracker.calc_stats_of_loss();
+            let (achieved, average_time_to_achieve) = tracker.calc_stats_of_loss()?;
             let conversion_rate = if appt_count == 0 {
                 None
             } else {
                 Some(achieved.len() as f64 / appt_count as f64)
             };
-            ConversionStats { achieved, conversion_rate, average_time_to_achieve }
+            mk_conv_stats(achieved, conversion_rate, average_time_to_achieve, appt_count)
         };
 
         let num_insure_appts =
-            tracker.calc_stats(Milestone::AppointmentMade.into_int(), &[iwc, iwo]).achieved.len();
+            tracker.calc_stats(Milestone::AppointmentMade.into_int(), &[iwc, iwo])?.achieved.len();
 
         // calculate stats for each conversion
         let appt_continge_conv = {
-            let job_tracker::Bucket { achieved, cum_achieve_time, .. } =
-                tracker.get_bucket(iwc, Milestone::ContingencySigned.into_int()).unwrap();
-            let num_achieved = achieved.len();
+            let bucket = tracker.get_bucket(iwc, Milestone::ContingencySigned.into_int()).unwrap();
+            let num_achieved = bucket.achieved.len();
             let conversion_rate = if num_insure_appts == 0 {
                 None
             } else {
                 Some(num_achieved as f64 / num_insure_appts as f64)
             };
-            let average_time_to_achieve = if num_achieved == 0 {
-                TimeDelta::zero()
-            } else {
-                *cum_achieve_time / num_achieved.try_into().unwrap()
-            };
-            ConversionStats { achieved: achieved.clone(), conversion_rate, average_time_to_achieve }
+            mk_conv_stats(
+                bucket.achieved.clone(),
+                conversion_rate,
+                bucket.average_achieve_time(),
+                num_insure_appts,
+            )
         };
         let appt_contract_insure_conv = {
-            let job_tracker::Bucket { achieved, cum_achieve_time, .. } =
-                tracker.get_bucket(iwo, Milestone::ContractSigned.into_int()).unwrap();
-            let num_achieved = achieved.len();
+            let bucket = tracker.get_bucket(iwo, Milestone::ContractSigned.into_int()).unwrap();
+            let num_achieved = bucket.achieved.len();
             let conversion_rate = if num_insure_appts == 0 {
                 None
             } else {
                 Some(num_achieved as f64 / num_insure_appts as f64)
             };
-            let average_time_to_achieve = if num_achieved == 0 {
-                TimeDelta::zero()
-            } else {
-                *cum_achieve_time / num_achieved.try_into().unwrap()
-            };
-            ConversionStats { achieved: achieved.clone(), conversion_rate, average_time_to_achieve }
+            mk_conv_stats(
+                bucket.achieved.clone(),
+                conversion_rate,
+                bucket.average_achieve_time(),
+                num_insure_appts,
+            )
         };
         let continge_contract_conv = {
-            let CalcStatsResult { achieved, conversion_rate, average_time_to_achieve } =
-                tracker.calc_stats(Milestone::ContractSigned.into_int(), &[iwc]);
-            ConversionStats { achieved, conversion_rate, average_time_to_achieve }
+            let CalcStatsResult {
+                achieved,
+                conversion_rate,
+                average_time_to_achieve,
+                num_candidates,
+            } = tracker.calc_stats(Milestone::ContractSigned.into_int(), &[iwc])?;
+            mk_conv_stats(achieved, conversion_rate, average_time_to_achieve, num_candidates)
         };
         let appt_contract_retail_conv = {
-            let CalcStatsResult { achieved, conversion_rate, average_time_to_achieve } =
-                tracker.calc_stats(Milestone::ContractSigned.into_int(), &[ret]);
-            ConversionStats { achieved, conversion_rate, average_time_to_achieve }
+            let CalcStatsResult {
+                achieved,
+                conversion_rate,
+                average_time_to_achieve,
+                num_candidates,
+            } = tracker.calc_stats(Milestone::ContractSigned.into_int(), &[ret])?;
+            mk_conv_stats(achieved, conversion_rate, average_time_to_achieve, num_candidates)
         };
         let install_insure_conv = {
-            let CalcStatsResult { achieved, conversion_rate, average_time_to_achieve } =
-                tracker.calc_stats(Milestone::Installed.into_int(), &[iwc, iwo]);
-            ConversionStats { achieved, conversion_rate, average_time_to_achieve }
+            let CalcStatsResult {
+                achieved,
+                conversion_rate,
+                average_time_to_achieve,
+                num_candidates,
+            } = tracker.calc_stats(Milestone::Installed.into_int(), &[iwc, iwo])?;
+            mk_conv_stats(achieved, conversion_rate, average_time_to_achieve, num_candidates)
         };
         let install_retail_conv = {
-            let CalcStatsResult { achieved, conversion_rate, average_time_to_achieve } =
-                tracker.calc_stats(Milestone::Installed.into_int(), &[ret]);
-            ConversionStats { achieved, conversion_rate, average_time_to_achieve }
+            let CalcStatsResult {
+                achieved,
+                conversion_rate,
+                average_time_to_achieve,
+                num_candidates,
+            } = tracker.calc_stats(Milestone::Installed.into_int(), &[ret])?;
+            mk_conv_stats(achieved, conversion_rate, average_time_to_achieve, num_candidates)
         };
 
-        JobTrackerStats {
+        let funnels = [
+            JobKind::InsuranceWithContingency,
+            JobKind::InsuranceWithoutContingency,
+            JobKind::Retail,
+        ]
+        .into_iter()
+        .map(|kind| (kind, calculate_funnel(tracker, kind)))
+        .collect();
+
+        Ok(JobTrackerStats {
             appt_count,
             install_count,
             loss_conv,
@@ -364,38 +1612,268 @@ mod processing {
             appt_contract_retail_conv,
             install_insure_conv,
             install_retail_conv,
-        }
+            funnels,
+        })
     }
-}
 
-mod output {
-    use std::{
-        io::{BufWriter, Write},
-        path::Path,
-        rc::Rc,
-    };
+    #[cfg(test)]
+    mod test {
+        use super::*;
+        use crate::jobs::{test::make_job, Status};
 
-    use chrono::Utc;
+        #[test]
+        fn conversion_definitions_describe_the_conversion() {
+            assert_eq!(
+                CONVERSION_DEFINITIONS[2].describe(),
+                "Insurance without Contingency jobs that went from Appointment Made to Contract \
+                Signed, out of insurance jobs with an appointment."
+            );
+            assert_eq!(
+                CONVERSION_DEFINITIONS[0].describe(),
+                "All jobs that were lost after Appointment Made, out of all jobs with an \
+                appointment."
+            );
+        }
+
+        #[test]
+        fn jobs_that_fail_analysis_are_excluded() {
+            let day = |days| Some(Timestamp::UNIX_EPOCH + TimeDelta::days(days));
+            let mut installed = make_job(false, day(1), None, day(3), day(4), None);
+            installed.jnid = "a".to_owned();
+            let mut out_of_order = make_job(false, day(5), None, day(3), None, None);
+            out_of_order.jnid = "b".to_owned();
+
+            let data = process_jobs(
+                [installed, out_of_order].into_iter(),
+                (None, None),
+                None,
+                None,
+                TimeDelta::days(365),
+                None,
+            );
+            let excluded: Vec<_> = data
+                .reconciliation
+                .excluded
+                .iter()
+                .map(|(job, reason)| (job.job.jnid.as_str(), reason))
+                .collect();
+            assert_eq!(
+                excluded,
+                [("b", &JobAnalysisError::OutOfOrderDates(Some(Milestone::ContractSigned)))]
+            );
+            let global = &data.trackers[&KpiSubject::Global];
+            assert_eq!(all_jobs(global).unwrap().len(), 1);
+            assert_eq!(data.red_flags[&KpiSubject::UnknownSalesRep].len(), 1);
+        }
+
+        #[test]
+        fn every_job_is_reconciled() {
+            let day = |days| Some(Timestamp::UNIX_EPOCH + TimeDelta::days(days));
+            let mut abandoned = make_job(false, day(1), None, None, None, None);
+            abandoned.status = Status::Lost;
+            let jobs = [
+                make_job(false, day(11), None, day(13), day(14), None),
+                make_job(false, day(1), None, day(3), day(4), None),
+                make_job(false, day(1), None, None, None, day(20)),
+                make_job(false, day(1), None, day(3), None, None),
+                abandoned,
+                make_job(false, None, None, None, None, None),
+                make_job(false, day(5), None, day(3), None, None),
+            ];
+
+            let data = process_jobs(
+                jobs.into_iter(),
+                (day(10), None),
+                None,
+                None,
+                TimeDelta::days(365),
+                None,
+            );
+            let reconciliation = data.reconciliation;
+            assert_eq!(reconciliation.counts().map(|(_, count)| count), [2, 1, 1, 1, 1, 1]);
+            assert_eq!(reconciliation.total(), 7);
+        }
+
+        #[test]
+        fn future_dated_jobs_are_flagged_or_excluded() {
+            let day = |days| Some(Timestamp::UNIX_EPOCH + TimeDelta::days(days));
+            let jobs = [
+                make_job(false, day(1), None, day(3), day(4), None),
+                make_job(false, day(1), None, day(3), day(40), None),
+            ];
+            let mut future_dates = FutureDates { horizon: day(10).unwrap(), exclude: false };
+            let process = |future_dates| {
+                process_jobs(
+                    jobs.clone().into_iter(),
+                    (None, None),
+                    None,
+                    None,
+                    TimeDelta::days(365),
+                    Some(future_dates),
+                )
+            };
+
+            let data = process(future_dates);
+            assert_eq!(data.reconciliation.settled_in_range, 2);
+            let red_flags: Vec<_> = data.red_flags[&KpiSubject::UnknownSalesRep]
+                .iter()
+                .map(|(_, error)| error.clone())
+                .collect();
+            assert_eq!(
+                red_flags,
+                [JobAnalysisError::FutureDate(Some(Milestone::Installed), day(40).unwrap())]
+            );
+
+            future_dates.exclude = true;
+            let data = process(future_dates);
+            assert_eq!(data.reconciliation.settled_in_range, 1);
+            assert_eq!(data.reconciliation.excluded.len(), 1);
+            assert_eq!(data.reconciliation.total(), 2);
+        }
+
+        #[test]
+        fn scheduled_installs_are_not_flagged_by_default() {
+            let now = Timestamp::UNIX_EPOCH + TimeDelta::days(1000);
+            let day = |days| Some(now + TimeDelta::days(days));
+            let job = make_job(false, day(-30), None, day(-10), day(7), None);
+            let future_dates = FutureDates {
+                horizon: now + TimeDelta::days(FutureDates::DEFAULT_HORIZON_DAYS),
+                exclude: true,
+            };
+            let data = process_jobs(
+                [job].into_iter(),
+                (None, None),
+                None,
+                None,
+                TimeDelta::days(365),
+                Some(future_dates),
+            );
+            assert!(data.red_flags.is_empty());
+            assert!(data.reconciliation.excluded.is_empty());
+            assert_eq!(data.reconciliation.settled_in_range, 1);
+        }
+    }
+}
+
+mod output {
+    use std::{
+        collections::BTreeMap,
+        io::{BufWriter, Write},
+        path::Path,
+        rc::Rc,
+    };
+
+    use chrono::Utc;
+    use minijinja::value::ViaDeserialize;
 
     use crate::{
-        apis::google_sheets::{
-            self,
-            spreadsheet::{
-                CellData, ExtendedValue, GridData, RowData, Sheet, SheetProperties, Spreadsheet,
-                SpreadsheetProperties,
+        apis::{
+            google_sheets::{
+                self,
+                spreadsheet::{
+                    mk_row, CellData, Color, ExtendedValue, GridData, RowData, Sheet,
+                    SheetProperties, Spreadsheet, SpreadsheetProperties,
+                },
             },
+            job_nimbus,
+        },
+        jobs::{self, AnalyzedJob, Job, JobAnalysisError, Timestamp},
+        red_flag_acks::{RedFlagAcks, RedFlagKey},
+        subcommands::{
+            carriers::{self, CarrierStats},
+            crews::{self, CrewStats},
+        },
+        templates,
+        utils::{
+            self, into_days, percent_change, percent_or_na, sampled_percent_or_na,
+            signed_percent_or_na,
         },
-        jobs::{AnalyzedJob, JobAnalysisError, TimeDelta},
-        utils,
     };
 
-    use super::{processing::JobTrackerStats, KpiSubject};
+    use super::{
+        processing::{
+            ConversionStats, JobTrackerStats, Reconciliation, CONVERSION_DEFINITIONS,
+            OTHER_DEFINITIONS,
+        },
+        report::KpiReport,
+        scoring::RepScore,
+        KpiSubject,
+    };
+
+    /// Prints the tracker of a single subject. `prev_stats` is `None` if the
+    /// report is not compared to the previous year, and `Some(None)` if the
+    /// subject has no stats from the previous year.
+    pub fn print_tracker_human(
+        rep: &KpiSubject,
+        stats: &JobTrackerStats,
+        prev_stats: Option<Option<&JobTrackerStats>>,
+        list_jobs: bool,
+        mut out: impl Write,
+    ) -> std::io::Result<()> {
+        writeln!(out, "Tracker for {}: ================", rep)?;
+        writeln!(out, "Appts {} | Installed {}", stats.appt_count, stats.install_count)?;
+        for (i, (name, conv_stats)) in stats.conversions().into_iter().enumerate() {
+            let yoy = prev_stats.map(|prev_stats| {
+                YoyComparison::new(conv_stats, prev_stats.map(|prev| prev.conversions()[i].1))
+            });
+            writeln!(
+                out,
+                "{:30}    Rate {} | Total {:2} | Avg Time {:.2} days",
+                name,
+                sampled_percent_or_na(conv_stats.conversion_rate, conv_stats.small_sample),
+                conv_stats.achieved.len(),
+                into_days(conv_stats.average_time_to_achieve),
+            )?;
+            if let Some(yoy) = yoy {
+                let [prev_rate, rate_change, prev_total, total_change, prev_time, time_change] =
+                    yoy.into_strings();
+                writeln!(
+                    out,
+                    "    vs last year: Rate {} ({}) | Total {} ({}) | Avg Time {} days ({})",
+                    prev_rate, rate_change, prev_total, total_change, prev_time, time_change
+                )?;
+            }
+            if list_jobs {
+                for job in &conv_stats.achieved {
+                    writeln!(out, "    - {}", describe_job(job))?;
+                }
+            } else if *rep != KpiSubject::Global {
+                writeln!(out, "    - {}", into_list_of_job_nums(&conv_stats.achieved))?;
+            }
+        }
+        writeln!(out)?;
+        for (kind, funnel) in &stats.funnels {
+            writeln!(out, "Funnel ({}):", kind)?;
+            let max = funnel.first().map(|stage| stage.count).unwrap_or(0);
+            for stage in funnel {
+                write!(
+                    out,
+                    "    {:20} {:4} {:40}",
+                    stage.milestone.to_string(),
+                    stage.count,
+                    funnel_bar(stage.count, max)
+                )?;
+                if let Some(drop_off) = stage.drop_off {
+                    write!(out, " (drop-off {})", percent_or_na(Some(drop_off)))?;
+                }
+                writeln!(out)?;
+            }
+        }
+        writeln!(out)?;
+        out.flush()
+    }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn print_report_human<'a>(
         tracker_stats: impl IntoIterator<Item = (&'a KpiSubject, &'a JobTrackerStats)>,
+        prev_tracker_stats: Option<&BTreeMap<KpiSubject, JobTrackerStats>>,
+        leaderboard: &[RepScore],
         red_flags: impl IntoIterator<
             Item = (&'a KpiSubject, &'a Vec<(Rc<AnalyzedJob>, JobAnalysisError)>),
         >,
+        reconciliation: &Reconciliation,
+        parameters: &str,
+        list_jobs: bool,
         output_dir: Option<&Path>,
     ) -> std::io::Result<()> {
         // make sure that output_dir exists
@@ -405,51 +1883,37 @@ mod output {
 
         for (rep, stats) in tracker_stats {
             // create the file for this rep
-            let mut out: Box<dyn Write> = if let Some(output_dir) = output_dir {
-                Box::new(BufWriter::new(
-                    std::fs::File::create(output_dir.join(format!("rep-{}-stats.txt", rep)))
-                        .expect("the directory should exist"),
-                ))
-            } else {
-                Box::new(std::io::stdout())
-            };
+            let mut out = open_output(output_dir, &format!("rep-{}-stats.txt", rep));
+            writeln!(out, "{}", parameters)?;
 
             // print the report into the file
-            writeln!(out, "Tracker for {}: ================", rep)?;
-            writeln!(out, "Appts {} | Installed {}", stats.appt_count, stats.install_count)?;
-            for (name, conv_stats) in [
-                ("All Losses", &stats.loss_conv),
-                ("(I) Appt to Contingency", &stats.appt_continge_conv),
-                ("(I) Appt to Contract", &stats.appt_contract_insure_conv),
-                ("(I) Contingency to Contract", &stats.continge_contract_conv),
-                ("(R) Appt to Contract", &stats.appt_contract_retail_conv),
-                ("(I) Contract to Installation", &stats.install_insure_conv),
-                ("(R) Contract to Installation", &stats.install_retail_conv),
-            ] {
-                writeln!(
-                    out,
-                    "{:30}    Rate {} | Total {:2} | Avg Time {:.2} days",
-                    name,
-                    percent_or_na(conv_stats.conversion_rate),
-                    conv_stats.achieved.len(),
-                    into_days(conv_stats.average_time_to_achieve),
-                )?;
-                if *rep != KpiSubject::Global {
-                    writeln!(out, "    - {}", into_list_of_job_nums(&conv_stats.achieved))?;
-                }
-            }
-            writeln!(out, "")?;
-            out.flush()?;
+            let prev_stats = prev_tracker_stats.map(|prev| prev.get(rep));
+            print_tracker_human(rep, stats, prev_stats, list_jobs, out)?;
         }
 
-        let mut out: Box<dyn Write> = if let Some(output_dir) = output_dir {
-            Box::new(BufWriter::new(
-                std::fs::File::create(output_dir.join("red-flags.txt"))
-                    .expect("the directory should exist"),
-            ))
-        } else {
-            Box::new(std::io::stdout())
-        };
+        let mut out = open_output(output_dir, "leaderboard.txt");
+
+        writeln!(out, "{}", parameters)?;
+        writeln!(out, "Leaderboard: ================")?;
+        for (i, rep_score) in leaderboard.iter().enumerate() {
+            writeln!(
+                out,
+                "{:3}. {:30} Score {:6.2} | Close Rate {} | Install Rate {} | Installs {:3} | Avg Time to Close {:.2} days",
+                i + 1,
+                rep_score.rep.to_string(),
+                rep_score.score,
+                percent_or_na(rep_score.close_rate),
+                percent_or_na(rep_score.install_rate),
+                rep_score.volume,
+                into_days(rep_score.average_time_to_close),
+            )?;
+        }
+        writeln!(out)?;
+        out.flush()?;
+
+        let mut out = open_output(output_dir, "red-flags.txt");
+
+        writeln!(out, "{}", parameters)?;
         for (rep, red_flags) in red_flags {
             writeln!(out, "Red flags for {}: ===============", rep)?;
             for (job, err) in red_flags {
@@ -460,18 +1924,49 @@ mod output {
                     err
                 )?;
             }
-            writeln!(out, "")?;
+            writeln!(out)?;
         }
         out.flush()?;
 
+        let mut out = open_output(output_dir, "reconciliation.txt");
+
+        writeln!(out, "{}", parameters)?;
+        writeln!(out, "Reconciliation: ===============")?;
+        writeln!(out, "{:40} {:6}", "Jobs processed", reconciliation.total())?;
+        for (name, count) in reconciliation.counts() {
+            writeln!(out, "    {:36} {:6}", name, count)?;
+        }
+        writeln!(out)?;
+        out.flush()?;
+
+        let excluded = &reconciliation.excluded;
+        let mut out = open_output(output_dir, "excluded-jobs.txt");
+        writeln!(out, "{}", parameters)?;
+        writeln!(out, "Excluded jobs ({}): ===============", excluded.len())?;
+        for (job, reason) in excluded {
+            writeln!(
+                out,
+                "{} ({}): {}",
+                job.job.job_number.as_deref().unwrap_or("unknown job #"),
+                sales_rep_name(job),
+                reason
+            )?;
+        }
+        writeln!(out)?;
+        out.flush()?;
+
         Ok(())
     }
 
     pub fn print_report_csv<'a>(
         tracker_stats: impl IntoIterator<Item = (&'a KpiSubject, &'a JobTrackerStats)>,
+        prev_tracker_stats: Option<&BTreeMap<KpiSubject, JobTrackerStats>>,
+        leaderboard: &[RepScore],
         red_flags: impl IntoIterator<
             Item = (&'a KpiSubject, &'a Vec<(Rc<AnalyzedJob>, JobAnalysisError)>),
         >,
+        reconciliation: &Reconciliation,
+        parameters: &str,
         output_dir: Option<&Path>,
     ) -> std::io::Result<()> {
         // make sure that output_dir exists
@@ -481,35 +1976,33 @@ mod output {
 
         for (rep, stats) in tracker_stats {
             // create the file for this rep
-            let out: Box<dyn Write> = if let Some(output_dir) = output_dir {
-                Box::new(BufWriter::new(
-                    std::fs::File::create(output_dir.join(format!("rep-{}-stats.csv", rep)))
-                        .expect("the directory should exist"),
-                ))
-            } else {
-                Box::new(std::io::stdout())
-            };
-            let mut out = csv::Writer::from_writer(out);
-
-            out.write_record(&["Conversion", "Rate", "Total", "Avg Time (days)", "Jobs"])?;
-            for (name, conv_stats) in [
-                ("All Losses", &stats.loss_conv),
-                ("(I) Appt to Contingency", &stats.appt_continge_conv),
-                ("(I) Appt to Contract", &stats.appt_contract_insure_conv),
-                ("(I) Contingency to Contract", &stats.continge_contract_conv),
-                ("(R) Appt to Contract", &stats.appt_contract_retail_conv),
-                ("(I) Contract to Installation", &stats.install_insure_conv),
-                ("(R) Contract to Installation", &stats.install_retail_conv),
-            ] {
-                out.write_record(&[
-                    name,
-                    &percent_or_na(conv_stats.conversion_rate),
-                    &conv_stats.achieved.len().to_string(),
-                    &into_days(conv_stats.average_time_to_achieve).to_string(),
-                    &into_list_of_job_nums(&conv_stats.achieved),
-                ])?;
+            let mut out =
+                open_csv_output(output_dir, &format!("rep-{}-stats.csv", rep), parameters)?;
+
+            let prev_stats = prev_tracker_stats.map(|prev| prev.get(rep));
+            let mut header = vec!["Conversion", "Rate", "Total", "Avg Time (days)"];
+            if prev_stats.is_some() {
+                header.extend(YoyComparison::HEADERS);
             }
-            out.write_record(&[
+            header.push("Jobs");
+            out.write_record(header)?;
+            for (i, (name, conv_stats)) in stats.conversions().into_iter().enumerate() {
+                let yoy = prev_stats.map(|prev_stats| {
+                    YoyComparison::new(conv_stats, prev_stats.map(|prev| prev.conversions()[i].1))
+                });
+                let mut record = vec![
+                    name.to_owned(),
+                    sampled_percent_or_na(conv_stats.conversion_rate, conv_stats.small_sample),
+                    conv_stats.achieved.len().to_string(),
+                    into_days(conv_stats.average_time_to_achieve).to_string(),
+                ];
+                if let Some(yoy) = yoy {
+                    record.extend(yoy.into_strings());
+                }
+                record.push(into_list_of_job_nums(&conv_stats.achieved));
+                out.write_record(record)?;
+            }
+            out.write_record([
                 "Appts",
                 &stats.appt_count.to_string(),
                 "",
@@ -518,21 +2011,44 @@ mod output {
             ])?;
 
             out.flush()?;
+
+            // create the funnel file for this rep
+            let mut out =
+                open_csv_output(output_dir, &format!("rep-{}-funnel.csv", rep), parameters)?;
+            out.write_record(["Job Kind", "Milestone", "Jobs", "Drop-off"])?;
+            for (kind, funnel) in &stats.funnels {
+                for stage in funnel {
+                    out.write_record([
+                        &kind.to_string(),
+                        &stage.milestone.to_string(),
+                        &stage.count.to_string(),
+                        &percent_or_na(stage.drop_off),
+                    ])?;
+                }
+            }
+            out.flush()?;
         }
 
-        let out: Box<dyn Write> = if let Some(output_dir) = output_dir {
-            Box::new(BufWriter::new(
-                std::fs::File::create(output_dir.join("red-flags.csv"))
-                    .expect("the directory should exist"),
-            ))
-        } else {
-            Box::new(std::io::stdout())
-        };
-        let mut out = csv::Writer::from_writer(out);
-        out.write_record(&["Sales Rep", "Job Number", "Error"])?;
+        let mut out = open_csv_output(output_dir, "leaderboard.csv", parameters)?;
+        out.write_record(RepScore::HEADERS)?;
+        for (i, rep_score) in leaderboard.iter().enumerate() {
+            out.write_record([
+                (i + 1).to_string(),
+                rep_score.rep.to_string(),
+                rep_score.score.to_string(),
+                percent_or_na(rep_score.close_rate),
+                percent_or_na(rep_score.install_rate),
+                rep_score.volume.to_string(),
+                into_days(rep_score.average_time_to_close).to_string(),
+            ])?;
+        }
+        out.flush()?;
+
+        let mut out = open_csv_output(output_dir, "red-flags.csv", parameters)?;
+        out.write_record(["Sales Rep", "Job Number", "Error"])?;
         for (rep, red_flags) in red_flags {
             for (job, err) in red_flags {
-                out.write_record(&[
+                out.write_record([
                     &rep.to_string(),
                     job.job.job_number.as_deref().unwrap_or("unknown job #"),
                     &err.to_string(),
@@ -541,53 +2057,212 @@ mod output {
         }
         out.flush()?;
 
+        let mut out = open_csv_output(output_dir, "reconciliation.csv", parameters)?;
+        out.write_record(["Jobs", "Count"])?;
+        out.write_record(["Jobs processed".to_string(), reconciliation.total().to_string()])?;
+        for (name, count) in reconciliation.counts() {
+            out.write_record([name.to_string(), count.to_string()])?;
+        }
+        out.flush()?;
+
+        let mut out = open_csv_output(output_dir, "excluded-jobs.csv", parameters)?;
+        out.write_record(["Sales Rep", "Job Number", "Reason"])?;
+        for (job, reason) in &reconciliation.excluded {
+            out.write_record([
+                sales_rep_name(job),
+                job.job.job_number.as_deref().unwrap_or("unknown job #"),
+                &reason.to_string(),
+            ])?;
+        }
+        out.flush()?;
+
+        let mut out = open_csv_output(output_dir, "definitions.csv", parameters)?;
+        out.write_record(["Metric", "Definition"])?;
+        for (name, definition) in definitions() {
+            out.write_record([name, &definition])?;
+        }
+        out.flush()?;
+
         Ok(())
     }
 
+    /// The parameters of the report on a single line, e.g. "Settled from:
+    /// 2024-01-01; Settled to: 2024-12-31; Filter: none; ...".
+    pub fn parameters_line(parameters: &[(String, String)]) -> String {
+        let parameters: Vec<_> =
+            parameters.iter().map(|(name, value)| format!("{}: {}", name, value)).collect();
+        parameters.join("; ")
+    }
+
+    /// Opens the file with this name in the output directory, or stdout if
+    /// there is no output directory.
+    fn open_output(output_dir: Option<&Path>, file_name: &str) -> Box<dyn Write> {
+        if let Some(output_dir) = output_dir {
+            Box::new(BufWriter::new(
+                std::fs::File::create(output_dir.join(file_name))
+                    .expect("the directory should exist"),
+            ))
+        } else {
+            Box::new(std::io::stdout())
+        }
+    }
+
+    /// Opens the CSV file with this name like `open_output`, starting it with
+    /// a row describing the parameters of the report.
+    fn open_csv_output(
+        output_dir: Option<&Path>,
+        file_name: &str,
+        parameters: &str,
+    ) -> std::io::Result<csv::Writer<Box<dyn Write>>> {
+        let mut out = csv::WriterBuilder::new()
+            .flexible(true)
+            .from_writer(open_output(output_dir, file_name));
+        out.write_record([parameters])?;
+        Ok(out)
+    }
+
+    /// The layout of the HTML report, used unless the user has customized it
+    /// in the templates directory.
+    const DEFAULT_HTML_TEMPLATE: &str = include_str!("../templates/kpi.html");
+
+    /// Renders the report through the `kpi.html` template into `report.html`
+    /// in the output directory (or into stdout).
+    pub fn print_report_html(report: &KpiReport, output_dir: Option<&Path>) -> anyhow::Result<()> {
+        let mut env = templates::load("kpi.html", DEFAULT_HTML_TEMPLATE)?;
+        env.add_filter("subject", |subject: ViaDeserialize<KpiSubject>| subject.to_string());
+        let rendered = env.get_template("kpi.html")?.render(report)?;
+
+        let mut out: Box<dyn Write> = if let Some(output_dir) = output_dir {
+            std::fs::create_dir_all(output_dir)?;
+            Box::new(BufWriter::new(std::fs::File::create(output_dir.join("report.html"))?))
+        } else {
+            Box::new(std::io::stdout())
+        };
+        out.write_all(rendered.as_bytes())?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// The tab colors of each category of sheets, so that the summary and the
+    /// sheets that need attention stand out.
+    const SUMMARY_TAB_COLOR: Color = Color::rgb(0x34, 0xa8, 0x53);
+    const STATS_TAB_COLOR: Color = Color::rgb(0x42, 0x85, 0xf4);
+    const ATTENTION_TAB_COLOR: Color = Color::rgb(0xea, 0x43, 0x35);
+    const REFERENCE_TAB_COLOR: Color = Color::rgb(0x9e, 0x9e, 0x9e);
+
+    #[allow(clippy::too_many_arguments)]
     pub fn generate_report_google_sheets<'a>(
         tracker_stats: impl IntoIterator<Item = (&'a KpiSubject, &'a JobTrackerStats)>,
-        red_flags: impl IntoIterator<
-            Item = (&'a KpiSubject, &'a Vec<(Rc<AnalyzedJob>, JobAnalysisError)>),
-        >,
-        update: bool,
-    ) -> anyhow::Result<()> {
-        fn mk_row(cells: impl IntoIterator<Item = ExtendedValue>) -> RowData {
-            RowData {
-                values: cells
-                    .into_iter()
-                    .map(|cell| CellData { user_entered_value: Some(cell) })
-                    .collect(),
-            }
-        }
+        prev_tracker_stats: Option<&BTreeMap<KpiSubject, JobTrackerStats>>,
+        leaderboard: &[RepScore],
+        extra_sheets: impl IntoIterator<Item = Sheet>,
+        carrier_stats: &BTreeMap<Option<String>, CarrierStats>,
+        crew_stats: &BTreeMap<Option<String>, CrewStats>,
+        parameters: &str,
+        destination: google_sheets::Destination<'_>,
+    ) -> anyhow::Result<String> {
+        let spreadsheet = report_spreadsheet(
+            tracker_stats,
+            prev_tracker_stats,
+            leaderboard,
+            extra_sheets,
+            carrier_stats,
+            crew_stats,
+            parameters,
+            Utc::now(),
+        );
+        let url = google_sheets::upload_to_destination_blocking(
+            google_sheets::SheetNickname::Kpi,
+            spreadsheet,
+            destination,
+        )?;
+        utils::open_url(url.as_str());
+        Ok(url)
+    }
 
-        // create a stats sheet for each rep
+    /// Builds the spreadsheet of the report, as exported at `exported_at`.
+    #[allow(clippy::too_many_arguments)]
+    fn report_spreadsheet<'a>(
+        tracker_stats: impl IntoIterator<Item = (&'a KpiSubject, &'a JobTrackerStats)>,
+        prev_tracker_stats: Option<&BTreeMap<KpiSubject, JobTrackerStats>>,
+        leaderboard: &[RepScore],
+        extra_sheets: impl IntoIterator<Item = Sheet>,
+        carrier_stats: &BTreeMap<Option<String>, CarrierStats>,
+        crew_stats: &BTreeMap<Option<String>, CrewStats>,
+        parameters: &str,
+        exported_at: Timestamp,
+    ) -> Spreadsheet {
+        // create a stats sheet for each rep, collecting the funnels of all
+        // reps along the way
+        let mut funnel_rows = vec![mk_row([
+            ExtendedValue::StringValue("Sales Rep".to_string()),
+            ExtendedValue::StringValue("Job Kind".to_string()),
+            ExtendedValue::StringValue("Milestone".to_string()),
+            ExtendedValue::StringValue("Jobs".to_string()),
+            ExtendedValue::StringValue("Drop-off".to_string()),
+            ExtendedValue::StringValue("".to_string()),
+        ])];
+        let mut global_stats = None;
         let mut sheets: Vec<_> = tracker_stats
             .into_iter()
             .map(|(rep, stats)| {
+                if *rep == KpiSubject::Global {
+                    global_stats = Some(stats);
+                }
+                let prev_stats = prev_tracker_stats.map(|prev| prev.get(rep));
+                for (kind, funnel) in &stats.funnels {
+                    let max = funnel.first().map(|stage| stage.count).unwrap_or(0);
+                    for stage in funnel {
+                        funnel_rows.push(mk_row([
+                            ExtendedValue::StringValue(rep.to_string()),
+                            ExtendedValue::StringValue(kind.to_string()),
+                            ExtendedValue::StringValue(stage.milestone.to_string()),
+                            ExtendedValue::NumberValue(stage.count as f64),
+                            ExtendedValue::StringValue(percent_or_na(stage.drop_off)),
+                            ExtendedValue::StringValue(funnel_bar(stage.count, max)),
+                        ]));
+                    }
+                }
+
                 let mut rows = Vec::new();
-                rows.push(mk_row([
-                    ExtendedValue::StringValue("Conversion".to_string()),
-                    ExtendedValue::StringValue("Rate".to_string()),
-                    ExtendedValue::StringValue("Total".to_string()),
-                    ExtendedValue::StringValue("Avg Time (days)".to_string()),
-                    ExtendedValue::StringValue("Jobs".to_string()),
-                ]));
-                for (name, conv_stats) in [
-                    ("All Losses", &stats.loss_conv),
-                    ("(I) Appt to Contingency", &stats.appt_continge_conv),
-                    ("(I) Appt to Contract", &stats.appt_contract_insure_conv),
-                    ("(I) Contingency to Contract", &stats.continge_contract_conv),
-                    ("(R) Appt to Contract", &stats.appt_contract_retail_conv),
-                    ("(I) Contract to Installation", &stats.install_insure_conv),
-                    ("(R) Contract to Installation", &stats.install_retail_conv),
-                ] {
-                    rows.push(mk_row([
+                let mut header = vec!["Conversion", "Rate", "Total", "Avg Time (days)"];
+                if prev_stats.is_some() {
+                    header.extend(YoyComparison::HEADERS);
+                }
+                header.push("Jobs");
+                rows.push(mk_row(
+                    header.into_iter().map(|cell| ExtendedValue::StringValue(cell.to_string())),
+                ));
+                for (i, (name, conv_stats)) in stats.conversions().into_iter().enumerate() {
+                    let yoy = prev_stats.map(|prev_stats| {
+                        YoyComparison::new(
+                            conv_stats,
+                            prev_stats.map(|prev| prev.conversions()[i].1),
+                        )
+                    });
+                    let mut cells = vec![
                         ExtendedValue::StringValue(name.to_string()),
-                        ExtendedValue::StringValue(percent_or_na(conv_stats.conversion_rate)),
+                        ExtendedValue::StringValue(sampled_percent_or_na(
+                            conv_stats.conversion_rate,
+                            conv_stats.small_sample,
+                        )),
                         ExtendedValue::NumberValue(conv_stats.achieved.len() as f64),
                         ExtendedValue::NumberValue(into_days(conv_stats.average_time_to_achieve)),
-                        ExtendedValue::StringValue(into_list_of_job_nums(&conv_stats.achieved)),
-                    ]));
+                    ];
+                    if let Some(yoy) = yoy {
+                        cells.extend(yoy.into_cells());
+                    }
+                    cells.push(ExtendedValue::StringValue(into_list_of_job_nums(
+                        &conv_stats.achieved,
+                    )));
+                    let mut row = mk_row(cells);
+                    if conv_stats.small_sample {
+                        row.values[1].note = Some(format!(
+                            "Only {} job(s) could have made this conversion, so the rate is not shown.",
+                            conv_stats.num_candidates
+                        ));
+                    }
+                    rows.push(row);
                 }
                 rows.push(mk_row([
                     ExtendedValue::StringValue("Appts".to_string()),
@@ -597,95 +2272,1265 @@ mod output {
                     ExtendedValue::NumberValue(stats.install_count as f64),
                 ]));
 
+                let tab_color =
+                    if *rep == KpiSubject::Global { SUMMARY_TAB_COLOR } else { STATS_TAB_COLOR };
                 Sheet {
                     properties: SheetProperties {
                         title: Some(format!("Stats {}", rep)),
+                        tab_color: Some(tab_color),
                         ..Default::default()
                     },
                     data: Some(GridData { start_row: 1, start_column: 1, row_data: rows }),
-                    ..Default::default()
                 }
             })
             .collect();
 
-        // create the red flags sheet
+        // create the leaderboard sheet, which goes first as a summary
+        let mut leaderboard_rows = vec![mk_row(
+            RepScore::HEADERS.map(|header| ExtendedValue::StringValue(header.to_string())),
+        )];
+        for (i, rep_score) in leaderboard.iter().enumerate() {
+            leaderboard_rows.push(mk_row([
+                ExtendedValue::NumberValue((i + 1) as f64),
+                ExtendedValue::StringValue(rep_score.rep.to_string()),
+                ExtendedValue::NumberValue(rep_score.score),
+                ExtendedValue::StringValue(percent_or_na(rep_score.close_rate)),
+                ExtendedValue::StringValue(percent_or_na(rep_score.install_rate)),
+                ExtendedValue::NumberValue(rep_score.volume as f64),
+                ExtendedValue::NumberValue(into_days(rep_score.average_time_to_close)),
+            ]));
+        }
+        sheets.insert(
+            0,
+            Sheet {
+                properties: SheetProperties {
+                    title: Some("Leaderboard".to_string()),
+                    tab_color: Some(SUMMARY_TAB_COLOR),
+                    ..Default::default()
+                },
+                data: Some(GridData { start_row: 0, start_column: 0, row_data: leaderboard_rows }),
+            },
+        );
+
+        // create the funnel sheet
+        sheets.push(Sheet {
+            properties: SheetProperties {
+                title: Some("Funnel".to_string()),
+                tab_color: Some(STATS_TAB_COLOR),
+                ..Default::default()
+            },
+            data: Some(GridData { start_row: 0, start_column: 0, row_data: funnel_rows }),
+        });
+
+        // create the insurance carriers sheet
+        let mut carriers_sheet = carriers::carrier_stats_sheet(carrier_stats);
+        carriers_sheet.properties.tab_color = Some(STATS_TAB_COLOR);
+        sheets.push(carriers_sheet);
+
+        // create the crews sheet
+        let mut crews_sheet = crews::crew_stats_sheet(crew_stats);
+        crews_sheet.properties.tab_color = Some(STATS_TAB_COLOR);
+        sheets.push(crews_sheet);
+
+        // add the red flags sheet (unless it is exported separately), the
+        // excluded jobs sheet, the reconciliation sheet, and the definitions
+        // sheet
+        sheets.extend(extra_sheets);
+
+        // start every sheet with the parameters of the report
+        let sheets =
+            sheets.into_iter().map(|sheet| add_parameters_row(sheet, parameters)).collect();
+
+        // append a summary of this export to the history sheet, so that the
+        // spreadsheet itself accumulates a trend over time
+        let mut appended_sheets = Vec::new();
+        if let Some(stats) = global_stats {
+            let conversions = stats.conversions();
+            let mut header =
+                vec!["Exported".to_string(), "Appts".to_string(), "Installed".to_string()];
+            header.extend(conversions.iter().map(|(name, _)| format!("{} Rate", name)));
+            let mut row = vec![
+                ExtendedValue::StringValue(exported_at.format("%Y-%m-%d %H:%M UTC").to_string()),
+                ExtendedValue::NumberValue(stats.appt_count as f64),
+                ExtendedValue::NumberValue(stats.install_count as f64),
+            ];
+            row.extend(conversions.iter().map(|(_, conv)| {
+                match conv.conversion_rate.filter(|_| !conv.small_sample) {
+                    Some(rate) => ExtendedValue::NumberValue(rate),
+                    None => ExtendedValue::StringValue(String::new()),
+                }
+            }));
+            appended_sheets.push(Sheet {
+                properties: SheetProperties {
+                    title: Some("History".to_string()),
+                    tab_color: Some(SUMMARY_TAB_COLOR),
+                    ..Default::default()
+                },
+                data: Some(GridData {
+                    start_row: 0,
+                    start_column: 0,
+                    row_data: vec![
+                        mk_row(header.into_iter().map(ExtendedValue::StringValue)),
+                        mk_row(row),
+                    ],
+                }),
+            });
+        }
+
+        Spreadsheet {
+            properties: SpreadsheetProperties {
+                title: Some(format!("KPI Report ({})", exported_at)),
+            },
+            sheets: Some(sheets),
+            appended_sheets,
+            ..Default::default()
+        }
+    }
+
+    /// Inserts a row describing the parameters of the report above the rest of
+    /// the sheet.
+    fn add_parameters_row(mut sheet: Sheet, parameters: &str) -> Sheet {
+        if let Some(data) = &mut sheet.data {
+            let cell = CellData {
+                user_entered_value: Some(ExtendedValue::StringValue(parameters.to_string())),
+                note: None,
+            };
+            data.row_data.insert(0, RowData { values: vec![cell] });
+        }
+        sheet
+    }
+
+    /// Creates the sheet listing the red flags of each subject, each with a
+    /// checkbox that is ticked if the red flag is acknowledged. The checkboxes
+    /// are read back by `red_flags_sheet_acks`.
+    pub fn red_flags_sheet<'a>(
+        red_flags: impl IntoIterator<
+            Item = (&'a KpiSubject, &'a Vec<(Rc<AnalyzedJob>, JobAnalysisError)>),
+        >,
+        acks: &RedFlagAcks,
+    ) -> Sheet {
         let mut rows = Vec::new();
         rows.push(mk_row([
             ExtendedValue::StringValue("Sales Rep".to_string()),
             ExtendedValue::StringValue("Job Number".to_string()),
             ExtendedValue::StringValue("Error".to_string()),
+            ExtendedValue::StringValue(ACKNOWLEDGED_HEADER.to_string()),
+            ExtendedValue::StringValue(JOB_ID_HEADER.to_string()),
+            ExtendedValue::StringValue(KIND_HEADER.to_string()),
         ]));
         for (rep, red_flags) in red_flags {
             for (job, err) in red_flags {
+                let acknowledged = acks.is_acknowledged(&job.job.jnid, err.kind());
                 rows.push(mk_row([
                     ExtendedValue::StringValue(rep.to_string()),
                     ExtendedValue::StringValue(
                         job.job.job_number.as_deref().unwrap_or("unknown job #").to_string(),
                     ),
                     ExtendedValue::StringValue(err.to_string()),
+                    ExtendedValue::Checkbox(acknowledged),
+                    ExtendedValue::StringValue(job.job.jnid.clone()),
+                    ExtendedValue::StringValue(err.kind().to_string()),
                 ]));
             }
         }
-        sheets.push(Sheet {
+        Sheet {
             properties: SheetProperties {
                 title: Some("Red Flags".to_string()),
+                tab_color: Some(ATTENTION_TAB_COLOR),
                 ..Default::default()
             },
             data: Some(GridData { start_row: 0, start_column: 0, row_data: rows }),
-            ..Default::default()
-        });
+        }
+    }
+
+    const ACKNOWLEDGED_HEADER: &str = "Acknowledged";
+    const JOB_ID_HEADER: &str = "Job ID";
+    const KIND_HEADER: &str = "Kind";
+
+    /// Reads whether each red flag is acknowledged from the rows of a red flags
+    /// sheet made by `red_flags_sheet`, which users may have ticked or unticked
+    /// since. The columns are found by their headers, so rows above the header
+    /// (e.g. the parameters) and columns moved by users are fine. Returns
+    /// nothing if the sheet has no checkboxes or kinds, e.g. because it was
+    /// made by an older version of ahitool.
+    pub fn red_flags_sheet_acks(rows: &[RowData]) -> Vec<(RedFlagKey, bool)> {
+        fn string(row: &RowData, column: usize) -> Option<&str> {
+            match row.values.get(column)?.user_entered_value.as_ref()? {
+                ExtendedValue::StringValue(string) => Some(string.as_str()),
+                _ => None,
+            }
+        }
+        let column = |row: &RowData, header| {
+            (0..row.values.len()).find(|&column| string(row, column) == Some(header))
+        };
+        let Some((header_index, columns)) = rows.iter().enumerate().find_map(|(i, row)| {
+            let columns = (
+                column(row, JOB_ID_HEADER)?,
+                column(row, KIND_HEADER)?,
+                column(row, ACKNOWLEDGED_HEADER)?,
+            );
+            Some((i, columns))
+        }) else {
+            return Vec::new();
+        };
+        let (jnid_column, kind_column, acknowledged_column) = columns;
+        rows[header_index + 1..]
+            .iter()
+            .filter_map(|row| {
+                let key = RedFlagKey {
+                    jnid: string(row, jnid_column)?.to_owned(),
+                    kind: string(row, kind_column)?.to_owned(),
+                };
+                let acknowledged = row.values.get(acknowledged_column).is_some_and(|cell| {
+                    matches!(cell.user_entered_value, Some(ExtendedValue::BoolValue(true)))
+                });
+                Some((key, acknowledged))
+            })
+            .collect()
+    }
 
-        // create the spreadsheet
+    /// Creates the sheet accounting for every processed job.
+    pub fn reconciliation_sheet(reconciliation: &Reconciliation) -> Sheet {
+        let mut rows = Vec::new();
+        rows.push(mk_row([
+            ExtendedValue::StringValue("Jobs".to_string()),
+            ExtendedValue::StringValue("Count".to_string()),
+        ]));
+        rows.push(mk_row([
+            ExtendedValue::StringValue("Jobs processed".to_string()),
+            ExtendedValue::NumberValue(reconciliation.total() as f64),
+        ]));
+        for (name, count) in reconciliation.counts() {
+            rows.push(mk_row([
+                ExtendedValue::StringValue(name.to_string()),
+                ExtendedValue::NumberValue(count as f64),
+            ]));
+        }
+        Sheet {
+            properties: SheetProperties {
+                title: Some("Reconciliation".to_string()),
+                tab_color: Some(REFERENCE_TAB_COLOR),
+                ..Default::default()
+            },
+            data: Some(GridData { start_row: 0, start_column: 0, row_data: rows }),
+        }
+    }
+
+    /// Creates the sheet listing the jobs that were left out of the stats.
+    pub fn excluded_jobs_sheet(excluded: &[(Rc<AnalyzedJob>, JobAnalysisError)]) -> Sheet {
+        let mut rows = Vec::new();
+        rows.push(mk_row([
+            ExtendedValue::StringValue("Sales Rep".to_string()),
+            ExtendedValue::StringValue("Job Number".to_string()),
+            ExtendedValue::StringValue("Reason".to_string()),
+        ]));
+        for (job, reason) in excluded {
+            rows.push(mk_row([
+                ExtendedValue::StringValue(sales_rep_name(job).to_string()),
+                ExtendedValue::StringValue(
+                    job.job.job_number.as_deref().unwrap_or("unknown job #").to_string(),
+                ),
+                ExtendedValue::StringValue(reason.to_string()),
+            ]));
+        }
+        Sheet {
+            properties: SheetProperties {
+                title: Some("Excluded Jobs".to_string()),
+                tab_color: Some(ATTENTION_TAB_COLOR),
+                ..Default::default()
+            },
+            data: Some(GridData { start_row: 0, start_column: 0, row_data: rows }),
+        }
+    }
+
+    /// Each metric of the report along with its definition, in the order in
+    /// which they should be displayed.
+    fn definitions() -> Vec<(&'static str, String)> {
+        CONVERSION_DEFINITIONS
+            .iter()
+            .map(|definition| (definition.name, definition.describe()))
+            .chain(OTHER_DEFINITIONS.map(|(name, definition)| (name, definition.to_owned())))
+            .collect()
+    }
+
+    /// Creates the sheet explaining what each metric of the report means.
+    pub fn definitions_sheet() -> Sheet {
+        let mut rows = Vec::new();
+        rows.push(mk_row([
+            ExtendedValue::StringValue("Metric".to_string()),
+            ExtendedValue::StringValue("Definition".to_string()),
+        ]));
+        for (name, definition) in definitions() {
+            rows.push(mk_row([
+                ExtendedValue::StringValue(name.to_string()),
+                ExtendedValue::StringValue(definition),
+            ]));
+        }
+        Sheet {
+            properties: SheetProperties {
+                title: Some("Definitions".to_string()),
+                tab_color: Some(REFERENCE_TAB_COLOR),
+                ..Default::default()
+            },
+            data: Some(GridData { start_row: 0, start_column: 0, row_data: rows }),
+        }
+    }
+
+    /// The name of the sales rep of the job, as shown next to the job in the
+    /// list of excluded jobs.
+    fn sales_rep_name(job: &AnalyzedJob) -> &str {
+        job.job.sales_rep.as_deref().unwrap_or("[Unknown]")
+    }
+
+    /// Uploads the red flags sheet as its own spreadsheet, separate from the
+    /// stats, and returns its URL.
+    pub fn generate_red_flags_google_sheets(
+        red_flags_sheet: Sheet,
+        parameters: &str,
+        destination: google_sheets::Destination<'_>,
+    ) -> anyhow::Result<String> {
         let spreadsheet = Spreadsheet {
             properties: SpreadsheetProperties {
-                title: Some(format!("KPI Report ({})", Utc::now())),
+                title: Some(format!("KPI Red Flags ({})", Utc::now())),
             },
-            sheets: Some(sheets),
+            sheets: Some(vec![add_parameters_row(red_flags_sheet, parameters)]),
             ..Default::default()
         };
-
-        let url =
-            tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap().block_on(
-                google_sheets::run_with_credentials(|token| {
-                    // FIXME cloning the token is a workaround because I can't
-                    // get lifetimes to work correctly in run_with_credentials
-                    let token = token.clone();
-                    let spreadsheet = &spreadsheet;
-                    async move {
-                        let spreadsheet = spreadsheet.clone();
-                        if update {
-                            google_sheets::create_or_write_spreadsheet(
-                                &token,
-                                google_sheets::SheetNickname::Kpi,
-                                spreadsheet,
-                            )
-                            .await
-                        } else {
-                            google_sheets::create_spreadsheet(
-                                &token,
-                                google_sheets::SheetNickname::Kpi,
-                                spreadsheet,
-                            )
-                            .await
-                        }
-                    }
-                }),
-            )?;
+        let url = google_sheets::upload_to_destination_blocking(
+            google_sheets::SheetNickname::RedFlags,
+            spreadsheet,
+            destination,
+        )?;
         utils::open_url(url.as_str());
-        Ok(())
+        Ok(url)
     }
 
-    fn into_days(time: TimeDelta) -> f64 {
-        const SECONDS_PER_DAY: f64 = 86400.0;
-        time.num_seconds() as f64 / SECONDS_PER_DAY
+    /// A comparison of a conversion's stats with the same conversion's stats
+    /// from the previous year. The previous values are `None` if the subject
+    /// has no stats from the previous year, and the changes are `None` if they
+    /// can't be calculated.
+    struct YoyComparison {
+        prev_rate: Option<f64>,
+        rate_change: Option<f64>,
+        prev_total: Option<usize>,
+        total_change: Option<f64>,
+        prev_avg_days: Option<f64>,
+        avg_time_change: Option<f64>,
+    }
+
+    impl YoyComparison {
+        const HEADERS: [&'static str; 6] = [
+            "Rate (Last Year)",
+            "Rate Change",
+            "Total (Last Year)",
+            "Total Change",
+            "Avg Time (Last Year)",
+            "Avg Time Change",
+        ];
+
+        fn new(current: &ConversionStats, previous: Option<&ConversionStats>) -> Self {
+            let prev_rate =
+                previous.filter(|prev| !prev.small_sample).and_then(|prev| prev.conversion_rate);
+            let prev_total = previous.map(|prev| prev.achieved.len());
+            let prev_avg_days = previous.map(|prev| into_days(prev.average_time_to_achieve));
+            let curr_avg_days = into_days(current.average_time_to_achieve);
+            YoyComparison {
+                prev_rate,
+                rate_change: Some(current)
+                    .filter(|current| !current.small_sample)
+                    .and_then(|current| current.conversion_rate)
+                    .zip(prev_rate)
+                    .and_then(|(curr, prev)| percent_change(curr, prev)),
+                prev_total,
+                total_change: prev_total
+                    .and_then(|prev| percent_change(current.achieved.len() as f64, prev as f64)),
+                prev_avg_days,
+                avg_time_change: prev_avg_days.and_then(|prev| percent_change(curr_avg_days, prev)),
+            }
+        }
+
+        fn into_strings(self) -> [String; 6] {
+            [
+                percent_or_na(self.prev_rate),
+                signed_percent_or_na(self.rate_change),
+                self.prev_total.map(|total| total.to_string()).unwrap_or_else(|| "N/A".to_owned()),
+                signed_percent_or_na(self.total_change),
+                self.prev_avg_days
+                    .map(|days| format!("{:.2}", days))
+                    .unwrap_or_else(|| "N/A".to_owned()),
+                signed_percent_or_na(self.avg_time_change),
+            ]
+        }
+
+        fn into_cells(self) -> [ExtendedValue; 6] {
+            let number_or_na = |value: Option<f64>| match value {
+                Some(value) => ExtendedValue::NumberValue(value),
+                None => ExtendedValue::StringValue("N/A".to_owned()),
+            };
+            [
+                ExtendedValue::StringValue(percent_or_na(self.prev_rate)),
+                ExtendedValue::StringValue(signed_percent_or_na(self.rate_change)),
+                number_or_na(self.prev_total.map(|total| total as f64)),
+                ExtendedValue::StringValue(signed_percent_or_na(self.total_change)),
+                number_or_na(self.prev_avg_days),
+                ExtendedValue::StringValue(signed_percent_or_na(self.avg_time_change)),
+            ]
+        }
     }
-    fn percent_or_na(rate: Option<f64>) -> String {
-        rate.map(|r| format!("{:6.2}%", r * 100.0)).unwrap_or_else(|| "    N/A".to_owned())
+
+    /// Renders a horizontal bar whose length is proportional to the fraction
+    /// of `max` that `count` makes up.
+    fn funnel_bar(count: usize, max: usize) -> String {
+        const MAX_WIDTH: usize = 40;
+        if max == 0 {
+            return String::new();
+        }
+        "█".repeat((count * MAX_WIDTH + max / 2) / max)
     }
+
+    /// Prints the stats of the subjects side by side, one column per subject,
+    /// marking the best and worst conversion rate of each row.
+    pub fn print_comparison(
+        subjects: &[(&KpiSubject, &JobTrackerStats)],
+        mut out: impl Write,
+    ) -> std::io::Result<()> {
+        const WIDTH: usize = 24;
+
+        write!(out, "{:30}", "")?;
+        for (subject, _) in subjects {
+            write!(out, " | {:>WIDTH$}", subject.to_string())?;
+        }
+        writeln!(out)?;
+        for (name, count) in [
+            ("Appts", subjects.iter().map(|(_, stats)| stats.appt_count).collect::<Vec<_>>()),
+            ("Installed", subjects.iter().map(|(_, stats)| stats.install_count).collect()),
+        ] {
+            write!(out, "{:30}", name)?;
+            for count in count {
+                write!(out, " | {:>WIDTH$}", count)?;
+            }
+            writeln!(out)?;
+        }
+
+        let conversions: Vec<_> = subjects.iter().map(|(_, stats)| stats.conversions()).collect();
+        for (i, (name, _)) in conversions[0].iter().enumerate() {
+            let rates: Vec<_> = conversions
+                .iter()
+                .map(|conversions| {
+                    let conv = conversions[i].1;
+                    conv.conversion_rate.filter(|_| !conv.small_sample)
+                })
+                .collect();
+            // losses are the only conversion where a lower rate is better
+            let (best, worst) = best_and_worst(&rates, i != 0);
+            write!(out, "{:30}", name)?;
+            for (j, conversions) in conversions.iter().enumerate() {
+                let conv = conversions[i].1;
+                let marker = if best == Some(j) {
+                    " (best)"
+                } else if worst == Some(j) {
+                    " (worst)"
+                } else {
+                    ""
+                };
+                let rate = sampled_percent_or_na(conv.conversion_rate, conv.small_sample);
+                write!(out, " | {:>WIDTH$}", format!("{}{}", rate.trim(), marker))?;
+            }
+            writeln!(out)?;
+        }
+        out.flush()
+    }
+
+    /// Prints how the job was counted in the report, to answer why a job is or
+    /// isn't counted toward a rep's stats.
+    pub fn print_job_lookup<'a>(
+        job: &Job,
+        (from_dt, to_dt): (Option<Timestamp>, Option<Timestamp>),
+        tracker_stats: &'a BTreeMap<KpiSubject, JobTrackerStats>,
+        red_flags: impl IntoIterator<
+            Item = (&'a KpiSubject, &'a Vec<(Rc<AnalyzedJob>, JobAnalysisError)>),
+        >,
+        mut out: impl Write,
+    ) -> std::io::Result<()> {
+        writeln!(
+            out,
+            "Job {} ({}): ================",
+            job.job_number.as_deref().unwrap_or("unknown job #"),
+            job.job_name.as_deref().unwrap_or_default()
+        )?;
+        writeln!(out, "Sales rep: {}", job.sales_rep.as_deref().unwrap_or("[Unknown]"))?;
+        writeln!(out, "Status: {}", job.status)?;
+        writeln!(out, "Link: {}", job_nimbus::job_url(&job.jnid))?;
+
+        let (analyzed, _) = jobs::analyze_job(job.clone());
+        let counted = match &analyzed.analysis {
+            None => {
+                writeln!(out, "Not counted: the job could not be analyzed (see red flags)")?;
+                false
+            }
+            Some(analysis) => match analysis.date_settled() {
+                None => {
+                    writeln!(out, "Not counted: the job is not settled yet")?;
+                    false
+                }
+                Some(date_settled)
+                    if from_dt.is_some_and(|from_dt| date_settled < from_dt)
+                        || to_dt.is_some_and(|to_dt| date_settled > to_dt) =>
+                {
+                    writeln!(
+                        out,
+                        "Not counted: the job was settled on {}, outside of the date range",
+                        date_settled.format("%Y-%m-%d")
+                    )?;
+                    false
+                }
+                Some(date_settled) => {
+                    writeln!(out, "Settled on {}", date_settled.format("%Y-%m-%d"))?;
+                    true
+                }
+            },
+        };
+
+        if counted {
+            writeln!(out, "Counted toward:")?;
+            for (subject, conversions) in conversions_of_job(tracker_stats, &job.jnid) {
+                if conversions.is_empty() {
+                    writeln!(out, "    {}: no conversions", subject)?;
+                } else {
+                    writeln!(out, "    {}: {}", subject, conversions.join(", "))?;
+                }
+            }
+        }
+
+        let red_flags: Vec<_> = red_flags
+            .into_iter()
+            .flat_map(|(_, red_flags)| red_flags)
+            .filter(|(flagged, _)| flagged.job.jnid == job.jnid)
+            .collect();
+        if red_flags.is_empty() {
+            writeln!(out, "Red flags: none")?;
+        } else {
+            writeln!(out, "Red flags:")?;
+            for (_, err) in red_flags {
+                writeln!(out, "    - {}", err)?;
+            }
+        }
+        out.flush()
+    }
+
+    /// The names of the conversions that the job counted toward in the stats
+    /// of each subject that includes the job.
+    fn conversions_of_job<'a>(
+        tracker_stats: &'a BTreeMap<KpiSubject, JobTrackerStats>,
+        jnid: &str,
+    ) -> Vec<(&'a KpiSubject, Vec<&'static str>)> {
+        tracker_stats
+            .iter()
+            .filter(|(_, stats)| {
+                stats
+                    .conversions()
+                    .iter()
+                    .any(|(_, conv)| conv.achieved.iter().any(|job| job.job.jnid == jnid))
+            })
+            .map(|(subject, stats)| {
+                let names = stats
+                    .conversions()
+                    .into_iter()
+                    .filter(|(_, conv)| conv.achieved.iter().any(|job| job.job.jnid == jnid))
+                    .map(|(name, _)| name)
+                    .collect();
+                (subject, names)
+            })
+            .collect()
+    }
+
+    /// The indices of the best and worst rates, ignoring missing rates. `None`
+    /// if there are fewer than two rates or they are all equal.
+    fn best_and_worst(
+        rates: &[Option<f64>],
+        higher_is_better: bool,
+    ) -> (Option<usize>, Option<usize>) {
+        let present: Vec<(usize, f64)> =
+            rates.iter().enumerate().filter_map(|(i, rate)| Some((i, (*rate)?))).collect();
+        let max = present.iter().max_by(|(_, a), (_, b)| a.total_cmp(b));
+        let min = present.iter().min_by(|(_, a), (_, b)| a.total_cmp(b));
+        match (max, min) {
+            (Some(&(max, max_rate)), Some(&(min, min_rate))) if max_rate != min_rate => {
+                if higher_is_better {
+                    (Some(max), Some(min))
+                } else {
+                    (Some(min), Some(max))
+                }
+            }
+            _ => (None, None),
+        }
+    }
+
+    /// A one-line description of the job with its number, name, settled date,
+    /// and a link to it in JobNimbus.
+    fn describe_job(job: &AnalyzedJob) -> String {
+        let settled = job
+            .analysis
+            .as_ref()
+            .and_then(|analysis| analysis.date_settled())
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|| "not settled".to_owned());
+        format!(
+            "{} | {} | settled {} | {}",
+            job.job.job_number.as_deref().unwrap_or("unknown job #"),
+            job.job.job_name.as_deref().unwrap_or_default(),
+            settled,
+            job_nimbus::job_url(&job.job.jnid),
+        )
+    }
+
     fn into_list_of_job_nums(jobs: &[Rc<AnalyzedJob>]) -> String {
         jobs.iter()
             .map(|job| job.job.job_number.as_deref().unwrap_or_else(|| &job.job.jnid))
             .collect::<Vec<_>>()
             .join(", ")
     }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+        use crate::jobs::{test::make_job, TimeDelta};
+        use crate::subcommands::kpi::{self, processing};
+        use crate::synthetic::generate_jobs;
+
+        #[test]
+        fn best_and_worst_rates() {
+            let rates = [Some(0.5), None, Some(0.2), Some(0.7)];
+            assert_eq!(best_and_worst(&rates, true), (Some(3), Some(2)));
+            assert_eq!(best_and_worst(&rates, false), (Some(2), Some(3)));
+            assert_eq!(best_and_worst(&[Some(0.5), Some(0.5)], true), (None, None));
+            assert_eq!(best_and_worst(&[Some(0.5), None], true), (None, None));
+        }
+
+        #[test]
+        fn conversions_of_job_by_subject() {
+            let day = |days| Some(Timestamp::UNIX_EPOCH + TimeDelta::days(days));
+            let mut job = make_job(false, day(1), None, day(3), day(4), None);
+            job.jnid = "a".to_owned();
+            job.sales_rep = Some("Alice".to_owned());
+            let processing::KpiData { trackers, .. } = processing::process_jobs(
+                [job].into_iter(),
+                (None, None),
+                None,
+                None,
+                TimeDelta::days(365),
+                None,
+            );
+            let tracker_stats: BTreeMap<_, _> = trackers
+                .into_iter()
+                .map(|(subject, tracker)| {
+                    (subject, processing::calculate_job_tracker_stats(&tracker, 0).unwrap())
+                })
+                .collect();
+
+            let expected = vec!["(R) Appt to Contract", "(R) Contract to Installation"];
+            assert_eq!(
+                conversions_of_job(&tracker_stats, "a"),
+                [
+                    (&KpiSubject::Global, expected.clone()),
+                    (&KpiSubject::SalesRep("Alice".to_owned()), expected),
+                ]
+            );
+            assert!(conversions_of_job(&tracker_stats, "b").is_empty());
+        }
+
+        #[test]
+        fn red_flag_checkboxes_are_read_back() {
+            let flag = |jnid: &str, err| {
+                let mut job = make_job(false, None, None, None, None, None);
+                job.jnid = jnid.to_owned();
+                (Rc::new(AnalyzedJob { job, analysis: None }), err)
+            };
+            let red_flags = BTreeMap::from([(
+                KpiSubject::SalesRep("Alice".to_owned()),
+                vec![
+                    flag("a", JobAnalysisError::ContingencyWithoutInsurance),
+                    flag("a", JobAnalysisError::InconsistentInsuranceInfo),
+                    flag("b", JobAnalysisError::ContingencyWithoutInsurance),
+                ],
+            )]);
+            let acks = RedFlagAcks::from_acknowledged([RedFlagKey {
+                jnid: "a".to_owned(),
+                kind: JobAnalysisError::InconsistentInsuranceInfo.kind().to_owned(),
+            }]);
+            let sheet = add_parameters_row(red_flags_sheet(&red_flags, &acks), "Parameters");
+            let mut rows = sheet.data.unwrap().row_data;
+
+            // checkboxes are read back as bools, and the user ticked another one
+            for row in &mut rows[2..] {
+                if let Some(ExtendedValue::Checkbox(ticked)) = row.values[3].user_entered_value {
+                    row.values[3].user_entered_value = Some(ExtendedValue::BoolValue(ticked));
+                }
+            }
+            rows[2].values[3].user_entered_value = Some(ExtendedValue::BoolValue(true));
+            let observed: Vec<_> = red_flags_sheet_acks(&rows)
+                .into_iter()
+                .map(|(key, acknowledged)| (key.jnid, acknowledged))
+                .collect();
+            assert_eq!(
+                observed,
+                [("a".to_owned(), true), ("a".to_owned(), true), ("b".to_owned(), false)]
+            );
+
+            // sheets without checkboxes aren't read
+            let sheet = red_flags_sheet(&red_flags, &acks);
+            let mut rows = sheet.data.unwrap().row_data;
+            rows[0].values.truncate(3);
+            assert!(red_flags_sheet_acks(&rows).is_empty());
+        }
+
+        #[test]
+        fn csv_output_starts_with_parameters() {
+            let parameters = parameters_line(&[
+                ("Settled from".to_owned(), "2024-01-01".to_owned()),
+                ("Trade".to_owned(), "Roofing, Gutters".to_owned()),
+            ]);
+            assert_eq!(parameters, "Settled from: 2024-01-01; Trade: Roofing, Gutters");
+
+            let dir = tempfile::tempdir().unwrap();
+            let mut out = open_csv_output(Some(dir.path()), "test.csv", &parameters).unwrap();
+            out.write_record(["Jobs", "Count"]).unwrap();
+            out.flush().unwrap();
+            drop(out);
+            let csv = std::fs::read_to_string(dir.path().join("test.csv")).unwrap();
+            assert_eq!(csv, "\"Settled from: 2024-01-01; Trade: Roofing, Gutters\"\nJobs,Count\n");
+        }
+
+        #[test]
+        fn html_report_uses_default_template() {
+            let day = |days| Some(Timestamp::UNIX_EPOCH + TimeDelta::days(days));
+            let mut installed = make_job(false, day(1), None, day(3), day(4), None);
+            installed.sales_rep = Some("Alice".to_owned());
+            let mut out_of_order = make_job(false, day(5), None, day(3), None, None);
+            out_of_order.job_number = Some("1234".to_owned());
+            let report =
+                kpi::analyze(vec![installed, out_of_order], &kpi::KpiOptions::default()).unwrap();
+
+            let dir = tempfile::tempdir().unwrap();
+            print_report_html(&report, Some(dir.path())).unwrap();
+            let html = std::fs::read_to_string(dir.path().join("report.html")).unwrap();
+            assert!(html.contains(">[Global]</h2>"));
+            assert!(html.contains("<th scope=\"row\">Alice</th>"));
+            assert!(html.contains("<td>1234</td>"));
+            assert!(html.contains("100.00%"));
+        }
+
+        #[test]
+        fn rep_profile_leaves_out_other_reps() {
+            let mut analysis = snapshot_analysis();
+            let rep = analysis
+                .tracker_stats
+                .keys()
+                .find(|subject| matches!(subject, KpiSubject::SalesRep(_)))
+                .unwrap()
+                .clone();
+            assert!(analysis.tracker_stats.len() > 2);
+            analysis.restrict_to(&rep);
+
+            let subjects: Vec<_> = analysis.tracker_stats.keys().collect();
+            assert_eq!(subjects, [&KpiSubject::Global, &rep]);
+            assert!(analysis.leaderboard.is_empty());
+            assert!(analysis.red_flags.keys().all(|subject| *subject == rep));
+            let KpiSubject::SalesRep(name) = &rep else { unreachable!() };
+            assert!(!analysis.settled_jobs.is_empty());
+            assert!(analysis
+                .settled_jobs
+                .iter()
+                .chain(analysis.reconciliation.excluded.iter().map(|(job, _)| job))
+                .all(|job| job.job.sales_rep.as_ref() == Some(name)));
+        }
+
+        // The golden tests below pin the exact output of each format for a
+        // fixed synthetic data set, because the spreadsheets that people build
+        // on top of the reports depend on the column order and formatting. If
+        // a change to the output is intended, review and accept the new
+        // snapshots with `cargo insta review`.
+
+        const SNAPSHOT_PARAMETERS: &str = "Settled from: 2023-01-01; Trade: any";
+
+        fn snapshot_analysis() -> kpi::Analysis {
+            kpi::analyze_jobs(generate_jobs(12, 1), &kpi::KpiOptions::default()).unwrap()
+        }
+
+        /// The contents of every file in the directory, in order of file name.
+        fn read_output_dir(dir: &Path) -> String {
+            let mut paths: Vec<_> =
+                std::fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+            paths.sort();
+            paths
+                .iter()
+                .map(|path| {
+                    let name = path.file_name().unwrap().to_string_lossy();
+                    format!("==> {} <==\n{}", name, std::fs::read_to_string(path).unwrap())
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+
+        #[test]
+        fn human_output_is_unchanged() {
+            let analysis = snapshot_analysis();
+            let dir = tempfile::tempdir().unwrap();
+            print_report_human(
+                &analysis.tracker_stats,
+                None,
+                &analysis.leaderboard,
+                &analysis.red_flags,
+                &analysis.reconciliation,
+                SNAPSHOT_PARAMETERS,
+                true,
+                Some(dir.path()),
+            )
+            .unwrap();
+            insta::assert_snapshot!(read_output_dir(dir.path()));
+        }
+
+        #[test]
+        fn csv_output_is_unchanged() {
+            let analysis = snapshot_analysis();
+            let dir = tempfile::tempdir().unwrap();
+            print_report_csv(
+                &analysis.tracker_stats,
+                None,
+                &analysis.leaderboard,
+                &analysis.red_flags,
+                &analysis.reconciliation,
+                SNAPSHOT_PARAMETERS,
+                Some(dir.path()),
+            )
+            .unwrap();
+            insta::assert_snapshot!(read_output_dir(dir.path()));
+        }
+
+        #[test]
+        fn google_sheets_payload_is_unchanged() {
+            let analysis = snapshot_analysis();
+            let jobs = analysis.settled_jobs.iter().map(Rc::as_ref);
+            let spreadsheet = report_spreadsheet(
+                &analysis.tracker_stats,
+                None,
+                &analysis.leaderboard,
+                [
+                    red_flags_sheet(&analysis.red_flags, &RedFlagAcks::default()),
+                    excluded_jobs_sheet(&analysis.reconciliation.excluded),
+                    reconciliation_sheet(&analysis.reconciliation),
+                ],
+                &carriers::calculate_carrier_stats(jobs.clone()),
+                &crews::calculate_crew_stats(jobs),
+                SNAPSHOT_PARAMETERS,
+                Timestamp::UNIX_EPOCH,
+            );
+            insta::assert_snapshot!(serde_json::to_string_pretty(&spreadsheet).unwrap());
+        }
+    }
+}
+
+/// The results of the KPI analysis in a stable, serializable form, for using
+/// ahitool as a library (see `analyze`). Jobs are referred to by their JobNimbus
+/// ID (jnid), and times are in RFC 3339 format.
+pub mod report {
+    use chrono::SecondsFormat;
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        jobs::{AnalyzedJob, Timestamp},
+        utils::into_days,
+    };
+
+    use super::{Analysis, KpiOptions, KpiSubject};
+
+    /// The version of the schema of `KpiReport`. It is incremented whenever a
+    /// field is removed or changes meaning; fields may be added without
+    /// incrementing it.
+    pub const SCHEMA_VERSION: u32 = 1;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct KpiReport {
+        /// The `SCHEMA_VERSION` that the report was generated with.
+        pub schema_version: u32,
+        /// The start of the date range that jobs were settled in, or `None`
+        /// for the beginning of time.
+        pub from: Option<String>,
+        /// The end of the date range that jobs were settled in, or `None` for
+        /// the end of time.
+        pub to: Option<String>,
+        /// The names and values of the parameters that the report was
+        /// generated with, e.g. the filter and the trade.
+        #[serde(default)]
+        pub parameters: Vec<(String, String)>,
+        /// The stats of each subject (global, team, trade, and sales rep).
+        pub subjects: Vec<SubjectStats>,
+        /// The sales reps from the highest score to the lowest.
+        pub leaderboard: Vec<LeaderboardEntry>,
+        pub red_flags: Vec<RedFlag>,
+        pub reconciliation: ReconciliationCounts,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct SubjectStats {
+        pub subject: KpiSubject,
+        pub appointments: usize,
+        pub contracts: usize,
+        pub installs: usize,
+        pub conversions: Vec<Conversion>,
+        /// The sales funnel of each kind of job.
+        pub funnels: Vec<Funnel>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct Conversion {
+        /// The name of the conversion, e.g. "(R) Appt to Contract".
+        pub name: String,
+        /// The conversion rate from 0 to 1. `None` if there were no
+        /// candidates.
+        pub rate: Option<f64>,
+        /// The number of jobs that could have made the conversion.
+        pub candidates: usize,
+        /// Whether there were too few candidates for the rate to be
+        /// meaningful.
+        pub small_sample: bool,
+        /// The average number of days that the conversion took.
+        pub average_days: f64,
+        /// The jnids of the jobs that made the conversion.
+        pub jobs: Vec<String>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct Funnel {
+        /// The kind of job, e.g. "Retail".
+        pub job_kind: String,
+        pub stages: Vec<FunnelStage>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct FunnelStage {
+        pub milestone: String,
+        /// The number of jobs that reached the milestone.
+        pub count: usize,
+        /// The fraction of jobs that reached the previous milestone but not
+        /// this one. `None` for the first milestone.
+        pub drop_off: Option<f64>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct LeaderboardEntry {
+        pub sales_rep: KpiSubject,
+        /// The composite score, from 0 to 100.
+        pub score: f64,
+        pub close_rate: Option<f64>,
+        pub install_rate: Option<f64>,
+        pub installs: usize,
+        pub average_days_to_close: f64,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct RedFlag {
+        /// The subject whose red flags include the job.
+        pub subject: KpiSubject,
+        pub jnid: String,
+        pub job_number: Option<String>,
+        pub message: String,
+    }
+
+    /// Accounts for every job that was analyzed. The counts add up to the
+    /// total.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct ReconciliationCounts {
+        pub total: usize,
+        pub settled_in_range: usize,
+        pub settled_out_of_range: usize,
+        pub unsettled: usize,
+        /// Jobs marked lost without a loss date.
+        pub abandoned: usize,
+        pub milestoneless: usize,
+        /// The jobs that were left out of the stats because they could not
+        /// be analyzed.
+        pub excluded: Vec<RedFlag>,
+    }
+
+    fn format_timestamp(timestamp: Timestamp) -> String {
+        timestamp.to_rfc3339_opts(SecondsFormat::Secs, true)
+    }
+
+    impl KpiReport {
+        pub(super) fn new(analysis: &Analysis, options: &KpiOptions) -> Self {
+            let subjects = analysis
+                .tracker_stats
+                .iter()
+                .map(|(subject, stats)| SubjectStats {
+                    subject: subject.clone(),
+                    appointments: stats.appt_count,
+                    contracts: stats.contract_count(),
+                    installs: stats.install_count,
+                    conversions: stats
+                        .conversions()
+                        .into_iter()
+                        .map(|(name, conv)| Conversion {
+                            name: name.to_owned(),
+                            rate: conv.conversion_rate,
+                            candidates: conv.num_candidates,
+                            small_sample: conv.small_sample,
+                            average_days: into_days(conv.average_time_to_achieve),
+                            jobs: conv.achieved.iter().map(|job| job.job.jnid.clone()).collect(),
+                        })
+                        .collect(),
+                    funnels: stats
+                        .funnels
+                        .iter()
+                        .map(|(kind, funnel)| Funnel {
+                            job_kind: kind.to_string(),
+                            stages: funnel
+                                .iter()
+                                .map(|stage| FunnelStage {
+                                    milestone: stage.milestone.to_string(),
+                                    count: stage.count,
+                                    drop_off: stage.drop_off,
+                                })
+                                .collect(),
+                        })
+                        .collect(),
+                })
+                .collect();
+            let leaderboard = analysis
+                .leaderboard
+                .iter()
+                .map(|rep_score| LeaderboardEntry {
+                    sales_rep: rep_score.rep.clone(),
+                    score: rep_score.score,
+                    close_rate: rep_score.close_rate,
+                    install_rate: rep_score.install_rate,
+                    installs: rep_score.volume,
+                    average_days_to_close: into_days(rep_score.average_time_to_close),
+                })
+                .collect();
+
+            let red_flag = |subject: &KpiSubject, job: &AnalyzedJob, message| RedFlag {
+                subject: subject.clone(),
+                jnid: job.job.jnid.clone(),
+                job_number: job.job.job_number.clone(),
+                message,
+            };
+            // sort the red flags by subject, since they are collected in a
+            // hash map
+            let mut red_flags: Vec<_> = analysis
+                .red_flags
+                .iter()
+                .flat_map(|(subject, red_flags)| {
+                    red_flags.iter().map(move |(job, err)| red_flag(subject, job, err.to_string()))
+                })
+                .collect();
+            red_flags.sort_by(|a, b| a.subject.cmp(&b.subject));
+
+            let reconciliation = &analysis.reconciliation;
+            let excluded = reconciliation
+                .excluded
+                .iter()
+                .map(|(job, reason)| {
+                    let subject = match &job.job.sales_rep {
+                        Some(name) => KpiSubject::SalesRep(name.clone()),
+                        None => KpiSubject::UnknownSalesRep,
+                    };
+                    red_flag(&subject, job, reason.to_string())
+                })
+                .collect();
+
+            KpiReport {
+                schema_version: SCHEMA_VERSION,
+                from: options.from.map(format_timestamp),
+                to: options.to.map(format_timestamp),
+                parameters: Vec::new(),
+                subjects,
+                leaderboard,
+                red_flags,
+                reconciliation: ReconciliationCounts {
+                    total: reconciliation.total(),
+                    settled_in_range: reconciliation.settled_in_range,
+                    settled_out_of_range: reconciliation.settled_out_of_range,
+                    unsettled: reconciliation.unsettled,
+                    abandoned: reconciliation.abandoned,
+                    milestoneless: reconciliation.milestoneless,
+                    excluded,
+                },
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+        use crate::jobs::{test::make_job, TimeDelta};
+        use crate::subcommands::kpi;
+
+        #[test]
+        fn kpi_report_round_trips() {
+            let day = |days| Some(Timestamp::UNIX_EPOCH + TimeDelta::days(days));
+            let mut installed = make_job(false, day(1), None, day(3), day(4), None);
+            installed.jnid = "a".to_owned();
+            installed.sales_rep = Some("Alice".to_owned());
+            let mut out_of_order = make_job(false, day(5), None, day(3), None, None);
+            out_of_order.jnid = "b".to_owned();
+
+            let report =
+                kpi::analyze(vec![installed, out_of_order], &KpiOptions::default()).unwrap();
+            assert_eq!(report.schema_version, SCHEMA_VERSION);
+            let subjects: Vec<_> = report.subjects.iter().map(|stats| &stats.subject).collect();
+            assert_eq!(subjects, [&KpiSubject::Global, &KpiSubject::SalesRep("Alice".to_owned())]);
+            assert_eq!(report.subjects[0].installs, 1);
+            assert_eq!(report.leaderboard.len(), 1);
+            assert_eq!(report.reconciliation.total, 2);
+            assert_eq!(report.reconciliation.excluded[0].jnid, "b");
+
+            let json = serde_json::to_string(&report).unwrap();
+            assert_eq!(serde_json::from_str::<KpiReport>(&json).unwrap(), report);
+        }
+    }
+}
+
+pub mod scoring {
+    use std::{collections::BTreeMap, path::Path};
+
+    use anyhow::Context as _;
+    use serde::Deserialize;
+
+    use crate::{jobs::TimeDelta, utils::into_days};
+
+    use super::{processing::JobTrackerStats, KpiSubject};
+
+    /// The weights of each metric in the composite score of a sales rep. The
+    /// weights don't need to add up to one.
+    #[derive(Debug, Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct ScoreWeights {
+        /// The fraction of appointments that signed a contract.
+        pub close_rate: f64,
+        /// The fraction of appointments that were installed.
+        pub install_rate: f64,
+        /// The number of installs.
+        pub volume: f64,
+        /// The average time to sign a contract. Shorter times score higher.
+        pub time_to_close: f64,
+    }
+
+    impl Default for ScoreWeights {
+        fn default() -> Self {
+            ScoreWeights { close_rate: 0.4, install_rate: 0.2, volume: 0.2, time_to_close: 0.2 }
+        }
+    }
+
+    impl ScoreWeights {
+        pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+            let path = path.as_ref();
+            let file = std::fs::File::open(path)
+                .with_context(|| format!("Failed to open scoring file {}", path.display()))?;
+            serde_json::from_reader(std::io::BufReader::new(file))
+                .with_context(|| format!("Invalid scoring file {}", path.display()))
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct RepScore {
+        pub rep: KpiSubject,
+        /// The composite score, from 0 to 100.
+        pub score: f64,
+        pub close_rate: Option<f64>,
+        pub install_rate: Option<f64>,
+        pub volume: usize,
+        pub average_time_to_close: TimeDelta,
+    }
+
+    impl RepScore {
+        pub const HEADERS: [&'static str; 7] = [
+            "Rank",
+            "Sales Rep",
+            "Score",
+            "Close Rate",
+            "Install Rate",
+            "Installs",
+            "Avg Time to Close (days)",
+        ];
+    }
+
+    /// Calculates the composite score of every sales rep and returns them
+    /// from highest to lowest score. Each metric is scaled so that the worst
+    /// rep gets 0 and the best rep gets 1 before being weighted, and missing
+    /// rates count as 0.
+    pub fn rank_reps(
+        tracker_stats: &BTreeMap<KpiSubject, JobTrackerStats>,
+        weights: &ScoreWeights,
+    ) -> Vec<RepScore> {
+        let mut rep_scores: Vec<RepScore> = tracker_stats
+            .iter()
+            .filter(|(rep, _)| matches!(rep, KpiSubject::SalesRep(_)))
+            .map(|(rep, stats)| {
+                let rate = |count: usize| {
+                    if stats.appt_count == 0 {
+                        None
+                    } else {
+                        Some(count as f64 / stats.appt_count as f64)
+                    }
+                };
+                RepScore {
+                    rep: rep.clone(),
+                    score: 0.0,
+                    close_rate: rate(stats.contract_count()),
+                    install_rate: rate(stats.install_count),
+                    volume: stats.install_count,
+                    average_time_to_close: stats.average_time_to_close(),
+                }
+            })
+            .collect();
+
+        let metrics: [(f64, Vec<f64>); 4] = [
+            (weights.close_rate, normalize(rep_scores.iter().map(|s| s.close_rate.unwrap_or(0.0)))),
+            (
+                weights.install_rate,
+                normalize(rep_scores.iter().map(|s| s.install_rate.unwrap_or(0.0))),
+            ),
+            (weights.volume, normalize(rep_scores.iter().map(|s| s.volume as f64))),
+            (
+                weights.time_to_close,
+                // negate so that shorter times are better
+                normalize(rep_scores.iter().map(|s| -into_days(s.average_time_to_close))),
+            ),
+        ];
+        let total_weight: f64 = metrics.iter().map(|(weight, _)| weight).sum();
+        if total_weight > 0.0 {
+            for (i, rep_score) in rep_scores.iter_mut().enumerate() {
+                let weighted: f64 = metrics.iter().map(|(weight, values)| weight * values[i]).sum();
+                rep_score.score = weighted / total_weight * 100.0;
+            }
+        }
+
+        rep_scores.sort_by(|a, b| b.score.total_cmp(&a.score));
+        rep_scores
+    }
+
+    /// Scales the values so that the smallest becomes 0 and the largest
+    /// becomes 1. If all the values are the same, they all become 1.
+    fn normalize(values: impl Iterator<Item = f64>) -> Vec<f64> {
+        let values: Vec<f64> = values.collect();
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        values
+            .into_iter()
+            .map(|value| if max > min { (value - min) / (max - min) } else { 1.0 })
+            .collect()
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn normalize_values() {
+            assert_eq!(normalize([2.0, 4.0, 3.0].into_iter()), vec![0.0, 1.0, 0.5]);
+            assert_eq!(normalize([5.0, 5.0].into_iter()), vec![1.0, 1.0]);
+            assert_eq!(normalize(std::iter::empty()), Vec::<f64>::new());
+        }
+    }
 }
@@ -0,0 +1,85 @@
+use std::{collections::BTreeMap, io::Write};
+
+use crate::{apis::job_nimbus, lead_sources::LeadSourceMap, subcommands::output};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The JobNimbus API key. This key will be cached.
+    #[arg(long, default_value = None, global = true, env)]
+    jn_api_key: Option<String>,
+
+    /// The action to perform on the lead source map.
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// List the lead sources of all jobs, grouped by their normalized name.
+    List {
+        /// The filter to use when query JobNimbus for jobs, using ElasticSearch
+        /// syntax.
+        #[arg(short, long = "filter", default_value = None)]
+        filter_filename: Option<String>,
+
+        /// The file to write the output to. "-" or unspecified will write to
+        /// stdout.
+        #[arg(short, long, default_value = None)]
+        output: Option<String>,
+    },
+    /// Map one or more lead sources to a single normalized name.
+    Map {
+        /// The normalized name of the lead source.
+        name: String,
+        /// The lead sources (as entered in JobNimbus) to map to the normalized
+        /// name. Matching ignores case and surrounding whitespace.
+        #[arg(required = true)]
+        aliases: Vec<String>,
+    },
+}
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let Args { jn_api_key, command } = args;
+
+    match command {
+        Command::List { filter_filename, output } => {
+            let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+            let jobs =
+                job_nimbus::get_all_jobs_with_filter_file(&jn_api_key, filter_filename.as_deref())?;
+
+            let lead_source_map = LeadSourceMap::load();
+            // normalized name -> raw lead source -> number of jobs
+            let mut lead_sources: BTreeMap<Option<&str>, BTreeMap<&str, usize>> = BTreeMap::new();
+            for job in &jobs {
+                let raw = job.lead_source.as_deref();
+                let normalized = raw.map(|raw| lead_source_map.normalize(raw));
+                *lead_sources
+                    .entry(normalized)
+                    .or_default()
+                    .entry(raw.unwrap_or(""))
+                    .or_default() += 1;
+            }
+
+            let mut output_writer = output::writer(output.as_deref())?;
+            for (normalized, raw_sources) in lead_sources {
+                let total: usize = raw_sources.values().sum();
+                writeln!(
+                    output_writer,
+                    "{} ({} jobs)",
+                    normalized.unwrap_or("No Lead Source"),
+                    total
+                )?;
+                if normalized.is_some() {
+                    for (raw, count) in raw_sources {
+                        writeln!(output_writer, "    \"{}\": {}", raw, count)?;
+                    }
+                }
+            }
+        }
+        Command::Map { name, aliases } => {
+            LeadSourceMap::update(|lead_source_map| lead_source_map.add_aliases(&name, aliases))?;
+        }
+    }
+
+    Ok(())
+}
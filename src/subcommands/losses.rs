@@ -0,0 +1,345 @@
+use std::{collections::BTreeMap, io::Write};
+
+use chrono::Utc;
+
+use crate::{
+    apis::{
+        google_sheets::{
+            self,
+            spreadsheet::{
+                mk_row, ExtendedValue, GridData, RowData, Sheet, SheetProperties, Spreadsheet,
+                SpreadsheetProperties,
+            },
+        },
+        job_nimbus,
+    },
+    jobs::{self, AnalyzedJob, Job, Milestone, TimeDelta, Timestamp},
+    lead_sources::LeadSourceMap,
+    subcommands::output::OutputArgs,
+    utils::{self, percent_or_na},
+};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The JobNimbus API key. This key will be cached.
+    #[arg(long, default_value = None, global = true, env)]
+    jn_api_key: Option<String>,
+
+    /// The filter to use when query JobNimbus for jobs, using ElasticSearch
+    /// syntax.
+    #[arg(short, long = "filter", default_value = None)]
+    filter_filename: Option<String>,
+
+    /// The format in which to print the output.
+    #[arg(long, value_enum, default_value = "google-sheets")]
+    format: OutputFormat,
+
+    #[command(flatten)]
+    output_args: OutputArgs,
+}
+
+#[derive(Debug, clap::ValueEnum, Clone, Copy, Eq, PartialEq)]
+enum OutputFormat {
+    /// Prints a human-readable report into the output file.
+    Human,
+    /// Prints a CSV file of all lost jobs into the output file.
+    Csv,
+    /// Outputs a Google Sheet on the user's Google Drive (requires OAuth
+    /// authorization).
+    GoogleSheets,
+}
+
+/// The upper bound (inclusive, in days) and label of each range of times to
+/// loss that lost jobs are grouped into.
+const TIME_TO_LOSS_RANGES: [(i64, &str); 5] = [
+    (7, "0-7 days"),
+    (30, "8-30 days"),
+    (90, "31-90 days"),
+    (180, "91-180 days"),
+    (i64::MAX, "Over 180 days"),
+];
+
+struct LostJob<'a> {
+    job: &'a Job,
+    /// The last milestone that the job reached before it was lost.
+    lost_after: Milestone,
+    loss_date: Timestamp,
+    /// The time between reaching the last milestone and being lost, if the
+    /// date of the last milestone is known.
+    time_to_loss: Option<TimeDelta>,
+    lead_source: Option<&'a str>,
+}
+
+/// The number of lost jobs in each group of a certain grouping of lost jobs.
+struct Breakdown {
+    title: &'static str,
+    /// The name of the grouping criterion, used as a column header.
+    criterion: &'static str,
+    counts: Vec<(String, usize)>,
+}
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let Args { jn_api_key, filter_filename, format, output_args } = args;
+
+    let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+    output_args.validate(format == OutputFormat::GoogleSheets);
+
+    let jobs = job_nimbus::get_all_jobs_with_filter_file(&jn_api_key, filter_filename.as_deref())?;
+
+    let analyzed: Vec<AnalyzedJob> = jobs.into_iter().map(|job| jobs::analyze_job(job).0).collect();
+    let lead_source_map = LeadSourceMap::load();
+    let lost_jobs = find_lost_jobs(&analyzed, &lead_source_map);
+    let breakdowns = calculate_breakdowns(&lost_jobs);
+
+    let output_writer = output_args.writer()?;
+
+    match format {
+        OutputFormat::Human => print_human(&lost_jobs, &breakdowns, output_writer)?,
+        OutputFormat::Csv => print_csv(&lost_jobs, output_writer)?,
+        OutputFormat::GoogleSheets => {
+            generate_report_google_sheets(&lost_jobs, &breakdowns, !output_args.new)?
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns all the jobs that were lost, in order of the date they were lost.
+fn find_lost_jobs<'a>(
+    jobs: &'a [AnalyzedJob],
+    lead_source_map: &'a LeadSourceMap,
+) -> Vec<LostJob<'a>> {
+    let mut lost_jobs: Vec<LostJob> = jobs
+        .iter()
+        .filter_map(|AnalyzedJob { job, analysis }| {
+            let analysis = analysis.as_ref()?;
+            let loss_date = analysis.loss_timestamp?;
+            let lost_after = Milestone::ordered_iter().nth(analysis.timestamps.len() - 1)?;
+            let time_to_loss = analysis
+                .timestamps
+                .iter()
+                .rev()
+                .find_map(|&timestamp| timestamp)
+                .map(|last_milestone_date| loss_date - last_milestone_date);
+            let lead_source = job.lead_source.as_deref().map(|raw| lead_source_map.normalize(raw));
+            Some(LostJob { job, lost_after, loss_date, time_to_loss, lead_source })
+        })
+        .collect();
+    lost_jobs.sort_by_key(|lost_job| lost_job.loss_date);
+    lost_jobs
+}
+
+fn calculate_breakdowns(lost_jobs: &[LostJob]) -> Vec<Breakdown> {
+    fn count_by<'a, K: Ord>(
+        lost_jobs: &[LostJob<'a>],
+        key: impl Fn(&LostJob<'a>) -> K,
+        label: impl Fn(K) -> String,
+    ) -> Vec<(String, usize)> {
+        let mut counts: BTreeMap<K, usize> = BTreeMap::new();
+        for lost_job in lost_jobs {
+            *counts.entry(key(lost_job)).or_default() += 1;
+        }
+        counts.into_iter().map(|(k, count)| (label(k), count)).collect()
+    }
+
+    vec![
+        Breakdown {
+            title: "By Stage",
+            criterion: "Lost After",
+            counts: count_by(lost_jobs, |lost_job| lost_job.lost_after, |ms| ms.to_string()),
+        },
+        Breakdown {
+            title: "By Rep",
+            criterion: "Sales Rep",
+            counts: count_by(
+                lost_jobs,
+                |lost_job| lost_job.job.sales_rep.as_deref(),
+                |rep| rep.unwrap_or("Unknown Sales Rep").to_owned(),
+            ),
+        },
+        Breakdown {
+            title: "By Lead Source",
+            criterion: "Lead Source",
+            counts: count_by(
+                lost_jobs,
+                |lost_job| lost_job.lead_source,
+                |source| source.unwrap_or("Unknown Lead Source").to_owned(),
+            ),
+        },
+        Breakdown {
+            title: "By Reason",
+            criterion: "Loss Reason",
+            counts: count_by(
+                lost_jobs,
+                |lost_job| lost_job.job.loss_reason.as_deref(),
+                |reason| reason.unwrap_or("No Reason Given").to_owned(),
+            ),
+        },
+        Breakdown {
+            title: "Time to Loss",
+            criterion: "Time to Loss",
+            counts: count_by(
+                lost_jobs,
+                |lost_job| lost_job.time_to_loss.map(time_to_loss_range),
+                |range| range.map(|i| TIME_TO_LOSS_RANGES[i].1).unwrap_or("Unknown").to_owned(),
+            ),
+        },
+    ]
+}
+
+/// Returns the index into `TIME_TO_LOSS_RANGES` of the range that contains
+/// the specified time to loss.
+fn time_to_loss_range(time_to_loss: TimeDelta) -> usize {
+    let days = time_to_loss.num_days();
+    TIME_TO_LOSS_RANGES
+        .iter()
+        .position(|&(max_days, _)| days <= max_days)
+        .expect("the last range should be unbounded")
+}
+
+fn print_human(
+    lost_jobs: &[LostJob],
+    breakdowns: &[Breakdown],
+    mut writer: impl Write,
+) -> std::io::Result<()> {
+    let total = lost_jobs.len();
+    writeln!(writer, "Lost jobs: {}", total)?;
+    for breakdown in breakdowns {
+        writeln!(writer, "{}:", breakdown.title)?;
+        for (label, count) in &breakdown.counts {
+            writeln!(
+                writer,
+                "    - {}: {} ({})",
+                label,
+                count,
+                percent_or_na(Some(*count as f64 / total as f64))
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn print_csv(lost_jobs: &[LostJob], writer: impl Write) -> std::io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record([
+        "Job Name",
+        "Sales Rep",
+        "Job Number",
+        "Lead Source",
+        "Lost After",
+        "Loss Date",
+        "Days to Loss",
+        "Loss Reason",
+    ])?;
+    for lost_job in lost_jobs {
+        let job = lost_job.job;
+        writer.write_record([
+            job.job_name.as_deref().unwrap_or(""),
+            job.sales_rep.as_deref().unwrap_or("Unknown Sales Rep"),
+            job.job_number.as_deref().unwrap_or("Unknown Job Number"),
+            lost_job.lead_source.unwrap_or(""),
+            &lost_job.lost_after.to_string(),
+            &lost_job.loss_date.date_naive().to_string(),
+            &lost_job.time_to_loss.map(|t| t.num_days().to_string()).unwrap_or_default(),
+            job.loss_reason.as_deref().unwrap_or(""),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn generate_report_google_sheets(
+    lost_jobs: &[LostJob],
+    breakdowns: &[Breakdown],
+    update: bool,
+) -> anyhow::Result<()> {
+    let total = lost_jobs.len();
+    let mut sheets: Vec<(&str, Vec<RowData>)> = breakdowns
+        .iter()
+        .map(|breakdown| {
+            let mut rows = vec![mk_row([
+                ExtendedValue::StringValue(breakdown.criterion.to_string()),
+                ExtendedValue::StringValue("Lost Jobs".to_string()),
+                ExtendedValue::StringValue("Percentage".to_string()),
+            ])];
+            for (label, count) in &breakdown.counts {
+                rows.push(mk_row([
+                    ExtendedValue::StringValue(label.clone()),
+                    ExtendedValue::NumberValue(*count as f64),
+                    ExtendedValue::StringValue(percent_or_na(Some(*count as f64 / total as f64))),
+                ]));
+            }
+            (breakdown.title, rows)
+        })
+        .collect();
+
+    let mut job_rows = vec![mk_row([
+        ExtendedValue::StringValue("Job Name".to_string()),
+        ExtendedValue::StringValue("Sales Rep".to_string()),
+        ExtendedValue::StringValue("Job Number".to_string()),
+        ExtendedValue::StringValue("Lead Source".to_string()),
+        ExtendedValue::StringValue("Lost After".to_string()),
+        ExtendedValue::StringValue("Loss Date".to_string()),
+        ExtendedValue::StringValue("Days to Loss".to_string()),
+        ExtendedValue::StringValue("Loss Reason".to_string()),
+    ])];
+    for lost_job in lost_jobs {
+        let job = lost_job.job;
+        job_rows.push(mk_row([
+            ExtendedValue::StringValue(job.job_name.clone().unwrap_or_default()),
+            ExtendedValue::StringValue(
+                job.sales_rep.clone().unwrap_or_else(|| "Unknown Sales Rep".to_owned()),
+            ),
+            ExtendedValue::StringValue(
+                job.job_number.clone().unwrap_or_else(|| "Unknown Job Number".to_owned()),
+            ),
+            ExtendedValue::StringValue(lost_job.lead_source.unwrap_or("").to_owned()),
+            ExtendedValue::StringValue(lost_job.lost_after.to_string()),
+            ExtendedValue::DateValue(lost_job.loss_date.date_naive()),
+            match lost_job.time_to_loss {
+                Some(time_to_loss) => ExtendedValue::NumberValue(time_to_loss.num_days() as f64),
+                None => ExtendedValue::StringValue("".to_owned()),
+            },
+            ExtendedValue::StringValue(job.loss_reason.clone().unwrap_or_default()),
+        ]));
+    }
+    sheets.push(("Jobs", job_rows));
+
+    let sheets = sheets
+        .into_iter()
+        .map(|(title, rows)| Sheet {
+            properties: SheetProperties { title: Some(title.to_string()), ..Default::default() },
+            data: Some(GridData { start_row: 0, start_column: 0, row_data: rows }),
+        })
+        .collect();
+
+    let spreadsheet = Spreadsheet {
+        properties: SpreadsheetProperties {
+            title: Some(format!("Win/Loss Report ({})", Utc::now())),
+        },
+        sheets: Some(sheets),
+        ..Default::default()
+    };
+
+    let url = google_sheets::upload_spreadsheet_blocking(
+        google_sheets::SheetNickname::Losses,
+        spreadsheet,
+        update,
+    )?;
+    utils::open_url(url.as_str());
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn time_to_loss_ranges() {
+        assert_eq!(time_to_loss_range(TimeDelta::zero()), 0);
+        assert_eq!(time_to_loss_range(TimeDelta::days(7)), 0);
+        assert_eq!(time_to_loss_range(TimeDelta::days(8)), 1);
+        assert_eq!(time_to_loss_range(TimeDelta::days(90)), 2);
+        assert_eq!(time_to_loss_range(TimeDelta::days(181)), 4);
+    }
+}
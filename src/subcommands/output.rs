@@ -0,0 +1,63 @@
+use std::io::Write;
+
+use crate::argument_conflict;
+
+/// The output formats of the reports that are printed into a file or uploaded
+/// as a Google Sheet.
+#[derive(Debug, clap::ValueEnum, Clone, Copy, Eq, PartialEq)]
+pub enum OutputFormat {
+    /// Prints a human-readable report into the output file.
+    Human,
+    /// Prints a CSV file into the output file.
+    Csv,
+    /// Outputs a Google Sheet on the user's Google Drive (requires OAuth
+    /// authorization).
+    GoogleSheets,
+}
+
+/// Where to write the report of a subcommand that can either print it into a
+/// file or upload it as a Google Sheet.
+#[derive(clap::Args, Debug, Clone)]
+pub struct OutputArgs {
+    /// The file to write the output to. "-" or unspecified will write to
+    /// stdout. This option is ignored with `--format google-sheets`.
+    #[arg(short, long, default_value = None)]
+    pub output: Option<String>,
+
+    /// Only valid with `--format google-sheets`. Whether to always create a new
+    /// Google Sheet. If not specified, then updates the existing Google Sheet
+    /// for this command if it exists.
+    #[arg(long)]
+    pub new: bool,
+}
+
+impl OutputArgs {
+    /// Exits with a usage error if the options don't apply to the format.
+    pub fn validate(&self, google_sheets: bool) {
+        validate(google_sheets, self.output.is_some(), self.new);
+    }
+
+    /// The file to print the report into, or stdout if none was specified.
+    pub fn writer(&self) -> std::io::Result<Box<dyn Write>> {
+        writer(self.output.as_deref())
+    }
+}
+
+/// Exits with a usage error if `--output` is specified for a Google Sheet or
+/// `--new` is specified for any other format.
+pub fn validate(google_sheets: bool, output: bool, new: bool) {
+    if google_sheets && output {
+        argument_conflict("The `--output` option cannot be used with `--format google-sheets`");
+    }
+    if !google_sheets && new {
+        argument_conflict("The `--new` option can only be used with `--format google-sheets`");
+    }
+}
+
+/// Opens the output file for writing. "-" or `None` is stdout.
+pub fn writer(output: Option<&str>) -> std::io::Result<Box<dyn Write>> {
+    Ok(match output {
+        Some("-") | None => Box::new(std::io::stdout()),
+        Some(path) => Box::new(std::fs::File::create(path)?),
+    })
+}
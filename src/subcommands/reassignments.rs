@@ -0,0 +1,423 @@
+use std::{collections::HashMap, io::Write};
+
+use chrono::Utc;
+
+use crate::{
+    apis::{
+        google_sheets::{
+            self,
+            spreadsheet::{
+                mk_row, ExtendedValue, GridData, Sheet, SheetProperties, Spreadsheet,
+                SpreadsheetProperties,
+            },
+        },
+        job_nimbus::{self, Activity},
+    },
+    jobs::{Job, Timestamp},
+    subcommands::output::OutputArgs,
+    utils::{self, percent_or_na},
+};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The JobNimbus API key. This key will be cached.
+    #[arg(long, default_value = None, global = true, env)]
+    jn_api_key: Option<String>,
+
+    /// The filter to use when query JobNimbus for jobs, using ElasticSearch
+    /// syntax.
+    #[arg(short, long = "filter", default_value = None)]
+    filter_filename: Option<String>,
+
+    /// The format in which to print the output.
+    #[arg(long, value_enum, default_value = "google-sheets")]
+    format: OutputFormat,
+
+    #[command(flatten)]
+    output_args: OutputArgs,
+}
+
+#[derive(Debug, clap::ValueEnum, Clone, Copy, Eq, PartialEq)]
+enum OutputFormat {
+    /// Prints a human-readable report into the output file.
+    Human,
+    /// Prints a CSV file of every job and when its sales rep was assigned into
+    /// the output file.
+    Csv,
+    /// Outputs a Google Sheet on the user's Google Drive (requires OAuth
+    /// authorization).
+    GoogleSheets,
+}
+
+/// The filter for the activities that log a change of sales rep, in
+/// ElasticSearch syntax.
+const REP_CHANGE_FILTER: &str = r#"{"must":[{"match_phrase":{"note":"Sales Rep changed"}}]}"#;
+
+/// A change of a job's sales rep, as logged in the job's activity feed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepChange {
+    pub date: Timestamp,
+    /// The previous sales rep, or `None` if the job had no sales rep.
+    pub from: Option<String>,
+    pub to: String,
+}
+
+/// The assignment history of a single job.
+#[derive(Debug)]
+pub struct JobAssignment<'a> {
+    pub job: &'a Job,
+    /// When the current sales rep was assigned, if the assignment was logged.
+    pub assigned_date: Option<Timestamp>,
+    /// The changes from one sales rep to another after the appointment and
+    /// before the job was settled, in order.
+    pub mid_pipeline_changes: Vec<RepChange>,
+}
+
+/// The conversions of a group of jobs that had an appointment.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct GroupStats {
+    pub num_appointments: usize,
+    pub num_contracts: usize,
+    pub num_installs: usize,
+}
+
+impl GroupStats {
+    /// The fraction of appointments that signed a contract. `None` if there
+    /// were no appointments.
+    pub fn close_rate(&self) -> Option<f64> {
+        (self.num_appointments != 0)
+            .then(|| self.num_contracts as f64 / self.num_appointments as f64)
+    }
+
+    /// The fraction of contracts that were installed. `None` if there were no
+    /// contracts.
+    pub fn install_rate(&self) -> Option<f64> {
+        (self.num_contracts != 0).then(|| self.num_installs as f64 / self.num_contracts as f64)
+    }
+}
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let Args { jn_api_key, filter_filename, format, output_args } = args;
+
+    let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+    output_args.validate(format == OutputFormat::GoogleSheets);
+
+    let jobs = job_nimbus::get_all_jobs_with_filter_file(&jn_api_key, filter_filename.as_deref())?;
+    let activities = job_nimbus::get_all_activities(&jn_api_key, Some(REP_CHANGE_FILTER))?;
+
+    let assignments = track_assignments(&jobs, &activities);
+    let (reassigned, not_reassigned) = compare_conversions(&assignments);
+
+    let output_writer = output_args.writer()?;
+
+    match format {
+        OutputFormat::Human => {
+            print_human(&assignments, &reassigned, &not_reassigned, output_writer)?
+        }
+        OutputFormat::Csv => print_csv(&assignments, output_writer)?,
+        OutputFormat::GoogleSheets => {
+            let spreadsheet = Spreadsheet {
+                properties: SpreadsheetProperties {
+                    title: Some(format!("Reassignment Report ({})", Utc::now())),
+                },
+                sheets: Some(vec![
+                    conversion_sheet(&reassigned, &not_reassigned),
+                    assignments_sheet(&assignments),
+                ]),
+                ..Default::default()
+            };
+            let url = google_sheets::upload_spreadsheet_blocking(
+                google_sheets::SheetNickname::Reassignments,
+                spreadsheet,
+                !output_args.new,
+            )?;
+            utils::open_url(url.as_str());
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses the note that JobNimbus logs when a job's sales rep changes, e.g.
+/// "Sales Rep changed from Jane Doe to John Smith", or "Sales Rep changed to
+/// John Smith" if the job had no sales rep. Names may be quoted.
+fn parse_rep_change(note: &str) -> Option<(Option<String>, String)> {
+    fn name(s: &str) -> Option<String> {
+        Some(s.trim().trim_matches('"').trim().to_owned()).filter(|name| !name.is_empty())
+    }
+
+    let rest = note.trim().strip_prefix("Sales Rep changed ")?;
+    if let Some(rest) = rest.strip_prefix("from ") {
+        let (from, to) = rest.rsplit_once(" to ")?;
+        Some((name(from), name(to)?))
+    } else {
+        Some((None, name(rest.strip_prefix("to ")?)?))
+    }
+}
+
+/// Reconstructs the assignment history of each job from the activities that
+/// log its changes of sales rep.
+fn track_assignments<'a>(jobs: &'a [Job], activities: &[Activity]) -> Vec<JobAssignment<'a>> {
+    let mut changes_by_job: HashMap<&str, Vec<RepChange>> = HashMap::new();
+    for activity in activities {
+        if let Some((from, to)) = parse_rep_change(&activity.note) {
+            changes_by_job.entry(&activity.primary_jnid).or_default().push(RepChange {
+                date: activity.date_created,
+                from,
+                to,
+            });
+        }
+    }
+
+    jobs.iter()
+        .map(|job| {
+            let mut changes = changes_by_job.remove(job.jnid.as_str()).unwrap_or_default();
+            changes.sort_by_key(|change| change.date);
+            let assigned_date = changes.last().map(|change| change.date);
+            let pipeline_start = job.milestone_dates.appointment_date;
+            let pipeline_end = job.milestone_dates.install_date.or(job.milestone_dates.loss_date);
+            let mid_pipeline_changes = changes
+                .into_iter()
+                .filter(|change| change.from.is_some())
+                .filter(|change| pipeline_start.is_some_and(|start| change.date >= start))
+                .filter(|change| pipeline_end.is_none_or(|end| change.date < end))
+                .collect();
+            JobAssignment { job, assigned_date, mid_pipeline_changes }
+        })
+        .collect()
+}
+
+/// Compares the conversions of the jobs that were reassigned mid-pipeline
+/// (first) with those of the jobs that weren't (second). Only jobs that had an
+/// appointment are counted.
+fn compare_conversions(assignments: &[JobAssignment]) -> (GroupStats, GroupStats) {
+    let mut reassigned = GroupStats::default();
+    let mut not_reassigned = GroupStats::default();
+    for assignment in assignments {
+        let dates = &assignment.job.milestone_dates;
+        if dates.appointment_date.is_none() {
+            continue;
+        }
+        let stats = if assignment.mid_pipeline_changes.is_empty() {
+            &mut not_reassigned
+        } else {
+            &mut reassigned
+        };
+        stats.num_appointments += 1;
+        if dates.contract_date.is_some() {
+            stats.num_contracts += 1;
+        }
+        if dates.install_date.is_some() {
+            stats.num_installs += 1;
+        }
+    }
+    (reassigned, not_reassigned)
+}
+
+fn job_number(job: &Job) -> &str {
+    job.job_number.as_deref().unwrap_or(&job.jnid)
+}
+
+fn rep_name(rep: Option<&str>) -> &str {
+    rep.unwrap_or("Unknown Sales Rep")
+}
+
+/// The mid-pipeline changes of sales rep on a single line, e.g. "Jane Doe ->
+/// John Smith (2024-03-01)".
+fn history(assignment: &JobAssignment) -> String {
+    assignment
+        .mid_pipeline_changes
+        .iter()
+        .map(|change| {
+            format!(
+                "{} -> {} ({})",
+                rep_name(change.from.as_deref()),
+                change.to,
+                change.date.format("%Y-%m-%d")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn print_human(
+    assignments: &[JobAssignment],
+    reassigned: &GroupStats,
+    not_reassigned: &GroupStats,
+    mut writer: impl Write,
+) -> std::io::Result<()> {
+    writeln!(writer, "Conversion of jobs with an appointment:")?;
+    for (name, stats) in [("Reassigned", reassigned), ("Not reassigned", not_reassigned)] {
+        writeln!(
+            writer,
+            "    {:15} Appointments {:5} | Close Rate {} | Install Rate {}",
+            name,
+            stats.num_appointments,
+            percent_or_na(stats.close_rate()),
+            percent_or_na(stats.install_rate()),
+        )?;
+    }
+    writeln!(writer)?;
+
+    let reassigned_jobs: Vec<_> =
+        assignments.iter().filter(|a| !a.mid_pipeline_changes.is_empty()).collect();
+    writeln!(writer, "Jobs reassigned mid-pipeline: {}", reassigned_jobs.len())?;
+    for assignment in reassigned_jobs {
+        writeln!(
+            writer,
+            "    - {} (#{}): {}",
+            assignment.job.job_name.as_deref().unwrap_or(""),
+            job_number(assignment.job),
+            history(assignment),
+        )?;
+    }
+    Ok(())
+}
+
+fn print_csv(assignments: &[JobAssignment], writer: impl Write) -> std::io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record([
+        "Job Number",
+        "Job Name",
+        "Sales Rep",
+        "Date Assigned",
+        "Mid-Pipeline Reassignments",
+        "History",
+    ])?;
+    for assignment in assignments {
+        writer.write_record([
+            job_number(assignment.job),
+            assignment.job.job_name.as_deref().unwrap_or(""),
+            rep_name(assignment.job.sales_rep.as_deref()),
+            &assignment
+                .assigned_date
+                .map(|date| date.format("%Y-%m-%d").to_string())
+                .unwrap_or_default(),
+            &assignment.mid_pipeline_changes.len().to_string(),
+            &history(assignment),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn conversion_sheet(reassigned: &GroupStats, not_reassigned: &GroupStats) -> Sheet {
+    let mut rows = vec![mk_row([
+        ExtendedValue::StringValue("Jobs".to_string()),
+        ExtendedValue::StringValue("Appointments".to_string()),
+        ExtendedValue::StringValue("Contracts".to_string()),
+        ExtendedValue::StringValue("Installs".to_string()),
+        ExtendedValue::StringValue("Close Rate".to_string()),
+        ExtendedValue::StringValue("Install Rate".to_string()),
+    ])];
+    for (name, stats) in [("Reassigned", reassigned), ("Not reassigned", not_reassigned)] {
+        rows.push(mk_row([
+            ExtendedValue::StringValue(name.to_owned()),
+            ExtendedValue::NumberValue(stats.num_appointments as f64),
+            ExtendedValue::NumberValue(stats.num_contracts as f64),
+            ExtendedValue::NumberValue(stats.num_installs as f64),
+            ExtendedValue::StringValue(percent_or_na(stats.close_rate())),
+            ExtendedValue::StringValue(percent_or_na(stats.install_rate())),
+        ]));
+    }
+
+    Sheet {
+        properties: SheetProperties { title: Some("Conversion".to_string()), ..Default::default() },
+        data: Some(GridData { start_row: 0, start_column: 0, row_data: rows }),
+    }
+}
+
+fn assignments_sheet(assignments: &[JobAssignment]) -> Sheet {
+    let mut rows = vec![mk_row([
+        ExtendedValue::StringValue("Job Number".to_string()),
+        ExtendedValue::StringValue("Job Name".to_string()),
+        ExtendedValue::StringValue("Sales Rep".to_string()),
+        ExtendedValue::StringValue("Date Assigned".to_string()),
+        ExtendedValue::StringValue("Mid-Pipeline Reassignments".to_string()),
+        ExtendedValue::StringValue("History".to_string()),
+    ])];
+    for assignment in assignments {
+        rows.push(mk_row([
+            ExtendedValue::StringValue(job_number(assignment.job).to_owned()),
+            ExtendedValue::StringValue(assignment.job.job_name.clone().unwrap_or_default()),
+            ExtendedValue::StringValue(rep_name(assignment.job.sales_rep.as_deref()).to_owned()),
+            match assignment.assigned_date {
+                Some(date) => ExtendedValue::DateValue(date.date_naive()),
+                None => ExtendedValue::StringValue(String::new()),
+            },
+            ExtendedValue::NumberValue(assignment.mid_pipeline_changes.len() as f64),
+            ExtendedValue::StringValue(history(assignment)),
+        ]));
+    }
+
+    Sheet {
+        properties: SheetProperties {
+            title: Some("Assignments".to_string()),
+            ..Default::default()
+        },
+        data: Some(GridData { start_row: 0, start_column: 0, row_data: rows }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jobs::{test::make_job, TimeDelta};
+
+    fn day(days: i64) -> Timestamp {
+        Timestamp::UNIX_EPOCH + TimeDelta::days(days)
+    }
+
+    fn activity(jnid: &str, days: i64, note: &str) -> Activity {
+        Activity { primary_jnid: jnid.to_owned(), date_created: day(days), note: note.to_owned() }
+    }
+
+    #[test]
+    fn rep_changes_are_parsed() {
+        assert_eq!(
+            parse_rep_change("Sales Rep changed from \"Jane Doe\" to John Smith"),
+            Some((Some("Jane Doe".to_owned()), "John Smith".to_owned()))
+        );
+        assert_eq!(parse_rep_change("Sales Rep changed to Jo"), Some((None, "Jo".to_owned())));
+        assert_eq!(parse_rep_change("Status changed to Lost"), None);
+        assert_eq!(parse_rep_change("Sales Rep changed from Jo to "), None);
+    }
+
+    #[test]
+    fn only_changes_during_the_pipeline_count() {
+        let mut reassigned = make_job(false, Some(day(10)), None, Some(day(20)), None, None);
+        reassigned.jnid = "a".to_owned();
+        let mut after_install =
+            make_job(false, Some(day(10)), None, Some(day(20)), Some(day(30)), None);
+        after_install.jnid = "b".to_owned();
+        let mut unchanged = make_job(false, Some(day(10)), None, None, None, None);
+        unchanged.jnid = "c".to_owned();
+        let jobs = [reassigned, after_install, unchanged];
+        let activities = [
+            activity("a", 1, "Sales Rep changed to Jane"),
+            activity("a", 15, "Sales Rep changed from Jane to John"),
+            activity("b", 1, "Sales Rep changed to Jane"),
+            activity("b", 40, "Sales Rep changed from Jane to John"),
+            activity("c", 12, "Sales Rep changed to Jane"),
+            activity("c", 13, "Appointment rescheduled"),
+        ];
+
+        let assignments = track_assignments(&jobs, &activities);
+        let num_changes: Vec<_> =
+            assignments.iter().map(|a| a.mid_pipeline_changes.len()).collect();
+        assert_eq!(num_changes, [1, 0, 0]);
+        assert_eq!(assignments[0].assigned_date, Some(day(15)));
+        assert_eq!(assignments[2].assigned_date, Some(day(12)));
+
+        let (reassigned, not_reassigned) = compare_conversions(&assignments);
+        assert_eq!(
+            reassigned,
+            GroupStats { num_appointments: 1, num_contracts: 1, num_installs: 0 }
+        );
+        assert_eq!(
+            not_reassigned,
+            GroupStats { num_appointments: 2, num_contracts: 1, num_installs: 1 }
+        );
+        assert_eq!(not_reassigned.close_rate(), Some(0.5));
+    }
+}
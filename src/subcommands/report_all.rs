@@ -0,0 +1,141 @@
+use std::{ffi::OsString, path::Path};
+
+use chrono::Utc;
+use clap::Parser;
+use tracing::info;
+
+use crate::{
+    apis::job_nimbus,
+    subcommands::{acc_receivable, jobs, kpi, output},
+};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The JobNimbus API key. This key will be cached.
+    #[arg(long, default_value = None, global = true, env)]
+    jn_api_key: Option<String>,
+
+    /// The filter to use when query JobNimbus for jobs, using ElasticSearch
+    /// syntax. Applies to all of the reports.
+    #[arg(short, long = "filter", default_value = None)]
+    filter_filename: Option<String>,
+
+    /// Only include jobs of the specified trade (e.g. "Roofing") in the KPI and
+    /// AR reports.
+    #[arg(long, default_value = None)]
+    trade: Option<String>,
+
+    /// The format in which to generate the KPI and AR reports. The list of all
+    /// jobs is a table with `--format human`, and a CSV file otherwise.
+    #[arg(long, value_enum, default_value = "google-sheets")]
+    format: OutputFormat,
+
+    /// The directory to write the reports to. With `--format google-sheets`,
+    /// only the list of all jobs is written to this directory.
+    #[arg(short, long, default_value = "reports")]
+    output: String,
+
+    /// Only valid with `--format google-sheets`. Whether to always create new
+    /// Google Sheets. If not specified, then updates the existing Google Sheets
+    /// for the KPI and AR reports if they exist.
+    #[arg(long)]
+    new: bool,
+}
+
+#[derive(Debug, clap::ValueEnum, Clone, Copy, Eq, PartialEq)]
+enum OutputFormat {
+    /// Prints human-readable reports into the output directory.
+    Human,
+    /// Prints CSV files into the output directory.
+    Csv,
+    /// Outputs the KPI and AR reports as Google Sheets on the user's Google
+    /// Drive (requires OAuth authorization).
+    GoogleSheets,
+}
+
+impl OutputFormat {
+    fn as_arg(self) -> &'static str {
+        match self {
+            OutputFormat::Human => "human",
+            OutputFormat::Csv => "csv",
+            OutputFormat::GoogleSheets => "google-sheets",
+        }
+    }
+}
+
+/// Wraps the arguments of another subcommand so that they can be parsed from a
+/// list of command-line arguments.
+#[derive(Parser)]
+struct SubcommandArgs<T: clap::Args> {
+    #[command(flatten)]
+    args: T,
+}
+
+fn parse_args<T: clap::Args>(args: impl IntoIterator<Item = OsString>) -> T {
+    let args = std::iter::once(OsString::from("ahitool")).chain(args);
+    SubcommandArgs::<T>::parse_from(args).args
+}
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let Args { jn_api_key, filter_filename, trade, format, output, new } = args;
+
+    // the output directory always has the list of all jobs
+    output::validate(format == OutputFormat::GoogleSheets, false, new);
+
+    let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+    let fetched_at = Utc::now();
+    let jobs = job_nimbus::get_all_jobs_with_filter_file(&jn_api_key, filter_filename.as_deref())?;
+
+    let output_dir = Path::new(&output);
+    std::fs::create_dir_all(output_dir)?;
+
+    // the reports take the same options as their own subcommands, so that
+    // they use the same defaults
+    let mut common_args: Vec<OsString> = vec!["--format".into(), format.as_arg().into()];
+    if let Some(trade) = &trade {
+        common_args.extend(["--trade".into(), trade.into()]);
+    }
+    if new {
+        common_args.push("--new".into());
+    }
+
+    info!("Generating the KPI report");
+    let mut kpi_args = common_args.clone();
+    if format != OutputFormat::GoogleSheets {
+        kpi_args.extend(["--output".into(), output_dir.join("kpi").into()]);
+    }
+    kpi::run(parse_args(kpi_args), Some((fetched_at, jobs.clone())))?;
+
+    info!("Generating the AR report");
+    let mut ar_args = common_args;
+    let ar_file = match format {
+        OutputFormat::Human => Some("ar.txt"),
+        OutputFormat::Csv => Some("ar.csv"),
+        OutputFormat::GoogleSheets => None,
+    };
+    if let Some(ar_file) = ar_file {
+        ar_args.extend(["--output".into(), output_dir.join(ar_file).into()]);
+    }
+    acc_receivable::run(parse_args(ar_args), Some((fetched_at, jobs.clone())))?;
+
+    info!("Writing the list of all jobs");
+    if format == OutputFormat::Human {
+        jobs::print_table(&jobs, std::fs::File::create(output_dir.join("jobs.txt"))?)?;
+    } else {
+        jobs::print_csv(&jobs, std::fs::File::create(output_dir.join("jobs.csv"))?)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_report_args() {
+        let args = || ["--format".into(), "csv".into(), "--output".into(), "out".into()];
+        let _: kpi::Args = parse_args(args());
+        let _: acc_receivable::Args = parse_args(args());
+    }
+}
@@ -0,0 +1,256 @@
+use std::{collections::BTreeMap, io::Write};
+
+use chrono::Utc;
+
+use crate::{
+    apis::{
+        google_sheets::{
+            self,
+            spreadsheet::{
+                mk_row, ExtendedValue, GridData, Sheet, SheetProperties, Spreadsheet,
+                SpreadsheetProperties,
+            },
+        },
+        job_nimbus,
+    },
+    jobs::{Job, TimeDelta},
+    subcommands::output::{OutputArgs, OutputFormat},
+    utils::{self, into_days, percent_or_na},
+};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The JobNimbus API key. This key will be cached.
+    #[arg(long, default_value = None, global = true, env)]
+    jn_api_key: Option<String>,
+
+    /// The filter to use when query JobNimbus for jobs, using ElasticSearch
+    /// syntax.
+    #[arg(short, long = "filter", default_value = None)]
+    filter_filename: Option<String>,
+
+    /// The format in which to print the output.
+    #[arg(long, value_enum, default_value = "google-sheets")]
+    format: OutputFormat,
+
+    #[command(flatten)]
+    output_args: OutputArgs,
+}
+
+/// Statistics about the rescheduled appointments of a single sales rep.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RescheduleStats {
+    /// The number of jobs that had an appointment.
+    pub num_appointments: usize,
+    /// The number of jobs whose appointment was rescheduled at least once.
+    pub num_rescheduled: usize,
+    /// The total number of times that appointments were rescheduled.
+    pub num_reschedules: usize,
+    /// The number of rescheduled jobs whose delay is known, i.e. the number of
+    /// jobs that `cum_delay` is calculated over.
+    pub num_delayed: usize,
+    /// The cumulative delay caused by rescheduling.
+    pub cum_delay: TimeDelta,
+}
+
+impl RescheduleStats {
+    /// The fraction of appointments that were rescheduled. `None` if there
+    /// were no appointments.
+    pub fn reschedule_rate(&self) -> Option<f64> {
+        if self.num_appointments == 0 {
+            None
+        } else {
+            Some(self.num_rescheduled as f64 / self.num_appointments as f64)
+        }
+    }
+
+    /// The average delay caused by rescheduling, over all rescheduled jobs.
+    /// Zero if no appointments were rescheduled.
+    pub fn average_delay(&self) -> TimeDelta {
+        if self.num_delayed == 0 {
+            TimeDelta::zero()
+        } else {
+            self.cum_delay / self.num_delayed.try_into().unwrap()
+        }
+    }
+}
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let Args { jn_api_key, filter_filename, format, output_args } = args;
+
+    let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+    output_args.validate(format == OutputFormat::GoogleSheets);
+
+    let jobs = job_nimbus::get_all_jobs_with_filter_file(&jn_api_key, filter_filename.as_deref())?;
+
+    let results = calculate_reschedule_stats(&jobs);
+
+    let output_writer = output_args.writer()?;
+
+    match format {
+        OutputFormat::Human => print_human(&results, output_writer)?,
+        OutputFormat::Csv => print_csv(&results, output_writer)?,
+        OutputFormat::GoogleSheets => {
+            let spreadsheet = Spreadsheet {
+                properties: SpreadsheetProperties {
+                    title: Some(format!("Reschedule Report ({})", Utc::now())),
+                },
+                sheets: Some(vec![reschedule_stats_sheet(&results)]),
+                ..Default::default()
+            };
+            let url = google_sheets::upload_spreadsheet_blocking(
+                google_sheets::SheetNickname::Reschedules,
+                spreadsheet,
+                !output_args.new,
+            )?;
+            utils::open_url(url.as_str());
+        }
+    }
+
+    Ok(())
+}
+
+/// Groups the jobs that had an appointment by sales rep and calculates
+/// reschedule statistics for each rep. Jobs without a sales rep are grouped
+/// under `None`.
+fn calculate_reschedule_stats<'a>(
+    jobs: impl IntoIterator<Item = &'a Job>,
+) -> BTreeMap<Option<String>, RescheduleStats> {
+    let mut results: BTreeMap<Option<String>, RescheduleStats> = BTreeMap::new();
+    for job in jobs {
+        if job.milestone_dates.appointment_date.is_none()
+            && job.rescheduled_appointment_dates.is_empty()
+        {
+            continue;
+        }
+
+        let stats = results.entry(job.sales_rep.clone()).or_default();
+        stats.num_appointments += 1;
+        if !job.rescheduled_appointment_dates.is_empty() {
+            stats.num_rescheduled += 1;
+            stats.num_reschedules += job.rescheduled_appointment_dates.len();
+        }
+        if let Some(delay) = job.reschedule_delay() {
+            stats.num_delayed += 1;
+            stats.cum_delay += delay;
+        }
+    }
+    results
+}
+
+fn rep_name(rep: &Option<String>) -> &str {
+    rep.as_deref().unwrap_or("Unknown Sales Rep")
+}
+
+fn print_human(
+    results: &BTreeMap<Option<String>, RescheduleStats>,
+    mut writer: impl Write,
+) -> std::io::Result<()> {
+    for (rep, stats) in results {
+        writeln!(
+            writer,
+            "{}: Appointments {} | Rescheduled {} ({}) | Reschedules {} | Avg Delay {:.2} days",
+            rep_name(rep),
+            stats.num_appointments,
+            stats.num_rescheduled,
+            percent_or_na(stats.reschedule_rate()),
+            stats.num_reschedules,
+            into_days(stats.average_delay()),
+        )?;
+    }
+    Ok(())
+}
+
+fn print_csv(
+    results: &BTreeMap<Option<String>, RescheduleStats>,
+    writer: impl Write,
+) -> std::io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record([
+        "Sales Rep",
+        "Appointments",
+        "Rescheduled",
+        "Reschedule Rate",
+        "Reschedules",
+        "Avg Days Delay",
+    ])?;
+    for (rep, stats) in results {
+        writer.write_record([
+            rep_name(rep),
+            &stats.num_appointments.to_string(),
+            &stats.num_rescheduled.to_string(),
+            &percent_or_na(stats.reschedule_rate()),
+            &stats.num_reschedules.to_string(),
+            &into_days(stats.average_delay()).to_string(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn reschedule_stats_sheet(results: &BTreeMap<Option<String>, RescheduleStats>) -> Sheet {
+    let mut rows = Vec::new();
+    rows.push(mk_row([
+        ExtendedValue::StringValue("Sales Rep".to_string()),
+        ExtendedValue::StringValue("Appointments".to_string()),
+        ExtendedValue::StringValue("Rescheduled".to_string()),
+        ExtendedValue::StringValue("Reschedule Rate".to_string()),
+        ExtendedValue::StringValue("Reschedules".to_string()),
+        ExtendedValue::StringValue("Avg Days Delay".to_string()),
+    ]));
+    for (rep, stats) in results {
+        rows.push(mk_row([
+            ExtendedValue::StringValue(rep_name(rep).to_owned()),
+            ExtendedValue::NumberValue(stats.num_appointments as f64),
+            ExtendedValue::NumberValue(stats.num_rescheduled as f64),
+            ExtendedValue::StringValue(percent_or_na(stats.reschedule_rate())),
+            ExtendedValue::NumberValue(stats.num_reschedules as f64),
+            ExtendedValue::NumberValue(into_days(stats.average_delay())),
+        ]));
+    }
+
+    Sheet {
+        properties: SheetProperties {
+            title: Some("Reschedules".to_string()),
+            ..Default::default()
+        },
+        data: Some(GridData { start_row: 0, start_column: 0, row_data: rows }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jobs::{test::make_job, Timestamp};
+
+    #[test]
+    fn reschedule_stats() {
+        fn day(days: i64) -> Timestamp {
+            Timestamp::UNIX_EPOCH + TimeDelta::days(days)
+        }
+        fn job(rescheduled: &[i64]) -> Job {
+            let mut job = make_job(false, Some(day(0)), None, None, None, None);
+            job.sales_rep = Some("Jo".to_owned());
+            job.rescheduled_appointment_dates = rescheduled.iter().copied().map(day).collect();
+            job
+        }
+
+        let jobs =
+            [job(&[]), job(&[3]), job(&[2, 9]), make_job(false, None, None, None, None, None)];
+        let results = calculate_reschedule_stats(&jobs);
+
+        assert_eq!(results.len(), 1);
+        let stats = &results[&Some("Jo".to_owned())];
+        assert_eq!(
+            *stats,
+            RescheduleStats {
+                num_appointments: 3,
+                num_rescheduled: 2,
+                num_reschedules: 3,
+                num_delayed: 2,
+                cum_delay: TimeDelta::days(12),
+            }
+        );
+        assert_eq!(stats.average_delay(), TimeDelta::days(6));
+    }
+}
@@ -0,0 +1,235 @@
+use std::io::Write;
+
+use chrono::{TimeDelta, Utc};
+use tracing::warn;
+
+use crate::{
+    apis::job_nimbus,
+    geocode_cache::GeocodeCache,
+    jobs::{Geo, Job, Timestamp},
+    subcommands::{geo, output},
+};
+
+/// The most stops that a Google Maps directions URL supports, including the
+/// origin and destination.
+const MAX_URL_STOPS: usize = 10;
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The JobNimbus API key. This key will be cached.
+    #[arg(long, default_value = None, global = true, env)]
+    jn_api_key: Option<String>,
+
+    /// The name of the sales rep whose appointments to plan a route for
+    /// (ignoring case).
+    #[arg(long)]
+    rep: String,
+
+    /// Include the appointments in this many days, starting from now.
+    #[arg(long, default_value = "1")]
+    days: i64,
+
+    /// The order in which to visit the appointments.
+    #[arg(long, value_enum, default_value = "time")]
+    order: RouteOrder,
+
+    /// The filter to use when query JobNimbus for jobs, using ElasticSearch
+    /// syntax.
+    #[arg(short, long = "filter", default_value = None)]
+    filter_filename: Option<String>,
+
+    /// The format in which to print the output.
+    #[arg(long, value_enum, default_value = "csv")]
+    format: OutputFormat,
+
+    /// The file to write the output to. "-" or unspecified will write to
+    /// stdout.
+    #[arg(short, long, default_value = None)]
+    output: Option<String>,
+}
+
+#[derive(Debug, clap::ValueEnum, Clone, Copy, Eq, PartialEq)]
+enum RouteOrder {
+    /// Visits the appointments in the order that they are scheduled.
+    Time,
+    /// Visits the appointments of each day starting with the earliest one, and
+    /// then going to the nearest unvisited appointment each time.
+    Nearest,
+}
+
+#[derive(Debug, clap::ValueEnum, Clone, Copy, Eq, PartialEq)]
+enum OutputFormat {
+    /// Prints a CSV file of the stops into the output file.
+    Csv,
+    /// Prints a Google Maps URL with directions through the stops of each day
+    /// into the output file.
+    Url,
+}
+
+/// An appointment on the route.
+#[derive(Debug, Clone, Copy)]
+struct Stop<'a> {
+    job: &'a Job,
+    date: Timestamp,
+    geo: Option<Geo>,
+}
+
+impl Stop<'_> {
+    /// How to refer to this stop in a Google Maps URL. Coordinates are
+    /// preferred over the address because they are unambiguous.
+    fn waypoint(&self) -> String {
+        match self.geo {
+            Some(Geo { lat, lon }) => format!("{},{}", lat, lon),
+            None => self.job.full_address().unwrap_or_default(),
+        }
+    }
+}
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let Args { jn_api_key, rep, days, order, filter_filename, format, output } = args;
+
+    let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+
+    let jobs = job_nimbus::get_all_jobs_with_filter_file(&jn_api_key, filter_filename.as_deref())?;
+
+    let now = Utc::now();
+    let rep = rep.trim().to_lowercase();
+    let locations = geo::locate_jobs(&jobs, &GeocodeCache::load());
+    let mut stops: Vec<Stop> = locations
+        .into_iter()
+        .filter(|(job, _)| {
+            job.sales_rep.as_deref().is_some_and(|name| name.trim().to_lowercase() == rep)
+        })
+        .filter_map(|(job, geo)| Some(Stop { job, date: job.current_appointment_date()?, geo }))
+        .filter(|stop| stop.date >= now && stop.date < now + TimeDelta::days(days))
+        .collect();
+    stops.sort_by_key(|stop| stop.date);
+    if order == RouteOrder::Nearest {
+        stops = order_by_nearest(stops);
+    }
+
+    let mut output_writer = output::writer(output.as_deref())?;
+
+    match format {
+        OutputFormat::Csv => print_csv(&stops, output_writer)?,
+        OutputFormat::Url => {
+            for day in stops.chunk_by(|a, b| a.date.date_naive() == b.date.date_naive()) {
+                writeln!(output_writer, "{}: {}", day[0].date.date_naive(), directions_url(day))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reorders the stops of each day so that, starting with the earliest stop,
+/// each stop is followed by the nearest unvisited stop. Stops without
+/// coordinates are visited last, in their original order. `stops` must be
+/// sorted by date.
+fn order_by_nearest(stops: Vec<Stop>) -> Vec<Stop> {
+    let mut ordered = Vec::with_capacity(stops.len());
+    for day in stops.chunk_by(|a, b| a.date.date_naive() == b.date.date_naive()) {
+        let (mut unvisited, unlocated): (Vec<Stop>, Vec<Stop>) =
+            day.iter().partition(|stop| stop.geo.is_some());
+        if !unvisited.is_empty() {
+            let mut current = unvisited.remove(0);
+            ordered.push(current);
+            while !unvisited.is_empty() {
+                let here = current.geo.expect("located stops have coordinates");
+                let (nearest, _) = unvisited
+                    .iter()
+                    .enumerate()
+                    .map(|(i, stop)| (i, here.distance_miles(&stop.geo.unwrap())))
+                    .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                    .expect("unvisited is not empty");
+                current = unvisited.remove(nearest);
+                ordered.push(current);
+            }
+        }
+        ordered.extend(unlocated);
+    }
+    ordered
+}
+
+/// A Google Maps URL with directions through the specified stops, in order.
+fn directions_url(stops: &[Stop]) -> String {
+    if stops.len() > MAX_URL_STOPS {
+        warn!(
+            "Google Maps only supports {} stops per route; leaving out the last {}",
+            MAX_URL_STOPS,
+            stops.len() - MAX_URL_STOPS
+        );
+    }
+    let waypoints: Vec<String> = stops.iter().take(MAX_URL_STOPS).map(Stop::waypoint).collect();
+    let mut params = vec![("api", "1".to_owned()), ("travelmode", "driving".to_owned())];
+    if let Some((destination, rest)) = waypoints.split_last() {
+        params.push(("destination", destination.clone()));
+        if !rest.is_empty() {
+            params.push(("waypoints", rest.join("|")));
+        }
+    }
+    url::Url::parse_with_params("https://www.google.com/maps/dir/", &params)
+        .expect("hardcoded URL should be valid")
+        .to_string()
+}
+
+fn print_csv(stops: &[Stop], writer: impl Write) -> std::io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record([
+        "Stop",
+        "Appointment Date",
+        "Job Number",
+        "Job Name",
+        "Address",
+        "Latitude",
+        "Longitude",
+    ])?;
+    for (i, stop) in stops.iter().enumerate() {
+        let (lat, lon) = match stop.geo {
+            Some(Geo { lat, lon }) => (lat.to_string(), lon.to_string()),
+            None => (String::new(), String::new()),
+        };
+        writer.write_record([
+            &(i + 1).to_string(),
+            &stop.date.to_rfc3339(),
+            stop.job.job_number.as_deref().unwrap_or_default(),
+            stop.job.job_name.as_deref().unwrap_or_default(),
+            &stop.job.full_address().unwrap_or_default(),
+            &lat,
+            &lon,
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jobs::test::make_job;
+
+    #[test]
+    fn nearest_order_and_url() {
+        let hour = |hours| Timestamp::UNIX_EPOCH + TimeDelta::hours(hours);
+        let job = make_job(false, None, None, None, None, None);
+        let stop = |hours, geo| Stop { job: &job, date: hour(hours), geo };
+        let stops = vec![
+            stop(1, Some(Geo { lat: 40.0, lon: -89.0 })),
+            stop(2, None),
+            stop(3, Some(Geo { lat: 42.0, lon: -89.0 })),
+            stop(4, Some(Geo { lat: 40.1, lon: -89.0 })),
+            // the next day
+            stop(30, Some(Geo { lat: 41.0, lon: -89.0 })),
+        ];
+
+        let ordered = order_by_nearest(stops);
+        let hours: Vec<_> = ordered.iter().map(|stop| stop.date.timestamp() / 3600).collect();
+        assert_eq!(hours, [1, 4, 3, 2, 30]);
+
+        assert_eq!(
+            directions_url(&ordered[..3]),
+            "https://www.google.com/maps/dir/?api=1&travelmode=driving&destination=42%2C-89\
+             &waypoints=40%2C-89%7C40.1%2C-89"
+        );
+    }
+}
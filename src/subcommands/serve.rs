@@ -0,0 +1,655 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    convert::Infallible,
+    fmt::Write as _,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use chrono::{Local, Utc};
+use http_body_util::{BodyExt as _, Full, Limited};
+use hyper::{
+    body::{Bytes, Incoming as IncomingBody},
+    header::CONTENT_TYPE,
+    server::conn::http1,
+    service::service_fn,
+    Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use serde_json::json;
+use tokio::{net::TcpListener, sync::Semaphore};
+use tracing::{info, warn};
+
+use crate::{
+    apis::job_nimbus,
+    date_filter::DateFilter,
+    jobs::{self, AnalyzedJob, Job, Milestone, Status, Timestamp},
+    run_summary,
+    schedule::ScheduledExport,
+    subcommands::acc_receivable,
+};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The JobNimbus API key. This key will be cached.
+    #[arg(long, default_value = None, global = true, env)]
+    jn_api_key: Option<String>,
+
+    /// The address to listen on. The server has no authentication, so it only
+    /// accepts connections from this computer by default; use e.g. `0.0.0.0`
+    /// to accept connections from the whole network.
+    #[arg(long, value_name = "ADDRESS", default_value = "127.0.0.1")]
+    bind: IpAddr,
+
+    /// The port to listen on.
+    #[arg(long, default_value = "9898")]
+    port: u16,
+
+    /// How often to fetch the jobs from JobNimbus and recalculate the metrics,
+    /// in minutes.
+    #[arg(long, default_value = "15")]
+    refresh_minutes: u64,
+
+    /// The maximum number of scheduled exports to run at the same time.
+    /// Exports that are due while this many are running wait for one to
+    /// finish.
+    #[arg(long, value_name = "N", default_value = "2")]
+    max_concurrent_exports: usize,
+
+    /// The filter to use when query JobNimbus for jobs, using ElasticSearch
+    /// syntax.
+    #[arg(short, long = "filter", default_value = None)]
+    filter_filename: Option<String>,
+}
+
+/// The KPI metrics exposed to Prometheus, as of the last refresh.
+#[derive(Debug, PartialEq, Eq)]
+struct Metrics {
+    /// The total amount receivable over all AR categories, in cents.
+    total_receivable: i32,
+    /// The amount receivable in each AR category, in cents.
+    receivable_by_category: Vec<(Status, i32)>,
+    /// The number of red flags of each kind.
+    red_flags: BTreeMap<&'static str, usize>,
+    /// The number of installed jobs of each sales rep.
+    installs_by_rep: BTreeMap<Option<String>, usize>,
+    /// When the jobs were fetched.
+    refreshed: Timestamp,
+    /// Whether a later refresh failed, so that the metrics are still
+    /// calculated from the jobs fetched at `refreshed`.
+    stale: bool,
+    /// How long each phase of the last refresh took, e.g. "fetch".
+    phase_durations: BTreeMap<&'static str, Duration>,
+}
+
+/// What a scheduled export is doing right now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExportActivity {
+    /// Waiting until its next run.
+    Idle,
+    /// Due, but waiting for another export to finish.
+    Queued,
+    Running,
+}
+
+impl ExportActivity {
+    fn as_str(self) -> &'static str {
+        match self {
+            ExportActivity::Idle => "idle",
+            ExportActivity::Queued => "queued",
+            ExportActivity::Running => "running",
+        }
+    }
+}
+
+/// The status of a scheduled export.
+#[derive(Debug)]
+struct ExportStatus {
+    export: ScheduledExport,
+    activity: ExportActivity,
+    last_run: Option<Timestamp>,
+    /// `Ok` if the last run succeeded, or an error message if it failed.
+    last_result: Option<Result<(), String>>,
+    next_run: Timestamp,
+}
+
+/// Where the background calculation of the metrics is at.
+#[derive(Debug, Default)]
+enum MetricsState {
+    /// The first refresh hasn't finished yet.
+    #[default]
+    Loading,
+    /// Every refresh so far has failed, the last one with this error.
+    Failed(String),
+    /// The metrics of the last successful refresh, which are marked stale if a
+    /// later refresh failed.
+    Ready(Metrics),
+}
+
+impl MetricsState {
+    /// Records a failed refresh, keeping the metrics of an earlier refresh (if
+    /// any) but marking them as stale.
+    fn refresh_failed(&mut self, error: String) {
+        match self {
+            MetricsState::Ready(metrics) => metrics.stale = true,
+            _ => *self = MetricsState::Failed(error),
+        }
+    }
+}
+
+/// The state shared between the background tasks and the request handlers.
+#[derive(Debug, Default)]
+struct ServerState {
+    metrics: RwLock<MetricsState>,
+    schedule: RwLock<Vec<ExportStatus>>,
+    /// The arguments of the exports that are queued or running, so that an
+    /// export isn't run again while an identical one is still in progress.
+    exports_in_flight: Mutex<HashSet<Vec<String>>>,
+    /// The filter for the dates of the jobs posted to be analyzed.
+    date_filter: DateFilter,
+}
+
+/// An export's claim on its arguments while it is queued or running, which is
+/// released when dropped.
+struct InFlight<'a> {
+    in_flight: &'a Mutex<HashSet<Vec<String>>>,
+    args: Vec<String>,
+}
+
+impl<'a> InFlight<'a> {
+    /// Claims the arguments, or returns `None` if an export with the same
+    /// arguments is already queued or running.
+    fn claim(in_flight: &'a Mutex<HashSet<Vec<String>>>, args: &[String]) -> Option<Self> {
+        if !in_flight.lock().unwrap().insert(args.to_vec()) {
+            return None;
+        }
+        Some(InFlight { in_flight, args: args.to_vec() })
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.in_flight.lock().unwrap().remove(&self.args);
+    }
+}
+
+/// The largest request body accepted, in bytes.
+const MAX_BODY_SIZE: usize = 1 << 20;
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let Args { jn_api_key, bind, port, refresh_minutes, max_concurrent_exports, filter_filename } =
+        args;
+
+    let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+    let filter = job_nimbus::read_filter_file(filter_filename.as_deref())?;
+    let refresh_interval = Duration::from_secs(refresh_minutes * 60);
+
+    tokio::runtime::Builder::new_multi_thread().enable_all().build()?.block_on(async move {
+        let state = Arc::new(ServerState { date_filter: DateFilter::load(), ..Default::default() });
+        tokio::spawn(refresh_metrics(jn_api_key, filter, refresh_interval, state.clone()));
+        let exports = ScheduledExport::load_all();
+        info!("Loaded {} scheduled exports", exports.len());
+        let export_slots = Arc::new(Semaphore::new(max_concurrent_exports.max(1)));
+        for export in exports {
+            let index = {
+                let mut schedule = state.schedule.write().unwrap();
+                let next_run = export.next_run(Local::now()).to_utc();
+                schedule.push(ExportStatus {
+                    export,
+                    activity: ExportActivity::Idle,
+                    last_run: None,
+                    last_result: None,
+                    next_run,
+                });
+                schedule.len() - 1
+            };
+            tokio::spawn(run_scheduled_export(index, state.clone(), export_slots.clone()));
+        }
+
+        let addr = SocketAddr::new(bind, port);
+        let tcp_listener = TcpListener::bind(addr).await?;
+        info!("Serving metrics at http://{}/metrics", addr);
+        info!("Analyzing jobs at http://{}/analyze-job", addr);
+        info!("Serving the schedule status at http://{}/schedule", addr);
+        loop {
+            let (tcp_stream, _) = tcp_listener.accept().await?;
+            let tcp_stream = TokioIo::new(tcp_stream);
+            let state = state.clone();
+            tokio::spawn(async move {
+                let handle_request = |req: Request<IncomingBody>| {
+                    let state = state.clone();
+                    async move { Ok::<_, Infallible>(handle_request(req, &state).await) }
+                };
+                if let Err(e) = http1::Builder::new()
+                    .serve_connection(tcp_stream, service_fn(handle_request))
+                    .await
+                {
+                    warn!("Failed to serve connection: {}", e);
+                }
+            });
+        }
+    })
+}
+
+/// Periodically fetches the jobs from JobNimbus and recalculates the metrics.
+/// Failed refreshes are logged and leave the previous metrics in place, marked
+/// as stale until the next successful refresh.
+async fn refresh_metrics(
+    jn_api_key: String,
+    filter: Option<String>,
+    refresh_interval: Duration,
+    state: Arc<ServerState>,
+) {
+    let categories = acc_receivable::load_categories();
+    loop {
+        let jn_api_key = jn_api_key.clone();
+        let filter = filter.clone();
+        let fetch_start = Instant::now();
+        let jobs = tokio::task::spawn_blocking(move || {
+            job_nimbus::get_all_jobs_from_job_nimbus(&jn_api_key, filter.as_deref())
+        })
+        .await;
+        let fetch_duration = fetch_start.elapsed();
+        let jobs = match jobs {
+            Ok(Ok(jobs)) => Ok(jobs),
+            Ok(Err(e)) => Err(format!("{:#}", e)),
+            Err(e) => Err(e.to_string()),
+        };
+        match jobs {
+            Ok(jobs) => {
+                let analyze_start = Instant::now();
+                let mut new_metrics = run_summary::time_phase("analyze", || {
+                    Metrics::calculate(jobs, &categories, Utc::now())
+                });
+                new_metrics.phase_durations = BTreeMap::from([
+                    ("fetch", fetch_duration),
+                    ("analyze", analyze_start.elapsed()),
+                ]);
+                *state.metrics.write().unwrap() = MetricsState::Ready(new_metrics);
+                info!("Refreshed metrics");
+            }
+            Err(e) => {
+                warn!("Failed to refresh metrics: {}", e);
+                state.metrics.write().unwrap().refresh_failed(e);
+            }
+        }
+        tokio::time::sleep(refresh_interval).await;
+    }
+}
+
+/// Repeatedly waits until the next run of the scheduled export at `index` and
+/// runs it once one of the `export_slots` is free. Each run is a separate
+/// `ahitool` process with the export's arguments, so that a failed run doesn't
+/// affect the server. A run is skipped if an export with the same arguments is
+/// still queued or running, e.g. because it took longer than expected.
+async fn run_scheduled_export(index: usize, state: Arc<ServerState>, export_slots: Arc<Semaphore>) {
+    loop {
+        let (export, next_run) = {
+            let schedule = state.schedule.read().unwrap();
+            (schedule[index].export.clone(), schedule[index].next_run)
+        };
+        let wait = (next_run - Utc::now()).to_std().unwrap_or_default();
+        info!("Next run of \"{}\" is at {}", export.name, next_run.with_timezone(&Local));
+        tokio::time::sleep(wait).await;
+
+        let Some(in_flight) = InFlight::claim(&state.exports_in_flight, &export.args) else {
+            warn!("Skipping \"{}\" because an identical export is still in progress", export.name);
+            let mut schedule = state.schedule.write().unwrap();
+            let status = &mut schedule[index];
+            status.last_result =
+                Some(Err("skipped because an identical export was still in progress".to_owned()));
+            status.next_run = export.next_run(Local::now()).to_utc();
+            continue;
+        };
+        state.schedule.write().unwrap()[index].activity = ExportActivity::Queued;
+        let _slot = export_slots.acquire().await.expect("the semaphore is never closed");
+        state.schedule.write().unwrap()[index].activity = ExportActivity::Running;
+
+        info!("Running \"{}\"", export.name);
+        let args = export.args.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let status =
+                std::process::Command::new(std::env::current_exe()?).args(args).status()?;
+            if status.success() {
+                Ok(())
+            } else {
+                anyhow::bail!("The command exited with {}", status)
+            }
+        })
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|result| result)
+        .map_err(|e| e.to_string());
+        match &result {
+            Ok(()) => info!("\"{}\" succeeded", export.name),
+            Err(e) => warn!("\"{}\" failed: {}", export.name, e),
+        }
+        drop(in_flight);
+
+        let mut schedule = state.schedule.write().unwrap();
+        let status = &mut schedule[index];
+        status.activity = ExportActivity::Idle;
+        status.last_run = Some(Utc::now());
+        status.last_result = Some(result);
+        status.next_run = export.next_run(Local::now()).to_utc();
+    }
+}
+
+async fn handle_request(req: Request<IncomingBody>, state: &ServerState) -> Response<Full<Bytes>> {
+    fn mk_response(status: StatusCode, body: String) -> Response<Full<Bytes>> {
+        Response::builder()
+            .status(status)
+            .header(CONTENT_TYPE, "text/plain; version=0.0.4")
+            .body(Full::new(Bytes::from(body)))
+            .expect("This should be a valid response")
+    }
+    fn mk_json_response(status: StatusCode, body: serde_json::Value) -> Response<Full<Bytes>> {
+        Response::builder()
+            .status(status)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Full::new(Bytes::from(body.to_string())))
+            .expect("This should be a valid response")
+    }
+
+    match (req.method(), req.uri().path()) {
+        (&Method::POST, "/analyze-job") => {
+            let body = match Limited::new(req.into_body(), MAX_BODY_SIZE).collect().await {
+                Ok(body) => body.to_bytes(),
+                Err(e) => {
+                    let error = format!("Failed to read request body: {}", e);
+                    return mk_json_response(StatusCode::BAD_REQUEST, json!({ "error": error }));
+                }
+            };
+            match analyze_job_json(&body, &state.date_filter) {
+                Ok(analysis) => mk_json_response(StatusCode::OK, analysis),
+                Err(error) => mk_json_response(StatusCode::BAD_REQUEST, json!({ "error": error })),
+            }
+        }
+        (&Method::GET, "/schedule") => {
+            let schedule = state.schedule.read().unwrap();
+            mk_json_response(StatusCode::OK, schedule_json(&schedule))
+        }
+        (&Method::GET, "/metrics") => match &*state.metrics.read().unwrap() {
+            MetricsState::Ready(metrics) => mk_response(StatusCode::OK, metrics.render()),
+            MetricsState::Loading => mk_response(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "The metrics have not been calculated yet.\n".to_owned(),
+            ),
+            MetricsState::Failed(error) => mk_response(
+                StatusCode::SERVICE_UNAVAILABLE,
+                format!("Failed to calculate the metrics: {}\n", error),
+            ),
+        },
+        _ => mk_response(StatusCode::NOT_FOUND, "Not found.\n".to_owned()),
+    }
+}
+
+/// Parses a JobNimbus job from the JSON request body and analyzes it. Returns
+/// the analysis and red flags as JSON, or an error message if the job couldn't
+/// be parsed.
+fn analyze_job_json(body: &[u8], date_filter: &DateFilter) -> Result<serde_json::Value, String> {
+    let value: serde_json::Value =
+        serde_json::from_slice(body).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let job = Job::from_json(value, date_filter).map_err(|e| e.to_string())?;
+    let (AnalyzedJob { job, analysis }, errors) = jobs::analyze_job(job);
+
+    let analysis = analysis.map(|analysis| {
+        let milestones: serde_json::Map<_, _> = Milestone::ordered_iter()
+            .map(|milestone| {
+                let date = analysis.timestamps.get(milestone.into_int()).copied().flatten();
+                (milestone.to_string(), json!(date.map(|date| date.to_rfc3339())))
+            })
+            .collect();
+        json!({
+            "kind": analysis.kind.to_string(),
+            "milestones": milestones,
+            "loss_date": analysis.loss_timestamp.map(|date| date.to_rfc3339()),
+            "settled_date": analysis.date_settled().map(|date| date.to_rfc3339()),
+        })
+    });
+    let errors: Vec<_> = errors
+        .iter()
+        .map(|error| json!({ "kind": error.kind(), "message": error.to_string() }))
+        .collect();
+    Ok(json!({
+        "jnid": job.jnid,
+        "job_number": job.job_number,
+        "analysis": analysis,
+        "errors": errors,
+    }))
+}
+
+/// The status of each scheduled export as JSON.
+fn schedule_json(schedule: &[ExportStatus]) -> serde_json::Value {
+    schedule
+        .iter()
+        .map(|status| {
+            let last_result = match &status.last_result {
+                None => json!(null),
+                Some(Ok(())) => json!("succeeded"),
+                Some(Err(e)) => json!(format!("failed: {}", e)),
+            };
+            json!({
+                "name": status.export.name,
+                "args": status.export.args,
+                "activity": status.activity.as_str(),
+                "last_run": status.last_run.map(|date| date.to_rfc3339()),
+                "last_result": last_result,
+                "next_run": status.next_run.to_rfc3339(),
+            })
+        })
+        .collect()
+}
+
+impl Metrics {
+    fn calculate(jobs: Vec<Job>, categories: &[Status], now: Timestamp) -> Self {
+        let mut receivable_by_category: Vec<_> =
+            categories.iter().map(|category| (category.clone(), 0)).collect();
+        let mut red_flags = BTreeMap::new();
+        let mut installs_by_rep = BTreeMap::new();
+        for job in jobs {
+            if let Some((_, total)) =
+                receivable_by_category.iter_mut().find(|(status, _)| *status == job.status)
+            {
+                *total += job.amt_receivable;
+            }
+            if job.milestone_dates.install_date.is_some() {
+                *installs_by_rep.entry(job.sales_rep.clone()).or_default() += 1;
+            }
+            for error in jobs::analyze_job(job).1 {
+                *red_flags.entry(error.kind()).or_default() += 1;
+            }
+        }
+        Self {
+            total_receivable: receivable_by_category.iter().map(|(_, total)| total).sum(),
+            receivable_by_category,
+            red_flags,
+            installs_by_rep,
+            refreshed: now,
+            stale: false,
+            phase_durations: BTreeMap::new(),
+        }
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format.
+    fn render(&self) -> String {
+        fn dollars(cents: i32) -> f64 {
+            cents as f64 / 100.0
+        }
+
+        let mut out = String::new();
+        let mut metric = |name: &str, help: &str, samples: Vec<(Option<(&str, &str)>, f64)>| {
+            writeln!(out, "# HELP {} {}", name, help).unwrap();
+            writeln!(out, "# TYPE {} gauge", name).unwrap();
+            for (label, value) in samples {
+                match label {
+                    Some((key, label)) => {
+                        writeln!(out, "{}{{{}=\"{}\"}} {}", name, key, escape_label(label), value)
+                    }
+                    None => writeln!(out, "{} {}", name, value),
+                }
+                .unwrap();
+            }
+        };
+
+        metric(
+            "ahitool_receivable_dollars",
+            "Total amount receivable over all AR categories.",
+            vec![(None, dollars(self.total_receivable))],
+        );
+        let categories: Vec<_> = self
+            .receivable_by_category
+            .iter()
+            .map(|(status, total)| (status.to_string(), *total))
+            .collect();
+        metric(
+            "ahitool_receivable_category_dollars",
+            "Amount receivable in each AR category.",
+            categories
+                .iter()
+                .map(|(status, total)| (Some(("category", status.as_str())), dollars(*total)))
+                .collect(),
+        );
+        metric(
+            "ahitool_red_flags",
+            "Number of red flags of each kind.",
+            self.red_flags
+                .iter()
+                .map(|(kind, count)| (Some(("kind", *kind)), *count as f64))
+                .collect(),
+        );
+        metric(
+            "ahitool_installs",
+            "Number of installed jobs of each sales rep.",
+            self.installs_by_rep
+                .iter()
+                .map(|(rep, count)| {
+                    (
+                        Some(("sales_rep", rep.as_deref().unwrap_or("Unknown Sales Rep"))),
+                        *count as f64,
+                    )
+                })
+                .collect(),
+        );
+        metric(
+            "ahitool_last_refresh_timestamp_seconds",
+            "When the jobs were last fetched from JobNimbus, as a Unix timestamp.",
+            vec![(None, self.refreshed.timestamp() as f64)],
+        );
+        metric(
+            "ahitool_metrics_stale",
+            "1 if the last refresh failed, so the metrics are from an earlier fetch.",
+            vec![(None, if self.stale { 1.0 } else { 0.0 })],
+        );
+        if !self.phase_durations.is_empty() {
+            metric(
+                "ahitool_refresh_phase_seconds",
+                "How long each phase of the last refresh took.",
+                self.phase_durations
+                    .iter()
+                    .map(|(phase, duration)| (Some(("phase", *phase)), duration.as_secs_f64()))
+                    .collect(),
+            );
+        }
+        out
+    }
+}
+
+/// Escapes a label value as required by the Prometheus text format.
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jobs::{test::make_job, JobAnalysisError, TimeDelta};
+
+    #[test]
+    fn render_metrics() {
+        let day = |days| Some(Timestamp::UNIX_EPOCH + TimeDelta::days(days));
+        let mut installed = make_job(false, day(0), None, day(1), day(2), None);
+        installed.sales_rep = Some("Jo \"JJ\" Smith".to_owned());
+        installed.status = Status::Collections;
+        installed.amt_receivable = 12_345;
+        // has a contingency date but isn't an insurance job
+        let flagged = make_job(false, day(0), day(1), None, None, None);
+
+        let metrics = Metrics::calculate(
+            vec![installed, flagged],
+            &[Status::Collections, Status::PendingPayments],
+            Timestamp::UNIX_EPOCH,
+        );
+        assert_eq!(metrics.total_receivable, 12_345);
+        assert_eq!(metrics.red_flags, BTreeMap::from([("contingency_without_insurance", 1)]));
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("\nahitool_receivable_dollars 123.45\n"));
+        assert!(rendered
+            .contains("\nahitool_receivable_category_dollars{category=\"Collections\"} 123.45\n"));
+        assert!(rendered.contains("\nahitool_installs{sales_rep=\"Jo \\\"JJ\\\" Smith\"} 1\n"));
+        assert!(rendered.contains("# TYPE ahitool_red_flags gauge\n"));
+        assert!(!rendered.contains("ahitool_refresh_phase_seconds"));
+        assert!(rendered.contains("\nahitool_metrics_stale 0\n"));
+
+        let mut state = MetricsState::Ready(metrics);
+        state.refresh_failed("timed out".to_owned());
+        let MetricsState::Ready(metrics) = state else {
+            panic!("the metrics of the earlier refresh should be kept");
+        };
+        assert!(metrics.render().contains("\nahitool_metrics_stale 1\n"));
+
+        let mut state = MetricsState::default();
+        state.refresh_failed("timed out".to_owned());
+        assert!(matches!(state, MetricsState::Failed(error) if error == "timed out"));
+
+        let mut metrics = metrics;
+        metrics.phase_durations.insert("fetch", Duration::from_millis(1500));
+        assert!(metrics
+            .render()
+            .contains("\nahitool_refresh_phase_seconds{phase=\"fetch\"} 1.5\n"));
+    }
+
+    #[test]
+    fn identical_exports_are_not_run_concurrently() {
+        let in_flight = Mutex::new(HashSet::new());
+        let kpi = vec!["kpi".to_owned(), "--from".to_owned(), "ytd".to_owned()];
+        let ar = vec!["ar".to_owned()];
+
+        let claim = InFlight::claim(&in_flight, &kpi).unwrap();
+        assert!(InFlight::claim(&in_flight, &kpi).is_none());
+        let other = InFlight::claim(&in_flight, &ar).unwrap();
+        drop(claim);
+        assert!(InFlight::claim(&in_flight, &kpi).is_some());
+        drop(other);
+        assert!(in_flight.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn analyze_job_from_json() {
+        let body = json!({
+            "jnid": "abc",
+            "number": "1001",
+            "status_name": "Jobs In Progress",
+            "date_status_change": 1,
+            "Sales Appt Date": 1_700_000_000,
+            "Signed Contingency Date": 1_700_086_400,
+        });
+        let date_filter = DateFilter::default();
+        let response = analyze_job_json(body.to_string().as_bytes(), &date_filter).unwrap();
+
+        assert_eq!(response["jnid"], "abc");
+        assert_eq!(response["job_number"], "1001");
+        assert_eq!(
+            response["errors"],
+            json!([{
+                "kind": "contingency_without_insurance",
+                "message": JobAnalysisError::ContingencyWithoutInsurance.to_string(),
+            }])
+        );
+
+        assert!(analyze_job_json(b"not json", &date_filter).is_err());
+        assert!(analyze_job_json(b"{}", &date_filter).is_err());
+    }
+}
@@ -0,0 +1,310 @@
+use std::{collections::BTreeMap, io::Write};
+
+use chrono::Utc;
+
+use crate::{
+    apis::{
+        google_sheets::{
+            self,
+            spreadsheet::{
+                mk_row, ExtendedValue, GridData, RowData, Sheet, SheetProperties, Spreadsheet,
+                SpreadsheetProperties,
+            },
+        },
+        job_nimbus,
+    },
+    jobs::{Job, Timestamp},
+    subcommands::output::OutputArgs,
+    utils::{self, percent_or_na},
+};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The JobNimbus API key. This key will be cached.
+    #[arg(long, default_value = None, global = true, env)]
+    jn_api_key: Option<String>,
+
+    /// The filter to use when query JobNimbus for jobs, using ElasticSearch
+    /// syntax.
+    #[arg(short, long = "filter", default_value = None)]
+    filter_filename: Option<String>,
+
+    /// The format in which to print the output.
+    #[arg(long, value_enum, default_value = "google-sheets")]
+    format: OutputFormat,
+
+    #[command(flatten)]
+    output_args: OutputArgs,
+}
+
+#[derive(Debug, clap::ValueEnum, Clone, Copy, Eq, PartialEq)]
+enum OutputFormat {
+    /// Prints a human-readable report into the output file.
+    Human,
+    /// Prints a CSV file of all jobs with supplements into the output file.
+    Csv,
+    /// Outputs a Google Sheet on the user's Google Drive (requires OAuth
+    /// authorization).
+    GoogleSheets,
+}
+
+#[derive(Debug, Default)]
+struct SupplementStats {
+    num_submitted: usize,
+    num_approved: usize,
+    /// The total amount requested, in cents.
+    amt_requested: i64,
+    /// The total amount approved (i.e. the revenue added by supplements), in
+    /// cents.
+    amt_approved: i64,
+}
+
+impl SupplementStats {
+    fn add_job(&mut self, job: &Job) {
+        self.num_submitted += 1;
+        self.amt_requested += job.supplement.amt_requested as i64;
+        if job.supplement.approved_date.is_some() {
+            self.num_approved += 1;
+            self.amt_approved += job.supplement.amt_approved as i64;
+        }
+    }
+
+    /// The fraction of submitted supplements that were approved. `None` if no
+    /// supplements were submitted.
+    fn approval_rate(&self) -> Option<f64> {
+        if self.num_submitted == 0 {
+            None
+        } else {
+            Some(self.num_approved as f64 / self.num_submitted as f64)
+        }
+    }
+}
+
+struct SupplementData<'a> {
+    /// All jobs that have submitted a supplement, outstanding ones first, and
+    /// otherwise in order of submission.
+    jobs: Vec<&'a Job>,
+    total: SupplementStats,
+    by_carrier: BTreeMap<Option<&'a str>, SupplementStats>,
+}
+
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let Args { jn_api_key, filter_filename, format, output_args } = args;
+
+    let jn_api_key = job_nimbus::get_api_key(jn_api_key)?;
+    output_args.validate(format == OutputFormat::GoogleSheets);
+
+    let jobs = job_nimbus::get_all_jobs_with_filter_file(&jn_api_key, filter_filename.as_deref())?;
+
+    let mut results =
+        SupplementData { jobs: Vec::new(), total: Default::default(), by_carrier: BTreeMap::new() };
+    for job in &jobs {
+        if job.supplement.submitted_date.is_none() {
+            continue;
+        }
+        results.jobs.push(job);
+        results.total.add_job(job);
+        results.by_carrier.entry(job.insurance_company_name.as_deref()).or_default().add_job(job);
+    }
+    results
+        .jobs
+        .sort_by_key(|job| (!job.supplement.is_outstanding(), job.supplement.submitted_date));
+
+    let output_writer = output_args.writer()?;
+
+    let now = Utc::now();
+    match format {
+        OutputFormat::Human => print_human(&results, now, output_writer)?,
+        OutputFormat::Csv => print_csv(&results, now, output_writer)?,
+        OutputFormat::GoogleSheets => {
+            generate_report_google_sheets(&results, now, !output_args.new)?
+        }
+    }
+
+    Ok(())
+}
+
+/// The number of days that the supplement has been waiting for approval, or
+/// `None` if the supplement is not outstanding.
+fn days_outstanding(job: &Job, now: Timestamp) -> Option<i64> {
+    if job.supplement.is_outstanding() {
+        job.supplement.submitted_date.map(|date| now.signed_duration_since(date).num_days())
+    } else {
+        None
+    }
+}
+
+fn print_human(
+    results: &SupplementData,
+    now: Timestamp,
+    mut writer: impl Write,
+) -> std::io::Result<()> {
+    let write_stats = |writer: &mut dyn Write, name: &str, stats: &SupplementStats| {
+        writeln!(
+            writer,
+            "{}: {} submitted | {} approved | Rate {} | Requested ${:.2} | Added Revenue ${:.2}",
+            name,
+            stats.num_submitted,
+            stats.num_approved,
+            percent_or_na(stats.approval_rate()),
+            stats.amt_requested as f64 / 100.0,
+            stats.amt_approved as f64 / 100.0,
+        )
+    };
+
+    write_stats(&mut writer, "Total", &results.total)?;
+    writeln!(writer, "By carrier:")?;
+    for (carrier, stats) in &results.by_carrier {
+        write_stats(
+            &mut writer,
+            &format!("    - {}", carrier.unwrap_or("Unknown Carrier")),
+            stats,
+        )?;
+    }
+
+    writeln!(writer, "Outstanding supplements:")?;
+    for job in results.jobs.iter().filter(|job| job.supplement.is_outstanding()) {
+        writeln!(
+            writer,
+            "    - {} (#{}): ${:.2} requested ({} days, assigned to {})",
+            job.job_name.as_deref().unwrap_or(""),
+            job.job_number.as_deref().unwrap_or("Unknown Job Number"),
+            job.supplement.amt_requested as f64 / 100.0,
+            days_outstanding(job, now).unwrap_or_default(),
+            job.sales_rep.as_deref().unwrap_or("Unknown Sales Rep")
+        )?;
+    }
+
+    writeln!(writer, "Approved supplements:")?;
+    for job in results.jobs.iter().filter(|job| job.supplement.approved_date.is_some()) {
+        writeln!(
+            writer,
+            "    - {} (#{}): ${:.2} of ${:.2} approved (assigned to {})",
+            job.job_name.as_deref().unwrap_or(""),
+            job.job_number.as_deref().unwrap_or("Unknown Job Number"),
+            job.supplement.amt_approved as f64 / 100.0,
+            job.supplement.amt_requested as f64 / 100.0,
+            job.sales_rep.as_deref().unwrap_or("Unknown Sales Rep")
+        )?;
+    }
+
+    Ok(())
+}
+
+fn print_csv(results: &SupplementData, now: Timestamp, writer: impl Write) -> std::io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record([
+        "Job Name",
+        "Sales Rep",
+        "Job Number",
+        "Carrier",
+        "Submitted Date",
+        "Approved Date",
+        "Amount Requested",
+        "Amount Approved",
+        "Days Outstanding",
+    ])?;
+    for job in &results.jobs {
+        let supplement = &job.supplement;
+        writer.write_record([
+            job.job_name.as_deref().unwrap_or(""),
+            job.sales_rep.as_deref().unwrap_or("Unknown Salesman"),
+            job.job_number.as_deref().unwrap_or("Unknown Job Number"),
+            job.insurance_company_name.as_deref().unwrap_or("Unknown Carrier"),
+            &supplement
+                .submitted_date
+                .map(|date| date.date_naive().to_string())
+                .unwrap_or_default(),
+            &supplement.approved_date.map(|date| date.date_naive().to_string()).unwrap_or_default(),
+            &(supplement.amt_requested as f64 / 100.0).to_string(),
+            &(supplement.amt_approved as f64 / 100.0).to_string(),
+            &days_outstanding(job, now).map(|days| days.to_string()).unwrap_or_default(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn generate_report_google_sheets(
+    results: &SupplementData,
+    now: Timestamp,
+    update: bool,
+) -> anyhow::Result<()> {
+    fn mk_stats_row(name: &str, stats: &SupplementStats) -> RowData {
+        mk_row([
+            ExtendedValue::StringValue(name.to_owned()),
+            ExtendedValue::NumberValue(stats.num_submitted as f64),
+            ExtendedValue::NumberValue(stats.num_approved as f64),
+            ExtendedValue::StringValue(percent_or_na(stats.approval_rate())),
+            ExtendedValue::NumberValue(stats.amt_requested as f64 / 100.0),
+            ExtendedValue::NumberValue(stats.amt_approved as f64 / 100.0),
+        ])
+    }
+
+    let mut carrier_rows = vec![mk_row([
+        ExtendedValue::StringValue("Carrier".to_string()),
+        ExtendedValue::StringValue("Submitted".to_string()),
+        ExtendedValue::StringValue("Approved".to_string()),
+        ExtendedValue::StringValue("Approval Rate".to_string()),
+        ExtendedValue::StringValue("Amount Requested".to_string()),
+        ExtendedValue::StringValue("Added Revenue".to_string()),
+    ])];
+    for (carrier, stats) in &results.by_carrier {
+        carrier_rows.push(mk_stats_row(carrier.unwrap_or("Unknown Carrier"), stats));
+    }
+    carrier_rows.push(mk_stats_row("Total", &results.total));
+
+    let mut job_rows = vec![mk_row([
+        ExtendedValue::StringValue("Job Name".to_string()),
+        ExtendedValue::StringValue("Job Salesman".to_string()),
+        ExtendedValue::StringValue("Job Number".to_string()),
+        ExtendedValue::StringValue("Carrier".to_string()),
+        ExtendedValue::StringValue("Amount Requested".to_string()),
+        ExtendedValue::StringValue("Amount Approved".to_string()),
+        ExtendedValue::StringValue("Days Outstanding".to_string()),
+    ])];
+    for job in &results.jobs {
+        job_rows.push(mk_row([
+            ExtendedValue::StringValue(job.job_name.clone().unwrap_or_default()),
+            ExtendedValue::StringValue(
+                job.sales_rep.clone().unwrap_or_else(|| "Unknown Salesman".to_owned()),
+            ),
+            ExtendedValue::StringValue(
+                job.job_number.clone().unwrap_or_else(|| "Unknown Job Number".to_owned()),
+            ),
+            ExtendedValue::StringValue(
+                job.insurance_company_name.clone().unwrap_or_else(|| "Unknown Carrier".to_owned()),
+            ),
+            ExtendedValue::NumberValue(job.supplement.amt_requested as f64 / 100.0),
+            ExtendedValue::NumberValue(job.supplement.amt_approved as f64 / 100.0),
+            match days_outstanding(job, now) {
+                Some(days) => ExtendedValue::NumberValue(days as f64),
+                None => ExtendedValue::StringValue("".to_owned()),
+            },
+        ]));
+    }
+
+    let sheets = [("By Carrier", carrier_rows), ("Jobs", job_rows)]
+        .into_iter()
+        .map(|(title, rows)| Sheet {
+            properties: SheetProperties { title: Some(title.to_string()), ..Default::default() },
+            data: Some(GridData { start_row: 0, start_column: 0, row_data: rows }),
+        })
+        .collect();
+
+    let spreadsheet = Spreadsheet {
+        properties: SpreadsheetProperties {
+            title: Some(format!("Supplement Report ({})", Utc::now())),
+        },
+        sheets: Some(sheets),
+        ..Default::default()
+    };
+
+    let url = google_sheets::upload_spreadsheet_blocking(
+        google_sheets::SheetNickname::Supplements,
+        spreadsheet,
+        update,
+    )?;
+    utils::open_url(url.as_str());
+    Ok(())
+}
@@ -1,5 +1,7 @@
 use tracing::info;
 
+use crate::apis::http;
+
 #[derive(clap::Args, Debug)]
 pub struct Args {
     /// The GitHub repository to check for updates.
@@ -38,10 +40,12 @@ fn update_executable(github_repo: &str) -> anyhow::Result<()> {
 
     let api_url = format!("https://api.github.com/repos/{}/releases/latest", github_repo);
 
-    let client = reqwest::blocking::Client::builder().user_agent(USER_AGENT).build()?;
+    let client = http::blocking_client_builder()?.user_agent(USER_AGENT).build()?;
+    let deadline = http::Deadline::start("download the update");
 
     info!("Checking for updates at {}", api_url);
-    let response: serde_json::Value = client.get(&api_url).send()?.json()?;
+    let response: serde_json::Value =
+        deadline.limit_request(client.get(&api_url))?.send()?.json()?;
 
     let version_tag =
         response["tag_name"].as_str().ok_or(anyhow::anyhow!("No tag_name found in release"))?;
@@ -62,7 +66,7 @@ fn update_executable(github_repo: &str) -> anyhow::Result<()> {
         .ok_or(anyhow::anyhow!("No suitable asset found for this platform"))?;
 
     info!("Downloading asset from {}", asset_url);
-    let mut response = client.get(asset_url).send()?;
+    let mut response = deadline.limit_request(client.get(asset_url))?.send()?;
     let mut temp_file = tempfile::Builder::new().suffix(".tmp").tempfile()?;
     response.copy_to(&mut temp_file)?;
 
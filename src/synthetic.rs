@@ -0,0 +1,176 @@
+use chrono::{TimeZone as _, Utc};
+
+use crate::jobs::{Job, MilestoneDates, Status, Supplement, TimeDelta, Timestamp};
+
+const SALES_REPS: &[&str] = &[
+    "Avery Brooks",
+    "Blake Carter",
+    "Casey Diaz",
+    "Drew Ellis",
+    "Emerson Ford",
+    "Finley Grant",
+    "Gray Hayes",
+    "Harper Irwin",
+    "Jordan Kemp",
+    "Kendall Lane",
+    "Logan Moss",
+    "Morgan Nash",
+];
+
+/// The trades of the jobs, along with how many jobs out of 10 are of that
+/// trade.
+const TRADES: &[(&str, u64)] = &[("Roofing", 7), ("Siding", 2), ("Gutters", 1)];
+
+/// A small deterministic pseudorandom number generator (SplitMix64), so that
+/// the same seed always generates the same jobs.
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns true with the specified probability.
+    fn chance(&mut self, probability: f64) -> bool {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64 <= probability
+    }
+
+    /// A number of days between `min` and `max` (inclusive), skewed towards
+    /// `min`, like the time it takes most jobs to reach a milestone.
+    fn days(&mut self, min: i64, max: i64) -> TimeDelta {
+        let u = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        TimeDelta::days(min + ((max - min) as f64 * u * u).round() as i64)
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[(self.next_u64() % items.len() as u64) as usize]
+    }
+}
+
+/// Generates `n` jobs whose milestones follow roughly the same distribution as
+/// real jobs: about half are insurance jobs, most jobs that sign a contract are
+/// installed, and some of the rest are lost. The jobs are spread over the two
+/// years starting at 2023-01-01. The same seed always generates the same jobs.
+/// This is used by the benchmarks, so that the KPI calculations can be measured
+/// on large data sets without access to JobNimbus.
+pub fn generate_jobs(n: usize, seed: u64) -> Vec<Job> {
+    let mut rng = Rng(seed);
+    let start = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+    (0..n).map(|i| generate_job(&mut rng, i, start)).collect()
+}
+
+fn generate_job(rng: &mut Rng, i: usize, start: Timestamp) -> Job {
+    let insurance = rng.chance(0.55);
+    let appointment_date = start + rng.days(0, 730);
+    let mut dates = MilestoneDates {
+        appointment_date: Some(appointment_date),
+        contingency_date: None,
+        contract_date: None,
+        install_date: None,
+        loss_date: None,
+    };
+
+    // walk the job through its milestones, stopping at the first milestone
+    // that it doesn't reach
+    let mut last_date = appointment_date;
+    let signed_contract = if insurance {
+        // some insurance jobs go straight to a contract without a contingency
+        if rng.chance(0.15) {
+            true
+        } else if rng.chance(0.7) {
+            last_date += rng.days(1, 14);
+            dates.contingency_date = Some(last_date);
+            rng.chance(0.75)
+        } else {
+            false
+        }
+    } else {
+        rng.chance(0.35)
+    };
+    if signed_contract {
+        last_date += rng.days(1, 60);
+        dates.contract_date = Some(last_date);
+        if rng.chance(0.85) {
+            last_date += rng.days(14, 90);
+            dates.install_date = Some(last_date);
+        }
+    } else if rng.chance(0.4) {
+        last_date += rng.days(5, 60);
+        dates.loss_date = Some(last_date);
+    }
+
+    let status = if dates.install_date.is_some() {
+        Status::JobCompleted
+    } else if dates.loss_date.is_some() {
+        Status::Lost
+    } else {
+        Status::JobsInProgress
+    };
+    let trade_roll = rng.next_u64() % 10;
+    let trade = TRADES
+        .iter()
+        .scan(0, |cumulative, &(trade, weight)| {
+            *cumulative += weight;
+            Some((trade, *cumulative))
+        })
+        .find(|&(_, cumulative)| trade_roll < cumulative)
+        .map(|(trade, _)| trade.to_owned());
+    let amt_estimate = 500_000 + (rng.next_u64() % 2_000_000) as i32;
+    let amt_receivable = if status == Status::JobCompleted { amt_estimate / 10 } else { 0 };
+
+    Job {
+        jnid: format!("synthetic-{}", i),
+        milestone_dates: dates,
+        status,
+        status_mod_date: last_date,
+        sales_rep: Some(rng.pick(SALES_REPS).to_owned()),
+        insurance_checkbox: insurance,
+        insurance_claim_number: insurance.then(|| format!("CLM-{}", i)),
+        insurance_company_name: None,
+        job_number: Some((1000 + i).to_string()),
+        job_name: Some(format!("Synthetic Job {}", i)),
+        address: Some(format!("{} Main St", 100 + i)),
+        city: None,
+        state: None,
+        zip: None,
+        geo: None,
+        amt_receivable,
+        amt_estimate,
+        branch: None,
+        lead_source: None,
+        loss_reason: None,
+        crew: None,
+        trade,
+        rescheduled_appointment_dates: Vec::new(),
+        supplement: Supplement::default(),
+        payment_plan: None,
+        rejected_dates: Vec::new(),
+        raw: serde_json::Map::new(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jobs::analyze_job;
+
+    #[test]
+    fn generated_jobs_are_valid() {
+        let jobs = generate_jobs(1000, 42);
+        assert_eq!(jobs, generate_jobs(1000, 42));
+        assert_ne!(jobs, generate_jobs(1000, 43));
+
+        for job in &jobs {
+            let (_, errors) = analyze_job(job.clone());
+            assert_eq!(errors, vec![], "{:?}", job.milestone_dates);
+        }
+        let installed = jobs.iter().filter(|job| job.milestone_dates.install_date.is_some());
+        let lost = jobs.iter().filter(|job| job.milestone_dates.loss_date.is_some());
+        assert!((300..500).contains(&installed.count()));
+        assert!((100..300).contains(&lost.count()));
+    }
+}
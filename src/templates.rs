@@ -0,0 +1,68 @@
+use std::path::Path;
+
+use anyhow::Context as _;
+use minijinja::Environment;
+
+use crate::storage;
+use crate::utils::percent_or_na;
+
+/// The directory containing the user's customized templates. A template in
+/// this directory replaces the default template of the same name, e.g.
+/// `templates/kpi.html` replaces the default layout of the HTML KPI report.
+const TEMPLATES_DIR: &str = "templates";
+
+/// Loads the template with the specified name from the templates directory,
+/// falling back to `default` if the user hasn't customized it. The returned
+/// environment contains the template under `name`, along with the filters
+/// shared by every report:
+///
+/// - `percent` formats a rate from 0 to 1 as a percentage, or "N/A" if there
+///   is no rate.
+/// - `days` formats a number of days with two decimal places.
+pub fn load(name: &str, default: &'static str) -> anyhow::Result<Environment<'static>> {
+    load_from(&storage::config_path(TEMPLATES_DIR), name, default)
+}
+
+fn load_from(
+    dir: &Path,
+    name: &str,
+    default: &'static str,
+) -> anyhow::Result<Environment<'static>> {
+    let path = dir.join(name);
+    let source = match std::fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => default.to_owned(),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read template {}", path.display()))
+        }
+    };
+
+    let mut env = Environment::new();
+    env.add_filter("percent", |rate: Option<f64>| percent_or_na(rate).trim().to_owned());
+    env.add_filter("days", |days: f64| format!("{:.2}", days));
+    env.add_template_owned(name.to_owned(), source)
+        .with_context(|| format!("Invalid template {}", path.display()))?;
+    Ok(env)
+}
+
+#[cfg(test)]
+mod test {
+    use minijinja::context;
+
+    use super::*;
+
+    #[test]
+    fn customized_template_replaces_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = load_from(dir.path(), "report.txt", "Rate: {{ rate | percent }}").unwrap();
+        let rendered =
+            env.get_template("report.txt").unwrap().render(context! { rate => 0.5 }).unwrap();
+        assert_eq!(rendered, "Rate: 50.00%");
+
+        std::fs::write(dir.path().join("report.txt"), "{{ days | days }} days").unwrap();
+        let env = load_from(dir.path(), "report.txt", "Rate: {{ rate | percent }}").unwrap();
+        let rendered =
+            env.get_template("report.txt").unwrap().render(context! { days => 1.5 }).unwrap();
+        assert_eq!(rendered, "1.50 days");
+    }
+}
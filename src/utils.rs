@@ -1,5 +1,7 @@
 use tracing::{info, warn};
 
+use crate::jobs::TimeDelta;
+
 pub fn open_url(url: &str) {
     match open::that(url) {
         Ok(()) => info!("Opened URL: {}", url),
@@ -9,3 +11,37 @@ pub fn open_url(url: &str) {
         }
     }
 }
+
+pub fn into_days(time: TimeDelta) -> f64 {
+    const SECONDS_PER_DAY: f64 = 86400.0;
+    time.num_seconds() as f64 / SECONDS_PER_DAY
+}
+
+pub fn percent_or_na(rate: Option<f64>) -> String {
+    rate.map(|r| format!("{:6.2}%", r * 100.0)).unwrap_or_else(|| "    N/A".to_owned())
+}
+
+/// Like `percent_or_na`, but renders "N/A (small sample)" if the rate was
+/// calculated from too small of a sample to be meaningful.
+pub fn sampled_percent_or_na(rate: Option<f64>, small_sample: bool) -> String {
+    if small_sample {
+        "N/A (small sample)".to_owned()
+    } else {
+        percent_or_na(rate)
+    }
+}
+
+/// The relative change from `previous` to `current`, or `None` if `previous`
+/// is zero.
+pub fn percent_change(current: f64, previous: f64) -> Option<f64> {
+    if previous == 0.0 {
+        None
+    } else {
+        Some((current - previous) / previous)
+    }
+}
+
+/// Like `percent_or_na`, but always includes the sign, for displaying changes.
+pub fn signed_percent_or_na(change: Option<f64>) -> String {
+    change.map(|c| format!("{:+7.2}%", c * 100.0)).unwrap_or_else(|| "     N/A".to_owned())
+}
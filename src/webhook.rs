@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+
+use anyhow::bail;
+use chrono::SecondsFormat;
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::{apis::http, jobs::Timestamp};
+
+/// A summary of a completed report run, sent as JSON to a webhook so that
+/// other tools (e.g. Zapier or Make) can act on it.
+#[derive(Debug, Serialize)]
+pub struct ReportSummary {
+    /// The subcommand that generated the report, e.g. "kpi".
+    pub report: &'static str,
+    /// When the report was generated, in RFC 3339 format.
+    pub generated_at: String,
+    /// When the jobs that the report is based on were fetched from JobNimbus,
+    /// in RFC 3339 format.
+    pub data_fetched_at: String,
+    /// Counts of things in the report, e.g. the number of jobs.
+    pub counts: BTreeMap<String, usize>,
+    /// Dollar amounts in the report, e.g. the total amount receivable.
+    pub totals: BTreeMap<String, f64>,
+    /// The URL of the generated Google Sheet, if the report was uploaded.
+    pub spreadsheet_url: Option<String>,
+}
+
+impl ReportSummary {
+    pub fn new(report: &'static str, generated_at: Timestamp, data_fetched_at: Timestamp) -> Self {
+        Self {
+            report,
+            generated_at: generated_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            data_fetched_at: data_fetched_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            counts: BTreeMap::new(),
+            totals: BTreeMap::new(),
+            spreadsheet_url: None,
+        }
+    }
+}
+
+/// Sends the summary to the specified URL as a JSON POST request.
+pub fn post_summary(url: &str, summary: &ReportSummary) -> anyhow::Result<()> {
+    let url = reqwest::Url::parse(url)?;
+    let response = http::blocking_client()?
+        .post(url)
+        .header(CONTENT_TYPE, "application/json")
+        .json(summary)
+        .send()?;
+    if !response.status().is_success() {
+        bail!("Request failed with status code: {}", response.status());
+    }
+    Ok(())
+}
+
+/// Like `post_summary`, but logs failures instead of returning them, since the
+/// report itself has already been generated.
+pub fn post_summary_or_warn(url: &str, summary: &ReportSummary) {
+    match post_summary(url, summary) {
+        Ok(()) => info!("Posted report summary to {}", url),
+        Err(e) => warn!("Failed to post report summary to {}: {}", url, e),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn summary_json() {
+        let mut summary = ReportSummary::new("ar", Timestamp::UNIX_EPOCH, Timestamp::UNIX_EPOCH);
+        summary.counts.insert("jobs".to_owned(), 3);
+        summary.totals.insert("receivable".to_owned(), 123.45);
+
+        assert_eq!(
+            serde_json::to_value(&summary).unwrap(),
+            serde_json::json!({
+                "report": "ar",
+                "generated_at": "1970-01-01T00:00:00Z",
+                "data_fetched_at": "1970-01-01T00:00:00Z",
+                "counts": { "jobs": 3 },
+                "totals": { "receivable": 123.45 },
+                "spreadsheet_url": null,
+            })
+        );
+    }
+}